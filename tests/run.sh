@@ -1,2 +1,3 @@
 # Run tests
-./_build/tester
\ No newline at end of file
+./_build/tester
+(cd unit && cargo test)
\ No newline at end of file
@@ -0,0 +1,4 @@
+#![feature(box_patterns)]
+#![allow(clippy::boxed_local, clippy::vec_box)]
+
+pub mod parser;
@@ -1,24 +1,56 @@
-#![feature(box_patterns)]
-
 use std::env;
+use std::io::{self, BufRead, Write};
+use std::process::ExitCode;
 
-mod parser;
+use unit::parser;
 
 use typeset::{
   compile,
   render
 };
 
-fn main() {
+/* Render each line of standard input as a layout as it arrives, such that the runner can be used as a filter in a pipeline */
+fn _stream() -> ExitCode {
+  let stdout = io::stdout();
+  let mut out = stdout.lock();
+  let mut result = ExitCode::SUCCESS;
+  for (index, line) in io::stdin().lock().lines().enumerate() {
+    let line = match line {
+      Ok(line) => line,
+      Err(error) => {
+        eprintln!("failed to read standard input: {}", error);
+        return ExitCode::FAILURE
+      }
+    };
+    if line.trim().is_empty() { continue }
+    match parser::parse(line.as_str(), &Vec::new()) {
+      Err(error) => {
+        eprintln!("record {}: {}", index + 1, error);
+        result = ExitCode::FAILURE
+      }
+      Ok(layout) => {
+        let document = compile(layout);
+        let written = writeln!(out, "!!!!output!!!!\n{}", render(document, 2, 80))
+          .and_then(|()| out.flush());
+        if written.is_err() { return ExitCode::FAILURE }
+      }
+    }
+  }
+  result
+}
+
+fn main() -> ExitCode {
   let args: Vec<String> = env::args().collect();
   let dsl = &args[1];
+  if dsl == "--stream" { return _stream() }
   match parser::parse(dsl.as_str(), &Vec::new()) {
     Err(error) => panic!("{}", error),
     Ok(layout) => {
       let document = compile(layout);
       let result = render(document, 2, 80);
       println!("!!!!output!!!!");
-      println!("{}", result)
+      println!("{}", result);
+      ExitCode::SUCCESS
     }
   }
-}
\ No newline at end of file
+}
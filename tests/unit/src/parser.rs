@@ -189,7 +189,7 @@ pub fn parse(
       }
     }
   }
-  match LayoutParser::parse(Rule::layout, &input) {
+  match LayoutParser::parse(Rule::layout, input) {
     Ok(mut tokens) =>
       _interp_syntax(
         _parse_syntax(tokens.next().unwrap().into_inner())?,
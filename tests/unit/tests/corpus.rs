@@ -0,0 +1,169 @@
+//! Regression corpus runner.
+//!
+//! Every `.layout` file in `tests/corpus` holds a layout in the test DSL, followed by sections of expected output, each introduced by a header line of the form `=== width <width>` or `=== width <width> tab <tab>`, where the tab size defaults to 2.
+//! The expected output of a section is every line between its header and the next header or the end of the file.
+//! Running with `TYPESET_BLESS=1` rewrites the expected outputs of every section from the actual outputs.
+//! The corpus is the shared test vectors of every way of rendering a document, so besides `render`, each section is rendered in small chunks and line by line with a `Renderer` and into a string with `render_into`, which must give the same output.
+
+use std::{
+  env,
+  fs,
+  path::{Path, PathBuf}
+};
+
+use typeset::{
+  Doc,
+  RenderOptions,
+  Renderer,
+  compile,
+  render,
+  render_into
+};
+
+use unit::parser;
+
+const HEADER: &str = "=== ";
+const CHUNK_BYTES: usize = 3;
+
+#[derive(Debug)]
+struct Section {
+  header: String,
+  width: usize,
+  tab: usize,
+  expected: String
+}
+
+#[derive(Debug)]
+struct Case {
+  dsl: String,
+  sections: Vec<Section>
+}
+
+fn _parse_header(
+  header: &str
+) -> Result<(usize, usize), String> {
+  let words: Vec<&str> = header[HEADER.len()..].split_whitespace().collect();
+  let number = |word: &str| word.parse::<usize>()
+    .map_err(|_| format!("invalid number {:?} in header {:?}", word, header));
+  match words.as_slice() {
+    ["width", width] => Ok((number(width)?, 2)),
+    ["width", width, "tab", tab] => Ok((number(width)?, number(tab)?)),
+    _ => Err(format!("invalid header {:?}", header))
+  }
+}
+
+fn _parse_case(
+  data: &str
+) -> Result<Case, String> {
+  let data = data.strip_suffix('\n').unwrap_or(data);
+  let lines: Vec<&str> = data.split('\n').collect();
+  let headers: Vec<usize> = lines.iter()
+    .enumerate()
+    .filter(|(_, line)| line.starts_with(HEADER))
+    .map(|(index, _)| index)
+    .collect();
+  let first = *headers.first().ok_or("no expected output sections")?;
+  let dsl = lines[..first].join("\n");
+  let mut sections = Vec::new();
+  for (position, start) in headers.iter().enumerate() {
+    let end = headers.get(position + 1).copied().unwrap_or(lines.len());
+    let (width, tab) = _parse_header(lines[*start])?;
+    sections.push(Section {
+      header: lines[*start].to_string(),
+      width,
+      tab,
+      expected: lines[start + 1..end].join("\n")
+    })
+  }
+  Ok(Case { dsl, sections })
+}
+
+fn _print_case(
+  case: &Case
+) -> String {
+  let mut result = case.dsl.clone();
+  for section in &case.sections {
+    result.push('\n');
+    result.push_str(&section.header);
+    result.push('\n');
+    result.push_str(&section.expected)
+  }
+  result.push('\n');
+  result
+}
+
+/* The outputs of the other ways of rendering a document that differ from the output of render, by name */
+fn _entry_points(
+  document: &Doc,
+  options: RenderOptions,
+  expected: &str
+) -> Vec<(&'static str, String)> {
+  let mut renderer = Renderer::new(document, options);
+  let mut chunked = String::new();
+  while let Some(chunk) = renderer.next_chunk(CHUNK_BYTES) {
+    chunked.push_str(chunk)
+  }
+  let mut renderer = Renderer::new(document, options);
+  let mut lines = String::new();
+  while let Some(line) = renderer.next_chunk(0) {
+    let body = line.strip_suffix('\n').unwrap_or(line);
+    assert!(!body.contains('\n'), "Renderer::next_chunk(0) gives more than a line: {:?}", line);
+    lines.push_str(line)
+  }
+  let mut into = String::new();
+  render_into(document, &options, &mut into);
+  [("Renderer::next_chunk", chunked), ("Renderer::next_chunk by lines", lines), ("render_into", into)]
+    .into_iter()
+    .filter(|(_, actual)| actual != expected)
+    .collect()
+}
+
+fn _corpus() -> Vec<PathBuf> {
+  let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("../corpus");
+  let mut paths: Vec<PathBuf> = fs::read_dir(&root)
+    .unwrap_or_else(|error| panic!("can not read {}: {}", root.display(), error))
+    .map(|entry| entry.unwrap().path())
+    .filter(|path| path.extension().is_some_and(|extension| extension == "layout"))
+    .collect();
+  paths.sort();
+  paths
+}
+
+#[test]
+fn corpus() {
+  let bless = env::var("TYPESET_BLESS").is_ok_and(|value| value == "1");
+  let paths = _corpus();
+  assert!(!paths.is_empty(), "the corpus is empty");
+  let mut failures = Vec::new();
+  for path in paths {
+    let name = path.file_name().unwrap().to_string_lossy().to_string();
+    let data = fs::read_to_string(&path).unwrap();
+    let mut case = match _parse_case(&data) {
+      Ok(case) => case,
+      Err(error) => { failures.push(format!("{}: {}", name, error)); continue }
+    };
+    let layout = match parser::parse(&case.dsl, &Vec::new()) {
+      Ok(layout) => layout,
+      Err(error) => { failures.push(format!("{}: {}", name, error)); continue }
+    };
+    for section in &mut case.sections {
+      let document = compile(layout.clone());
+      let options = RenderOptions::new(section.tab, section.width);
+      for (entry_point, other) in _entry_points(&document, options, &section.expected) {
+        failures.push(format!(
+          "{}: {}: {} differs\n--- expected\n{}\n--- actual\n{}",
+          name, section.header, entry_point, section.expected, other
+        ))
+      }
+      let actual = render(document, section.tab, section.width);
+      if actual == section.expected { continue }
+      if bless { section.expected = actual; continue }
+      failures.push(format!(
+        "{}: {}\n--- expected\n{}\n--- actual\n{}",
+        name, section.header, section.expected, actual
+      ))
+    }
+    if bless { fs::write(&path, _print_case(&case)).unwrap() }
+  }
+  assert!(failures.is_empty(), "\n{}", failures.join("\n\n"))
+}
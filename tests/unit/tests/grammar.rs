@@ -0,0 +1,262 @@
+//! Grammar runner.
+//!
+//! Reads the operators of the layout DSL from `typeset::grammar()`, checks that the runtime parser defines the same operators, and generates cases from fixed seeds over every operator, with chains of unary and binary operators to cover their precedence and associativity.
+//! Every case is parsed by the runtime parser and must give the layout built from its syntax tree with the layout constructors.
+//! The same cases are written as `layout!` invocations to `typeset-parser/tests/grammar.rs`, which must be up to date, such that the macro is held to the same layouts; running with `TYPESET_BLESS=1` rewrites it.
+
+use std::{
+  env,
+  fs,
+  path::Path
+};
+
+use typeset::{
+  Layout,
+  null,
+  text,
+  fix,
+  grp,
+  seq,
+  nest,
+  pack,
+  line,
+  comp
+};
+
+use unit::parser;
+
+const SEEDS: u64 = 48;
+const DEPTH: u32 = 4;
+const VARIABLES: usize = 3;
+const TEXTS: [(&str, &str); 5] = [
+  ("a", "a"),
+  ("bb", "bb"),
+  ("c d", "c d"),
+  ("e\\tf", "e\tf"),
+  ("\\\"g\\\"", "\"g\"")
+];
+
+/* A xorshift generator, such that the cases are the same on every run */
+struct Rng(u64);
+
+impl Rng {
+  fn _below(&mut self, bound: u64) -> u64 {
+    self.0 ^= self.0 << 13;
+    self.0 ^= self.0 >> 7;
+    self.0 ^= self.0 << 17;
+    self.0 % bound
+  }
+}
+
+#[derive(Debug, Clone)]
+enum Syntax {
+  Null,
+  Variable(usize),
+  Text(usize),
+  Unary(String, Box<Syntax>),
+  Binary(String, Box<Syntax>, Box<Syntax>)
+}
+
+/* The quoted alternatives of a production of the grammar */
+fn _alternatives(
+  grammar: &str,
+  production: &str
+) -> Vec<String> {
+  let prefix = format!("{} = ", production);
+  let rule = grammar.lines()
+    .find_map(|line| line.strip_prefix(&prefix))
+    .unwrap_or_else(|| panic!("the grammar has no production {}", production));
+  rule.trim_end_matches(';')
+    .split('|')
+    .map(|alternative| alternative.trim().trim_matches('"').to_string())
+    .collect()
+}
+
+/* The operators of the runtime parser, the literals of its rules named by an _op suffix */
+fn _runtime_operators() -> Vec<String> {
+  let mut operators: Vec<String> = include_str!("../src/layout.pest").lines()
+    .filter_map(|line| line.split_once("_op = { \""))
+    .map(|(_, rest)| rest.trim_end_matches("\" }").to_string())
+    .collect();
+  operators.sort();
+  operators
+}
+
+fn _unary(
+  op: &str,
+  layout: Box<Layout>
+) -> Box<Layout> {
+  match op {
+    "fix" => fix(layout),
+    "grp" => grp(layout),
+    "seq" => seq(layout),
+    "nest" => nest(layout),
+    "pack" => pack(layout),
+    _ => panic!("the grammar runner does not know the unary operator {}", op)
+  }
+}
+
+fn _binary(
+  op: &str,
+  left: Box<Layout>,
+  right: Box<Layout>
+) -> Box<Layout> {
+  match op {
+    "@" => line(left, right),
+    "@@" => line(left, line(null(), right)),
+    "&" => comp(left, right, false, false),
+    "+" => comp(left, right, true, false),
+    "!&" => comp(left, right, false, true),
+    "!+" => comp(left, right, true, true),
+    _ => panic!("the grammar runner does not know the binary operator {}", op)
+  }
+}
+
+fn _build(
+  syntax: &Syntax,
+  variables: &[Box<Layout>]
+) -> Box<Layout> {
+  match syntax {
+    Syntax::Null => null(),
+    Syntax::Variable(index) => variables[*index].clone(),
+    Syntax::Text(index) => text(TEXTS[*index].1),
+    Syntax::Unary(op, syntax1) => _unary(op, _build(syntax1, variables)),
+    Syntax::Binary(op, left, right) =>
+      _binary(op, _build(left, variables), _build(right, variables))
+  }
+}
+
+/* The source of a syntax tree, with the fewest parentheses that chaining unary operators and the binary operators associating to the right allow */
+fn _print(
+  syntax: &Syntax,
+  variable: &dyn Fn(usize) -> String
+) -> String {
+  let grouped = |syntax: &Syntax| format!("({})", _print(syntax, variable));
+  match syntax {
+    Syntax::Null => "null".to_string(),
+    Syntax::Variable(index) => variable(*index),
+    Syntax::Text(index) => format!("\"{}\"", TEXTS[*index].0),
+    Syntax::Unary(op, syntax1) => match syntax1.as_ref() {
+      Syntax::Binary(..) => format!("{} {}", op, grouped(syntax1)),
+      _ => format!("{} {}", op, _print(syntax1, variable))
+    },
+    Syntax::Binary(op, left, right) => match left.as_ref() {
+      Syntax::Binary(..) => format!("{} {} {}", grouped(left), op, _print(right, variable)),
+      _ => format!("{} {} {}", _print(left, variable), op, _print(right, variable))
+    }
+  }
+}
+
+fn _syntax(
+  rng: &mut Rng,
+  unary: &[String],
+  binary: &[String],
+  depth: u32
+) -> Syntax {
+  let primary = |rng: &mut Rng| match rng._below(4) {
+    0 => Syntax::Null,
+    1 => Syntax::Variable(rng._below(VARIABLES as u64) as usize),
+    _ => Syntax::Text(rng._below(TEXTS.len() as u64) as usize)
+  };
+  if depth == 0 { return primary(rng) }
+  match rng._below(4) {
+    0 => primary(rng),
+    1 => {
+      let op = unary[rng._below(unary.len() as u64) as usize].clone();
+      Syntax::Unary(op, Box::new(_syntax(rng, unary, binary, depth - 1)))
+    }
+    _ => {
+      let op = binary[rng._below(binary.len() as u64) as usize].clone();
+      let left = _syntax(rng, unary, binary, depth - 1);
+      let right = _syntax(rng, unary, binary, depth - 1);
+      Syntax::Binary(op, Box::new(left), Box::new(right))
+    }
+  }
+}
+
+/* One case per operator, a chain of every binary operator, chains of every unary operator binding tighter than a binary operator, every variable, then the cases generated from the seeds */
+fn _cases(
+  unary: &[String],
+  binary: &[String]
+) -> Vec<Syntax> {
+  let texts = |left, right| (Box::new(Syntax::Text(left)), Box::new(Syntax::Text(right)));
+  let mut cases = Vec::new();
+  for op in unary {
+    cases.push(Syntax::Unary(op.clone(), Box::new(Syntax::Text(0))))
+  }
+  for op in binary {
+    let (left, right) = texts(0, 1);
+    cases.push(Syntax::Binary(op.clone(), left, right))
+  }
+  cases.push(binary.iter().enumerate().rev().fold(
+    Syntax::Text(binary.len() % TEXTS.len()),
+    |right, (index, op)| Syntax::Binary(op.clone(), Box::new(Syntax::Text(index % TEXTS.len())), Box::new(right))
+  ));
+  let chain = |operand| unary.iter().rev().fold(operand, |syntax, op| Syntax::Unary(op.clone(), Box::new(syntax)));
+  for op in binary {
+    let (left, right) = texts(0, 1);
+    cases.push(Syntax::Binary(op.clone(), Box::new(chain(*left)), Box::new(chain(*right))));
+    let (left, right) = texts(0, 1);
+    cases.push(chain(Syntax::Binary(op.clone(), left, right)))
+  }
+  cases.push((1..VARIABLES).rev().fold(
+    Syntax::Variable(VARIABLES - 1),
+    |right, index| Syntax::Binary(binary[0].clone(), Box::new(Syntax::Variable(index - 1)), Box::new(right))
+  ));
+  for seed in 1..=SEEDS {
+    let mut rng = Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    cases.push(_syntax(&mut rng, unary, binary, DEPTH))
+  }
+  cases
+}
+
+/* The macro runner, checking the layout! macro against the expected layouts of the cases */
+fn _print_macro_runner(
+  cases: &[(String, String)]
+) -> String {
+  let mut result = String::new();
+  result.push_str("//! Grammar runner of the `layout!` macro.\n");
+  result.push_str("//!\n");
+  result.push_str("//! Generated from `typeset::grammar()` by the grammar runner of `tests/unit`, which checks the runtime parser against the same cases; do not edit it by hand, but run the grammar runner with `TYPESET_BLESS=1`.\n\n");
+  result.push_str("use typeset::text;\n");
+  result.push_str("use typeset_parser::layout;\n\n");
+  result.push_str("#[test]\n");
+  result.push_str("fn grammar() {\n");
+  for index in 0..VARIABLES {
+    result.push_str(&format!("  let v{} = text(\"v{}\");\n", index, index))
+  }
+  for (source, expected) in cases {
+    result.push_str(&format!("  assert_eq!(format!(\"{{:?}}\", layout!{{ {} }}), {:?});\n", source, expected))
+  }
+  result.push_str("}\n");
+  result
+}
+
+#[test]
+fn grammar() {
+  let bless = env::var("TYPESET_BLESS").is_ok_and(|value| value == "1");
+  let unary = _alternatives(typeset::grammar(), "unary_op");
+  let binary = _alternatives(typeset::grammar(), "binary_op");
+  let mut operators: Vec<String> = unary.iter().chain(binary.iter()).cloned().collect();
+  operators.sort();
+  assert_eq!(_runtime_operators(), operators, "the runtime parser and the grammar define different operators");
+  let variables: Vec<Box<Layout>> = (0..VARIABLES).map(|index| text(format!("v{}", index))).collect();
+  let mut failures = Vec::new();
+  let mut macro_cases = Vec::new();
+  for syntax in _cases(&unary, &binary) {
+    let expected = format!("{:?}", _build(&syntax, &variables));
+    let source = _print(&syntax, &|index| format!("{{{}}}", index));
+    match parser::parse(&source, &variables) {
+      Ok(layout) if format!("{:?}", layout) == expected => (),
+      Ok(layout) => failures.push(format!("{}\n--- expected\n{}\n--- actual\n{:?}", source, expected, layout)),
+      Err(error) => failures.push(format!("{}\n{}", source, error))
+    }
+    macro_cases.push((_print(&syntax, &|index| format!("v{}", index)), expected))
+  }
+  assert!(failures.is_empty(), "\n{}", failures.join("\n\n"));
+  let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../typeset-parser/tests/grammar.rs");
+  let runner = _print_macro_runner(&macro_cases);
+  if bless { fs::write(&path, &runner).unwrap(); return }
+  let current = fs::read_to_string(&path).unwrap_or_default();
+  assert!(current == runner, "{} is out of date with the grammar, run the grammar runner with TYPESET_BLESS=1", path.display())
+}
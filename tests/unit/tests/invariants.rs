@@ -0,0 +1,142 @@
+//! Rendering invariants runner.
+//!
+//! Generates layouts from fixed seeds, over texts and every kind of composition and modal layout including fixes, and checks the invariants of rendering stated in `typeset::semantics` at every width up to `WIDTHS`:
+//! the same document rendered twice with the same options gives the same output, as does rendering it again without hysteresis or rebuilt from its parts, the estimated length of the output is at most its length, a document whose widest rendering fits within a width renders the same at that width, and rendering the output again as plain text, or as a layout inferred from its indentation, gives the output back; a layout accepted by `indent_sensitive` never breaks a composition onto a line indented no deeper than the line it is on.
+
+use typeset::{
+  Layout,
+  Doc,
+  text,
+  fix,
+  grp,
+  seq,
+  nest,
+  pack,
+  line,
+  comp,
+  compile,
+  render,
+  render_stable,
+  render_with,
+  indent_sensitive,
+  LineKind,
+  BreakMemory,
+  RenderOptions,
+  from_indented_text,
+  with_ruler
+};
+
+const SEEDS: u64 = 500;
+const DEPTH: u32 = 6;
+const WIDTHS: usize = 48;
+const WORDS: [&str; 4] = ["a", "bb", "ccc", "dddd"];
+
+/* A xorshift generator, such that the layouts are the same on every run */
+struct Rng(u64);
+
+impl Rng {
+  fn _below(&mut self, bound: u64) -> u64 {
+    self.0 ^= self.0 << 13;
+    self.0 ^= self.0 >> 7;
+    self.0 ^= self.0 << 17;
+    self.0 % bound
+  }
+}
+
+fn _layout(
+  rng: &mut Rng,
+  depth: u32
+) -> Box<Layout> {
+  let word = |rng: &mut Rng| text(WORDS[rng._below(WORDS.len() as u64) as usize]);
+  if depth == 0 { return word(rng) }
+  match rng._below(10) {
+    0 => word(rng),
+    1 => fix(_layout(rng, depth - 1)),
+    2 => grp(_layout(rng, depth - 1)),
+    3 => seq(_layout(rng, depth - 1)),
+    4 => nest(_layout(rng, depth - 1)),
+    5 => pack(_layout(rng, depth - 1)),
+    6 => line(_layout(rng, depth - 1), _layout(rng, depth - 1)),
+    _ => {
+      let pad = rng._below(2) == 0;
+      let fixed = rng._below(4) == 0;
+      comp(_layout(rng, depth - 1), _layout(rng, depth - 1), pad, fixed)
+    }
+  }
+}
+
+fn _widest(
+  output: &str
+) -> usize {
+  output.split('\n').map(|line| line.chars().count()).max().unwrap_or(0)
+}
+
+#[test]
+fn invariants() {
+  let mut failures = Vec::new();
+  for seed in 1..=SEEDS {
+    let mut rng = Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    let layout = _layout(&mut rng, DEPTH);
+    let document = compile(layout.clone());
+    let wide = render(document.clone(), 2, usize::MAX);
+    let mut memory = BreakMemory::new();
+    let rebuilt = Doc::from_parts(document.clone().into_parts());
+    for width in 1..=WIDTHS {
+      let output = render(document.clone(), 2, width);
+      if output.len() < document.estimated_output_len(&RenderOptions::new(2, width)) {
+        failures.push(format!("seed {} width {}: the estimated output length exceeds the output\n{}", seed, width, layout))
+      }
+      if render_stable(&document, &RenderOptions::new(2, width), &mut memory).text != output {
+        failures.push(format!("seed {} width {}: rendering again without hysteresis differs\n{}", seed, width, layout))
+      }
+      if output != render(document.clone(), 2, width) {
+        failures.push(format!("seed {} width {}: rendering twice differs\n{}", seed, width, layout))
+      }
+      if output != render(rebuilt.clone(), 2, width) {
+        failures.push(format!("seed {} width {}: rendering the document rebuilt from its parts differs\n{}", seed, width, layout))
+      }
+      if _widest(&wide) <= width && output != wide {
+        failures.push(format!(
+          "seed {} width {}: a fitting document is broken\n{}\n--- expected\n{}\n--- actual\n{}",
+          seed, width, layout, with_ruler(&wide, width), with_ruler(&output, width)
+        ))
+      }
+      if render(Doc::from_plain_text(&output), 2, width) != output {
+        failures.push(format!("seed {} width {}: rendering the output again differs\n{}", seed, width, layout))
+      }
+      if render(compile(from_indented_text(&output, 2)), 2, width) != output {
+        failures.push(format!("seed {} width {}: rendering the output again by its indentation differs\n{}", seed, width, layout))
+      }
+    }
+  }
+  assert!(failures.is_empty(), "\n{}", failures.join("\n\n"))
+}
+
+#[test]
+fn indent_sensitive_breaks() {
+  let mut failures = Vec::new();
+  let mut checked = 0;
+  for seed in 1..=SEEDS * 4 {
+    let mut rng = Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    let Ok(layout) = indent_sensitive(_layout(&mut rng, DEPTH)) else { continue };
+    checked += 1;
+    let document = compile(layout.clone());
+    for width in 1..=WIDTHS {
+      let options = RenderOptions { line_info: true, ..RenderOptions::new(2, width) };
+      let output = render_with(&document, &options);
+      let mut statement = 0;
+      for (info, data) in output.lines.unwrap_or_default().iter().zip(output.text.split('\n')) {
+        match info.kind {
+          LineKind::Forced => statement = info.indent,
+          LineKind::Break if !data.is_empty() && info.indent <= statement => failures.push(format!(
+            "seed {} width {}: a broken line is not indented deeper than its statement\n{}\n{}",
+            seed, width, layout, with_ruler(&output.text, width)
+          )),
+          LineKind::Break => ()
+        }
+      }
+    }
+  }
+  assert!(0 < checked, "no layout is indent sensitive");
+  assert!(failures.is_empty(), "\n{}", failures.join("\n\n"))
+}
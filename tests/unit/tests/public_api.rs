@@ -0,0 +1,138 @@
+//! Public API runner.
+//!
+//! Every function exported from the root of `typeset` or from one of its public modules, and every constructor of `LayoutArena`, must have an `# Examples` section in its doc comment with at least one assertion, such that the examples are executable specifications of the exported behavior.
+//! Constructors, the functions giving a `Box<Layout>`, a `Box<WadlerDoc>` or a `LayoutId`, must also be `#[must_use]`.
+
+use std::{
+  fs,
+  path::{Path, PathBuf}
+};
+
+const PUBLIC_MODULES: [&str; 2] = ["compat", "expr"];
+
+const CONSTRUCTOR_RESULTS: [&str; 3] = [
+  "-> Box<Layout>",
+  "-> Box<WadlerDoc>",
+  "-> LayoutId"
+];
+
+#[derive(Debug)]
+struct Item {
+  name: String,
+  docs: String,
+  signature: String
+}
+
+fn _source(
+  module: &str
+) -> (PathBuf, String) {
+  let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+    .join("../../typeset/src")
+    .join(format!("{}.rs", module));
+  let data = fs::read_to_string(&path)
+    .unwrap_or_else(|error| panic!("can not read {}: {}", path.display(), error));
+  (path, data)
+}
+
+/* The functions of a source file with the given indentation, their docs and attributes, and their signatures */
+fn _items(
+  data: &str,
+  indent: &str
+) -> Vec<Item> {
+  let lines: Vec<&str> = data.lines().collect();
+  let prefix = format!("{}pub fn ", indent);
+  let mut items = Vec::new();
+  for (index, line) in lines.iter().enumerate() {
+    let Some(rest) = line.strip_prefix(&prefix) else { continue };
+    let name: String = rest.chars()
+      .take_while(|char| char.is_alphanumeric() || *char == '_')
+      .collect();
+    let docs = lines[..index].iter().rev()
+      .take_while(|line| {
+        let line = line.trim_start();
+        line.starts_with("///") || line.starts_with("#[")
+      })
+      .copied()
+      .collect::<Vec<&str>>()
+      .join("\n");
+    let signature = lines[index..].iter()
+      .take_while(|line| !line.ends_with('{'))
+      .chain(lines[index..].iter().find(|line| line.ends_with('{')))
+      .copied()
+      .collect::<Vec<&str>>()
+      .join("\n");
+    items.push(Item { name, docs, signature })
+  }
+  items
+}
+
+/* The names exported from the modules of the crate root, by module */
+fn _exports() -> Vec<(String, Vec<String>)> {
+  let (_, data) = _source("lib");
+  let mut exports = Vec::new();
+  let mut rest = data.as_str();
+  while let Some(start) = rest.find("pub use self::") {
+    let clause = &rest[start + "pub use self::".len()..];
+    let end = clause.find(';').unwrap();
+    let (module, names) = match clause[..end].split_once("::") {
+      Some(split) => split,
+      None => { rest = &clause[end..]; continue }
+    };
+    let names = names.trim_matches(|char: char| char == '{' || char == '}' || char.is_whitespace())
+      .split(',')
+      .map(|name| name.trim().to_string())
+      .filter(|name| !name.is_empty())
+      .collect();
+    exports.push((module.to_string(), names));
+    rest = &clause[end..]
+  }
+  exports
+}
+
+fn _check(
+  path: &Path,
+  item: &Item,
+  failures: &mut Vec<String>
+) {
+  if !item.docs.contains("# Examples") {
+    failures.push(format!("{}: {} has no examples", path.display(), item.name))
+  } else if !item.docs.contains("assert") {
+    failures.push(format!("{}: {} has no assertions in its examples", path.display(), item.name))
+  }
+  let constructor = CONSTRUCTOR_RESULTS.iter()
+    .any(|result| item.signature.contains(result));
+  if constructor && !item.docs.contains("#[must_use]") {
+    failures.push(format!("{}: {} is not must_use", path.display(), item.name))
+  }
+}
+
+#[test]
+fn public_api() {
+  let mut failures = Vec::new();
+  let mut checked = 0;
+  for (module, names) in _exports() {
+    let (path, data) = _source(&module);
+    for item in _items(&data, "") {
+      if !names.contains(&item.name) || item.name.starts_with('_') { continue }
+      _check(&path, &item, &mut failures);
+      checked += 1
+    }
+  }
+  for module in PUBLIC_MODULES {
+    let (path, data) = _source(module);
+    for item in _items(&data, "") {
+      _check(&path, &item, &mut failures);
+      checked += 1
+    }
+  }
+  let (path, data) = _source("arena");
+  for item in _items(&data, "  ") {
+    let constructor = CONSTRUCTOR_RESULTS.iter()
+      .any(|result| item.signature.contains(result));
+    if !constructor && !item.name.starts_with("compile") { continue }
+    _check(&path, &item, &mut failures);
+    checked += 1
+  }
+  assert!(0 < checked, "no exported functions were found");
+  assert!(failures.is_empty(), "\n{}", failures.join("\n"))
+}
@@ -0,0 +1,125 @@
+//! Stress runner.
+//!
+//! Renders documents far larger than the corpus, whole and line by line, such that rendering time that is not linear in the size of the document shows up as a test that does not finish.
+//! Compiles layouts of groups and sequences nested far deeper than the corpus, such that compile time or memory that is not linear in the depth of the layout shows up likewise.
+//! The tests run on the default stack of a test thread, such that compiling, rendering or dropping that recurses on the depth of a layout or the length of a document shows up as a stack overflow.
+
+use typeset::{
+  Layout,
+  Doc,
+  DocObj,
+  RenderOptions,
+  DocObjFix,
+  CompileOptions,
+  null,
+  text,
+  fix,
+  grp,
+  seq,
+  comp,
+  compile,
+  compile_with_report,
+  render,
+  assert_fits,
+  limits::TESTED_DEPTH
+};
+
+const FIXED_CHARS: usize = 100_000;
+const WORD: &str = "abc";
+const NESTED_DEPTH: usize = TESTED_DEPTH;
+const WIDTH: usize = 80;
+
+fn _words() -> usize {
+  FIXED_CHARS.div_ceil(WORD.len() + 1)
+}
+
+fn _expected() -> String {
+  vec![WORD; _words()].join(" ")
+}
+
+/* A fixed chain of words composed to the right, as the compiler gives it */
+fn _fixed_chain() -> Box<DocObjFix> {
+  let word = || Box::new(DocObjFix::Text(WORD.to_string(), None));
+  (1.._words()).fold(word(), |right, _| Box::new(DocObjFix::Comp(word(), right, true)))
+}
+
+#[test]
+fn fixed_chain_left() {
+  let layout = (0.._words()).fold(null(), |layout, _| comp(layout, text(WORD), true, false));
+  let document = compile(fix(layout));
+  assert_eq!(render(document, 2, 80), _expected())
+}
+
+#[test]
+fn fixed_chain_right() {
+  let layout = (0.._words()).fold(null(), |layout, _| comp(text(WORD), layout, true, false));
+  let document = compile(fix(layout));
+  assert_eq!(render(document, 2, 80), _expected())
+}
+
+#[test]
+fn fixed_chain_broken() {
+  let head = Box::new(DocObj::Text("head".to_string(), None));
+  let obj = Box::new(DocObj::Comp(head, Box::new(DocObj::Fix(_fixed_chain())), true));
+  let document = Box::new(Doc::Line(obj));
+  assert_eq!(render(document, 2, 80), format!("head\n{}", _expected()))
+}
+
+#[test]
+fn lines_in_order() {
+  let lines = vec![WORD; _words()].join("\n");
+  let document = Doc::from_plain_text(&lines);
+  let rendered: Vec<String> = document.render_lines(&RenderOptions::default()).collect();
+  assert_eq!(rendered.join("\n"), lines)
+}
+
+/* Groups and sequences nested in turn, with a composition of a word in each */
+fn _nested(
+  depth: usize,
+  right: bool
+) -> Box<Layout> {
+  (0..depth).fold(text(WORD), |inner, level| {
+    let inner1 =
+      if right { comp(text(WORD), inner, true, false) }
+      else { comp(inner, text(WORD), true, false) };
+    if level % 2 == 0 { grp(inner1) } else { seq(inner1) }
+  })
+}
+
+fn _check_nested(
+  depth: usize,
+  right: bool
+) {
+  let result = render(compile(_nested(depth, right)), 2, WIDTH);
+  assert!(result.split_whitespace().all(|word| word == WORD));
+  assert_eq!(result.split_whitespace().count(), depth + 1);
+  assert_fits!(result, WIDTH)
+}
+
+#[test]
+fn nested_modal_only() {
+  let layout = (0..NESTED_DEPTH).fold(text(WORD), |layout, level|
+    if level % 2 == 0 { grp(layout) } else { seq(layout) });
+  assert_eq!(render(compile(layout), 2, WIDTH), WORD)
+}
+
+#[test]
+fn nested_modal_right() {
+  _check_nested(NESTED_DEPTH, true)
+}
+
+#[test]
+fn nested_modal_left() {
+  _check_nested(NESTED_DEPTH, false)
+}
+
+#[test]
+fn nested_modal_memory() {
+  let bytes = |depth| {
+    let report = compile_with_report(_nested(depth, true), &CompileOptions::default()).unwrap();
+    report.total_bytes()
+  };
+  let half = bytes(NESTED_DEPTH / 2);
+  let full = bytes(NESTED_DEPTH);
+  assert!(full < 3 * half, "{} bytes at depth {}, {} bytes at depth {}", half, NESTED_DEPTH / 2, full, NESTED_DEPTH)
+}
@@ -0,0 +1,373 @@
+//! Structurize runner.
+//!
+//! Generates fixed documents from fixed seeds, by running the passes before `structurize` on random layouts such that the documents are valid by construction, and checks the groups and sequences that `structurize` rebuilds from the scopes of every line:
+//! the items of the line and the paddings between them are kept in order, every scope over two or more items is rebuilt as exactly one scope of the same kind, the rebuilt scopes are properly nested, and a rebuilt scope only starts and ends where a scope of the line started and ended, covering every scope it was solved from.
+
+use std::collections::BTreeMap;
+
+use typeset::{
+  Layout,
+  text,
+  fix,
+  grp,
+  seq,
+  nest,
+  pack,
+  line,
+  comp
+};
+use typeset::passes::{
+  Bump,
+  FixedDoc,
+  FixedObj,
+  FixedItem,
+  FixedTerm,
+  FixedComp,
+  FixedFix,
+  Scopes,
+  RebuildDoc,
+  RebuildObj,
+  RebuildFix,
+  RebuildTerm,
+  broken,
+  serialize,
+  linearize,
+  fixed,
+  structurize
+};
+
+const SEEDS: u64 = 2000;
+const DEPTH: u32 = 7;
+const WORDS: [&str; 4] = ["a", "bb", "ccc", "dddd"];
+
+/* A xorshift generator, such that the layouts are the same on every run */
+struct Rng(u64);
+
+impl Rng {
+  fn _below(&mut self, bound: u64) -> u64 {
+    self.0 ^= self.0 << 13;
+    self.0 ^= self.0 >> 7;
+    self.0 ^= self.0 << 17;
+    self.0 % bound
+  }
+}
+
+/* Layouts heavy in groups, sequences and fixes, since these decide the scopes of a line */
+fn _layout(
+  rng: &mut Rng,
+  depth: u32
+) -> Box<Layout> {
+  let word = |rng: &mut Rng| text(WORDS[rng._below(WORDS.len() as u64) as usize]);
+  if depth == 0 { return word(rng) }
+  match rng._below(12) {
+    0 => word(rng),
+    1 => fix(_layout(rng, depth - 1)),
+    2 | 3 => grp(_layout(rng, depth - 1)),
+    4 | 5 => seq(_layout(rng, depth - 1)),
+    6 => nest(_layout(rng, depth - 1)),
+    7 => pack(_layout(rng, depth - 1)),
+    8 => line(_layout(rng, depth - 1), _layout(rng, depth - 1)),
+    _ => {
+      let pad = rng._below(2) == 0;
+      let fixed = rng._below(3) == 0;
+      comp(_layout(rng, depth - 1), _layout(rng, depth - 1), pad, fixed)
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Kind {
+  Grp,
+  Seq
+}
+
+/* A scope of a line, by its kind and the first and last items it covers */
+type Scope = (Kind, usize, usize);
+
+#[derive(Debug, Default)]
+struct Line {
+  texts: Vec<Vec<String>>,
+  pads: Vec<bool>,
+  scopes: Vec<Scope>
+}
+
+fn _fixed_term(
+  term: &FixedTerm,
+  texts: &mut Vec<String>
+) {
+  match term {
+    FixedTerm::Null => (),
+    FixedTerm::Text(data, _) => texts.push(data.to_string()),
+    FixedTerm::Nest(term1) |
+    FixedTerm::Pack(_, term1) => _fixed_term(term1, texts)
+  }
+}
+
+fn _fixed_comp(
+  comp: &FixedComp,
+  stack: &mut Vec<(Kind, u64)>
+) -> bool {
+  let FixedComp::Comp(pad, scopes) = comp;
+  let mut scopes = *scopes;
+  loop {
+    match scopes {
+      Scopes::Top => return *pad,
+      Scopes::Grp(index, outer) => {
+        stack.push((Kind::Grp, *index));
+        scopes = outer
+      }
+      Scopes::Seq(index, outer) => {
+        stack.push((Kind::Seq, *index));
+        scopes = outer
+      }
+    }
+  }
+}
+
+/*
+  The scopes of a fixed line, where a scope spans from the item of its
+  first composition to the item after its last composition; the
+  compositions within a fix are all within the item of the fix.
+*/
+fn _fixed_line(
+  obj: &FixedObj
+) -> Line {
+  fn _extend(
+    spans: &mut BTreeMap<(Kind, u64), (usize, usize)>,
+    stack: Vec<(Kind, u64)>,
+    from: usize,
+    to: usize
+  ) {
+    for scope in stack {
+      spans.entry(scope).and_modify(|span| span.1 = to).or_insert((from, to));
+    }
+  }
+  fn _item(
+    item: &FixedItem,
+    index: usize,
+    line: &mut Line,
+    spans: &mut BTreeMap<(Kind, u64), (usize, usize)>
+  ) {
+    let mut texts = Vec::new();
+    match item {
+      FixedItem::Term(term) => _fixed_term(term, &mut texts),
+      FixedItem::Fix(fix) => {
+        let mut fix = *fix;
+        loop {
+          match fix {
+            FixedFix::Next(term, comp, fix1) => {
+              _fixed_term(term, &mut texts);
+              let mut stack = Vec::new();
+              _fixed_comp(comp, &mut stack);
+              _extend(spans, stack, index, index);
+              fix = fix1
+            }
+            FixedFix::Last(term) => {
+              _fixed_term(term, &mut texts);
+              break
+            }
+          }
+        }
+      }
+    }
+    line.texts.push(texts)
+  }
+  let mut line = Line::default();
+  let mut spans = BTreeMap::new();
+  let mut obj = obj;
+  loop {
+    let index = line.texts.len();
+    match obj {
+      FixedObj::Next(item, comp, obj1) => {
+        _item(item, index, &mut line, &mut spans);
+        let mut stack = Vec::new();
+        line.pads.push(_fixed_comp(comp, &mut stack));
+        _extend(&mut spans, stack, index, index + 1);
+        obj = obj1
+      }
+      FixedObj::Last(item) => {
+        _item(item, index, &mut line, &mut spans);
+        break
+      }
+    }
+  }
+  line.scopes = spans
+    .into_iter()
+    .filter(|(_, (from, to))| from != to)
+    .map(|((kind, _), (from, to))| (kind, from, to))
+    .collect();
+  line.scopes.sort();
+  line
+}
+
+fn _rebuild_term(
+  term: &RebuildTerm,
+  texts: &mut Vec<String>
+) {
+  match term {
+    RebuildTerm::Null => (),
+    RebuildTerm::Text(data, _) => texts.push(data.to_string()),
+    RebuildTerm::Nest(term1) |
+    RebuildTerm::Pack(_, term1) => _rebuild_term(term1, texts)
+  }
+}
+
+fn _rebuild_fix(
+  fix: &RebuildFix,
+  texts: &mut Vec<String>
+) {
+  match fix {
+    RebuildFix::Term(term) => _rebuild_term(term, texts),
+    RebuildFix::Comp(left, right, _) => {
+      _rebuild_fix(left, texts);
+      _rebuild_fix(right, texts)
+    }
+  }
+}
+
+fn _rebuild_obj(
+  obj: &RebuildObj,
+  line: &mut Line
+) {
+  match obj {
+    RebuildObj::Term(term) => {
+      let mut texts = Vec::new();
+      _rebuild_term(term, &mut texts);
+      line.texts.push(texts)
+    }
+    RebuildObj::Fix(fix) => {
+      let mut texts = Vec::new();
+      _rebuild_fix(fix, &mut texts);
+      line.texts.push(texts)
+    }
+    RebuildObj::Grp(_, obj1) => {
+      let from = line.texts.len();
+      _rebuild_obj(obj1, line);
+      line.scopes.push((Kind::Grp, from, line.texts.len() - 1))
+    }
+    RebuildObj::Seq(_, obj1) => {
+      let from = line.texts.len();
+      _rebuild_obj(obj1, line);
+      line.scopes.push((Kind::Seq, from, line.texts.len() - 1))
+    }
+    RebuildObj::Comp(left, right, pad) => {
+      _rebuild_obj(left, line);
+      line.pads.push(*pad);
+      _rebuild_obj(right, line)
+    }
+  }
+}
+
+fn _check_line(
+  given: &Line,
+  rebuilt: &Line
+) -> Result<(), String> {
+  if given.texts != rebuilt.texts { return Err("the items differ".to_string()) }
+  if given.pads != rebuilt.pads { return Err("the paddings differ".to_string()) }
+  for kind in [Kind::Grp, Kind::Seq] {
+    let count = |scopes: &[Scope]| scopes.iter().filter(|scope| scope.0 == kind).count();
+    if count(&given.scopes) != count(&rebuilt.scopes) {
+      return Err(format!("the number of {:?} scopes differs", kind))
+    }
+  }
+  for (index, left) in rebuilt.scopes.iter().enumerate() {
+    if left.2 <= left.1 { return Err(format!("{:?} covers a single item", left)) }
+    for right in &rebuilt.scopes[index + 1..] {
+      let nested =
+        (left.1 <= right.1 && right.2 <= left.2) ||
+        (right.1 <= left.1 && left.2 <= right.2);
+      let disjoint = left.2 < right.1 || right.2 < left.1;
+      if !nested && !disjoint { return Err(format!("{:?} and {:?} cross", left, right)) }
+    }
+    if !given.scopes.iter().any(|scope| scope.1 == left.1) {
+      return Err(format!("{:?} starts where no scope started", left))
+    }
+    if !given.scopes.iter().any(|scope| scope.2 == left.2) {
+      return Err(format!("{:?} ends where no scope ended", left))
+    }
+  }
+  for scope in &given.scopes {
+    if !rebuilt.scopes.iter().any(|outer| outer.1 <= scope.1 && scope.2 <= outer.2) {
+      return Err(format!("{:?} is not covered", scope))
+    }
+  }
+  Ok(())
+}
+
+/* The lines of a layout, as given to structurize and as rebuilt by it */
+fn _lines(
+  layout: Box<Layout>
+) -> Result<Vec<(Line, Line)>, String> {
+  let mem = Bump::new();
+  let edsl = broken(&mem, layout, Default::default()).map_err(|error| error.to_string())?;
+  let (serial, names, _anchors) = serialize(&mem, edsl);
+  let mut given = fixed(&mem, linearize(&mem, serial));
+  let mut rebuilt = structurize(&mem, given, &names);
+  let mut lines = Vec::new();
+  loop {
+    match (given, rebuilt) {
+      (FixedDoc::EOD, RebuildDoc::EOD) => return Ok(lines),
+      (FixedDoc::Break(obj, given1), RebuildDoc::Break(obj1, rebuilt1)) => {
+        let mut line = Line::default();
+        _rebuild_obj(obj1, &mut line);
+        lines.push((_fixed_line(obj), line));
+        given = given1;
+        rebuilt = rebuilt1
+      }
+      _ => return Err("the number of lines differs".to_string())
+    }
+  }
+}
+
+fn _check(
+  layout: Box<Layout>
+) -> Result<(), String> {
+  for (index, (given, rebuilt)) in _lines(layout)?.iter().enumerate() {
+    _check_line(given, rebuilt).map_err(|error| format!("line {}: {}", index, error))?
+  }
+  Ok(())
+}
+
+#[test]
+fn structurize_scopes() {
+  let mut failures = Vec::new();
+  for seed in 1..=SEEDS {
+    let mut rng = Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    let layout = _layout(&mut rng, DEPTH);
+    if let Err(error) = _check(layout.clone()) {
+      failures.push(format!("seed {}: {}\n{}", seed, error, layout))
+    }
+  }
+  assert!(failures.is_empty(), "\n{}", failures.join("\n\n"))
+}
+
+#[test]
+fn structurize_crossing_scopes() {
+  // The fix joins the last item of the grp with the first item of the seq, so their scopes cross on it
+  let layout = comp(
+    grp(comp(text("a"), text("b"), true, false)),
+    seq(comp(text("c"), text("d"), true, false)),
+    true, true
+  );
+  let lines = _lines(layout).unwrap();
+  let (given, rebuilt) = &lines[0];
+  assert_eq!(given.scopes, vec![(Kind::Grp, 0, 1), (Kind::Seq, 1, 2)]);
+  assert!(_check_line(given, given).is_err());
+  assert_eq!(_check_line(given, rebuilt), Ok(()))
+}
+
+#[test]
+fn structurize_shared_scopes() {
+  // A grp and a seq over the same items are rebuilt in the order of the edges of the line, which tells a grp of a seq from a seq of a grp
+  let items = || comp(text("a"), comp(text("b"), text("c"), true, false), true, false);
+  let cases = [
+    (seq(grp(items())), vec![(Kind::Seq, 0, 2), (Kind::Grp, 0, 2)]),
+    (grp(seq(items())), vec![(Kind::Grp, 0, 2), (Kind::Seq, 0, 2)])
+  ];
+  for (layout, expected) in cases {
+    let lines = _lines(layout).unwrap();
+    let (given, rebuilt) = &lines[0];
+    assert_eq!(rebuilt.scopes, expected);
+    assert_eq!(_check_line(given, rebuilt), Ok(()))
+  }
+}
@@ -1,3 +1,5 @@
+#![allow(clippy::disallowed_names)]
+
 use typeset_parser::layout;
 
 fn main() {
@@ -7,7 +9,7 @@ fn main() {
     pack ("baz" !+ foo) @@
     grp null + seq (foo + foo !& foo)
   };
-  let document = typeset::compile(foobar.clone());
+  let document = typeset::compile_safe(foobar.clone()).unwrap();
   println!("---------------------");
   println!("{}", foobar);
   println!("---------------------");
@@ -1,8 +1,6 @@
-#![feature(proc_macro_diagnostic)]
-
 use std::ops::ControlFlow;
 use proc_macro::TokenStream;
-use proc_macro2::{ TokenStream as Quoted };
+use proc_macro2::{ TokenStream as Quoted, TokenTree, Delimiter, Group };
 use std::fmt::Debug;
 use quote::quote;
 use syn::{
@@ -128,6 +126,7 @@ fn _parse_binary_op(
 }
 
 #[derive(Debug, Clone)]
+#[allow(clippy::upper_case_acronyms)]
 enum AST {
   Null,
   Variable(Ident),
@@ -190,7 +189,7 @@ fn _parse_unary(
   input: ParseStream
 ) -> Result<Box<AST>> {
   let op = _parse_unary_op(input)?;
-  let ast = _parse_primary(input)?;
+  let ast = _parse_atom(input)?;
   Ok(Box::new(AST::Unary(op, ast)))
 }
 
@@ -224,7 +223,7 @@ impl Parse for Box<AST> {
         } else {
           Err(Error::new(
             input.span(),
-            format!("Failed to parse layout:\n{}", input.to_string())
+            format!("Failed to parse layout:\n{}", input)
           ))
         }
       }
@@ -232,6 +231,7 @@ impl Parse for Box<AST> {
   }
 }
 
+#[allow(clippy::boxed_local)]
 fn _reify_layout(ast: Box<AST>) -> Quoted {
   match *ast {
     AST::Null => quote! { typeset::null() },
@@ -331,8 +331,42 @@ fn _reify_layout(ast: Box<AST>) -> Quoted {
   }
 }
 
+/* Doc comments within the input, given as #[doc = ...] or #![doc = ...] attributes; line and block comments never reach the macro */
+fn _strip_doc_comments(input: Quoted) -> Quoted {
+  let mut output = Vec::new();
+  let mut tokens = input.into_iter().peekable();
+  while let Some(token) = tokens.next() {
+    match token {
+      TokenTree::Punct(punct) if punct.as_char() == '#' => {
+        let mut lookahead = tokens.clone();
+        if let Some(TokenTree::Punct(bang)) = lookahead.peek() {
+          if bang.as_char() == '!' { lookahead.next(); }
+        }
+        match lookahead.next() {
+          Some(TokenTree::Group(group))
+          if group.delimiter() == Delimiter::Bracket &&
+             matches!(group.stream().into_iter().next(), Some(TokenTree::Ident(ident)) if ident == "doc") =>
+            tokens = lookahead,
+          _ => output.push(TokenTree::Punct(punct))
+        }
+      }
+      TokenTree::Group(group) => {
+        let mut group1 = Group::new(group.delimiter(), _strip_doc_comments(group.stream()));
+        group1.set_span(group.span());
+        output.push(TokenTree::Group(group1))
+      }
+      token => output.push(token)
+    }
+  }
+  output.into_iter().collect()
+}
+
+/// Parses a layout in the DSL given by `typeset::grammar()`, with Rust identifiers as variables bound to layouts.
+///
+/// The input may span several lines and hold comments, line, block and doc comments alike, which are ignored.
 #[proc_macro]
 pub fn layout(input: TokenStream) -> TokenStream {
+  let input = _strip_doc_comments(input.into()).into();
   let ast = parse_macro_input!(input as Box<AST>);
   let output = _reify_layout(ast);
   quote! { #output }.into()
@@ -0,0 +1,25 @@
+//! Comments runner of the `layout!` macro.
+//!
+//! A layout spanning several lines with line, block and doc comments within it must give the same layout as without them.
+
+use typeset::{compile_safe, render};
+use typeset_parser::layout;
+
+#[test]
+fn comments() {
+  let args = layout!{ "a," + "bb," + "ccc" };
+  let commented = layout!{
+    // A call, documented inline
+    "call(" &
+      /* The arguments break all or nothing */
+      seq nest (
+        /// The arguments, bound outside of the macro
+        args
+        //! Closed on the line of the last argument
+      ) &
+      ")"
+  };
+  let plain = layout!{ "call(" & seq nest (args) & ")" };
+  assert_eq!(format!("{:?}", commented), format!("{:?}", plain));
+  assert_eq!(render(compile_safe(commented).unwrap(), 2, 10), "call(a,\n  bb,\n  ccc)")
+}
@@ -0,0 +1,86 @@
+//! Grammar runner of the `layout!` macro.
+//!
+//! Generated from `typeset::grammar()` by the grammar runner of `tests/unit`, which checks the runtime parser against the same cases; do not edit it by hand, but run the grammar runner with `TYPESET_BLESS=1`.
+
+use typeset::text;
+use typeset_parser::layout;
+
+#[test]
+fn grammar() {
+  let v0 = text("v0");
+  let v1 = text("v1");
+  let v2 = text("v2");
+  assert_eq!(format!("{:?}", layout!{ fix "a" }), "Fix(Text(\"a\", None))");
+  assert_eq!(format!("{:?}", layout!{ grp "a" }), "Grp(None, Text(\"a\", None))");
+  assert_eq!(format!("{:?}", layout!{ seq "a" }), "Seq(None, Text(\"a\", None))");
+  assert_eq!(format!("{:?}", layout!{ nest "a" }), "Nest(Text(\"a\", None))");
+  assert_eq!(format!("{:?}", layout!{ pack "a" }), "Pack(Text(\"a\", None))");
+  assert_eq!(format!("{:?}", layout!{ "a" @ "bb" }), "Line(Text(\"a\", None), Text(\"bb\", None))");
+  assert_eq!(format!("{:?}", layout!{ "a" @@ "bb" }), "Line(Text(\"a\", None), Line(Null, Text(\"bb\", None)))");
+  assert_eq!(format!("{:?}", layout!{ "a" & "bb" }), "Comp(Text(\"a\", None), Text(\"bb\", None), Attr { pad: false, fix: false })");
+  assert_eq!(format!("{:?}", layout!{ "a" + "bb" }), "Comp(Text(\"a\", None), Text(\"bb\", None), Attr { pad: true, fix: false })");
+  assert_eq!(format!("{:?}", layout!{ "a" !& "bb" }), "Comp(Text(\"a\", None), Text(\"bb\", None), Attr { pad: false, fix: true })");
+  assert_eq!(format!("{:?}", layout!{ "a" !+ "bb" }), "Comp(Text(\"a\", None), Text(\"bb\", None), Attr { pad: true, fix: true })");
+  assert_eq!(format!("{:?}", layout!{ "a" @ "bb" @@ "c d" & "e\tf" + "\"g\"" !& "a" !+ "bb" }), "Line(Text(\"a\", None), Line(Text(\"bb\", None), Line(Null, Comp(Text(\"c d\", None), Comp(Text(\"e\\tf\", None), Comp(Text(\"\\\"g\\\"\", None), Comp(Text(\"a\", None), Text(\"bb\", None), Attr { pad: true, fix: true }), Attr { pad: false, fix: true }), Attr { pad: true, fix: false }), Attr { pad: false, fix: false }))))");
+  assert_eq!(format!("{:?}", layout!{ fix grp seq nest pack "a" @ fix grp seq nest pack "bb" }), "Line(Fix(Grp(None, Seq(None, Nest(Pack(Text(\"a\", None)))))), Fix(Grp(None, Seq(None, Nest(Pack(Text(\"bb\", None)))))))");
+  assert_eq!(format!("{:?}", layout!{ fix grp seq nest pack ("a" @ "bb") }), "Fix(Grp(None, Seq(None, Nest(Pack(Line(Text(\"a\", None), Text(\"bb\", None)))))))");
+  assert_eq!(format!("{:?}", layout!{ fix grp seq nest pack "a" @@ fix grp seq nest pack "bb" }), "Line(Fix(Grp(None, Seq(None, Nest(Pack(Text(\"a\", None)))))), Line(Null, Fix(Grp(None, Seq(None, Nest(Pack(Text(\"bb\", None))))))))");
+  assert_eq!(format!("{:?}", layout!{ fix grp seq nest pack ("a" @@ "bb") }), "Fix(Grp(None, Seq(None, Nest(Pack(Line(Text(\"a\", None), Line(Null, Text(\"bb\", None))))))))");
+  assert_eq!(format!("{:?}", layout!{ fix grp seq nest pack "a" & fix grp seq nest pack "bb" }), "Comp(Fix(Grp(None, Seq(None, Nest(Pack(Text(\"a\", None)))))), Fix(Grp(None, Seq(None, Nest(Pack(Text(\"bb\", None)))))), Attr { pad: false, fix: false })");
+  assert_eq!(format!("{:?}", layout!{ fix grp seq nest pack ("a" & "bb") }), "Fix(Grp(None, Seq(None, Nest(Pack(Comp(Text(\"a\", None), Text(\"bb\", None), Attr { pad: false, fix: false }))))))");
+  assert_eq!(format!("{:?}", layout!{ fix grp seq nest pack "a" + fix grp seq nest pack "bb" }), "Comp(Fix(Grp(None, Seq(None, Nest(Pack(Text(\"a\", None)))))), Fix(Grp(None, Seq(None, Nest(Pack(Text(\"bb\", None)))))), Attr { pad: true, fix: false })");
+  assert_eq!(format!("{:?}", layout!{ fix grp seq nest pack ("a" + "bb") }), "Fix(Grp(None, Seq(None, Nest(Pack(Comp(Text(\"a\", None), Text(\"bb\", None), Attr { pad: true, fix: false }))))))");
+  assert_eq!(format!("{:?}", layout!{ fix grp seq nest pack "a" !& fix grp seq nest pack "bb" }), "Comp(Fix(Grp(None, Seq(None, Nest(Pack(Text(\"a\", None)))))), Fix(Grp(None, Seq(None, Nest(Pack(Text(\"bb\", None)))))), Attr { pad: false, fix: true })");
+  assert_eq!(format!("{:?}", layout!{ fix grp seq nest pack ("a" !& "bb") }), "Fix(Grp(None, Seq(None, Nest(Pack(Comp(Text(\"a\", None), Text(\"bb\", None), Attr { pad: false, fix: true }))))))");
+  assert_eq!(format!("{:?}", layout!{ fix grp seq nest pack "a" !+ fix grp seq nest pack "bb" }), "Comp(Fix(Grp(None, Seq(None, Nest(Pack(Text(\"a\", None)))))), Fix(Grp(None, Seq(None, Nest(Pack(Text(\"bb\", None)))))), Attr { pad: true, fix: true })");
+  assert_eq!(format!("{:?}", layout!{ fix grp seq nest pack ("a" !+ "bb") }), "Fix(Grp(None, Seq(None, Nest(Pack(Comp(Text(\"a\", None), Text(\"bb\", None), Attr { pad: true, fix: true }))))))");
+  assert_eq!(format!("{:?}", layout!{ v0 @ v1 @ v2 }), "Line(Text(\"v0\", None), Line(Text(\"v1\", None), Text(\"v2\", None)))");
+  assert_eq!(format!("{:?}", layout!{ pack (v1 @ ("c d" @@ "e\tf") @@ v1 !& v0) }), "Pack(Line(Text(\"v1\", None), Line(Line(Text(\"c d\", None), Line(Null, Text(\"e\\tf\", None))), Line(Null, Comp(Text(\"v1\", None), Text(\"v0\", None), Attr { pad: false, fix: true })))))");
+  assert_eq!(format!("{:?}", layout!{ fix "e\tf" & pack ("bb" !& pack null) }), "Comp(Fix(Text(\"e\\tf\", None)), Pack(Comp(Text(\"bb\", None), Pack(Null), Attr { pad: false, fix: true })), Attr { pad: false, fix: false })");
+  assert_eq!(format!("{:?}", layout!{ "\"g\"" @@ nest (pack null & null & "e\tf") }), "Line(Text(\"\\\"g\\\"\", None), Line(Null, Nest(Comp(Pack(Null), Comp(Null, Text(\"e\\tf\", None), Attr { pad: false, fix: false }), Attr { pad: false, fix: false }))))");
+  assert_eq!(format!("{:?}", layout!{ v0 }), "Text(\"v0\", None)");
+  assert_eq!(format!("{:?}", layout!{ nest ((("c d" @ null) !& v2) + ("e\tf" @@ v1) @ "e\tf" + v2) }), "Nest(Comp(Comp(Line(Text(\"c d\", None), Null), Text(\"v2\", None), Attr { pad: false, fix: true }), Line(Line(Text(\"e\\tf\", None), Line(Null, Text(\"v1\", None))), Comp(Text(\"e\\tf\", None), Text(\"v2\", None), Attr { pad: true, fix: false })), Attr { pad: true, fix: false }))");
+  assert_eq!(format!("{:?}", layout!{ "bb" @ v2 !+ null }), "Line(Text(\"bb\", None), Comp(Text(\"v2\", None), Null, Attr { pad: true, fix: true }))");
+  assert_eq!(format!("{:?}", layout!{ ("bb" + seq (null + v1)) !+ fix v1 !+ (null @@ "c d") @ pack null }), "Comp(Comp(Text(\"bb\", None), Seq(None, Comp(Null, Text(\"v1\", None), Attr { pad: true, fix: false })), Attr { pad: true, fix: false }), Comp(Fix(Text(\"v1\", None)), Line(Line(Null, Line(Null, Text(\"c d\", None))), Pack(Null)), Attr { pad: true, fix: true }), Attr { pad: true, fix: true })");
+  assert_eq!(format!("{:?}", layout!{ nest "a" }), "Nest(Text(\"a\", None))");
+  assert_eq!(format!("{:?}", layout!{ "c d" }), "Text(\"c d\", None)");
+  assert_eq!(format!("{:?}", layout!{ (fix "e\tf" !& (null !+ v1) !& "\"g\"" + "c d") @ (fix "bb" !& "e\tf" !+ "\"g\"") & v0 !+ null @ v2 }), "Line(Comp(Fix(Text(\"e\\tf\", None)), Comp(Comp(Null, Text(\"v1\", None), Attr { pad: true, fix: true }), Comp(Text(\"\\\"g\\\"\", None), Text(\"c d\", None), Attr { pad: true, fix: false }), Attr { pad: false, fix: true }), Attr { pad: false, fix: true }), Comp(Comp(Fix(Text(\"bb\", None)), Comp(Text(\"e\\tf\", None), Text(\"\\\"g\\\"\", None), Attr { pad: true, fix: true }), Attr { pad: false, fix: true }), Comp(Text(\"v0\", None), Line(Null, Text(\"v2\", None)), Attr { pad: true, fix: true }), Attr { pad: false, fix: false }))");
+  assert_eq!(format!("{:?}", layout!{ (fix (null @ null) & "\"g\"") !+ "\"g\"" }), "Comp(Comp(Fix(Line(Null, Null)), Text(\"\\\"g\\\"\", None), Attr { pad: false, fix: false }), Text(\"\\\"g\\\"\", None), Attr { pad: true, fix: true })");
+  assert_eq!(format!("{:?}", layout!{ seq nest (null + null + "e\tf") }), "Seq(None, Nest(Comp(Null, Comp(Null, Text(\"e\\tf\", None), Attr { pad: true, fix: false }), Attr { pad: true, fix: false })))");
+  assert_eq!(format!("{:?}", layout!{ "a" @ null }), "Line(Text(\"a\", None), Null)");
+  assert_eq!(format!("{:?}", layout!{ "bb" }), "Text(\"bb\", None)");
+  assert_eq!(format!("{:?}", layout!{ seq ((grp "bb" !& "e\tf" @@ "\"g\"") !+ (null + null) !+ "e\tf" @@ v1) }), "Seq(None, Comp(Comp(Grp(None, Text(\"bb\", None)), Line(Text(\"e\\tf\", None), Line(Null, Text(\"\\\"g\\\"\", None))), Attr { pad: false, fix: true }), Comp(Comp(Null, Null, Attr { pad: true, fix: false }), Line(Text(\"e\\tf\", None), Line(Null, Text(\"v1\", None))), Attr { pad: true, fix: true }), Attr { pad: true, fix: true }))");
+  assert_eq!(format!("{:?}", layout!{ v1 !+ "e\tf" !+ "c d" & "a" }), "Comp(Text(\"v1\", None), Comp(Text(\"e\\tf\", None), Comp(Text(\"c d\", None), Text(\"a\", None), Attr { pad: false, fix: false }), Attr { pad: true, fix: true }), Attr { pad: true, fix: true })");
+  assert_eq!(format!("{:?}", layout!{ "\"g\"" @@ pack v1 }), "Line(Text(\"\\\"g\\\"\", None), Line(Null, Pack(Text(\"v1\", None))))");
+  assert_eq!(format!("{:?}", layout!{ v0 }), "Text(\"v0\", None)");
+  assert_eq!(format!("{:?}", layout!{ null }), "Null");
+  assert_eq!(format!("{:?}", layout!{ ((("a" @ v0) !+ v2 @ "e\tf") !& null) @ "\"g\"" }), "Line(Comp(Comp(Line(Text(\"a\", None), Text(\"v0\", None)), Line(Text(\"v2\", None), Text(\"e\\tf\", None)), Attr { pad: true, fix: true }), Null, Attr { pad: false, fix: true }), Text(\"\\\"g\\\"\", None))");
+  assert_eq!(format!("{:?}", layout!{ (grp ("e\tf" + "a") @ v2) @@ pack null }), "Line(Line(Grp(None, Comp(Text(\"e\\tf\", None), Text(\"a\", None), Attr { pad: true, fix: false })), Text(\"v2\", None)), Line(Null, Pack(Null)))");
+  assert_eq!(format!("{:?}", layout!{ grp v1 }), "Grp(None, Text(\"v1\", None))");
+  assert_eq!(format!("{:?}", layout!{ "c d" }), "Text(\"c d\", None)");
+  assert_eq!(format!("{:?}", layout!{ ("c d" & (null !& "\"g\"") !+ "bb" !& null) & (fix null @ v2 @@ "\"g\"") @@ null }), "Comp(Comp(Text(\"c d\", None), Comp(Comp(Null, Text(\"\\\"g\\\"\", None), Attr { pad: false, fix: true }), Comp(Text(\"bb\", None), Null, Attr { pad: false, fix: true }), Attr { pad: true, fix: true }), Attr { pad: false, fix: false }), Line(Line(Fix(Null), Line(Text(\"v2\", None), Line(Null, Text(\"\\\"g\\\"\", None)))), Line(Null, Null)), Attr { pad: false, fix: false })");
+  assert_eq!(format!("{:?}", layout!{ grp (((v2 !& v1) @ null + v0) & grp null @@ pack null) }), "Grp(None, Comp(Line(Comp(Text(\"v2\", None), Text(\"v1\", None), Attr { pad: false, fix: true }), Comp(Null, Text(\"v0\", None), Attr { pad: true, fix: false })), Line(Grp(None, Null), Line(Null, Pack(Null))), Attr { pad: false, fix: false }))");
+  assert_eq!(format!("{:?}", layout!{ null !+ fix ("\"g\"" @ null) !+ ("c d" & null) @ null @@ "\"g\"" }), "Comp(Null, Comp(Fix(Line(Text(\"\\\"g\\\"\", None), Null)), Line(Comp(Text(\"c d\", None), Null, Attr { pad: false, fix: false }), Line(Null, Line(Null, Text(\"\\\"g\\\"\", None)))), Attr { pad: true, fix: true }), Attr { pad: true, fix: true })");
+  assert_eq!(format!("{:?}", layout!{ nest "\"g\"" & grp ("a" & "bb") }), "Comp(Nest(Text(\"\\\"g\\\"\", None)), Grp(None, Comp(Text(\"a\", None), Text(\"bb\", None), Attr { pad: false, fix: false })), Attr { pad: false, fix: false })");
+  assert_eq!(format!("{:?}", layout!{ null }), "Null");
+  assert_eq!(format!("{:?}", layout!{ grp ((null + nest null) & v1) }), "Grp(None, Comp(Comp(Null, Nest(Null), Attr { pad: true, fix: false }), Text(\"v1\", None), Attr { pad: false, fix: false }))");
+  assert_eq!(format!("{:?}", layout!{ grp seq ("c d" !+ "e\tf") !+ v0 @ ("\"g\"" @ "\"g\"") !+ "bb" @@ "\"g\"" }), "Comp(Grp(None, Seq(None, Comp(Text(\"c d\", None), Text(\"e\\tf\", None), Attr { pad: true, fix: true }))), Line(Text(\"v0\", None), Comp(Line(Text(\"\\\"g\\\"\", None), Text(\"\\\"g\\\"\", None)), Line(Text(\"bb\", None), Line(Null, Text(\"\\\"g\\\"\", None))), Attr { pad: true, fix: true })), Attr { pad: true, fix: true })");
+  assert_eq!(format!("{:?}", layout!{ ((grp "bb" !& v2 !& "e\tf") + seq v0 !& seq v1) & (("bb" & null) + nest v0) !+ (null & null) !& "bb" @ null }), "Comp(Comp(Comp(Grp(None, Text(\"bb\", None)), Comp(Text(\"v2\", None), Text(\"e\\tf\", None), Attr { pad: false, fix: true }), Attr { pad: false, fix: true }), Comp(Seq(None, Text(\"v0\", None)), Seq(None, Text(\"v1\", None)), Attr { pad: false, fix: true }), Attr { pad: true, fix: false }), Comp(Comp(Comp(Text(\"bb\", None), Null, Attr { pad: false, fix: false }), Nest(Text(\"v0\", None)), Attr { pad: true, fix: false }), Comp(Comp(Null, Null, Attr { pad: false, fix: false }), Line(Text(\"bb\", None), Null), Attr { pad: false, fix: true }), Attr { pad: true, fix: true }), Attr { pad: false, fix: false })");
+  assert_eq!(format!("{:?}", layout!{ fix seq grp "e\tf" }), "Fix(Seq(None, Grp(None, Text(\"e\\tf\", None))))");
+  assert_eq!(format!("{:?}", layout!{ null }), "Null");
+  assert_eq!(format!("{:?}", layout!{ fix seq (null @@ "c d") & v1 }), "Comp(Fix(Seq(None, Line(Null, Line(Null, Text(\"c d\", None))))), Text(\"v1\", None), Attr { pad: false, fix: false })");
+  assert_eq!(format!("{:?}", layout!{ "\"g\"" @@ pack seq v1 & ("c d" + null) + grp v0 }), "Line(Text(\"\\\"g\\\"\", None), Line(Null, Comp(Pack(Seq(None, Text(\"v1\", None))), Comp(Comp(Text(\"c d\", None), Null, Attr { pad: true, fix: false }), Grp(None, Text(\"v0\", None)), Attr { pad: true, fix: false }), Attr { pad: false, fix: false })))");
+  assert_eq!(format!("{:?}", layout!{ seq (v0 @@ grp "c d") }), "Seq(None, Line(Text(\"v0\", None), Line(Null, Grp(None, Text(\"c d\", None)))))");
+  assert_eq!(format!("{:?}", layout!{ (null !+ null) @ (("a" !& "e\tf") & pack v0) !& "\"g\"" }), "Line(Comp(Null, Null, Attr { pad: true, fix: true }), Comp(Comp(Comp(Text(\"a\", None), Text(\"e\\tf\", None), Attr { pad: false, fix: true }), Pack(Text(\"v0\", None)), Attr { pad: false, fix: false }), Text(\"\\\"g\\\"\", None), Attr { pad: false, fix: true }))");
+  assert_eq!(format!("{:?}", layout!{ null }), "Null");
+  assert_eq!(format!("{:?}", layout!{ nest "a" }), "Nest(Text(\"a\", None))");
+  assert_eq!(format!("{:?}", layout!{ ("\"g\"" !& "c d" !& "c d" @@ v2) @@ (nest "bb" @ "bb" !+ null) @@ ("\"g\"" & "\"g\"") + "c d" }), "Line(Comp(Text(\"\\\"g\\\"\", None), Comp(Text(\"c d\", None), Line(Text(\"c d\", None), Line(Null, Text(\"v2\", None))), Attr { pad: false, fix: true }), Attr { pad: false, fix: true }), Line(Null, Line(Line(Nest(Text(\"bb\", None)), Comp(Text(\"bb\", None), Null, Attr { pad: true, fix: true })), Line(Null, Comp(Comp(Text(\"\\\"g\\\"\", None), Text(\"\\\"g\\\"\", None), Attr { pad: false, fix: false }), Text(\"c d\", None), Attr { pad: true, fix: false })))))");
+  assert_eq!(format!("{:?}", layout!{ (seq ("\"g\"" @ "e\tf") & nest ("c d" + v1)) @ null }), "Line(Comp(Seq(None, Line(Text(\"\\\"g\\\"\", None), Text(\"e\\tf\", None))), Nest(Comp(Text(\"c d\", None), Text(\"v1\", None), Attr { pad: true, fix: false })), Attr { pad: false, fix: false }), Null)");
+  assert_eq!(format!("{:?}", layout!{ "a" }), "Text(\"a\", None)");
+  assert_eq!(format!("{:?}", layout!{ "\"g\"" }), "Text(\"\\\"g\\\"\", None)");
+  assert_eq!(format!("{:?}", layout!{ "bb" @@ "c d" @@ fix fix "\"g\"" }), "Line(Text(\"bb\", None), Line(Null, Line(Text(\"c d\", None), Line(Null, Fix(Fix(Text(\"\\\"g\\\"\", None)))))))");
+  assert_eq!(format!("{:?}", layout!{ seq (("bb" !+ "c d") @ nest null) & (grp "e\tf" !+ "bb") !& nest pack "bb" }), "Comp(Seq(None, Line(Comp(Text(\"bb\", None), Text(\"c d\", None), Attr { pad: true, fix: true }), Nest(Null))), Comp(Comp(Grp(None, Text(\"e\\tf\", None)), Text(\"bb\", None), Attr { pad: true, fix: true }), Nest(Pack(Text(\"bb\", None))), Attr { pad: false, fix: true }), Attr { pad: false, fix: false })");
+  assert_eq!(format!("{:?}", layout!{ pack ((("c d" @ "e\tf") + v0 !+ null) & v2) }), "Pack(Comp(Comp(Line(Text(\"c d\", None), Text(\"e\\tf\", None)), Comp(Text(\"v0\", None), Null, Attr { pad: true, fix: true }), Attr { pad: true, fix: false }), Text(\"v2\", None), Attr { pad: false, fix: false }))");
+  assert_eq!(format!("{:?}", layout!{ v1 }), "Text(\"v1\", None)");
+  assert_eq!(format!("{:?}", layout!{ grp "\"g\"" + ((null @ v2) + pack "c d") !& seq pack v0 }), "Comp(Grp(None, Text(\"\\\"g\\\"\", None)), Comp(Comp(Line(Null, Text(\"v2\", None)), Pack(Text(\"c d\", None)), Attr { pad: true, fix: false }), Seq(None, Pack(Text(\"v0\", None))), Attr { pad: false, fix: true }), Attr { pad: true, fix: false })");
+}
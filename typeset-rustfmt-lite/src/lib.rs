@@ -0,0 +1,275 @@
+//! A small Rust formatter built on the typeset constructors, laying out a subset of `syn::File`.
+//!
+//! Function signatures, structs and use trees are laid out by hand with groups, sequences and
+//! nests, so that a list is kept on one line when it fits and is otherwise broken with one item
+//! per line, and the predicates of a broken where clause are packed under the first; every other
+//! item, and the statements of function bodies, are laid out from their tokens with
+//! `typeset::from_token_stream`.
+//! The crate is an example and a stress test of the constructors, not a replacement for rustfmt:
+//! comments are not kept, and the output only follows the usual style where it is simple to.
+
+#![allow(clippy::vec_box)]
+
+use quote::{quote, ToTokens};
+use syn::{
+  AttrStyle,
+  Attribute,
+  Expr,
+  ExprLit,
+  Fields,
+  File,
+  Item,
+  ItemFn,
+  ItemStruct,
+  ItemUse,
+  Lit,
+  Meta,
+  ReturnType,
+  UseTree
+};
+use typeset::{
+  Layout,
+  null,
+  text,
+  comp,
+  nest,
+  pack,
+  grp,
+  seq,
+  line,
+  compile_safe,
+  render,
+  from_token_stream
+};
+
+fn _tokens(
+  tokens: impl ToTokens
+) -> Box<Layout> {
+  from_token_stream(tokens.into_token_stream())
+}
+
+/* The items separated by a text fixed to the item before them */
+fn _separated(
+  items: Vec<Box<Layout>>,
+  separator: &str
+) -> Option<Box<Layout>> {
+  items
+    .into_iter()
+    .rev()
+    .reduce(|body, item| comp(
+      comp(item, text(separator.to_string()), false, true),
+      body,
+      true, false
+    ))
+}
+
+/*
+  The items are broken all together by the seq, and the nest indents the
+  broken items; the blocks within the items are kept whole by the grp that
+  the caller puts around each item.
+*/
+fn _block(
+  open: &str,
+  items: Vec<Box<Layout>>,
+  separator: &str,
+  padded: bool,
+  close: &str
+) -> Box<Layout> {
+  let Some(body) = _separated(items, separator) else {
+    return text(format!("{}{}", open, close))
+  };
+  seq(comp(
+    comp(text(open.to_string()), nest(body), padded, false),
+    text(close.to_string()),
+    padded, false
+  ))
+}
+
+/* A doc attribute is given back as the doc comment it was written as */
+fn _attribute(
+  attr: &Attribute
+) -> Box<Layout> {
+  let Meta::NameValue(meta) = &attr.meta else { return _tokens(attr) };
+  let Expr::Lit(ExprLit { lit: Lit::Str(doc), .. }) = &meta.value else { return _tokens(attr) };
+  if !meta.path.is_ident("doc") { return _tokens(attr) }
+  match attr.style {
+    AttrStyle::Outer => text(format!("///{}", doc.value())),
+    AttrStyle::Inner(_) => text(format!("//!{}", doc.value()))
+  }
+}
+
+/* The outer attributes of an item, each on a line of its own before it */
+fn _attributes(
+  attrs: &[Attribute],
+  item: Box<Layout>
+) -> Box<Layout> {
+  attrs.iter().rev().fold(item, |item, attr| line(_attribute(attr), item))
+}
+
+fn _use_tree(
+  tree: &UseTree
+) -> Box<Layout> {
+  match tree {
+    UseTree::Path(path) => {
+      let grouped = matches!(&*path.tree, UseTree::Group(_));
+      comp(text(format!("{}::", path.ident)), _use_tree(&path.tree), false, !grouped)
+    }
+    UseTree::Name(name) => text(name.ident.to_string()),
+    UseTree::Rename(rename) => text(format!("{} as {}", rename.ident, rename.rename)),
+    UseTree::Glob(_) => text("*"),
+    UseTree::Group(group) =>
+      _block("{", group.items.iter().map(|tree| grp(_use_tree(tree))).collect(), ",", false, "}")
+  }
+}
+
+fn _use(
+  item: &ItemUse
+) -> Box<Layout> {
+  let ItemUse { attrs, vis, leading_colon, tree, .. } = item;
+  let head = _tokens(quote!(#vis use #leading_colon));
+  _attributes(attrs, comp(
+    comp(head, _use_tree(tree), true, true),
+    text(";"),
+    false, true
+  ))
+}
+
+fn _where(
+  layout: Box<Layout>,
+  clause: Option<&syn::WhereClause>
+) -> Box<Layout> {
+  let predicates = clause.map(|clause| clause.predicates.iter().map(_tokens).collect());
+  match predicates.and_then(|predicates| _separated(predicates, ",")) {
+    None => layout,
+    Some(predicates) =>
+      comp(layout, grp(seq(comp(text("where"), pack(predicates), true, false))), true, false)
+  }
+}
+
+fn _struct(
+  item: &ItemStruct
+) -> Box<Layout> {
+  let ItemStruct { attrs, vis, ident, generics, fields, .. } = item;
+  let head = _tokens(quote!(#vis struct #ident #generics));
+  let clause = generics.where_clause.as_ref();
+  let layout = match fields {
+    Fields::Named(named) => {
+      let fields = named.named.iter().map(|field| grp(_tokens(field))).collect();
+      comp(_where(head, clause), _block("{", fields, ",", true, "}"), true, true)
+    }
+    Fields::Unnamed(unnamed) => {
+      let fields = unnamed.unnamed.iter().map(|field| grp(_tokens(field))).collect();
+      let head = comp(head, _block("(", fields, ",", false, ")"), false, true);
+      comp(_where(head, clause), text(";"), false, true)
+    }
+    Fields::Unit => comp(_where(head, clause), text(";"), false, true)
+  };
+  _attributes(attrs, layout)
+}
+
+fn _fn(
+  item: &ItemFn
+) -> Box<Layout> {
+  let ItemFn { attrs, vis, sig, block } = item;
+  let syn::Signature { constness, asyncness, unsafety, abi, ident, generics, inputs, variadic, output, .. } = sig;
+  let head = _tokens(quote!(#vis #constness #asyncness #unsafety #abi fn #ident #generics));
+  let mut params: Vec<Box<Layout>> = inputs.iter().map(_tokens).collect();
+  if let Some(variadic) = variadic { params.push(_tokens(variadic)) }
+  let mut signature = comp(head, _block("(", params, ",", false, ")"), false, true);
+  if let ReturnType::Type(arrow, ty) = output {
+    signature = comp(signature, _tokens(quote!(#arrow #ty)), true, true)
+  }
+  let signature = _where(signature, generics.where_clause.as_ref());
+  let statements = block.stmts.iter().map(_tokens).collect();
+  _attributes(attrs, comp(signature, _block("{", statements, "", true, "}"), true, true))
+}
+
+/* Lay out an item, falling back to its tokens for the items without a layout of their own */
+fn _item(
+  item: &Item
+) -> Box<Layout> {
+  match item {
+    Item::Use(item) => _use(item),
+    Item::Struct(item) => _struct(item),
+    Item::Fn(item) => _fn(item),
+    item => _tokens(item)
+  }
+}
+
+/*
+  The inner attributes and items of a file, each with whether a blank line
+  is put before it; there is none before the first part, between attributes
+  or between consecutive use declarations.
+*/
+fn _parts(
+  file: &File
+) -> Vec<(Box<Layout>, bool)> {
+  let mut parts = Vec::new();
+  for attr in &file.attrs {
+    parts.push((_attribute(attr), false))
+  }
+  let mut used = false;
+  for item in &file.items {
+    let blank = !parts.is_empty() && !(used && matches!(item, Item::Use(_)));
+    used = matches!(item, Item::Use(_));
+    parts.push((_item(item), blank))
+  }
+  parts
+}
+
+/// Lays out a `syn::File`, with its inner attributes first and its items after, where the items are separated by a blank line, except between consecutive use declarations.
+///
+/// # Examples
+/// ```
+/// use typeset::{compile_safe, render};
+/// use typeset_rustfmt_lite::format_file;
+///
+/// let file = syn::parse_file("use std::{fmt,io};struct Unit;").unwrap();
+/// let document = compile_safe(format_file(&file)).unwrap();
+/// assert_eq!(render(document, 2, 80), "use std::{fmt, io};\n\nstruct Unit;");
+/// ```
+#[must_use]
+pub fn format_file(
+  file: &File
+) -> Box<Layout> {
+  let mut parts = _parts(file).into_iter();
+  let Some((mut result, _)) = parts.next() else { return null() };
+  for (part, blank) in parts {
+    result =
+      if blank { line(result, line(null(), part)) }
+      else { line(result, part) }
+  }
+  result
+}
+
+/// Formats Rust source within the given width, parsing it with `syn` and laying it out as `format_file` does.
+///
+/// The parts of the file are compiled and rendered one at a time, since they are separated by lines anyway, which keeps the layouts given to the compiler small however long the file is.
+/// A part that fails to compile is reported as a `syn::Error` spanning the whole source.
+///
+/// # Examples
+/// ```
+/// use typeset_rustfmt_lite::format_source;
+///
+/// let source = "pub fn add(left: u64, right: u64) -> u64 { left + right }";
+/// assert_eq!(format_source(source, 80).unwrap(), source);
+/// assert_eq!(
+///   format_source(source, 30).unwrap(),
+///   "pub fn add(\n  left: u64,\n  right: u64\n) -> u64 {\n  left + right\n}"
+/// );
+/// ```
+pub fn format_source(
+  source: &str,
+  width: usize
+) -> syn::Result<String> {
+  let file = syn::parse_file(source)?;
+  let mut result = String::new();
+  for (part, blank) in _parts(&file) {
+    if !result.is_empty() { result.push('\n') }
+    if blank { result.push('\n') }
+    let document = compile_safe(part)
+      .map_err(|error| syn::Error::new(proc_macro2::Span::call_site(), error))?;
+    result.push_str(&render(document, 2, width))
+  }
+  Ok(result)
+}
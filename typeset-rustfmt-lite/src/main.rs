@@ -0,0 +1,32 @@
+//! Formats the Rust source read from standard input, within the width given as the only argument.
+//!
+//! Run with `cargo run -p typeset-rustfmt-lite -- 80 < src/lib.rs`.
+
+use std::io::{self, Read};
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+  let width = match std::env::args().nth(1).map(|arg| arg.parse::<usize>()) {
+    None => 80,
+    Some(Ok(width)) => width,
+    Some(Err(error)) => {
+      eprintln!("invalid width: {}", error);
+      return ExitCode::FAILURE
+    }
+  };
+  let mut source = String::new();
+  if let Err(error) = io::stdin().read_to_string(&mut source) {
+    eprintln!("failed to read standard input: {}", error);
+    return ExitCode::FAILURE
+  }
+  match typeset_rustfmt_lite::format_source(&source, width) {
+    Ok(output) => {
+      println!("{}", output);
+      ExitCode::SUCCESS
+    }
+    Err(error) => {
+      eprintln!("failed to parse: {}", error);
+      ExitCode::FAILURE
+    }
+  }
+}
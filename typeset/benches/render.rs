@@ -0,0 +1,121 @@
+use std::time::Instant;
+
+use typeset::{
+  Layout,
+  null,
+  text,
+  fix,
+  comp,
+  line,
+  nest,
+  seq,
+  pack,
+  compile_safe,
+  render,
+  LayoutArena,
+  LayoutId,
+  render_to,
+  RenderOptions,
+  PackScope
+};
+
+fn _document(lines: usize, words: usize) -> Box<Layout> {
+  fn _line(index: usize, words: usize) -> Box<Layout> {
+    (1..words).rev().fold(
+      text(format!("word{}", index)),
+      |layout, word| comp(
+        layout,
+        nest(text(format!("word{}", word))),
+        true, false
+      )
+    )
+  }
+  (1..lines).fold(seq(_line(0, words)), |layout, index|
+    line(layout, seq(_line(index, words))))
+}
+
+fn _document_packs(lines: usize, words: usize) -> Box<Layout> {
+  fn _line(index: usize, words: usize) -> Box<Layout> {
+    let args = (1..words).rev().fold(
+      text(format!("arg{}", index)),
+      |layout, word| comp(layout, pack(text(format!("arg{}", word))), true, false)
+    );
+    comp(text(format!("call{}(", index)), pack(args), false, false)
+  }
+  (1..lines).fold(_line(0, words), |layout, index|
+    line(layout, _line(index, words)))
+}
+
+fn _document_fixed(words: usize) -> Box<Layout> {
+  (0..words).fold(null(), |layout, _| comp(layout, text("abc".to_string()), true, false))
+}
+
+fn _document_arena(arena: &mut LayoutArena, lines: usize, words: usize) -> LayoutId {
+  fn _line(arena: &mut LayoutArena, index: usize, words: usize) -> LayoutId {
+    let first = arena.text(format!("word{}", index));
+    (1..words).rev().fold(first, |layout, word| {
+      let word1 = arena.text(format!("word{}", word));
+      let word2 = arena.nest(word1);
+      arena.comp(layout, word2, true, false)
+    })
+  }
+  let first = _line(arena, 0, words);
+  let first1 = arena.seq(first);
+  (1..lines).fold(first1, |layout, index| {
+    let line1 = _line(arena, index, words);
+    let line2 = arena.seq(line1);
+    arena.line(layout, line2)
+  })
+}
+
+fn _bench<F: FnMut() -> usize>(name: &str, iterations: u32, mut run: F) {
+  let start = Instant::now();
+  let mut bytes = 0;
+  for _ in 0..iterations { bytes = run(); }
+  let elapsed = start.elapsed() / iterations;
+  println!("{:<24} {:>10} bytes {:>12.3?}", name, bytes, elapsed);
+}
+
+fn main() {
+  _bench("compile", 2, || {
+    let layout = _document(13_000, 12);
+    compile_safe(layout).unwrap().to_string().len()
+  });
+  _bench("compile_arena", 2, || {
+    let mut arena = LayoutArena::with_capacity(13_000 * 25);
+    let layout = _document_arena(&mut arena, 13_000, 12);
+    arena.compile(layout).unwrap().to_string().len()
+  });
+  let layout = _document(13_000, 12);
+  let document = compile_safe(layout).unwrap();
+  let options = RenderOptions::new(2, 80);
+  _bench("render", 5, || {
+    render(document.clone(), options.tab, options.width).len()
+  });
+  _bench("render_to", 5, || {
+    let mut output = String::new();
+    render_to(&document, &options, &mut output).unwrap();
+    output.len()
+  });
+  let layout = _document_packs(13_000, 12);
+  let document = compile_safe(layout).unwrap();
+  _bench("render_packs", 5, || {
+    let mut output = String::new();
+    render_to(&document, &options, &mut output).unwrap();
+    output.len()
+  });
+  let options1 = RenderOptions { pack_scope: PackScope::Line, ..options };
+  _bench("render_packs_line", 5, || {
+    let mut output = String::new();
+    render_to(&document, &options1, &mut output).unwrap();
+    output.len()
+  });
+  for words in [25_000, 100_000] {
+    let document = compile_safe(fix(_document_fixed(words))).unwrap();
+    _bench(&format!("render_fixed_{}", words), 5, || {
+      let mut output = String::new();
+      render_to(&document, &options, &mut output).unwrap();
+      output.len()
+    });
+  }
+}
@@ -0,0 +1,115 @@
+//! A JSON pretty printer; arrays and objects are laid out on one line when they fit, and otherwise with one item per line.
+//!
+//! Run with `cargo run --example json`.
+
+use typeset::{
+  Layout,
+  text,
+  comp,
+  nest,
+  grp,
+  seq,
+  compile_safe,
+  render
+};
+
+enum Json {
+  Null,
+  Bool(bool),
+  Number(f64),
+  String(String),
+  Array(Vec<Json>),
+  Object(Vec<(String, Json)>)
+}
+
+fn _string(data: &str) -> String {
+  let mut result = String::from("\"");
+  for c in data.chars() {
+    match c {
+      '"' => result.push_str("\\\""),
+      '\\' => result.push_str("\\\\"),
+      '\n' => result.push_str("\\n"),
+      '\t' => result.push_str("\\t"),
+      c if c.is_control() => result.push_str(&format!("\\u{:04x}", c as u32)),
+      c => result.push(c)
+    }
+  }
+  result.push('"');
+  result
+}
+
+/*
+  The items are separated by commas fixed to the item before them, and are
+  broken all together by the seq; the nest indents the broken items, and the
+  grp keeps the block whole while the blocks around it are broken.
+*/
+fn _block(
+  open: &str,
+  items: impl DoubleEndedIterator<Item = Box<Layout>>,
+  close: &str
+) -> Box<Layout> {
+  let body = items
+    .rev()
+    .reduce(|body, item| comp(
+      comp(item, text(",".to_string()), false, true),
+      body,
+      true, false
+    ));
+  let Some(body) = body else {
+    return text(format!("{}{}", open, close))
+  };
+  grp(seq(comp(
+    comp(text(open.to_string()), nest(body), false, false),
+    text(close.to_string()),
+    false, false
+  )))
+}
+
+fn layout_json(json: &Json) -> Box<Layout> {
+  match json {
+    Json::Null => text("null".to_string()),
+    Json::Bool(value) => text(value.to_string()),
+    Json::Number(value) => text(value.to_string()),
+    Json::String(data) => text(_string(data)),
+    Json::Array(items) =>
+      _block("[", items.iter().map(layout_json), "]"),
+    Json::Object(entries) =>
+      _block("{", entries.iter().map(|(key, value)| comp(
+        text(format!("{}:", _string(key))),
+        layout_json(value),
+        true, true
+      )), "}")
+  }
+}
+
+fn main() {
+  let json = Json::Object(vec![
+    ("name".to_string(), Json::String("typeset".to_string())),
+    ("version".to_string(), Json::Number(2.0)),
+    ("keywords".to_string(), Json::Array(vec![
+      Json::String("dsl".to_string()),
+      Json::String("pretty".to_string()),
+      Json::String("printer".to_string())
+    ])),
+    ("license".to_string(), Json::Null),
+    ("stable".to_string(), Json::Bool(true)),
+    ("dependencies".to_string(), Json::Object(Vec::new()))
+  ]);
+  let wide = render(compile_safe(layout_json(&json)).unwrap(), 2, 140);
+  assert_eq!(wide, concat!(
+    "{\"name\": \"typeset\", \"version\": 2, \"keywords\": [\"dsl\", \"pretty\", \"printer\"], ",
+    "\"license\": null, \"stable\": true, \"dependencies\": {}}"
+  ));
+  let narrow = render(compile_safe(layout_json(&json)).unwrap(), 2, 50);
+  assert_eq!(narrow, concat!(
+    "{\n",
+    "  \"name\": \"typeset\",\n",
+    "  \"version\": 2,\n",
+    "  \"keywords\": [\"dsl\", \"pretty\", \"printer\"],\n",
+    "  \"license\": null,\n",
+    "  \"stable\": true,\n",
+    "  \"dependencies\": {}\n",
+    "}"
+  ));
+  println!("{}\n\n{}", wide, narrow)
+}
@@ -0,0 +1,74 @@
+//! An S-expression formatter; a list keeps its head and first argument together, and when broken puts each of the other arguments on a line of its own, indented with a nest.
+//!
+//! Run with `cargo run --example sexp`.
+
+use typeset::{
+  Layout,
+  text,
+  comp,
+  grp,
+  seq,
+  nest,
+  compile_safe,
+  render
+};
+
+enum Sexp {
+  Atom(String),
+  List(Vec<Sexp>)
+}
+
+fn _atom(data: &str) -> Sexp {
+  Sexp::Atom(data.to_string())
+}
+
+fn layout_sexp(sexp: &Sexp) -> Box<Layout> {
+  match sexp {
+    Sexp::Atom(data) => text(data.clone()),
+    Sexp::List(items) => {
+      let mut items = items.iter().map(layout_sexp);
+      let Some(head) = items.next() else {
+        return text("()".to_string())
+      };
+      let mut head1 = comp(text("(".to_string()), head, false, true);
+      if let Some(first) = items.next() {
+        head1 = comp(head1, first, true, true)
+      }
+      let body = match items.reduce(|args, item| comp(args, item, true, false)) {
+        None => head1,
+        Some(args) => seq(comp(head1, nest(args), true, false))
+      };
+      grp(comp(body, text(")".to_string()), false, true))
+    }
+  }
+}
+
+fn main() {
+  let sexp = Sexp::List(vec![
+    _atom("define"),
+    Sexp::List(vec![_atom("fact"), _atom("n")]),
+    Sexp::List(vec![
+      _atom("if"),
+      Sexp::List(vec![_atom("<="), _atom("n"), _atom("1")]),
+      _atom("1"),
+      Sexp::List(vec![
+        _atom("*"),
+        _atom("n"),
+        Sexp::List(vec![
+          _atom("fact"),
+          Sexp::List(vec![_atom("-"), _atom("n"), _atom("1")])
+        ])
+      ])
+    ])
+  ]);
+  let wide = render(compile_safe(layout_sexp(&sexp)).unwrap(), 2, 80);
+  assert_eq!(wide, "(define (fact n) (if (<= n 1) 1 (* n (fact (- n 1)))))");
+  let narrow = render(compile_safe(layout_sexp(&sexp)).unwrap(), 2, 30);
+  assert_eq!(narrow, concat!(
+    "(define (fact n)\n",
+    "  (if (<= n 1)\n",
+    "    1\n",
+    "    (* n (fact (- n 1)))))"
+  ));
+  println!("{}\n\n{}", wide, narrow)
+}
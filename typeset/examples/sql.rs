@@ -0,0 +1,88 @@
+//! A skeleton of a SQL formatter for simple queries; the clauses of a query are broken all together, and the items of a clause are aligned after its keyword with a pack.
+//!
+//! Run with `cargo run --example sql`.
+
+use typeset::{
+  Layout,
+  null,
+  text,
+  comp,
+  grp,
+  seq,
+  pack,
+  compile_safe,
+  render
+};
+
+struct Select {
+  columns: Vec<String>,
+  from: String,
+  conditions: Vec<String>,
+  order_by: Vec<String>
+}
+
+fn _join(items: &[String], separator: &str, fixed: bool) -> Box<Layout> {
+  items.iter()
+    .rev()
+    .map(|item| text(item.clone()))
+    .reduce(|right, left| {
+      let left1 = if fixed {
+        comp(left, text(separator.to_string()), false, true)
+      } else { left };
+      let right1 = if fixed { right } else {
+        comp(text(separator.to_string()), right, true, true)
+      };
+      comp(left1, right1, true, false)
+    })
+    .unwrap_or_else(null)
+}
+
+fn _clause(keyword: &str, body: Box<Layout>) -> Box<Layout> {
+  grp(comp(text(keyword.to_string()), pack(body), true, false))
+}
+
+fn layout_select(select: &Select) -> Box<Layout> {
+  let columns = _clause("SELECT", seq(_join(&select.columns, ",", true)));
+  let from = _clause("FROM", text(select.from.clone()));
+  let conditions = if select.conditions.is_empty() { null() } else {
+    _clause("WHERE", _join(&select.conditions, "AND", false))
+  };
+  let order_by = if select.order_by.is_empty() { null() } else {
+    _clause("ORDER BY", _join(&select.order_by, ",", true))
+  };
+  seq(comp(
+    comp(comp(columns, from, true, false), conditions, true, false),
+    order_by,
+    true, false
+  ))
+}
+
+fn main() {
+  let select = Select {
+    columns: ["id", "name", "email", "created_at"].map(String::from).to_vec(),
+    from: "users".to_string(),
+    conditions: ["active = 1", "age > 18"].map(String::from).to_vec(),
+    order_by: vec!["name".to_string()]
+  };
+  let wide = render(compile_safe(layout_select(&select)).unwrap(), 2, 100);
+  assert_eq!(wide, "SELECT id, name, email, created_at FROM users WHERE active = 1 AND age > 18 ORDER BY name");
+  let medium = render(compile_safe(layout_select(&select)).unwrap(), 2, 40);
+  assert_eq!(medium, concat!(
+    "SELECT id, name, email, created_at\n",
+    "FROM users\n",
+    "WHERE active = 1 AND age > 18\n",
+    "ORDER BY name"
+  ));
+  let narrow = render(compile_safe(layout_select(&select)).unwrap(), 2, 20);
+  assert_eq!(narrow, concat!(
+    "SELECT id,\n",
+    "       name,\n",
+    "       email,\n",
+    "       created_at\n",
+    "FROM users\n",
+    "WHERE active = 1\n",
+    "      AND age > 18\n",
+    "ORDER BY name"
+  ));
+  println!("{}\n\n{}\n\n{}", wide, medium, narrow)
+}
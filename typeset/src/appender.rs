@@ -0,0 +1,92 @@
+use crate::{
+  compiler::{
+    Layout,
+    CompileOptions,
+    RenderOptions,
+    render_into
+  },
+  error::CompilerError,
+  session::Session
+};
+
+/// A builder of a growing document, e.g. the log or report of a long-running tool, which is given layouts over time and keeps the rendering of everything given so far.
+///
+/// Each layout is compiled and rendered once, when it is pushed, as a document of its own starting on a new line; the output so far is kept, such that only the lines of the new layout are rendered, however long the output grows.
+/// The layouts are compiled in a `Session`, so pushing many layouts does not allocate the memory of the compiler anew for each of them.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, DocAppender, RenderOptions};
+///
+/// let mut appender = DocAppender::new(RenderOptions::new(2, 8));
+/// assert_eq!(appender.push(comp(text("foo"), text("bar"), true, false)).unwrap(), "foo bar");
+/// assert_eq!(appender.push(comp(text("foo"), text("barbaz"), true, false)).unwrap(), "\nfoo\nbarbaz");
+/// assert_eq!(appender.output(), "foo bar\nfoo\nbarbaz");
+/// assert_eq!(appender.len(), 2);
+/// ```
+#[derive(Debug, Default)]
+pub struct DocAppender {
+  session: Session,
+  options: RenderOptions,
+  output: String,
+  len: usize
+}
+
+impl DocAppender {
+  /// Constructs a new appender with the given render options and the default compile options.
+  #[must_use]
+  pub fn new(
+    options: RenderOptions
+  ) -> Self {
+    DocAppender::with_options(CompileOptions::default(), options)
+  }
+
+  /// Constructs a new appender with the given compile and render options.
+  #[must_use]
+  pub fn with_options(
+    compile_options: CompileOptions,
+    options: RenderOptions
+  ) -> Self {
+    DocAppender {
+      session: Session::with_options(compile_options),
+      options,
+      output: String::new(),
+      len: 0
+    }
+  }
+
+  /// Compiles and renders a layout after the layouts pushed so far, and gives the output it added, which starts with a newline unless it is the first.
+  ///
+  /// A layout that fails to compile is not added, and the output is left as it was.
+  pub fn push(
+    &mut self,
+    layout: Box<Layout>
+  ) -> Result<&str, CompilerError> {
+    let doc = self.session.compile(layout)?;
+    let start = self.output.len();
+    if self.len != 0 { self.output.push('\n') }
+    render_into(&doc, &self.options, &mut self.output);
+    self.len += 1;
+    Ok(&self.output[start..])
+  }
+
+  /// Gives the rendering of every layout pushed so far.
+  pub fn output(&self) -> &str {
+    &self.output
+  }
+
+  /// Gives the number of layouts pushed so far.
+  pub fn len(&self) -> usize {
+    self.len
+  }
+
+  /// Tells whether no layout has been pushed yet.
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+
+  /// Gives the output so far, and leaves the appender without output, such that the output of the following layouts can be taken piece by piece; the layouts pushed so far are still counted.
+  pub fn take_output(&mut self) -> String {
+    std::mem::take(&mut self.output)
+  }
+}
@@ -0,0 +1,617 @@
+use std::borrow::Cow;
+
+use crate::{
+  compiler::{
+    Layout,
+    LayoutView,
+    LayoutSource,
+    Attr,
+    Annotation,
+    Hint,
+    PackAnchor,
+    Doc,
+    CompileOptions,
+    _compile_traced
+  },
+  error::CompilerError
+};
+
+/// The id of a layout in a `LayoutArena`, only meaningful for the arena that gave it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LayoutId(u32);
+
+#[derive(Debug, Clone)]
+enum Node {
+  Null,
+  Text(Cow<'static, str>, Option<usize>),
+  PackReset,
+  Slot(Cow<'static, str>),
+  Annotate(Annotation, LayoutId),
+  Fix(LayoutId),
+  Grp(Option<Cow<'static, str>>, LayoutId),
+  Seq(Option<Cow<'static, str>>, LayoutId),
+  Nest(LayoutId),
+  Pack(LayoutId),
+  PackGroup(Vec<PackAnchor>, LayoutId),
+  PackAt(PackAnchor, LayoutId),
+  Line(LayoutId, LayoutId),
+  Comp(LayoutId, LayoutId, Attr)
+}
+
+/// An arena of layouts, an alternative to the boxed layout constructors for large documents, where the layouts are kept in one buffer and referred to by ids.
+///
+/// The constructors of the arena mirror the layout constructors of the same names, and the arena is compiled directly, without building a `Box<Layout>` for every node.
+/// Giving an arena an id from another arena may panic or refer to an unrelated layout.
+///
+/// # Examples
+/// ```
+/// use typeset::{LayoutArena, render};
+///
+/// let mut arena = LayoutArena::new();
+/// let foo = arena.text("foo");
+/// let bar = arena.text("bar");
+/// let layout = arena.comp(foo, bar, true, false);
+/// let document = arena.compile(layout).unwrap();
+/// assert_eq!(render(document.clone(), 2, 80), "foo bar");
+/// assert_eq!(render(document, 2, 6), "foo\nbar");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct LayoutArena {
+  nodes: Vec<Node>
+}
+
+#[derive(Copy, Clone)]
+struct ArenaLayout<'s> {
+  arena: &'s LayoutArena,
+  id: LayoutId
+}
+
+impl<'s> ArenaLayout<'s> {
+  fn _at(self, id: LayoutId) -> Self {
+    ArenaLayout { arena: self.arena, id }
+  }
+}
+
+impl<'s> LayoutSource<'s> for ArenaLayout<'s> {
+  fn view(self) -> LayoutView<'s, Self> {
+    match &self.arena.nodes[self.id.0 as usize] {
+      Node::Null => LayoutView::Null,
+      Node::Text(data, width) => LayoutView::Text(data, *width),
+      Node::PackReset => LayoutView::PackReset,
+      Node::Slot(name) => LayoutView::Slot(name),
+      Node::Annotate(annotation, id) => LayoutView::Annotate(annotation, self._at(*id)),
+      Node::Fix(id) => LayoutView::Fix(self._at(*id)),
+      Node::Grp(name, id) => LayoutView::Grp(name.as_deref(), self._at(*id)),
+      Node::Seq(name, id) => LayoutView::Seq(name.as_deref(), self._at(*id)),
+      Node::Nest(id) => LayoutView::Nest(self._at(*id)),
+      Node::Pack(id) => LayoutView::Pack(self._at(*id)),
+      Node::PackGroup(anchors, id) => LayoutView::PackGroup(anchors, self._at(*id)),
+      Node::PackAt(anchor, id) => LayoutView::PackAt(anchor, self._at(*id)),
+      Node::Line(left, right) => LayoutView::Line(self._at(*left), self._at(*right)),
+      Node::Comp(left, right, attr) =>
+        LayoutView::Comp(self._at(*left), self._at(*right), *attr)
+    }
+  }
+}
+
+impl LayoutArena {
+  /// Constructs a new empty arena.
+  #[must_use]
+  pub fn new() -> Self {
+    LayoutArena { nodes: Vec::new() }
+  }
+
+  /// Constructs a new empty arena with room for the given number of layouts.
+  #[must_use]
+  pub fn with_capacity(
+    capacity: usize
+  ) -> Self {
+    LayoutArena { nodes: Vec::with_capacity(capacity) }
+  }
+
+  /// Gives the number of layouts in the arena.
+  pub fn len(&self) -> usize {
+    self.nodes.len()
+  }
+
+  /// Tells whether the arena has no layouts.
+  pub fn is_empty(&self) -> bool {
+    self.nodes.is_empty()
+  }
+
+  fn _push(&mut self, node: Node) -> LayoutId {
+    let id = u32::try_from(self.nodes.len())
+      .expect("A layout arena holds at most u32::MAX layouts");
+    self.nodes.push(node);
+    LayoutId(id)
+  }
+
+  /// Constructs a new Null layout in the arena, see `null`.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{LayoutArena, render};
+  ///
+  /// let mut arena = LayoutArena::new();
+  /// let foo = arena.text("foo");
+  /// let null = arena.null();
+  /// let layout = arena.comp(foo, null, true, false);
+  /// assert_eq!(render(arena.compile(layout).unwrap(), 2, 80), "foo");
+  /// ```
+  #[must_use]
+  pub fn null(&mut self) -> LayoutId {
+    self._push(Node::Null)
+  }
+
+  /// Constructs a new Text layout in the arena, see `text`.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{LayoutArena, render};
+  ///
+  /// let mut arena = LayoutArena::new();
+  /// let layout = arena.text("foo");
+  /// assert_eq!(render(arena.compile(layout).unwrap(), 2, 80), "foo");
+  /// ```
+  #[must_use]
+  pub fn text(
+    &mut self,
+    data: impl Into<Cow<'static, str>>
+  ) -> LayoutId {
+    self._push(Node::Text(data.into(), None))
+  }
+
+  /// Constructs a new Text layout with a given display width in the arena, see `text_with_width`.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{LayoutArena, render};
+  ///
+  /// let mut arena = LayoutArena::new();
+  /// let foo = arena.text_with_width("\x1b[1mfoo\x1b[0m", 3);
+  /// let bar = arena.text("bar");
+  /// let layout = arena.comp(foo, bar, true, false);
+  /// assert_eq!(render(arena.compile(layout).unwrap(), 2, 7), "\x1b[1mfoo\x1b[0m bar");
+  /// ```
+  #[must_use]
+  pub fn text_with_width(
+    &mut self,
+    data: impl Into<Cow<'static, str>>,
+    display_width: usize
+  ) -> LayoutId {
+    self._push(Node::Text(data.into(), Some(display_width)))
+  }
+
+  /// Constructs a new Fix layout in the arena, see `fix`.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{LayoutArena, render};
+  ///
+  /// let mut arena = LayoutArena::new();
+  /// let foo = arena.text("foo");
+  /// let bar = arena.text("bar");
+  /// let foobar = arena.comp(foo, bar, false, false);
+  /// let layout = arena.fix(foobar);
+  /// assert_eq!(render(arena.compile(layout).unwrap(), 2, 3), "foobar");
+  /// ```
+  #[must_use]
+  pub fn fix(&mut self, layout: LayoutId) -> LayoutId {
+    self._push(Node::Fix(layout))
+  }
+
+  /// Constructs a new Grp layout in the arena, see `grp`.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{LayoutArena, render};
+  ///
+  /// let mut arena = LayoutArena::new();
+  /// let foo = arena.text("foo");
+  /// let bar = arena.text("bar");
+  /// let baz = arena.text("baz");
+  /// let barbaz = arena.comp(bar, baz, true, false);
+  /// let group = arena.grp(barbaz);
+  /// let layout = arena.comp(foo, group, true, false);
+  /// assert_eq!(render(arena.compile(layout).unwrap(), 2, 8), "foo\nbar baz");
+  /// ```
+  #[must_use]
+  pub fn grp(&mut self, layout: LayoutId) -> LayoutId {
+    self._push(Node::Grp(None, layout))
+  }
+
+  /// Constructs a new named Grp layout in the arena, see `grp_named`.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{LayoutArena, render};
+  ///
+  /// let mut arena = LayoutArena::new();
+  /// let foo = arena.text("foo");
+  /// let bar = arena.text("bar");
+  /// let foobar = arena.comp(foo, bar, true, false);
+  /// let layout = arena.grp_named("args", foobar);
+  /// assert_eq!(render(arena.compile(layout).unwrap(), 2, 80), "foo bar");
+  /// ```
+  #[must_use]
+  pub fn grp_named(
+    &mut self,
+    name: impl Into<Cow<'static, str>>,
+    layout: LayoutId
+  ) -> LayoutId {
+    self._push(Node::Grp(Some(name.into()), layout))
+  }
+
+  /// Constructs a new Grp layout with a break hint in the arena, see `grp_hint`.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{LayoutArena, Hint, render};
+  ///
+  /// let mut arena = LayoutArena::new();
+  /// let foo = arena.text("foo");
+  /// let bar = arena.text("barbaz");
+  /// let foobar = arena.comp(foo, bar, true, false);
+  /// let layout = arena.grp_hint(foobar, Hint::PreferFlat);
+  /// assert_eq!(render(arena.compile(layout).unwrap(), 2, 8), "foo barbaz");
+  /// ```
+  #[must_use]
+  pub fn grp_hint(
+    &mut self,
+    layout: LayoutId,
+    hint: Hint
+  ) -> LayoutId {
+    let hinted = self._push(Node::Annotate(Annotation::Hint(hint), layout));
+    self._push(Node::Grp(None, hinted))
+  }
+
+  /// Constructs a new Seq layout in the arena, see `seq`.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{LayoutArena, render};
+  ///
+  /// let mut arena = LayoutArena::new();
+  /// let foo = arena.text("foo");
+  /// let bar = arena.text("bar");
+  /// let baz = arena.text("baz");
+  /// let barbaz = arena.comp(bar, baz, true, false);
+  /// let foobarbaz = arena.comp(foo, barbaz, true, false);
+  /// let layout = arena.seq(foobarbaz);
+  /// assert_eq!(render(arena.compile(layout).unwrap(), 2, 8), "foo\nbar\nbaz");
+  /// ```
+  #[must_use]
+  pub fn seq(&mut self, layout: LayoutId) -> LayoutId {
+    self._push(Node::Seq(None, layout))
+  }
+
+  /// Constructs a new named Seq layout in the arena, see `seq_named`.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{LayoutArena, render};
+  ///
+  /// let mut arena = LayoutArena::new();
+  /// let foo = arena.text("foo");
+  /// let bar = arena.text("bar");
+  /// let foobar = arena.comp(foo, bar, true, false);
+  /// let layout = arena.seq_named("items", foobar);
+  /// assert_eq!(render(arena.compile(layout).unwrap(), 2, 5), "foo\nbar");
+  /// ```
+  #[must_use]
+  pub fn seq_named(
+    &mut self,
+    name: impl Into<Cow<'static, str>>,
+    layout: LayoutId
+  ) -> LayoutId {
+    self._push(Node::Seq(Some(name.into()), layout))
+  }
+
+  /// Constructs a new Nest layout in the arena, see `nest`.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{LayoutArena, render};
+  ///
+  /// let mut arena = LayoutArena::new();
+  /// let foo = arena.text("foo");
+  /// let bar = arena.text("bar");
+  /// let nested = arena.nest(bar);
+  /// let layout = arena.comp(foo, nested, false, false);
+  /// assert_eq!(render(arena.compile(layout).unwrap(), 2, 3), "foo\n  bar");
+  /// ```
+  #[must_use]
+  pub fn nest(&mut self, layout: LayoutId) -> LayoutId {
+    self._push(Node::Nest(layout))
+  }
+
+  /// Constructs a new Pack layout in the arena, see `pack`.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{LayoutArena, render};
+  ///
+  /// let mut arena = LayoutArena::new();
+  /// let keyword = arena.text("let");
+  /// let foo = arena.text("foo");
+  /// let bar = arena.text("bar");
+  /// let foobar = arena.comp(foo, bar, true, false);
+  /// let packed = arena.pack(foobar);
+  /// let layout = arena.comp(keyword, packed, true, false);
+  /// assert_eq!(render(arena.compile(layout).unwrap(), 2, 8), "let foo\n    bar");
+  /// ```
+  #[must_use]
+  pub fn pack(&mut self, layout: LayoutId) -> LayoutId {
+    self._push(Node::Pack(layout))
+  }
+
+  /// Constructs a new PackReset layout in the arena, see `pack_reset`.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{LayoutArena, render};
+  ///
+  /// let mut arena = LayoutArena::new();
+  /// let reset = arena.pack_reset();
+  /// let foo = arena.text("foo");
+  /// let layout = arena.comp(reset, foo, false, false);
+  /// assert_eq!(render(arena.compile(layout).unwrap(), 2, 80), "foo");
+  /// ```
+  #[must_use]
+  pub fn pack_reset(&mut self) -> LayoutId {
+    self._push(Node::PackReset)
+  }
+
+  /// Constructs a new Slot layout in the arena, see `slot`.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{LayoutArena, text, render};
+  ///
+  /// let mut arena = LayoutArena::new();
+  /// let comment = arena.slot("comment");
+  /// let foo = arena.text("foo");
+  /// let layout = arena.comp(comment, foo, false, false);
+  /// let document = arena.compile(layout).unwrap()
+  ///   .fill_slot("comment", text("/* bar */ "));
+  /// assert_eq!(render(document, 2, 80), "/* bar */ foo");
+  /// ```
+  #[must_use]
+  pub fn slot(
+    &mut self,
+    name: impl Into<Cow<'static, str>>
+  ) -> LayoutId {
+    self._push(Node::Slot(name.into()))
+  }
+
+  /// Constructs a new PackGroup layout in the arena, see `pack_group`.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{LayoutArena, PackAnchor, render};
+  ///
+  /// let mut arena = LayoutArena::new();
+  /// let anchor = PackAnchor::new("value");
+  /// let x = arena.text("x =");
+  /// let one = arena.text("1");
+  /// let one = arena.pack_at(&anchor, one);
+  /// let first = arena.comp(x, one, true, false);
+  /// let width = arena.text("width =");
+  /// let eighty = arena.text("80");
+  /// let eighty = arena.pack_at(&anchor, eighty);
+  /// let second = arena.comp(width, eighty, true, false);
+  /// let lines = arena.line(first, second);
+  /// let layout = arena.pack_group(&[anchor], lines);
+  /// assert_eq!(render(arena.compile(layout).unwrap(), 2, 80), "x =     1\nwidth = 80");
+  /// ```
+  #[must_use]
+  pub fn pack_group(
+    &mut self,
+    anchors: &[PackAnchor],
+    layout: LayoutId
+  ) -> LayoutId {
+    self._push(Node::PackGroup(anchors.to_vec(), layout))
+  }
+
+  /// Constructs a new PackAt layout in the arena, see `pack_at`.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{LayoutArena, PackAnchor, CompilerError};
+  ///
+  /// let mut arena = LayoutArena::new();
+  /// let anchor = PackAnchor::new("value");
+  /// let x = arena.text("x =");
+  /// let one = arena.text("1");
+  /// let one = arena.pack_at(&anchor, one);
+  /// let layout = arena.comp(x, one, true, false);
+  /// assert_eq!(
+  ///   arena.compile(layout).unwrap_err(),
+  ///   CompilerError::UnboundAnchor("value".to_string())
+  /// );
+  /// ```
+  #[must_use]
+  pub fn pack_at(
+    &mut self,
+    anchor: &PackAnchor,
+    layout: LayoutId
+  ) -> LayoutId {
+    self._push(Node::PackAt(anchor.clone(), layout))
+  }
+
+  /// Constructs a new link annotation of a layout in the arena, see `link`.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{LayoutArena, render, render_html, RenderOptions, Theme};
+  ///
+  /// let mut arena = LayoutArena::new();
+  /// let foo = arena.text("foo");
+  /// let layout = arena.link("https://example.com", foo);
+  /// let document = arena.compile(layout).unwrap();
+  /// assert_eq!(render(document.clone(), 2, 80), "foo");
+  /// assert_eq!(
+  ///   render_html(&document, &RenderOptions::default(), &Theme::default()),
+  ///   "<a href=\"https://example.com\">foo</a>"
+  /// );
+  /// ```
+  #[must_use]
+  pub fn link(
+    &mut self,
+    url: impl Into<Cow<'static, str>>,
+    layout: LayoutId
+  ) -> LayoutId {
+    self._push(Node::Annotate(Annotation::Link(url.into()), layout))
+  }
+
+  /// Constructs a new tag annotation of a layout in the arena, see `tag`.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{LayoutArena, render, render_html, RenderOptions, Theme};
+  ///
+  /// let mut arena = LayoutArena::new();
+  /// let keyword = arena.text("let");
+  /// let layout = arena.tag("keyword", keyword);
+  /// let document = arena.compile(layout).unwrap();
+  /// assert_eq!(render(document.clone(), 2, 80), "let");
+  /// assert_eq!(
+  ///   render_html(&document, &RenderOptions::default(), &Theme::default()),
+  ///   "<span class=\"keyword\">let</span>"
+  /// );
+  /// ```
+  #[must_use]
+  pub fn tag(
+    &mut self,
+    name: impl Into<Cow<'static, str>>,
+    layout: LayoutId
+  ) -> LayoutId {
+    self._push(Node::Annotate(Annotation::Tag(name.into()), layout))
+  }
+
+  /// Constructs a new Line layout in the arena, see `line`.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{LayoutArena, render};
+  ///
+  /// let mut arena = LayoutArena::new();
+  /// let foo = arena.text("foo");
+  /// let bar = arena.text("bar");
+  /// let layout = arena.line(foo, bar);
+  /// assert_eq!(render(arena.compile(layout).unwrap(), 2, 80), "foo\nbar");
+  /// ```
+  #[must_use]
+  pub fn line(
+    &mut self,
+    left: LayoutId,
+    right: LayoutId
+  ) -> LayoutId {
+    self._push(Node::Line(left, right))
+  }
+
+  /// Constructs a new Comp layout in the arena, see `comp`.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{LayoutArena, render};
+  ///
+  /// let mut arena = LayoutArena::new();
+  /// let foo = arena.text("foo");
+  /// let bar = arena.text("bar");
+  /// let layout = arena.comp(foo, bar, false, false);
+  /// let document = arena.compile(layout).unwrap();
+  /// assert_eq!(render(document.clone(), 2, 80), "foobar");
+  /// assert_eq!(render(document, 2, 3), "foo\nbar");
+  /// ```
+  #[must_use]
+  pub fn comp(
+    &mut self,
+    left: LayoutId,
+    right: LayoutId,
+    pad: bool,
+    fix: bool
+  ) -> LayoutId {
+    self._push(Node::Comp(left, right, Attr { pad, fix }))
+  }
+
+  /// Compiles a layout of the arena into a document, as `compile_safe` does for boxed layouts.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{LayoutArena, render};
+  ///
+  /// let mut arena = LayoutArena::new();
+  /// let foo = arena.text("foo");
+  /// let bar = arena.text("bar");
+  /// let layout = arena.comp(foo, bar, true, false);
+  /// assert_eq!(render(arena.compile(layout).unwrap(), 2, 80), "foo bar");
+  /// ```
+  pub fn compile(
+    &self,
+    layout: LayoutId
+  ) -> Result<Box<Doc>, CompilerError> {
+    self.compile_with(layout, &CompileOptions::default())
+  }
+
+  /// Compiles a layout of the arena into a document with the given options, as `compile_with` does for boxed layouts.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{LayoutArena, CompileOptions, render};
+  ///
+  /// let mut arena = LayoutArena::new();
+  /// let foo = arena.text("foo");
+  /// let bar = arena.text("bar");
+  /// let layout = arena.line(foo, bar);
+  /// let options = CompileOptions::default();
+  /// assert_eq!(render(arena.compile_with(layout, &options).unwrap(), 2, 80), "foo\nbar");
+  /// ```
+  pub fn compile_with(
+    &self,
+    layout: LayoutId,
+    options: &CompileOptions
+  ) -> Result<Box<Doc>, CompilerError> {
+    let source = ArenaLayout { arena: self, id: layout };
+    _compile_traced(source, options, &mut |_pass, _dump, _mem| {})
+  }
+
+  /// Copies a layout of the arena out into a boxed layout, e.g. to compose it with layouts built by the constructors.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{LayoutArena, comp, text, compile, render};
+  ///
+  /// let mut arena = LayoutArena::new();
+  /// let foo = arena.text("foo");
+  /// let layout = comp(arena.to_layout(foo), text("bar"), true, false);
+  /// assert_eq!(render(compile(layout), 2, 80), "foo bar");
+  /// ```
+  #[must_use]
+  pub fn to_layout(
+    &self,
+    layout: LayoutId
+  ) -> Box<Layout> {
+    let result = match &self.nodes[layout.0 as usize] {
+      Node::Null => Layout::Null,
+      Node::Text(data, width) => Layout::Text(data.clone(), *width),
+      Node::PackReset => Layout::PackReset,
+      Node::Slot(name) => Layout::Slot(name.clone()),
+      Node::Annotate(annotation, id) =>
+        Layout::Annotate(annotation.clone(), self.to_layout(*id)),
+      Node::Fix(id) => Layout::Fix(self.to_layout(*id)),
+      Node::Grp(name, id) => Layout::Grp(name.clone(), self.to_layout(*id)),
+      Node::Seq(name, id) => Layout::Seq(name.clone(), self.to_layout(*id)),
+      Node::Nest(id) => Layout::Nest(self.to_layout(*id)),
+      Node::Pack(id) => Layout::Pack(self.to_layout(*id)),
+      Node::PackGroup(anchors, id) =>
+        Layout::PackGroup(anchors.clone(), self.to_layout(*id)),
+      Node::PackAt(anchor, id) => Layout::PackAt(anchor.clone(), self.to_layout(*id)),
+      Node::Line(left, right) =>
+        Layout::Line(self.to_layout(*left), self.to_layout(*right)),
+      Node::Comp(left, right, attr) =>
+        Layout::Comp(self.to_layout(*left), self.to_layout(*right), *attr)
+    };
+    Box::new(result)
+  }
+}
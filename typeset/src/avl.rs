@@ -4,12 +4,13 @@ use std::cmp::max;
 use bumpalo::Bump;
 
 use crate::{
-  util::compose,
-  order::{Order, total},
+  util::invariant,
+  order::Order,
   list::{List, nil, cons}
 };
 
 #[derive(Debug)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum AVL<'a, T: Copy + Clone + Debug> {
   Null,
   Node(u64, u64, T, &'a AVL<'a, T>, &'a AVL<'a, T>)
@@ -109,12 +110,12 @@ fn _local_rebalance<'b, 'a: 'b, T: Copy + Clone + Debug>(
     p: &'a AVL<'a, T>
   ) -> &'b AVL<'b, T> {
     match p {
-      AVL::Null => unreachable!("Invariant"),
+      AVL::Null => invariant!(p),
       AVL::Node(c_p, _, u, a, q) => {
         let c_a = get_count(a);
         let h_a = get_height(a);
         match q {
-          AVL::Null => unreachable!("Invariant"),
+          AVL::Null => invariant!(q),
           AVL::Node (_, _, v, b, c) => {
             let c_b = get_count(b);
             let h_b = get_height(b);
@@ -133,12 +134,12 @@ fn _local_rebalance<'b, 'a: 'b, T: Copy + Clone + Debug>(
     q: &'a AVL<'a, T>
   ) -> &'b AVL<'b, T> {
     match q {
-      AVL::Null => unreachable!("Invariant"),
+      AVL::Null => invariant!(q),
       AVL::Node(c_q, _, v, p, c) => {
         let c_c = get_count(c);
         let h_c = get_height(c);
         match p {
-          AVL::Null => unreachable!("Invariant"),
+          AVL::Null => invariant!(p),
           AVL::Node(_, _, u, a, b) => {
             let c_b = get_count(b);
             let h_b = get_height(b);
@@ -159,83 +160,150 @@ fn _local_rebalance<'b, 'a: 'b, T: Copy + Clone + Debug>(
   }
 }
 
+/*
+  The nodes passed through when descending a tree to the place of an item,
+  each with the side descended to, and the subtree at that place, which is
+  either null or the node holding an item equal to the given one.
+*/
+fn _descend<'a, T: Copy + Clone + Debug>(
+  order: &'a dyn Fn(T, T) -> Order,
+  data: T,
+  tree: &'a AVL<'a, T>
+) -> (Vec<(&'a AVL<'a, T>, Order)>, &'a AVL<'a, T>) {
+  let mut path = Vec::new();
+  let mut tree = tree;
+  while let AVL::Node(_, _, data1, left, right) = tree {
+    match order(data, *data1) {
+      Order::EQ => break,
+      Order::LT => { path.push((tree, Order::LT)); tree = left }
+      Order::GT => { path.push((tree, Order::GT)); tree = right }
+    }
+  }
+  (path, tree)
+}
+
+/*
+  Insert an item, or replace an equal one, keeping the items in order and
+  the counts exact; this is all that insert keeps, the tree is not
+  height-balanced. Each node on the path is only rotated once, if its
+  subtrees lean away from the side it hangs on, judged by the stored
+  heights; these are estimates, neither exact nor bounds on the actual
+  heights, as a node grown on the right keeps the height of its old right
+  subtree. The maps of the compiler use insert, as the order of their
+  entries follows the shape of their trees, see to_list.
+*/
 pub fn insert<'b, 'a: 'b, T: Copy + Clone + Debug>(
   mem: &'b Bump,
   order: &'a dyn Fn(T, T) -> Order,
   data: T,
   tree: &'a AVL<'a, T>
 ) -> &'b AVL<'b, T> {
-  fn _visit<'b, 'a: 'b, T: Copy + Clone + Debug>(
+  let (path, place) = _descend(order, data, tree);
+  if let AVL::Node(count, height, _, left, right) = place {
+    let tree1 = node(mem, *count, *height, data, left, right);
+    return path.into_iter().rev().fold(tree1, |tree2, (tree3, side)| match tree3 {
+      AVL::Null => invariant!(tree3),
+      AVL::Node(count, height, data1, left, right) => match side {
+        Order::LT => node(mem, *count, *height, *data1, tree2, right),
+        _ => node(mem, *count, *height, *data1, left, tree2)
+      }
+    })
+  }
+  let leaf = node(mem, 1, 1, data, null(mem), null(mem));
+  let tree1 = path.iter().enumerate().rev().fold(leaf, |tree2, (index, (tree3, side))| {
+    let pos = if index == 0 { Order::EQ } else { path[index - 1].1 };
+    match tree3 {
+      AVL::Null => invariant!(tree3),
+      AVL::Node(count, height, data1, left, right) => {
+        let tree4 = match side {
+          Order::LT => node(mem, count + 1, max(get_height(tree2) + 1, *height), *data1, tree2, right),
+          _ => node(mem, count + 1, max(get_height(right) + 1, *height), *data1, left, tree2)
+        };
+        _local_rebalance(mem, pos, tree4)
+      }
+    }
+  });
+  _local_rebalance(mem, Order::EQ, tree1)
+}
+
+/*
+  Build a node from its parts, recomputing its count and height, and
+  rotating it once or twice if the heights of its subtrees differ by more
+  than one; the subtrees must be balanced, and differ by at most two.
+*/
+fn _balance<'b, 'a: 'b, T: Copy + Clone + Debug>(
+  mem: &'b Bump,
+  data: T,
+  left: &'a AVL<'a, T>,
+  right: &'a AVL<'a, T>
+) -> &'b AVL<'b, T> {
+  fn _make<'b, 'a: 'b, T: Copy + Clone + Debug>(
     mem: &'b Bump,
-    order: &'a dyn Fn(T, T) -> Order,
     data: T,
-    tree: &'a AVL<'a, T>,
-    pos: Order,
-    updated: &'b dyn Fn(&'b Bump, &'b AVL<'b, T>) -> &'b AVL<'b, T>,
-    inserted: &'b dyn Fn(&'b Bump, &'b AVL<'b, T>) -> &'b AVL<'b, T>
+    left: &'a AVL<'a, T>,
+    right: &'a AVL<'a, T>
   ) -> &'b AVL<'b, T> {
-    match tree {
-      AVL::Null => inserted(mem, node(mem, 1, 1, data, null(mem), null(mem))),
-      AVL::Node(count, height, data1, left, right) =>
-        match order(data, *data1) {
-          Order::EQ =>
-            updated(mem, node(mem, *count, *height, data, left, right)),
-          Order::LT =>
-            _visit(mem, order, data, left, Order::LT,
-              compose(mem, updated, mem.alloc(|mem, left1|
-                node(
-                  mem,
-                  *count,
-                  *height,
-                  data1.clone(),
-                  left1,
-                  right
-                ))),
-              compose(mem, inserted, mem.alloc(move |mem, left1| {
-                let height1 = max(get_height(left1) + 1, *height);
-                _local_rebalance(mem, pos,
-                  node(
-                    mem,
-                    count + 1,
-                    height1,
-                    data1.clone(),
-                    left1,
-                    right
-                  ))}))),
-          Order::GT =>
-            _visit(mem, order, data, right, Order::GT,
-              compose(mem, updated, mem.alloc(|mem, right1|
-                node(
-                  mem,
-                  *count,
-                  *height,
-                  data1.clone(),
-                  left,
-                  right1
-                ))),
-              compose(mem, inserted, mem.alloc(move |mem, right1| {
-                let height1 = max(get_height(right) + 1, *height);
-                _local_rebalance(mem, pos,
-                  node(
-                    mem,
-                    count + 1,
-                    height1,
-                    data1.clone(),
-                    left,
-                    right1
-                  ))})))
-      }
+    let count = get_count(left) + get_count(right) + 1;
+    let height = max(get_height(left), get_height(right)) + 1;
+    node(mem, count, height, data, left, right)
+  }
+  let h_l = get_height(left);
+  let h_r = get_height(right);
+  if h_r + 1 < h_l {
+    match left {
+      AVL::Null => invariant!(left),
+      AVL::Node(_, _, data1, a, b) =>
+        if get_height(b) <= get_height(a) {
+          _make(mem, *data1, a, _make(mem, data, b, right))
+        } else {
+        match b {
+          AVL::Null => invariant!(b),
+          AVL::Node(_, _, data2, c, d) =>
+            _make(mem, *data2, _make(mem, *data1, a, c), _make(mem, data, d, right))
+        }}
+    }
+  } else if h_l + 1 < h_r {
+    match right {
+      AVL::Null => invariant!(right),
+      AVL::Node(_, _, data1, a, b) =>
+        if get_height(a) <= get_height(b) {
+          _make(mem, *data1, _make(mem, data, left, a), b)
+        } else {
+        match a {
+          AVL::Null => invariant!(a),
+          AVL::Node(_, _, data2, c, d) =>
+            _make(mem, *data2, _make(mem, data, left, c), _make(mem, *data1, d, b))
+        }}
     }
+  } else {
+    _make(mem, data, left, right)
   }
-  _visit(
-    mem,
-    order,
-    data,
-    tree,
-    Order::EQ,
-    mem.alloc(|_mem, tree1| tree1),
-    mem.alloc(|mem, tree1| _local_rebalance(mem, Order::EQ, tree1))
-  )
+}
+
+/*
+  Insert an item, or replace an equal one, rebalancing each node on the path
+  with _balance, such that the tree stays height-balanced, with exact heights
+  and counts; the sets use it, the maps keep using insert, as the output of
+  the compiler depends on the shape of their trees.
+*/
+pub fn insert_balanced<'b, 'a: 'b, T: Copy + Clone + Debug>(
+  mem: &'b Bump,
+  order: &'a dyn Fn(T, T) -> Order,
+  data: T,
+  tree: &'a AVL<'a, T>
+) -> &'b AVL<'b, T> {
+  let (path, place) = _descend(order, data, tree);
+  let tree1 = match place {
+    AVL::Null => node(mem, 1, 1, data, null(mem), null(mem)),
+    AVL::Node(count, height, _, left, right) => node(mem, *count, *height, data, left, right)
+  };
+  path.into_iter().rev().fold(tree1, |tree2, (tree3, side)| match tree3 {
+    AVL::Null => invariant!(tree3),
+    AVL::Node(_, _, data1, left, right) => match side {
+      Order::LT => _balance(mem, *data1, tree2, right),
+      _ => _balance(mem, *data1, left, tree2)
+    }
+  })
 }
 
 pub fn remove<'b, 'a: 'b, T: Copy + Clone + Debug>(
@@ -246,137 +314,36 @@ pub fn remove<'b, 'a: 'b, T: Copy + Clone + Debug>(
 ) -> &'b AVL<'b, T> {
   fn _leftmost<'a, T: Copy + Clone + Debug>(tree: &'a AVL<'a, T>) -> T {
     match tree {
-      AVL::Null => unreachable!("Invariant"),
+      AVL::Null => invariant!(tree),
       AVL::Node(_, _, data, AVL::Null, _) => *data,
       AVL::Node(_, _, _, left, _) => _leftmost(left)
     }
   }
-  fn _rightmost<'a, T: Copy + Clone + Debug>(tree: &'a AVL<'a, T>) -> T {
-    match tree {
-      AVL::Null => unreachable!("Invariant"),
-      AVL::Node(_, _, data, _, AVL::Null) => *data,
-      AVL::Node(_, _, _, _, right) => _rightmost(right)
-    }
-  }
-  fn _visit<'b, 'a: 'b, T: Copy + Clone + Debug, R>(
+  fn _visit<'b, 'a: 'b, T: Copy + Clone + Debug>(
     mem: &'b Bump,
     order: &'a dyn Fn(T, T) -> Order,
     data: T,
-    tree: &'a AVL<'a, T>,
-    pos: Order,
-    cont: &'b dyn Fn(&'b Bump, &'b AVL<'b, T>) -> R
-  ) -> R {
+    tree: &'a AVL<'a, T>
+  ) -> &'b AVL<'b, T> {
     match tree {
-      AVL::Null => cont(mem, null(mem)),
-      AVL::Node(count, height, data1, left, right) =>
+      AVL::Null => tree,
+      AVL::Node(_, _, data1, left, right) =>
         match order(data, *data1) {
+          Order::LT => _balance(mem, *data1, _visit(mem, order, data, left), right),
+          Order::GT => _balance(mem, *data1, left, _visit(mem, order, data, right)),
           Order::EQ =>
             match (left, right) {
-              (AVL::Null, AVL::Null) => cont(mem, null(mem)),
-              (AVL::Null, _) => {
-                let data2 = _leftmost(right);
-                _visit(mem, order, data2, right, Order::GT,
-                  compose(mem, cont, mem.alloc(move |mem, right1| {
-                    let height1 = max(get_height(right1) + 1, *height);
-                    _local_rebalance(mem, pos,
-                      node(
-                        mem,
-                        count - 1,
-                        height1,
-                        data2.clone(),
-                        left,
-                        right1
-                      ))
-                  })))}
-              (_, AVL::Null) => {
-                let data2 = _rightmost(left);
-                _visit(mem, order, data2, left, Order::LT,
-                  compose(mem, cont, mem.alloc(move |mem, left1| {
-                    let height1 = max(get_height(left1) + 1, *height);
-                    _local_rebalance(mem, pos,
-                      node(
-                        mem,
-                        count - 1,
-                        height1,
-                        data2.clone(),
-                        left1,
-                        right
-                      ))
-                  })))}
+              (AVL::Null, _) => right,
+              (_, AVL::Null) => left,
               (_, _) => {
-                let left_count = get_count(left);
-                let right_count = get_count(right);
-                match total(&left_count, &right_count) {
-                  Order::LT => {
-                    let data1 = _leftmost(right);
-                    _visit(mem, order, data1, right, Order::GT,
-                      compose(mem, cont, mem.alloc(move |mem, right1| {
-                        let height1 = max(get_height(right1) + 1, *height);
-                        _local_rebalance(mem, pos,
-                          node(
-                            mem,
-                            count - 1,
-                            height1,
-                            data1.clone(),
-                            left,
-                            right1
-                          ))})))
-                  }
-                  Order::GT | Order::EQ => {
-                    let data1 = _rightmost(left);
-                    _visit(mem, order, data1, left, Order::LT,
-                      compose(mem, cont, mem.alloc(move |mem, left1| {
-                        let height1 = max(get_height(left1) + 1, *height);
-                        _local_rebalance(mem, pos,
-                          node(
-                            mem,
-                            count - 1,
-                            height1,
-                            data1.clone(),
-                            left1,
-                            right
-                          ))})))
-                  }
-                }
+                let data2 = _leftmost(right);
+                _balance(mem, data2, left, _visit(mem, order, data2, right))
               }
             }
-          Order::LT =>
-            _visit(mem, order, data, left, Order::LT,
-              compose(mem, cont, mem.alloc(move |mem, left| {
-                let height1 = max(get_height(left) + 1, *height);
-                _local_rebalance(mem, pos,
-                  node(
-                    mem,
-                    count - 1,
-                    height1,
-                    data.clone(),
-                    left,
-                    right
-                  ))}))),
-          Order::GT =>
-            _visit(mem, order, data, right, Order::GT,
-              compose(mem, cont, mem.alloc(move |mem, right| {
-                let height1 = max(get_height(right) + 1, *height);
-                _local_rebalance(mem, pos,
-                  node(
-                    mem,
-                    count - 1,
-                    height1,
-                    data,
-                    left,
-                    right
-                  ))})))
-      }
+        }
     }
   }
-  _visit(
-    mem,
-    order,
-    data,
-    tree,
-    Order::EQ,
-    mem.alloc(|mem, tree1| _local_rebalance(mem, Order::EQ, tree1))
-  )
+  _visit(mem, order, data, tree)
 }
 
 pub fn is_member<'a, T: Copy + Clone + Debug>(
@@ -438,6 +405,14 @@ pub fn get_rightmost<'a, T: Copy + Clone + Debug>(
   }
 }
 
+/*
+  The items of a tree, consed in pre-order, i.e. the item at the root, then
+  the items of the left subtree, then of the right subtree, such that the list
+  is the reverse of a pre-order traversal. The order depends on the shape of
+  the tree and not only on the order of its items; structurize makes the edges
+  of a line in this order, which decides how scopes over the same items are
+  nested, so changing it changes the rendered output.
+*/
 pub fn to_list<'b, 'a: 'b, T: Copy + Clone + Debug>(
   mem: &'b Bump,
   tree: &'a AVL<'a, T>
@@ -479,7 +454,7 @@ pub fn from_list<'b, 'a: 'b, T: Copy + Clone + Debug>(
       0 => cont(mem, items, 0, null(mem)),
       1 =>
       match items {
-        List::Nil => unreachable!("Invariant"),
+        List::Nil => invariant!(items),
         List::Cons(_, data, items1) =>
           cont(mem, items1, 1, node(mem, 1, 1, *data, null(mem), null(mem)))
       },
@@ -491,7 +466,7 @@ pub fn from_list<'b, 'a: 'b, T: Copy + Clone + Debug>(
             _build(mem, Order::LT, m, items,
               mem.alloc(move |mem, items1: &'a List<'a, T>, l_h, left|
                 match items1 {
-                  List::Nil => unreachable!("Invariant"),
+                  List::Nil => invariant!(items1),
                   List::Cons(_, data, items2) =>
                     _build(mem, Order::GT, m, items2,
                       mem.alloc(move |mem, items3, r_h, right| {
@@ -500,7 +475,7 @@ pub fn from_list<'b, 'a: 'b, T: Copy + Clone + Debug>(
                           mem,
                           count,
                           height,
-                          data.clone(),
+                          *data,
                           left,
                           right
                         );
@@ -513,7 +488,7 @@ pub fn from_list<'b, 'a: 'b, T: Copy + Clone + Debug>(
             _build(mem, Order::LT, sm, items,
               mem.alloc(move |mem, items1: &'a List<'a, T>, l_h, left|
                 match items1 {
-                  List::Nil => unreachable!("Invariant"),
+                  List::Nil => invariant!(items1),
                   List::Cons(_, data, items2) =>
                     _build(mem, Order::GT, m, items2,
                       mem.alloc(move |mem, items3, r_h, right| {
@@ -522,7 +497,7 @@ pub fn from_list<'b, 'a: 'b, T: Copy + Clone + Debug>(
                           mem,
                           count,
                           height,
-                          data.clone(),
+                          *data,
                           left,
                           right
                         );
@@ -535,7 +510,7 @@ pub fn from_list<'b, 'a: 'b, T: Copy + Clone + Debug>(
             _build(mem, Order::LT, m, items,
               mem.alloc(move |mem, items1: &'a List<'a, T>, l_h, left|
                 match items1 {
-                  List::Nil => unreachable!("Invariant"),
+                  List::Nil => invariant!(items1),
                   List::Cons(_, data, items2) =>
                     _build(mem, Order::GT, sm, items2,
                       mem.alloc(move |mem, items3, r_h, right| {
@@ -544,7 +519,7 @@ pub fn from_list<'b, 'a: 'b, T: Copy + Clone + Debug>(
                           mem,
                           count,
                           height,
-                          data.clone(),
+                          *data,
                           left,
                           right
                         );
@@ -563,4 +538,141 @@ pub fn from_list<'b, 'a: 'b, T: Copy + Clone + Debug>(
     items,
     mem.alloc(|_, _, _, result| result)
   )
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+  use bumpalo::Bump;
+
+  use crate::order::{Order, total};
+
+  use super::*;
+
+  const ORDER: &dyn Fn(u64, u64) -> Order = &total;
+
+  /* Pseudo-random items from a xorshift generator, such that the tests are reproducible */
+  fn _random(count: usize) -> Vec<u64> {
+    let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+    (0..count).map(|_| {
+      seed ^= seed << 13;
+      seed ^= seed >> 7;
+      seed ^= seed << 17;
+      seed % 1000
+    }).collect()
+  }
+
+  fn _sequences() -> Vec<Vec<u64>> {
+    vec![
+      (0..200).collect(),
+      (0..200).rev().collect(),
+      (0..200u64).map(|index| if index.is_multiple_of(2) { index } else { 400 - index }).collect(),
+      _random(200)
+    ]
+  }
+
+  /* The items of a tree in order and its actual height, checking that its counts are exact, and its heights and balance when it should be balanced */
+  fn _check(
+    tree: &AVL<u64>,
+    balanced: bool
+  ) -> (Vec<u64>, u64) {
+    match tree {
+      AVL::Null => (Vec::new(), 0),
+      AVL::Node(count, height, data, left, right) => {
+        let (mut items, l_height) = _check(left, balanced);
+        let (r_items, r_height) = _check(right, balanced);
+        items.push(*data);
+        items.extend(r_items);
+        let height1 = max(l_height, r_height) + 1;
+        assert_eq!(*count, items.len() as u64);
+        if balanced {
+          assert_eq!(*height, height1);
+          assert!(l_height.abs_diff(r_height) <= 1, "{} and {} under {}", l_height, r_height, data)
+        }
+        (items, height1)
+      }
+    }
+  }
+
+  fn _sorted(items: &[u64]) -> Vec<u64> {
+    let mut items1 = items.to_vec();
+    items1.sort_unstable();
+    items1.dedup();
+    items1
+  }
+
+  #[test]
+  fn insert_keeps_order_and_counts() {
+    let mem = Bump::new();
+    for items in _sequences() {
+      let mut tree = null(&mem);
+      for (index, item) in items.iter().enumerate() {
+        tree = insert(&mem, ORDER, *item, tree);
+        assert_eq!(_check(tree, false).0, _sorted(&items[..=index]))
+      }
+    }
+  }
+
+  #[test]
+  fn insert_replaces_equal_items() {
+    let mem = Bump::new();
+    let order: &dyn Fn((u64, char), (u64, char)) -> Order = &|left, right| total(left.0, right.0);
+    let tree = [(2, 'a'), (1, 'b'), (3, 'c')].into_iter()
+      .fold(null(&mem), |tree, item| insert(&mem, order, item, tree));
+    let tree1 = insert(&mem, order, (1, 'd'), tree);
+    assert_eq!(get_count(tree1), 3);
+    assert_eq!(get_height(tree1), get_height(tree));
+    assert_eq!(get_member(0, tree1), Some((2, 'a')));
+    assert_eq!(get_leftmost(tree1), Some((1, 'd')))
+  }
+
+  /* The order of the entries of the maps of the compiler follows the shape of their trees, so the shapes given by insert are pinned */
+  #[test]
+  fn insert_shapes() {
+    let mem = Bump::new();
+    let shapes: [(Vec<u64>, [u64; 10]); 4] = [
+      (vec![5, 3, 8, 1, 4, 7, 9, 2, 6, 0], [9, 8, 6, 7, 4, 2, 0, 1, 3, 5]),
+      ((0..10).collect(), [9, 8, 6, 7, 4, 5, 2, 0, 1, 3]),
+      ((0..10).rev().collect(), [9, 7, 8, 5, 3, 4, 0, 1, 2, 6]),
+      (vec![0, 9, 1, 8, 2, 7, 3, 6, 4, 5], [9, 8, 6, 5, 3, 4, 0, 1, 2, 7])
+    ];
+    for (items, shape) in shapes {
+      let tree = items.into_iter().fold(null(&mem), |tree, item| insert(&mem, ORDER, item, tree));
+      let tree1 = insert(&mem, ORDER, 4, tree);
+      assert_eq!(to_list(&mem, tree1).iter().collect::<Vec<_>>(), shape)
+    }
+  }
+
+  #[test]
+  fn insert_balanced_keeps_balance() {
+    let mem = Bump::new();
+    for items in _sequences() {
+      let mut tree = null(&mem);
+      for (index, item) in items.iter().enumerate() {
+        tree = insert_balanced(&mem, ORDER, *item, tree);
+        assert_eq!(_check(tree, true).0, _sorted(&items[..=index]))
+      }
+    }
+  }
+
+  #[test]
+  fn remove_keeps_balance() {
+    let mem = Bump::new();
+    for items in _sequences() {
+      let mut tree = items.iter().fold(null(&mem), |tree, item| insert_balanced(&mem, ORDER, *item, tree));
+      let mut expected = _sorted(&items);
+      for item in items.iter().step_by(2) {
+        tree = remove(&mem, ORDER, *item, tree);
+        expected.retain(|item1| item1 != item);
+        assert_eq!(_check(tree, true).0, expected)
+      }
+    }
+  }
+
+  #[test]
+  fn from_list_is_balanced() {
+    let mem = Bump::new();
+    for count in 0..64 {
+      let tree = from_list(&mem, crate::list::from_iter(&mem, 0..count));
+      assert_eq!(_check(tree, true).0, (0..count).collect::<Vec<_>>())
+    }
+  }
+}
@@ -0,0 +1,649 @@
+use std::fmt;
+
+use crate::{
+  compiler::{
+    Annotation,
+    Doc,
+    Marker,
+    RenderOptions,
+    Renderer,
+    TabPolicy,
+    _text_columns
+  },
+  theme::Theme
+};
+
+/// A target for rendering documents, which is given the rendered text, padding, linebreaks and annotation markers in order.
+///
+/// All measuring is done by the renderer before the pieces are given to the backend, so a backend is free to escape or decorate them without changing where lines are broken.
+pub trait Backend {
+  /// Writes a piece of text.
+  fn text(&mut self, data: &str) -> fmt::Result;
+
+  /// Writes the given number of columns of padding, as spaces by default.
+  fn pad(&mut self, n: usize) -> fmt::Result {
+    const SPACES: &str = "                                ";
+    let mut n = n;
+    while 0 < n {
+      let m = n.min(SPACES.len());
+      self.text(&SPACES[..m])?;
+      n -= m
+    }
+    Ok(())
+  }
+
+  /// Writes a linebreak.
+  fn newline(&mut self) -> fmt::Result {
+    self.text("\n")
+  }
+
+  /// Writes an annotation marker, which is ignored by default.
+  fn marker(&mut self, _marker: &Marker) -> fmt::Result {
+    Ok(())
+  }
+}
+
+/// The plain text backend used by `render`, which writes text as is and ignores annotations.
+#[derive(Debug)]
+pub struct Plain<W: fmt::Write>(pub W);
+
+impl<W: fmt::Write> Backend for Plain<W> {
+  fn text(&mut self, data: &str) -> fmt::Result {
+    self.0.write_str(data)
+  }
+
+  fn pad(&mut self, n: usize) -> fmt::Result {
+    write!(self.0, "{:1$}", "", n)
+  }
+
+  fn newline(&mut self) -> fmt::Result {
+    self.0.write_char('\n')
+  }
+}
+
+/// A backend that discards everything, used by the renderer for passes that only measure.
+#[derive(Debug)]
+pub(crate) struct Discard;
+
+impl Backend for Discard {
+  fn text(&mut self, _data: &str) -> fmt::Result {
+    Ok(())
+  }
+
+  fn pad(&mut self, _n: usize) -> fmt::Result {
+    Ok(())
+  }
+}
+
+/// A backend for terminals, which writes text as is, links as OSC 8 hyperlinks, and styles tags with SGR escape sequences from a theme.
+///
+/// The bytes of a link URL outside printable ASCII, control characters such as ESC and BEL included, are percent-encoded, such that a URL can not end the hyperlink early and write escape sequences of its own.
+#[derive(Debug)]
+pub struct Ansi<'a, W: fmt::Write> {
+  out: W,
+  theme: &'a Theme,
+  styles: Vec<&'a str>
+}
+
+impl<'a, W: fmt::Write> Ansi<'a, W> {
+  /// Constructs a new ANSI backend writing into a target with a theme.
+  pub fn new(
+    out: W,
+    theme: &'a Theme
+  ) -> Self {
+    Ansi { out, theme, styles: Vec::new() }
+  }
+
+  fn _style(
+    &self,
+    tag: &str
+  ) -> Option<&'a str> {
+    let theme = self.theme;
+    theme.get(tag)
+      .map(|style| style.ansi.as_str())
+      .filter(|ansi| !ansi.is_empty())
+  }
+}
+
+impl<'a, W: fmt::Write> Backend for Ansi<'a, W> {
+  fn text(&mut self, data: &str) -> fmt::Result {
+    self.out.write_str(data)
+  }
+
+  fn marker(&mut self, marker: &Marker) -> fmt::Result {
+    match marker {
+      Marker::Open(Annotation::Link(url)) => {
+        self.out.write_str("\x1b]8;;")?;
+        _escape_url(url, &mut self.out)?;
+        self.out.write_str("\x1b\\")
+      }
+      Marker::Close(Annotation::Link(_)) =>
+        self.out.write_str("\x1b]8;;\x1b\\"),
+      Marker::Open(Annotation::Tag(tag)) => {
+        let Some(ansi) = self._style(tag) else { return Ok(()) };
+        self.styles.push(ansi);
+        write!(self.out, "\x1b[{}m", ansi)
+      }
+      Marker::Close(Annotation::Tag(tag)) => {
+        if self._style(tag).is_none() { return Ok(()) }
+        self.styles.pop();
+        self.out.write_str("\x1b[0m")?;
+        for ansi in &self.styles {
+          write!(self.out, "\x1b[{}m", ansi)?
+        }
+        Ok(())
+      }
+      Marker::Open(Annotation::Hint(_) | Annotation::Hyphen) |
+      Marker::Close(Annotation::Hint(_) | Annotation::Hyphen) |
+      Marker::PackReset | Marker::PageBreak | Marker::Slot(_) => Ok(())
+    }
+  }
+}
+
+/// A backend for HTML, which escapes text, writes links as `<a>` elements, and tags as `<span>` elements with the CSS classes of a theme.
+///
+/// The output is meant to be placed in a `<pre>` element, or any other element that preserves whitespace.
+#[derive(Debug)]
+pub struct Html<'a, W: fmt::Write> {
+  out: W,
+  theme: &'a Theme
+}
+
+impl<'a, W: fmt::Write> Html<'a, W> {
+  /// Constructs a new HTML backend writing into a target with a theme.
+  pub fn new(
+    out: W,
+    theme: &'a Theme
+  ) -> Self {
+    Html { out, theme }
+  }
+
+  fn _class(
+    &self,
+    tag: &str
+  ) -> Option<&'a str> {
+    let theme = self.theme;
+    theme.get(tag)
+      .map(|style| style.class.as_str())
+      .filter(|class| !class.is_empty())
+  }
+}
+
+fn _escape_url<W: fmt::Write>(
+  data: &str,
+  out: &mut W
+) -> fmt::Result {
+  let mut start = 0;
+  for (index, byte) in data.bytes().enumerate() {
+    if (b'!'..=b'~').contains(&byte) { continue }
+    out.write_str(&data[start..index])?;
+    write!(out, "%{:02X}", byte)?;
+    start = index + 1
+  }
+  out.write_str(&data[start..])
+}
+
+fn _escape_html<W: fmt::Write>(
+  data: &str,
+  out: &mut W
+) -> fmt::Result {
+  let mut start = 0;
+  for (index, c) in data.char_indices() {
+    let escaped = match c {
+      '&' => "&amp;",
+      '<' => "&lt;",
+      '>' => "&gt;",
+      '"' => "&quot;",
+      '\'' => "&#39;",
+      _ => continue
+    };
+    out.write_str(&data[start..index])?;
+    out.write_str(escaped)?;
+    start = index + c.len_utf8()
+  }
+  out.write_str(&data[start..])
+}
+
+impl<'a, W: fmt::Write> Backend for Html<'a, W> {
+  fn text(&mut self, data: &str) -> fmt::Result {
+    _escape_html(data, &mut self.out)
+  }
+
+  fn marker(&mut self, marker: &Marker) -> fmt::Result {
+    match marker {
+      Marker::Open(Annotation::Link(url)) => {
+        self.out.write_str("<a href=\"")?;
+        _escape_html(url, &mut self.out)?;
+        self.out.write_str("\">")
+      }
+      Marker::Close(Annotation::Link(_)) =>
+        self.out.write_str("</a>"),
+      Marker::Open(Annotation::Tag(tag)) => {
+        let Some(class) = self._class(tag) else { return Ok(()) };
+        self.out.write_str("<span class=\"")?;
+        _escape_html(class, &mut self.out)?;
+        self.out.write_str("\">")
+      }
+      Marker::Close(Annotation::Tag(tag)) => {
+        if self._class(tag).is_none() { return Ok(()) }
+        self.out.write_str("</span>")
+      }
+      Marker::Open(Annotation::Hint(_) | Annotation::Hyphen) |
+      Marker::Close(Annotation::Hint(_) | Annotation::Hyphen) |
+      Marker::PackReset | Marker::PageBreak | Marker::Slot(_) => Ok(())
+    }
+  }
+}
+
+/// A backend for LaTeX, which escapes the special characters of text, writes spaces and padding as `~` and linebreaks as `\\`, and ignores annotations.
+///
+/// Since every space is non-breaking, LaTeX keeps the breaking decisions of the renderer; the output is meant to be placed in a monospaced block, e.g. a `\ttfamily` group.
+#[derive(Debug)]
+pub struct Latex<W: fmt::Write> {
+  out: W,
+  empty: bool
+}
+
+impl<W: fmt::Write> Latex<W> {
+  /// Constructs a new LaTeX backend writing into a target.
+  pub fn new(out: W) -> Self {
+    Latex { out, empty: true }
+  }
+}
+
+fn _escape_latex<W: fmt::Write>(
+  data: &str,
+  out: &mut W
+) -> fmt::Result {
+  let mut start = 0;
+  for (index, c) in data.char_indices() {
+    let escaped = match c {
+      '\\' => "\\textbackslash{}",
+      '{' => "\\{",
+      '}' => "\\}",
+      '$' => "\\$",
+      '&' => "\\&",
+      '#' => "\\#",
+      '%' => "\\%",
+      '_' => "\\_",
+      '^' => "\\textasciicircum{}",
+      '~' => "\\textasciitilde{}",
+      '[' => "{[}",
+      ']' => "{]}",
+      ' ' | '\t' => "~",
+      _ => continue
+    };
+    out.write_str(&data[start..index])?;
+    out.write_str(escaped)?;
+    start = index + c.len_utf8()
+  }
+  out.write_str(&data[start..])
+}
+
+impl<W: fmt::Write> Backend for Latex<W> {
+  fn text(&mut self, data: &str) -> fmt::Result {
+    if data.is_empty() { return Ok(()) }
+    self.empty = false;
+    _escape_latex(data, &mut self.out)
+  }
+
+  fn pad(&mut self, n: usize) -> fmt::Result {
+    if n == 0 { return Ok(()) }
+    self.empty = false;
+    for _ in 0..n {
+      self.out.write_char('~')?
+    }
+    Ok(())
+  }
+
+  fn newline(&mut self) -> fmt::Result {
+    if self.empty { self.out.write_str("\\mbox{}")? }
+    self.empty = true;
+    self.out.write_str("\\\\\n")
+  }
+}
+
+/// The metrics of a monospaced font used by `render_svg`, in pixels.
+///
+/// # Examples
+/// ```
+/// use typeset::FontMetrics;
+///
+/// let metrics = FontMetrics::monospace(10.0);
+/// assert_eq!(metrics.advance, 6.0);
+/// assert_eq!(metrics.line_height, 12.0);
+/// assert_eq!(metrics.ascent, 8.0);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontMetrics {
+  pub family: String,
+  pub size: f64,
+  pub advance: f64,
+  pub line_height: f64,
+  pub ascent: f64
+}
+
+impl FontMetrics {
+  /// Constructs the metrics of a generic monospaced font of a given size, with an advance of 0.6em, a line height of 1.2em and an ascent of 0.8em.
+  pub fn monospace(size: f64) -> Self {
+    FontMetrics {
+      family: "monospace".to_string(),
+      size,
+      advance: size * 0.6,
+      line_height: size * 1.2,
+      ascent: size * 0.8
+    }
+  }
+}
+
+impl Default for FontMetrics {
+  fn default() -> Self {
+    FontMetrics::monospace(14.0)
+  }
+}
+
+#[derive(Debug)]
+struct SvgLines {
+  tabs: TabPolicy,
+  lines: Vec<(String, usize)>
+}
+
+impl SvgLines {
+  fn _last(&mut self) -> &mut (String, usize) {
+    self.lines.last_mut().expect("There is always a line")
+  }
+}
+
+impl Backend for SvgLines {
+  fn text(&mut self, data: &str) -> fmt::Result {
+    let tabs = self.tabs;
+    let (line, columns) = self._last();
+    *columns += _text_columns(data, *columns, tabs);
+    _escape_html(data, line)
+  }
+
+  fn pad(&mut self, n: usize) -> fmt::Result {
+    let (line, columns) = self._last();
+    *columns += n;
+    line.extend(std::iter::repeat_n(' ', n));
+    Ok(())
+  }
+
+  fn newline(&mut self) -> fmt::Result {
+    self.lines.push((String::new(), 0));
+    Ok(())
+  }
+}
+
+fn _px(value: f64) -> f64 {
+  (value * 100.0).round() / 100.0
+}
+
+/// A function for rendering documents into a backend, takes a `&Doc`, render options and the backend.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, compile, render_to_backend, Plain, RenderOptions};
+///
+/// let layout = comp(
+///   text("foo".to_string()),
+///   text("bar".to_string()),
+///   true, false
+/// );
+/// let document = compile(layout);
+/// let mut output = String::new();
+/// render_to_backend(&document, &RenderOptions::default(), &mut Plain(&mut output)).unwrap();
+/// assert_eq!(output, "foo bar");
+/// ```
+pub fn render_to_backend<B: Backend>(
+  doc: &Doc,
+  options: &RenderOptions,
+  backend: &mut B
+) -> fmt::Result {
+  Renderer::new(doc, *options)._run(backend)
+}
+
+/// A function for rendering documents for terminals, takes a `&Doc`, render options and a theme, and gives a `String` with links as OSC 8 hyperlinks and tags styled by the theme.
+///
+/// When color is disabled in the render options, tags are left unstyled.
+/// The bytes of link URLs outside printable ASCII are percent-encoded, such that a URL can not inject escape sequences.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, tag, link, compile, render_ansi, RenderOptions, Theme};
+///
+/// let layout = comp(tag("keyword", text("let")), text("x"), true, false);
+/// let document = compile(layout);
+/// let theme = Theme::default();
+/// let options = RenderOptions::default();
+/// assert_eq!(render_ansi(&document, &options, &theme), "\x1b[1;34mlet\x1b[0m x");
+/// let options = RenderOptions { color: false, ..options };
+/// assert_eq!(render_ansi(&document, &options, &theme), "let x");
+///
+/// let layout = link("https://a.b/\x1b]8;;evil\x07c d", text("x"));
+/// assert_eq!(
+///   render_ansi(&compile(layout), &options, &theme),
+///   "\x1b]8;;https://a.b/%1B]8;;evil%07c%20d\x1b\\x\x1b]8;;\x1b\\"
+/// );
+/// ```
+pub fn render_ansi(
+  doc: &Doc,
+  options: &RenderOptions,
+  theme: &Theme
+) -> String {
+  let empty = Theme::empty();
+  let theme = if options.color { theme } else { &empty };
+  let mut result = String::new();
+  render_to_backend(doc, options, &mut Ansi::new(&mut result, theme))
+    .expect("Writing to a String can not fail");
+  result
+}
+
+/// A function for rendering documents as HTML, takes a `&Doc`, render options and a theme, and gives a `String` of escaped text with links as `<a>` elements and tags as `<span>` elements with the classes of the theme.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, tag, compile, render_html, RenderOptions, Theme};
+///
+/// let layout = comp(tag("keyword", text("let")), text("x<y"), true, false);
+/// let document = compile(layout);
+/// assert_eq!(
+///   render_html(&document, &RenderOptions::default(), &Theme::default()),
+///   "<span class=\"keyword\">let</span> x&lt;y"
+/// );
+/// ```
+pub fn render_html(
+  doc: &Doc,
+  options: &RenderOptions,
+  theme: &Theme
+) -> String {
+  let mut result = String::new();
+  render_to_backend(doc, options, &mut Html::new(&mut result, theme))
+    .expect("Writing to a String can not fail");
+  result
+}
+
+/// A function for rendering documents as LaTeX, takes a `&Doc` and render options, and gives a `String` with the same linebreaks as `render`.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, compile, render_latex, RenderOptions};
+///
+/// let layout = comp(
+///   text("x_1".to_string()),
+///   text("{50%}".to_string()),
+///   true, false
+/// );
+/// let document = compile(layout);
+/// assert_eq!(
+///   render_latex(&document, &RenderOptions::default()),
+///   "x\\_1~\\{50\\%\\}"
+/// );
+/// assert_eq!(
+///   render_latex(&document, &RenderOptions::new(2, 4)),
+///   "x\\_1\\\\\n\\{50\\%\\}"
+/// );
+/// ```
+pub fn render_latex(
+  doc: &Doc,
+  options: &RenderOptions
+) -> String {
+  let mut result = String::new();
+  render_to_backend(doc, options, &mut Latex::new(&mut result))
+    .expect("Writing to a String can not fail");
+  result
+}
+
+/// A function for rendering documents as SVG, takes a `&Doc`, render options and font metrics, and gives a `String` of an SVG image with a `<text>` element per non-empty line, sized to fit the rendered text.
+///
+/// The image only depends on the document, the options and the metrics, so it is deterministic; annotations are ignored.
+/// The image is as wide as the widest line measured by the renderer, in bytes with tabs as wide as the tab policy makes them, such that a rendering fitting the buffer width fits an image of that many advances.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, compile, render_svg, FontMetrics, RenderOptions};
+///
+/// let layout = comp(
+///   text("a<b".to_string()),
+///   text("c".to_string()),
+///   true, false
+/// );
+/// let document = compile(layout);
+/// let svg = render_svg(&document, &RenderOptions::new(2, 3), &FontMetrics::monospace(10.0));
+/// assert_eq!(svg, concat!(
+///   "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"18\" height=\"24\" viewBox=\"0 0 18 24\" font-family=\"monospace\" font-size=\"10\">\n",
+///   "<text x=\"0\" y=\"8\" xml:space=\"preserve\">a&lt;b</text>\n",
+///   "<text x=\"0\" y=\"20\" xml:space=\"preserve\">c</text>\n",
+///   "</svg>\n"
+/// ));
+///
+/// let svg = render_svg(&compile(text("çà".to_string())), &RenderOptions::default(), &FontMetrics::monospace(10.0));
+/// assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"24\" height=\"12\""));
+/// ```
+pub fn render_svg(
+  doc: &Doc,
+  options: &RenderOptions,
+  font_metrics: &FontMetrics
+) -> String {
+  let mut svg = SvgLines { tabs: options.tabs, lines: vec![(String::new(), 0)] };
+  render_to_backend(doc, options, &mut svg)
+    .expect("Writing to a String can not fail");
+  let columns = svg.lines.iter().map(|(_, columns)| *columns).max().unwrap_or(0);
+  let width = _px(columns as f64 * font_metrics.advance);
+  let height = _px(svg.lines.len() as f64 * font_metrics.line_height);
+  let mut family = String::new();
+  _escape_html(&font_metrics.family, &mut family)
+    .expect("Writing to a String can not fail");
+  let mut result = format!(
+    "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\" font-family=\"{2}\" font-size=\"{3}\">\n",
+    width, height, family, _px(font_metrics.size)
+  );
+  for (index, (line, _)) in svg.lines.iter().enumerate() {
+    if line.is_empty() { continue }
+    let y = _px(index as f64 * font_metrics.line_height + font_metrics.ascent);
+    result.push_str(&format!(
+      "<text x=\"0\" y=\"{}\" xml:space=\"preserve\">{}</text>\n",
+      y, line
+    ))
+  }
+  result.push_str("</svg>\n");
+  result
+}
+
+#[derive(Debug)]
+struct PageLines {
+  height: usize,
+  pages: Vec<Vec<String>>
+}
+
+impl PageLines {
+  fn _page(&mut self) -> &mut Vec<String> {
+    self.pages.last_mut().expect("There is always a page")
+  }
+}
+
+impl Backend for PageLines {
+  fn text(&mut self, data: &str) -> fmt::Result {
+    self._page().last_mut().expect("There is always a line").push_str(data);
+    Ok(())
+  }
+
+  fn newline(&mut self) -> fmt::Result {
+    let height = self.height;
+    let page = self._page();
+    if page.len() < height { page.push(String::new()) }
+    else { self.pages.push(vec![String::new()]) }
+    Ok(())
+  }
+
+  fn marker(&mut self, marker: &Marker) -> fmt::Result {
+    if *marker != Marker::PageBreak { return Ok(()) }
+    let page = self._page();
+    if page.len() == 1 { return Ok(()) }
+    let line = page.pop().expect("There is always a line");
+    self.pages.push(vec![line]);
+    Ok(())
+  }
+}
+
+/// A function for rendering documents into pages, takes a `&Doc`, render options and the number of lines per page, and gives the text of each page.
+///
+/// Pages are split between lines, so fixed compositions are never split across pages; a new page is also started at each `Marker::PageBreak`, see `Doc::append_page`. A page height of zero is taken as one.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, compile, render_pages, Doc, RenderOptions};
+///
+/// let document = Doc::from_plain_text("a\nb\nc")
+///   .append_page(compile(text("d".to_string())));
+/// let pages = render_pages(&document, &RenderOptions::default(), 2);
+/// assert_eq!(pages, vec!["a\nb", "c", "d"]);
+/// ```
+pub fn render_pages(
+  doc: &Doc,
+  options: &RenderOptions,
+  page_height: usize
+) -> Vec<String> {
+  render_pages_with(doc, options, page_height, None, None)
+}
+
+/// A function for rendering documents into pages with a header and a footer, takes a `&Doc`, render options, the number of lines per page, and optionally functions giving the header and the footer of a page.
+///
+/// The header and footer functions are given the page number, counting from 1, and the number of pages; their text is placed on the lines above and below the lines of the page, and is not counted in the page height.
+///
+/// # Examples
+/// ```
+/// use typeset::{Doc, render_pages_with, RenderOptions};
+///
+/// let document = Doc::from_plain_text("a\nb\nc");
+/// let pages = render_pages_with(
+///   &document, &RenderOptions::default(), 2,
+///   Some(&|_page, _pages| "Report".to_string()),
+///   Some(&|page, pages| format!("{}/{}", page, pages))
+/// );
+/// assert_eq!(pages, vec!["Report\na\nb\n1/2", "Report\nc\n2/2"]);
+/// ```
+pub fn render_pages_with(
+  doc: &Doc,
+  options: &RenderOptions,
+  page_height: usize,
+  header: Option<&dyn Fn(usize, usize) -> String>,
+  footer: Option<&dyn Fn(usize, usize) -> String>
+) -> Vec<String> {
+  let mut lines = PageLines { height: page_height.max(1), pages: vec![vec![String::new()]] };
+  render_to_backend(doc, options, &mut lines)
+    .expect("Writing to a String can not fail");
+  let pages = lines.pages.len();
+  lines.pages.into_iter()
+    .enumerate()
+    .map(|(index, page)| {
+      let header = header.map(|header| header(index + 1, pages));
+      let footer = footer.map(|footer| footer(index + 1, pages));
+      header.into_iter()
+        .chain(page)
+        .chain(footer)
+        .collect::<Vec<_>>()
+        .join("\n")
+    })
+    .collect()
+}
@@ -0,0 +1,392 @@
+//! Wadler-style combinators for users migrating from other pretty printing libraries.
+//!
+//! Documents are built from `text`, breakable `line`s and `group`s as in the classic
+//! "prettier printer" style, and are then lowered into a typeset `Layout` with `to_layout`.
+
+use crate::compiler::{
+  Layout,
+  null,
+  text as _text,
+  grp,
+  seq,
+  nest as _nest,
+  line as _line,
+  comp
+};
+
+/// A Wadler-style document, built by the combinators of this module and lowered into a `Layout` by `to_layout`.
+///
+/// The variants mirror the combinators: `Line` is built by `line`, `LineBreak` by `line_`, `Hardline` by `hardline`, and `Nest` carries the number of levels given to `nest`.
+#[derive(Debug, Clone)]
+pub enum WadlerDoc {
+  Nil,
+  Text(String),
+  Line,
+  LineBreak,
+  Hardline,
+  Group(Box<WadlerDoc>),
+  Nest(usize, Box<WadlerDoc>),
+  Concat(Vec<Box<WadlerDoc>>)
+}
+
+/// Constructs the empty document.
+///
+/// # Examples
+/// ```
+/// use typeset::{compile, render};
+/// use typeset::compat::{nil, to_layout};
+///
+/// let doc = nil();
+/// assert_eq!(render(compile(to_layout(doc)), 2, 80), "");
+/// ```
+#[must_use]
+pub fn nil() -> Box<WadlerDoc> {
+  Box::new(WadlerDoc::Nil)
+}
+
+/// Constructs a text document.
+///
+/// # Examples
+/// ```
+/// use typeset::{compile, render};
+/// use typeset::compat::{text, to_layout};
+///
+/// let doc = text("foo");
+/// assert_eq!(render(compile(to_layout(doc)), 2, 80), "foo");
+/// ```
+#[must_use]
+pub fn text<T: Into<String>>(
+  data: T
+) -> Box<WadlerDoc> {
+  Box::new(WadlerDoc::Text(data.into()))
+}
+
+/// Constructs a line, rendered as a space when flat and as a newline when broken.
+///
+/// # Examples
+/// ```
+/// use typeset::{compile, render};
+/// use typeset::compat::{text, line, concat, group, to_layout};
+///
+/// let doc = group(concat(vec![text("foo"), line(), text("bar")]));
+/// let layout = to_layout(doc);
+/// assert_eq!(render(compile(layout.clone()), 2, 80), "foo bar");
+/// assert_eq!(render(compile(layout), 2, 6), "foo\nbar");
+/// ```
+#[must_use]
+pub fn line() -> Box<WadlerDoc> {
+  Box::new(WadlerDoc::Line)
+}
+
+/// Constructs a line break, rendered as nothing when flat and as a newline when broken.
+///
+/// # Examples
+/// ```
+/// use typeset::{compile, render};
+/// use typeset::compat::{text, line_, concat, group, to_layout};
+///
+/// let doc = group(concat(vec![text("foo"), line_(), text("bar")]));
+/// let layout = to_layout(doc);
+/// assert_eq!(render(compile(layout.clone()), 2, 80), "foobar");
+/// assert_eq!(render(compile(layout), 2, 5), "foo\nbar");
+/// ```
+#[must_use]
+pub fn line_() -> Box<WadlerDoc> {
+  Box::new(WadlerDoc::LineBreak)
+}
+
+/// Constructs a soft line, a `line` that is broken independently of its surroundings.
+///
+/// # Examples
+/// ```
+/// use typeset::{compile, render};
+/// use typeset::compat::{text, softline, concat, to_layout};
+///
+/// let doc = concat(vec![text("foo"), softline(), text("bar")]);
+/// let layout = to_layout(doc);
+/// assert_eq!(render(compile(layout.clone()), 2, 80), "foo bar");
+/// assert_eq!(render(compile(layout), 2, 6), "foo\nbar");
+/// ```
+#[must_use]
+pub fn softline() -> Box<WadlerDoc> {
+  group(line())
+}
+
+/// Constructs a soft line break, a `line_` that is broken independently of its surroundings.
+///
+/// # Examples
+/// ```
+/// use typeset::{compile, render};
+/// use typeset::compat::{text, softline_, concat, to_layout};
+///
+/// let doc = concat(vec![text("foo"), softline_(), text("bar")]);
+/// let layout = to_layout(doc);
+/// assert_eq!(render(compile(layout.clone()), 2, 80), "foobar");
+/// assert_eq!(render(compile(layout), 2, 5), "foo\nbar");
+/// ```
+#[must_use]
+pub fn softline_() -> Box<WadlerDoc> {
+  group(line_())
+}
+
+/// Constructs a hard line, which is always rendered as a newline.
+///
+/// # Examples
+/// ```
+/// use typeset::{compile, render};
+/// use typeset::compat::{text, hardline, concat, to_layout};
+///
+/// let doc = concat(vec![text("foo"), hardline(), text("bar")]);
+/// assert_eq!(render(compile(to_layout(doc)), 2, 80), "foo\nbar");
+/// ```
+#[must_use]
+pub fn hardline() -> Box<WadlerDoc> {
+  Box::new(WadlerDoc::Hardline)
+}
+
+/// Constructs a group, its lines are either all flat or all broken.
+///
+/// # Examples
+/// ```
+/// use typeset::{compile, render};
+/// use typeset::compat::{text, line, concat, group, to_layout};
+///
+/// let doc = group(concat(vec![
+///   text("foo"), line(), text("bar"), line(), text("baz")
+/// ]));
+/// let layout = to_layout(doc);
+/// assert_eq!(render(compile(layout.clone()), 2, 80), "foo bar baz");
+/// assert_eq!(render(compile(layout), 2, 8), "foo\nbar\nbaz");
+/// ```
+#[must_use]
+pub fn group(
+  doc: Box<WadlerDoc>
+) -> Box<WadlerDoc> {
+  Box::new(WadlerDoc::Group(doc))
+}
+
+/// Constructs a nested document, lines broken within it are indented.
+///
+/// The document is nested by `n` levels, each as wide as the tab size passed to `render`; render with a tab size of 1 for `n` to be the number of columns, as in other Wadler-style libraries.
+///
+/// # Examples
+/// ```
+/// use typeset::{compile, render};
+/// use typeset::compat::{text, line, concat, nest, to_layout};
+///
+/// let doc = |n| concat(vec![text("foo"), nest(n, concat(vec![line(), text("bar")]))]);
+/// assert_eq!(render(compile(to_layout(doc(2))), 1, 80), "foo bar");
+/// assert_eq!(render(compile(to_layout(doc(2))), 1, 6), "foo\n  bar");
+/// assert_eq!(render(compile(to_layout(doc(3))), 1, 6), "foo\n   bar");
+/// assert_eq!(render(compile(to_layout(doc(2))), 4, 6), "foo\n        bar");
+/// assert_eq!(render(compile(to_layout(doc(0))), 1, 6), "foo\nbar");
+/// ```
+#[must_use]
+pub fn nest(
+  n: usize,
+  doc: Box<WadlerDoc>
+) -> Box<WadlerDoc> {
+  Box::new(WadlerDoc::Nest(n, doc))
+}
+
+/// Constructs the concatenation of a sequence of documents.
+///
+/// # Examples
+/// ```
+/// use typeset::{compile, render};
+/// use typeset::compat::{text, concat, to_layout};
+///
+/// let doc = concat(vec![text("foo"), text("bar")]);
+/// assert_eq!(render(compile(to_layout(doc)), 2, 1), "foobar");
+/// ```
+#[must_use]
+pub fn concat(
+  docs: Vec<Box<WadlerDoc>>
+) -> Box<WadlerDoc> {
+  Box::new(WadlerDoc::Concat(docs))
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Sep {
+  Break,
+  Line,
+  Hard
+}
+
+#[derive(Debug)]
+enum Item {
+  Atom(Box<Layout>),
+  Sep(Sep)
+}
+
+#[allow(clippy::boxed_local)]
+fn _flatten(
+  doc: Box<WadlerDoc>,
+  items: &mut Vec<Item>
+) {
+  fn _wrap(
+    doc: Box<WadlerDoc>,
+    items: &mut Vec<Item>,
+    wrap: &dyn Fn(Box<Layout>) -> Box<Layout>
+  ) {
+    let mut inner = Vec::new();
+    _flatten(doc, &mut inner);
+    let first = inner.iter().position(|item| matches!(item, Item::Atom(_)));
+    let last = inner.iter().rposition(|item| matches!(item, Item::Atom(_)));
+    match (first, last) {
+      (Some(first), Some(last)) => {
+        let trailing = inner.split_off(last + 1);
+        let middle = inner.split_off(first);
+        items.extend(inner);
+        items.push(Item::Atom(wrap(_build(middle))));
+        items.extend(trailing)
+      }
+      (_, _) => items.extend(inner)
+    }
+  }
+  match doc {
+    box WadlerDoc::Nil => (),
+    box WadlerDoc::Text(data) => items.push(Item::Atom(_text(data))),
+    box WadlerDoc::Line => items.push(Item::Sep(Sep::Line)),
+    box WadlerDoc::LineBreak => items.push(Item::Sep(Sep::Break)),
+    box WadlerDoc::Hardline => items.push(Item::Sep(Sep::Hard)),
+    box WadlerDoc::Group(doc1) =>
+      _wrap(doc1, items, &|layout| seq(grp(layout))),
+    box WadlerDoc::Nest(0, doc1) => _flatten(doc1, items),
+    box WadlerDoc::Nest(n, doc1) =>
+      _wrap(doc1, items, &|layout| (0..n).fold(layout, |layout1, _| _nest(layout1))),
+    box WadlerDoc::Concat(docs) =>
+      docs.into_iter().for_each(|doc1| _flatten(doc1, items))
+  }
+}
+
+fn _build(
+  items: Vec<Item>
+) -> Box<Layout> {
+  fn _join(
+    left: Box<Layout>,
+    seps: &[Sep],
+    right: Box<Layout>
+  ) -> Box<Layout> {
+    let hards = seps.iter().filter(|sep| matches!(sep, Sep::Hard)).count();
+    if 0 < hards {
+      let right1 = (1..hards).fold(right, |right1, _| _line(null(), right1));
+      return _line(left, right1)
+    }
+    let padded = seps.iter().any(|sep| matches!(sep, Sep::Line));
+    comp(left, right, padded, seps.is_empty())
+  }
+  let mut result: Option<Box<Layout>> = None;
+  let mut seps = Vec::new();
+  for item in items.into_iter().rev() {
+    match item {
+      Item::Sep(sep) => seps.push(sep),
+      Item::Atom(layout) => {
+        result = Some(match result {
+          None => {
+            let hards = seps.iter().filter(|sep| matches!(sep, Sep::Hard)).count();
+            (0..hards).fold(layout, |layout1, _| _line(layout1, null()))
+          }
+          Some(right) => _join(layout, &seps, right)
+        });
+        seps.clear()
+      }
+    }
+  }
+  let hards = seps.iter().filter(|sep| matches!(sep, Sep::Hard)).count();
+  (0..hards).fold(result.unwrap_or_else(null), |layout, _| _line(null(), layout))
+}
+
+/// Lowers a Wadler-style document into a typeset layout.
+///
+/// Lines become breakable compositions (padded for `line`, unpadded for `line_`), adjacent texts become fixed compositions, hard lines become forced linebreaks, groups become a `seq` over a `grp`, and a nest by `n` becomes `n` nests.
+///
+/// # Examples
+/// ```
+/// use typeset::{compile, render};
+/// use typeset::compat::{text, line, concat, group, nest, to_layout};
+///
+/// let doc = group(concat(vec![
+///   text("foo"),
+///   nest(2, concat(vec![line(), text("bar"), line(), text("baz")]))
+/// ]));
+/// let layout = to_layout(doc);
+/// assert_eq!(render(compile(layout.clone()), 1, 80), "foo bar baz");
+/// assert_eq!(render(compile(layout), 1, 8), "foo\n  bar\n  baz");
+/// ```
+#[must_use]
+pub fn to_layout(
+  doc: Box<WadlerDoc>
+) -> Box<Layout> {
+  let mut items = Vec::new();
+  _flatten(doc, &mut items);
+  _build(items)
+}
+
+impl From<Box<WadlerDoc>> for Box<Layout> {
+  fn from(doc: Box<WadlerDoc>) -> Self {
+    to_layout(doc)
+  }
+}
+
+/// Converts a document of the `pretty` crate, with the `pretty` feature.
+///
+/// The conversion is structural, since a `WadlerDoc` has no notion of the column it is rendered at:
+/// - `column` and `nesting` documents, which `align`, `hang`, `indent` and `width` are built on, are evaluated at column 0 and nesting 0, such that their content is kept but aligned as if they started a line;
+/// - `flat_alt` and `union` documents always give their broken alternative, except for the flat alternatives of `line` and `line_`, which become a `line` and a `line_`;
+/// - `nest(n)` becomes a nest by `n` levels, which is `n` columns only when rendered with a tab size of 1;
+/// - annotations are dropped, and a `fail` document becomes the empty document.
+///
+/// # Examples
+/// ```
+/// use pretty::{DocAllocator, RcAllocator, RcDoc};
+/// use typeset::{compile_safe, render, Layout};
+///
+/// let allocator = RcAllocator;
+/// let doc: RcDoc<()> = allocator.text("let ")
+///   .append(allocator.text("x").append(allocator.line()).append(allocator.text("y")).hang(2))
+///   .group()
+///   .into_doc();
+/// let layout: Box<Layout> = doc.into();
+/// assert_eq!(render(compile_safe(layout.clone()).unwrap(), 1, 80), "let x y");
+/// // pretty aligns y to the column of x plus 2, the layout nests it by 2 from the start of the line
+/// assert_eq!(render(compile_safe(layout).unwrap(), 1, 5), "let x\n  y");
+/// ```
+#[cfg(feature = "pretty")]
+impl<'a, A> From<pretty::RcDoc<'a, A>> for Box<WadlerDoc> {
+  fn from(doc: pretty::RcDoc<'a, A>) -> Self {
+    fn _visit<'a, A>(
+      doc: &pretty::RcDoc<'a, A>
+    ) -> Box<WadlerDoc> {
+      use pretty::Doc;
+      match &**doc {
+        Doc::Nil | Doc::Fail => nil(),
+        Doc::Column(f) | Doc::Nesting(f) => _visit(&f(0)),
+        Doc::Append(left, right) => concat(vec![_visit(left), _visit(right)]),
+        Doc::Group(doc1) => group(_visit(doc1)),
+        Doc::FlatAlt(broken, flat) =>
+          match (&**broken, &**flat) {
+            (Doc::Hardline, Doc::Nil) => line_(),
+            (Doc::Hardline, Doc::BorrowedText(" ")) => line(),
+            (_, _) => _visit(broken)
+          },
+        Doc::Nest(n, doc1) => nest((*n).max(0) as usize, _visit(doc1)),
+        Doc::Hardline => hardline(),
+        Doc::RenderLen(_, doc1) | Doc::Annotated(_, doc1) => _visit(doc1),
+        Doc::OwnedText(data) => text(data.to_string()),
+        Doc::BorrowedText(data) => text(*data),
+        Doc::SmallText(data) => text(data.as_str()),
+        Doc::Union(_flat, broken) => _visit(broken)
+      }
+    }
+    _visit(&doc)
+  }
+}
+
+/// Converts a document of the `pretty` crate into a layout, with the `pretty` feature, see the conversion into a `WadlerDoc`.
+#[cfg(feature = "pretty")]
+impl<'a, A> From<pretty::RcDoc<'a, A>> for Box<Layout> {
+  fn from(doc: pretty::RcDoc<'a, A>) -> Self {
+    to_layout(doc.into())
+  }
+}
@@ -1,81 +1,440 @@
+/* Layouts are passed as boxes, and the passes name their documents EDSL and their ends EOD */
+#![allow(
+  clippy::boxed_local,
+  clippy::type_complexity,
+  clippy::upper_case_acronyms
+)]
+
 use std::{
+  borrow::Cow,
   cell::Cell,
+  collections::{BTreeMap, BTreeSet},
   option::Option,
-  cmp::max,
+  cmp::{max, min},
+  panic::{self, AssertUnwindSafe},
+  sync::OnceLock,
   fmt
 };
 use bumpalo::Bump;
 
 use crate::{
-  util::compose,
+  util::invariant,
+  backend::{Backend, Plain, Discard},
   order::total,
   list::{self as _list, List},
-  map::{self as _map, Map}
+  map::{self as _map, Map},
+  error::{CompilerError, DocError},
+  limits::{
+    DEFAULT_TAB,
+    DEFAULT_WIDTH,
+    DEFAULT_HINT_TOLERANCE,
+    DEFAULT_HYSTERESIS,
+    DEFAULT_MAX_NODES,
+    DEFAULT_MAX_TEXT_BYTES,
+    DEFAULT_MAX_DEPTH,
+    DEFAULT_MAX_ALLOCATED_BYTES,
+    MAX_SCOPE_INDEX,
+    MAX_PACK_INDEX
+  }
 };
 
+/// The type of the indices that the compiler gives the groups, sequences and packs of a layout, and that documents refer to their packs by.
+///
+/// The indices are `u64` by default, or `u32` with the `compact-indices` feature, which halves the size of the indices in the intermediate representations and documents, for memory-constrained targets; a layout with more groups and sequences, or more packs, than can be indexed fails to compile with a `CompilerError::IndexOverflow`, see `limits::MAX_SCOPE_INDEX` and `limits::MAX_PACK_INDEX`.
+#[cfg(not(feature = "compact-indices"))]
+pub type Index = u64;
+
+/// The type of the indices that the compiler gives the groups, sequences and packs of a layout, and that documents refer to their packs by.
+///
+/// The indices are `u64` by default, or `u32` with the `compact-indices` feature, which halves the size of the indices in the intermediate representations and documents, for memory-constrained targets; a layout with more groups and sequences, or more packs, than can be indexed fails to compile with a `CompilerError::IndexOverflow`, see `limits::MAX_SCOPE_INDEX` and `limits::MAX_PACK_INDEX`.
+#[cfg(feature = "compact-indices")]
+pub type Index = u32;
+
 // EDSL syntax
 #[derive(Debug, Copy, Clone)]
 pub struct Attr {
-  pad: bool,
-  fix: bool
+  pub(crate) pad: bool,
+  pub(crate) fix: bool
 }
 
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum Layout {
   Null,
-  Text(String),
+  Text(Cow<'static, str>, Option<usize>),
+  PackReset,
+  Slot(Cow<'static, str>),
+  Annotate(Annotation, Box<Layout>),
   Fix(Box<Layout>),
-  Grp(Box<Layout>),
-  Seq(Box<Layout>),
+  Grp(Option<Cow<'static, str>>, Box<Layout>),
+  Seq(Option<Cow<'static, str>>, Box<Layout>),
   Nest(Box<Layout>),
   Pack(Box<Layout>),
+  PackGroup(Vec<PackAnchor>, Box<Layout>),
+  PackAt(PackAnchor, Box<Layout>),
   Line(Box<Layout>, Box<Layout>),
   Comp(Box<Layout>, Box<Layout>, Attr)
 }
 
+/// A named alignment column of a pack group, see `pack_group` and `pack_at`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PackAnchor(String);
+
+impl PackAnchor {
+  /// Constructs a new anchor with the given name.
+  #[must_use]
+  pub fn new(
+    name: &str
+  ) -> Self {
+    PackAnchor(name.to_string())
+  }
+
+  /// Gives the name of the anchor.
+  pub fn name(&self) -> &str {
+    &self.0
+  }
+}
+
+/// An annotation of a layout, which is emitted as markup around the layout by the rendering backends that support it, a break hint of a group, which is read by the renderer and emitted by no backend, see `grp_hint`, or a soft hyphen after the layout, which the renderer emits as `-` when the line is broken right after the layout, see `hyphenated_text`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Annotation {
+  Link(Cow<'static, str>),
+  Tag(Cow<'static, str>),
+  Hint(Hint),
+  Hyphen
+}
+
+/// A preference of a group for keeping its compositions on one line or for breaking them, when the measured width is within the `hint_tolerance` render option of the available width, see `grp_hint`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Hint {
+  PreferFlat,
+  PreferBroken
+}
+
+/// The opening or closing marker of an annotation in a document, a boundary at which the renderer forgets its pack marks, see `pack_reset`, a page break before the line it starts, see `Doc::append_page`, or a named slot to be filled after compilation, see `slot`.
+///
+/// Markers take up no width: the renderer does not measure them, and the backends emit annotations as markup that takes up no columns, e.g. escape codes and HTML tags, or not at all; so a document breaks the same in every backend, and styled and unstyled output can be rendered from one compiled document.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, tag, compile, render, render_ansi, render_html, RenderOptions, Theme};
+///
+/// let layout = comp(tag("keyword", text("let")), text("x"), true, false);
+/// let document = compile(layout);
+/// let options = RenderOptions::new(2, 5);
+/// let theme = Theme::default();
+/// assert_eq!(render(document.clone(), 2, 5), "let x");
+/// assert_eq!(render_ansi(&document, &options, &theme), "\x1b[1;34mlet\x1b[0m x");
+/// assert_eq!(render_html(&document, &options, &theme), "<span class=\"keyword\">let</span> x");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Marker {
+  Open(Annotation),
+  Close(Annotation),
+  PackReset,
+  PageBreak,
+  Slot(Cow<'static, str>)
+}
+
 impl fmt::Display for Layout {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     fn _visit(
-      layout: Box<Layout>
+      layout: &Layout
     ) -> String {
       match layout {
-        box Layout::Null =>
+        Layout::Null =>
           "Null".to_string(),
-        box Layout::Text(data) =>
+        Layout::Text(data, None) =>
           format!("(Text \"{}\"", data),
-        box Layout::Fix(layout1) => {
+        Layout::Text(data, Some(width)) =>
+          format!("(Text \"{}\" {})", data, width),
+        Layout::PackReset =>
+          "PackReset".to_string(),
+        Layout::Slot(name) =>
+          format!("(Slot \"{}\")", name),
+        Layout::Annotate(annotation, layout1) => {
+          let layout_s = _visit(layout1);
+          format!("(Annotate {:?} {})", annotation, layout_s)
+        }
+        Layout::Fix(layout1) => {
           let layout_s = _visit(layout1);
           format!("(Fix {})", layout_s)
         }
-        box Layout::Grp(layout1) => {
+        Layout::Grp(name, layout1) => {
           let layout_s = _visit(layout1);
-          format!("(Grp {})", layout_s)
+          format!("(Grp {}{})", _print_name(name.as_deref()), layout_s)
         }
-        box Layout::Seq(layout1) => {
+        Layout::Seq(name, layout1) => {
           let layout_s = _visit(layout1);
-          format!("(Seq {})", layout_s)
+          format!("(Seq {}{})", _print_name(name.as_deref()), layout_s)
         }
-        box Layout::Nest(layout1) => {
+        Layout::Nest(layout1) => {
           let layout_s = _visit(layout1);
           format!("(Nest {})", layout_s)
         }
-        box Layout::Pack(layout1) => {
+        Layout::Pack(layout1) => {
           let layout_s = _visit(layout1);
           format!("(Pack {})", layout_s)
         }
-        box Layout::Line(left, right) => {
+        Layout::PackGroup(anchors, layout1) => {
+          let layout_s = _visit(layout1);
+          let anchors_s: Vec<&str> = anchors.iter().map(PackAnchor::name).collect();
+          format!("(PackGroup {:?} {})", anchors_s, layout_s)
+        }
+        Layout::PackAt(anchor, layout1) => {
+          let layout_s = _visit(layout1);
+          format!("(PackAt {:?} {})", anchor.name(), layout_s)
+        }
+        Layout::Line(left, right) => {
           let left_s = _visit(left);
           let right_s = _visit(right);
           format!("(Line {} {})", left_s, right_s)
         }
-        box Layout::Comp(left, right, attr) => {
+        Layout::Comp(left, right, attr) => {
           let left_s = _visit(left);
           let right_s = _visit(right);
           format!("(Comp {} {} {} {})", left_s, right_s, attr.pad, attr.fix)
         }
       }
     }
-    write!(f, "{}", _visit(Box::new(self.clone())))
+    write!(f, "{}", _visit(self))
+  }
+}
+
+/*
+  Drop the layouts under a layout with an explicit stack rather than by
+  recursion, such that dropping a deeply nested layout does not overflow the
+  call stack; the layouts are moved out of their boxes, which are left
+  holding a null layout.
+*/
+impl Drop for Layout {
+  fn drop(&mut self) {
+    fn _take(
+      layout: &mut Layout,
+      stack: &mut Vec<Layout>
+    ) {
+      match layout {
+        Layout::Null | Layout::Text(_, _) | Layout::PackReset | Layout::Slot(_) => (),
+        Layout::Annotate(_, layout1) | Layout::Fix(layout1) |
+        Layout::Grp(_, layout1) | Layout::Seq(_, layout1) |
+        Layout::Nest(layout1) | Layout::Pack(layout1) |
+        Layout::PackGroup(_, layout1) | Layout::PackAt(_, layout1) =>
+          stack.push(std::mem::replace(&mut **layout1, Layout::Null)),
+        Layout::Line(left, right) | Layout::Comp(left, right, _) => {
+          stack.push(std::mem::replace(&mut **left, Layout::Null));
+          stack.push(std::mem::replace(&mut **right, Layout::Null))
+        }
+      }
+    }
+    let mut stack = Vec::new();
+    _take(self, &mut stack);
+    while let Some(mut layout) = stack.pop() {
+      _take(&mut layout, &mut stack)
+    }
+  }
+}
+
+impl Layout {
+  /// Gives the layouts directly under the layout, from left to right; literals have none.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{text, comp, nest};
+  ///
+  /// let layout = comp(text("foo"), nest(text("bar")), true, false);
+  /// assert_eq!(layout.children().len(), 2);
+  /// assert_eq!(layout.children()[1].children().len(), 1);
+  /// ```
+  pub fn children(&self) -> Vec<&Layout> {
+    match self {
+      Layout::Null | Layout::Text(_, _) | Layout::PackReset | Layout::Slot(_) =>
+        Vec::new(),
+      Layout::Annotate(_, layout) | Layout::Fix(layout) |
+      Layout::Grp(_, layout) | Layout::Seq(_, layout) |
+      Layout::Nest(layout) | Layout::Pack(layout) |
+      Layout::PackGroup(_, layout) | Layout::PackAt(_, layout) =>
+        vec![&**layout],
+      Layout::Line(left, right) | Layout::Comp(left, right, _) =>
+        vec![&**left, &**right]
+    }
+  }
+
+  fn _child_mut(
+    &mut self,
+    index: usize
+  ) -> Option<&mut Box<Layout>> {
+    match (self, index) {
+      (Layout::Annotate(_, layout), 0) | (Layout::Fix(layout), 0) |
+      (Layout::Grp(_, layout), 0) | (Layout::Seq(_, layout), 0) |
+      (Layout::Nest(layout), 0) | (Layout::Pack(layout), 0) |
+      (Layout::PackGroup(_, layout), 0) | (Layout::PackAt(_, layout), 0) |
+      (Layout::Line(layout, _), 0) | (Layout::Comp(layout, _, _), 0) |
+      (Layout::Line(_, layout), 1) | (Layout::Comp(_, layout, _), 1) =>
+        Some(layout),
+      _ => None
+    }
+  }
+
+  /// Gives the layout at a path under the layout, where every index of the path picks a child as ordered by `children`; the empty path gives the layout itself, and a path through a missing child gives `None`.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{text, comp, nest, Layout};
+  ///
+  /// let layout = comp(text("foo"), nest(text("bar")), true, false);
+  /// assert!(matches!(layout.get(&[1, 0]), Some(Layout::Text(data, _)) if data == "bar"));
+  /// assert!(layout.get(&[0, 0]).is_none());
+  /// assert!(layout.get(&[]).is_some());
+  /// ```
+  pub fn get(
+    &self,
+    path: &[usize]
+  ) -> Option<&Layout> {
+    path.iter().try_fold(self, |layout, index|
+      layout.children().get(*index).copied())
+  }
+
+  /// Replaces the layout at a path under the layout, as found by `get`, and gives the replaced layout; a path through a missing child leaves the layout unchanged and gives `None`, and the empty path can not be replaced.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{text, comp, nest, compile, render, Layout};
+  ///
+  /// let mut layout = comp(text("foo"), nest(text("bar")), true, false);
+  /// let old = layout.replace(&[1, 0], text("baz"));
+  /// assert!(matches!(old.as_deref(), Some(Layout::Text(data, _)) if data == "bar"));
+  /// assert_eq!(render(compile(layout.clone()), 2, 80), "foo baz");
+  /// assert!(layout.replace(&[0, 0], text("qux")).is_none());
+  /// assert!(layout.replace(&[], text("qux")).is_none());
+  /// ```
+  pub fn replace(
+    &mut self,
+    path: &[usize],
+    layout: impl Into<Box<Layout>>
+  ) -> Option<Box<Layout>> {
+    let (last, path) = path.split_last()?;
+    let mut parent = self;
+    for index in path {
+      parent = parent._child_mut(*index)?
+    }
+    let child = parent._child_mut(*last)?;
+    Some(std::mem::replace(child, layout.into()))
+  }
+
+  /// Concatenates independently built layouts into one document, with a hard break between every two parts.
+  ///
+  /// The parts do not influence each other's alignment: every pack is indented to a mark of its own, and a `pack_at` is bound by a `pack_group` within its own part, so the same anchor used in two parts gives two independent columns; the packs and groups of a part also get the same indices wherever the part is placed.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{text, comp, pack_group, pack_at, compile, render, Layout, PackAnchor};
+  ///
+  /// let value = PackAnchor::new("value");
+  /// let item = |name: &str| pack_group(&[value.clone()], comp(
+  ///   text(format!("{} =", name)),
+  ///   pack_at(&value, text("1")),
+  ///   true, false
+  /// ));
+  /// let layout = Layout::concat_documents(vec![item("x"), item("width")]);
+  /// assert_eq!(render(compile(layout), 2, 80), "x = 1\nwidth = 1");
+  /// assert_eq!(render(compile(Layout::concat_documents(Vec::new())), 2, 80), "");
+  /// ```
+  #[must_use]
+  pub fn concat_documents(
+    parts: Vec<Box<Layout>>
+  ) -> Box<Layout> {
+    parts.into_iter()
+      .rev()
+      .reduce(|right, left| line(left, right))
+      .unwrap_or_else(null)
+  }
+}
+
+/*
+  A view of a layout node with its children as layout sources, such that the
+  compiler can read layouts from the boxed constructors and from a layout
+  arena alike.
+*/
+pub(crate) enum LayoutView<'s, S> {
+  Null,
+  Text(&'s str, Option<usize>),
+  PackReset,
+  Slot(&'s str),
+  Annotate(&'s Annotation, S),
+  Fix(S),
+  Grp(Option<&'s str>, S),
+  Seq(Option<&'s str>, S),
+  Nest(S),
+  Pack(S),
+  PackGroup(&'s [PackAnchor], S),
+  PackAt(&'s PackAnchor, S),
+  Line(S, S),
+  Comp(S, S, Attr)
+}
+
+pub(crate) trait LayoutSource<'s>: Copy {
+  fn view(self) -> LayoutView<'s, Self>;
+}
+
+impl<'s> LayoutSource<'s> for &'s Layout {
+  fn view(self) -> LayoutView<'s, Self> {
+    match self {
+      Layout::Null => LayoutView::Null,
+      Layout::Text(data, width) => LayoutView::Text(data, *width),
+      Layout::PackReset => LayoutView::PackReset,
+      Layout::Slot(name) => LayoutView::Slot(name),
+      Layout::Annotate(annotation, layout) => LayoutView::Annotate(annotation, layout),
+      Layout::Fix(layout) => LayoutView::Fix(layout),
+      Layout::Grp(name, layout) => LayoutView::Grp(name.as_deref(), layout),
+      Layout::Seq(name, layout) => LayoutView::Seq(name.as_deref(), layout),
+      Layout::Nest(layout) => LayoutView::Nest(layout),
+      Layout::Pack(layout) => LayoutView::Pack(layout),
+      Layout::PackGroup(anchors, layout) => LayoutView::PackGroup(anchors, layout),
+      Layout::PackAt(anchor, layout) => LayoutView::PackAt(anchor, layout),
+      Layout::Line(left, right) => LayoutView::Line(left, right),
+      Layout::Comp(left, right, attr) => LayoutView::Comp(left, right, *attr)
+    }
+  }
+}
+
+/// Text layouts from string slices, such that literals can be given directly to the layout constructors.
+///
+/// # Examples
+/// ```
+/// use typeset::{comp, compile, render};
+///
+/// let layout = comp("foo", "bar", true, false);
+/// assert_eq!(render(compile(layout), 2, 80), "foo bar");
+/// ```
+impl From<&str> for Box<Layout> {
+  fn from(data: &str) -> Self {
+    text(data.to_string())
+  }
+}
+
+/// Text layouts from strings, as with `text`.
+impl From<String> for Box<Layout> {
+  fn from(data: String) -> Self {
+    text(data)
+  }
+}
+
+/// Layouts joined by unpadded compositions, or the Null layout if there are none.
+///
+/// # Examples
+/// ```
+/// use typeset::{Layout, text, grp, compile, render};
+///
+/// let layout = grp(vec![text("foo".to_string()), ",".into(), text("bar".to_string())]);
+/// assert_eq!(render(compile(layout), 2, 80), "foo,bar");
+/// ```
+impl From<Vec<Box<Layout>>> for Box<Layout> {
+  fn from(items: Vec<Box<Layout>>) -> Self {
+    items.into_iter().rev()
+      .reduce(|result, item| comp(item, result, false, false))
+      .unwrap_or_else(null)
   }
 }
 
@@ -85,10 +444,13 @@ impl fmt::Display for Layout {
 ///
 /// # Examples
 /// ```
-/// use typeset::null;
+/// use typeset::{null, text, comp, compile, render};
 ///
-/// let layout = null();
+/// let layout = comp(text("foo".to_string()), null(), true, false);
+/// assert_eq!(render(compile(layout), 2, 80), "foo");
+/// assert_eq!(render(compile(null()), 2, 80), "");
 /// ```
+#[must_use]
 pub fn null() -> Box<Layout> {
   Box::new(Layout::Null)
 }
@@ -97,16 +459,157 @@ pub fn null() -> Box<Layout> {
 ///
 /// Text layouts are literals and basic elements of layout compositions.
 ///
+/// The data is either an owned `String` or a `&'static str`, which is kept borrowed rather than copied; the same holds for the names, urls and tags taken by the other constructors.
+///
 /// # Examples
 /// ```
-/// use typeset::text;
+/// use typeset::{text, compile, render};
 ///
 /// let layout = text("foobar".to_string());
+/// let keyword = text("let");
+/// assert_eq!(render(compile(layout), 2, 80), "foobar");
+/// assert_eq!(render(compile(keyword), 2, 1), "let");
 /// ```
+#[must_use]
 pub fn text(
-  data: String
+  data: impl Into<Cow<'static, str>>
+) -> Box<Layout> {
+  Box::new(Layout::Text(data.into(), None))
+}
+
+/// Constructs a new Text layout with a given display width.
+///
+/// The display width is used in place of the length of the text when measuring it during rendering, e.g. for text containing terminal escape sequences, which take up no columns in the output.
+/// A text split into lines by the newline policy is measured line by line as usual, and display widths beyond `u32::MAX` are capped.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, text_with_width, comp, compile, render};
+///
+/// let layout = comp(
+///   text_with_width("\x1b[1mfoo\x1b[0m".to_string(), 3),
+///   text("bar".to_string()),
+///   true, false
+/// );
+/// let document = compile(layout);
+/// assert_eq!(render(document, 2, 7), "\x1b[1mfoo\x1b[0m bar");
+/// ```
+#[must_use]
+pub fn text_with_width(
+  data: impl Into<Cow<'static, str>>,
+  display_width: usize
+) -> Box<Layout> {
+  Box::new(Layout::Text(data.into(), Some(display_width)))
+}
+
+/// Constructs a new breakable text layout, a text that may be split after the given break characters when it can not fit, e.g. a URL or a long qualified path.
+///
+/// The text is split after every run of break characters that is not at its end, such that `::` and `//` are kept whole; the pieces are composed without padding in a group, the pieces after the first nested under it as a continuation indent, and filled onto each line as far as they fit.
+/// A text without any such run is a text layout as given by `text`.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, breakable_text, compile, render};
+///
+/// let layout = comp(
+///   text("use"),
+///   comp(breakable_text("std::collections::hash_map::Entry", &[':']), text(";"), false, true),
+///   true, false
+/// );
+/// let document = compile(layout);
+/// assert_eq!(render(document.clone(), 4, 40), "use std::collections::hash_map::Entry;");
+/// assert_eq!(render(document.clone(), 4, 20), "use\nstd::collections::\n    hash_map::Entry;");
+///
+/// let layout = breakable_text("https://example.com/foo-bar".to_string(), &['/', '-']);
+/// assert_eq!(render(compile(layout), 2, 12), "https://\n  example.com/\n  foo-bar");
+/// ```
+#[must_use]
+pub fn breakable_text(
+  data: impl Into<Cow<'static, str>>,
+  break_chars: &[char]
+) -> Box<Layout> {
+  let data = data.into();
+  let mut splits = Vec::new();
+  let mut chars = data.char_indices().peekable();
+  while let Some((_, char)) = chars.next() {
+    let Some((index, next)) = chars.peek() else { break };
+    if break_chars.contains(&char) && !break_chars.contains(next) {
+      splits.push(*index)
+    }
+  }
+  if splits.is_empty() { return text(data) }
+  let piece = |from: usize, to: usize| match &data {
+    Cow::Borrowed(data) => text(&data[from..to]),
+    Cow::Owned(data) => text(data[from..to].to_string())
+  };
+  let mut bounds = splits.iter().copied().chain([data.len()]).rev();
+  let mut to = bounds.next().expect("The end of the text is a bound");
+  let mut rest = None;
+  for from in bounds {
+    let item = piece(from, to);
+    rest = Some(match rest {
+      None => item,
+      Some(rest) => comp(item, rest, false, false)
+    });
+    to = from
+  }
+  let rest = rest.expect("The text has a split");
+  grp(comp(piece(0, to), nest(rest), false, false))
+}
+
+/// A hook for language-aware hyphenation of words, see `hyphenated_text`.
+pub trait Hyphenator {
+  /// Gives the byte offsets into the word at which it may be broken with a hyphen; offsets at either end of the word or within a character are ignored.
+  fn break_points(&self, word: &str) -> Vec<usize>;
+}
+
+/// Constructs a new hyphenated text layout, a word that may be broken with a hyphen at the break points given by a hyphenator, e.g. a long word of prose in generated documentation.
+///
+/// The pieces of the word are composed without padding, and filled onto the line as far as they fit, the last piece on the line followed by a hyphen; a hyphen is only emitted when the line is broken within the word, and is measured with the piece before it, such that a hyphenated line fits the buffer width.
+/// A word without any break points is a text layout as given by `text`.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, hyphenated_text, compile, render, Hyphenator};
+///
+/// struct Syllables;
+/// impl Hyphenator for Syllables {
+///   fn break_points(&self, word: &str) -> Vec<usize> {
+///     if word == "typesetting" { vec![4, 7] } else { vec![] }
+///   }
+/// }
+///
+/// let layout = comp(text("modern"), hyphenated_text("typesetting", &Syllables), true, false);
+/// let document = compile(layout);
+/// assert_eq!(render(document.clone(), 2, 80), "modern typesetting");
+/// assert_eq!(render(document.clone(), 2, 12), "modern type-\nsetting");
+/// assert_eq!(render(document, 2, 15), "modern typeset-\nting");
+/// ```
+#[must_use]
+pub fn hyphenated_text(
+  data: impl Into<Cow<'static, str>>,
+  hyphenator: &dyn Hyphenator
 ) -> Box<Layout> {
-  Box::new(Layout::Text(data))
+  let data = data.into();
+  let mut splits: Vec<usize> = hyphenator.break_points(&data).into_iter()
+    .filter(|index| 0 < *index && *index < data.len() && data.is_char_boundary(*index))
+    .collect();
+  splits.sort_unstable();
+  splits.dedup();
+  let piece = |from: usize, to: usize| match &data {
+    Cow::Borrowed(data) => text(&data[from..to]),
+    Cow::Owned(data) => text(data[from..to].to_string())
+  };
+  let mut bounds = splits.into_iter().rev();
+  let Some(last) = bounds.next() else { return text(data) };
+  let mut rest = piece(last, data.len());
+  let mut to = last;
+  for from in bounds.chain([0]) {
+    let item = Box::new(Layout::Annotate(Annotation::Hyphen, piece(from, to)));
+    rest = comp(item, rest, false, false);
+    to = from
+  }
+  rest
 }
 
 /// Constructs a new Fix layout.
@@ -115,18 +618,20 @@ pub fn text(
 ///
 /// # Examples
 /// ```
-/// use typeset::{text, comp, fix};
+/// use typeset::{text, comp, fix, compile, render};
 ///
 /// let layout = fix(comp(
 ///   text("foo".to_string()),
 ///   text("bar".to_string()),
 ///   false, false
 /// ));
+/// assert_eq!(render(compile(layout), 2, 3), "foobar");
 /// ```
+#[must_use]
 pub fn fix(
-  layout: Box<Layout>
+  layout: impl Into<Box<Layout>>
 ) -> Box<Layout> {
-  Box::new(Layout::Fix(layout))
+  Box::new(Layout::Fix(layout.into()))
 }
 
 /// Constructs a new Grp layout.
@@ -135,38 +640,152 @@ pub fn fix(
 ///
 /// # Examples
 /// ```
-/// use typeset::{text, comp, grp};
+/// use typeset::{text, comp, grp, compile, render};
 ///
 /// let layout = grp(comp(
 ///   text("foo".to_string()),
 ///   text("bar".to_string()),
 ///   false, false
 /// ));
+/// assert_eq!(render(compile(layout), 2, 3), "foo\nbar");
+///
+/// let layout = comp(
+///   text("foo".to_string()),
+///   grp(comp(
+///     text("bar".to_string()),
+///     text("baz".to_string()),
+///     true, false
+///   )),
+///   true, false
+/// );
+/// assert_eq!(render(compile(layout), 2, 8), "foo\nbar baz");
 /// ```
+#[must_use]
 pub fn grp(
-  layout: Box<Layout>
+  layout: impl Into<Box<Layout>>
+) -> Box<Layout> {
+  Box::new(Layout::Grp(None, layout.into()))
+}
+
+/// Constructs a new named Grp layout.
+///
+/// Named Grp layouts render exactly as Grp layouts, the name is only kept for debugging, and is shown wherever the group survives compilation, e.g. in the `Display` of the compiled document and in compile traces.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, grp_named, compile, render};
+///
+/// let layout = comp(
+///   text("foo".to_string()),
+///   grp_named("args".to_string(), comp(
+///     text("bar".to_string()),
+///     text("baz".to_string()),
+///     true, false
+///   )),
+///   true, false
+/// );
+/// let document = compile(layout);
+/// assert!(document.to_string().contains("(Grp \"args\" "));
+/// assert_eq!(render(document, 2, 80), "foo bar baz");
+/// ```
+#[must_use]
+pub fn grp_named(
+  name: impl Into<Cow<'static, str>>,
+  layout: impl Into<Box<Layout>>
+) -> Box<Layout> {
+  Box::new(Layout::Grp(Some(name.into()), layout.into()))
+}
+
+/// Constructs a new Grp layout with a break hint.
+///
+/// Hinted Grp layouts render as Grp layouts, except that the compositions under them are broken as if the available width was wider or narrower by the `hint_tolerance` render option: with `Hint::PreferFlat` a composition is kept on its line when it overflows the width by at most the tolerance, and with `Hint::PreferBroken` a composition is broken when it comes within the tolerance of the width.
+/// The hint of the innermost hinted group applies, and the hint is carried by the texts of the group, such that a group without any text has no hint.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, grp, grp_hint, compile, render_with, Hint, RenderOptions};
+///
+/// let closure = || comp(text("|x|"), text("x + 1"), true, false);
+/// let options = RenderOptions { width: 8, ..RenderOptions::default() };
+/// assert_eq!(render_with(&compile(grp(closure())), &options).text, "|x|\nx + 1");
+/// let document = compile(grp_hint(closure(), Hint::PreferFlat));
+/// assert_eq!(render_with(&document, &options).text, "|x| x + 1");
+///
+/// let options = RenderOptions { width: 10, ..RenderOptions::default() };
+/// assert_eq!(render_with(&compile(grp(closure())), &options).text, "|x| x + 1");
+/// let document = compile(grp_hint(closure(), Hint::PreferBroken));
+/// assert_eq!(render_with(&document, &options).text, "|x|\nx + 1");
+/// let options = RenderOptions { hint_tolerance: 0, ..options };
+/// assert_eq!(render_with(&document, &options).text, "|x| x + 1");
+/// ```
+#[must_use]
+pub fn grp_hint(
+  layout: impl Into<Box<Layout>>,
+  hint: Hint
 ) -> Box<Layout> {
-  Box::new(Layout::Grp(layout))
+  grp(Box::new(Layout::Annotate(Annotation::Hint(hint), layout.into())))
 }
 
 /// Constructs a new Seq layout.
 ///
 /// Seq layouts are modal layouts that will ensure that all compositions under them will be broken into newlines during rendering, if any one of the compositions are broken.
 ///
+/// A sequence is kept on one line when it fits from the column it starts at, i.e. after the text, padding and indentation before it on its line, up to the buffer width or the ribbon width; the width of the sequence alone is not enough.
+///
 /// # Examples
 /// ```
-/// use typeset::{text, comp, seq};
+/// use typeset::{text, comp, seq, nest, compile, render};
 ///
 /// let layout = seq(comp(
 ///   text("foo".to_string()),
-///   text("bar".to_string()),
-///   false, false
+///   comp(text("bar".to_string()), text("baz".to_string()), true, false),
+///   true, false
 /// ));
+/// assert_eq!(render(compile(layout.clone()), 2, 11), "foo bar baz");
+/// assert_eq!(render(compile(layout), 2, 8), "foo\nbar\nbaz");
+///
+/// let layout = comp(
+///   text("header"),
+///   nest(seq(comp(text("foo"), text("bar"), true, false))),
+///   true, false
+/// );
+/// assert_eq!(render(compile(layout.clone()), 2, 14), "header foo bar");
+/// assert_eq!(render(compile(layout), 2, 13), "header foo\n  bar");
 /// ```
+#[must_use]
 pub fn seq(
-  layout: Box<Layout>
+  layout: impl Into<Box<Layout>>
+) -> Box<Layout> {
+  Box::new(Layout::Seq(None, layout.into()))
+}
+
+/// Constructs a new named Seq layout.
+///
+/// Named Seq layouts render exactly as Seq layouts, the name is only kept for debugging, and is shown wherever the sequence survives compilation, e.g. in the `Display` of the compiled document and in compile traces.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, seq_named, compile, render};
+///
+/// let layout = seq_named("items".to_string(), comp(
+///   text("foo".to_string()),
+///   comp(
+///     text("bar".to_string()),
+///     text("baz".to_string()),
+///     true, false
+///   ),
+///   true, false
+/// ));
+/// let document = compile(layout);
+/// assert!(document.to_string().contains("(Seq \"items\" "));
+/// assert_eq!(render(document, 2, 8), "foo\nbar\nbaz");
+/// ```
+#[must_use]
+pub fn seq_named(
+  name: impl Into<Cow<'static, str>>,
+  layout: impl Into<Box<Layout>>
 ) -> Box<Layout> {
-  Box::new(Layout::Seq(layout))
+  Box::new(Layout::Seq(Some(name.into()), layout.into()))
 }
 
 /// Constructs a new Nest layout.
@@ -175,38 +794,197 @@ pub fn seq(
 ///
 /// # Examples
 /// ```
-/// use typeset::{text, comp, nest};
+/// use typeset::{text, comp, nest, compile, render};
 ///
-/// let layout = nest(comp(
+/// let layout = comp(
 ///   text("foo".to_string()),
-///   text("bar".to_string()),
+///   nest(text("bar".to_string())),
 ///   false, false
-/// ));
+/// );
+/// assert_eq!(render(compile(layout), 2, 3), "foo\n  bar");
 /// ```
+#[must_use]
 pub fn nest(
-  layout: Box<Layout>
+  layout: impl Into<Box<Layout>>
 ) -> Box<Layout> {
-  Box::new(Layout::Nest(layout))
+  Box::new(Layout::Nest(layout.into()))
 }
 
 /// Constructs a new Pack layout.
 ///
 /// Pack layouts are modal layouts that will ensure that indentation will be prefixed to any broken compositions, making sure all the indentations line up with the index of the first character in the pack.
 ///
+/// The index of the first character is marked when the pack is first rendered, and the mark is kept for the rest of the document; when a pack spans several lines, the lines after the first are indented to the same mark, however distant they are.
+/// Use `pack_reset` to forget the marks at a boundary, or the `pack_scope` render option to forget them at every line.
+///
 /// # Examples
 /// ```
-/// use typeset::{text, comp, pack};
+/// use typeset::{text, comp, pack, compile, render};
 ///
-/// let layout = pack(comp(
-///   text("foo".to_string()),
-///   text("bar".to_string()),
-///   false, false
-/// ));
+/// let layout = comp(
+///   text("let".to_string()),
+///   pack(comp(
+///     text("foo".to_string()),
+///     text("bar".to_string()),
+///     true, false
+///   )),
+///   true, false
+/// );
+/// assert_eq!(render(compile(layout), 2, 8), "let foo\n    bar");
 /// ```
+#[must_use]
 pub fn pack(
-  layout: Box<Layout>
+  layout: impl Into<Box<Layout>>
 ) -> Box<Layout> {
-  Box::new(Layout::Pack(layout))
+  Box::new(Layout::Pack(layout.into()))
+}
+
+/// Constructs a new PackReset layout.
+///
+/// PackReset layouts are boundaries that take up no width, at which the renderer forgets the marks of all packs; the packs rendered after the boundary are marked anew at the index of their next character.
+/// The boundary is placed right after the text that precedes it, and a boundary without any preceding text is dropped.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, line, pack, pack_reset, compile, render};
+///
+/// let layout = comp(
+///   text("let".to_string()),
+///   pack(line(
+///     text("x".to_string()),
+///     line(
+///       text("y".to_string()),
+///       comp(pack_reset(), text("z".to_string()), false, false)
+///     )
+///   )),
+///   true, false
+/// );
+/// let document = compile(layout);
+/// assert_eq!(render(document, 2, 80), "let x\n    y\nz");
+/// ```
+#[must_use]
+pub fn pack_reset() -> Box<Layout> {
+  Box::new(Layout::PackReset)
+}
+
+/// Constructs a new Slot layout.
+///
+/// Slot layouts are named places that take up no width, which are kept in the compiled document, such that they can be filled with a layout after compilation by `Doc::fill_slot`, e.g. to attach comments after the structure of a document has been laid out.
+/// The slot is placed right before the text that follows it, or else right after the text that precedes it, and a slot in a layout without any text is dropped; slots that are never filled are ignored when rendering.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, slot, compile, render};
+///
+/// let layout = comp(slot("comment"), text("foo"), false, false);
+/// let document = compile(layout);
+/// assert_eq!(render(document.clone(), 2, 80), "foo");
+/// let document = document.fill_slot("comment", text("/* bar */ "));
+/// assert_eq!(render(document, 2, 80), "/* bar */ foo");
+/// ```
+#[must_use]
+pub fn slot(
+  name: impl Into<Cow<'static, str>>
+) -> Box<Layout> {
+  Box::new(Layout::Slot(name.into()))
+}
+
+/// Constructs a new PackGroup layout.
+///
+/// PackGroup layouts declare a set of anchors, each an independent alignment column within the group; every `pack_at` of the same anchor in the group shares one pack mark, which is the furthest column at which any of them would start, as found when rendering.
+/// Each of them is padded up to the mark, also in the middle of a line, and their broken compositions are indented to it like those of a single pack; the marks of anchors are not forgotten by `pack_reset` or the `pack_scope` render option.
+/// An anchor is bound by the innermost group that declares it, such that nested groups get columns of their own.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, line, pack_group, pack_at, compile, render, PackAnchor};
+///
+/// let value = PackAnchor::new("value");
+/// let comment = PackAnchor::new("comment");
+/// let assign = |name: &str, data: &str, note: &str| comp(
+///   comp(
+///     text(format!("{} =", name)),
+///     pack_at(&value, text(data.to_string())),
+///     true, false
+///   ),
+///   pack_at(&comment, text(format!("// {}", note))),
+///   true, false
+/// );
+/// let layout = pack_group(&[value.clone(), comment.clone()], line(
+///   assign("x", "1", "tab"),
+///   assign("width", "80", "columns")
+/// ));
+/// let document = compile(layout);
+/// assert_eq!(render(document, 2, 80), "x =     1  // tab\nwidth = 80 // columns");
+/// ```
+#[must_use]
+pub fn pack_group(
+  anchors: &[PackAnchor],
+  layout: impl Into<Box<Layout>>
+) -> Box<Layout> {
+  Box::new(Layout::PackGroup(anchors.to_vec(), layout.into()))
+}
+
+/// Constructs a new PackAt layout.
+///
+/// PackAt layouts are packs whose mark is shared with every other `pack_at` of the same anchor in the enclosing `pack_group`, see `pack_group`; compiling a `pack_at` whose anchor is not declared by an enclosing group gives an `UnboundAnchor` error.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, pack_at, compile_safe, PackAnchor, CompilerError};
+///
+/// let anchor = PackAnchor::new("value");
+/// let layout = comp(
+///   text("x =".to_string()),
+///   pack_at(&anchor, text("1".to_string())),
+///   true, false
+/// );
+/// assert_eq!(
+///   compile_safe(layout).unwrap_err(),
+///   CompilerError::UnboundAnchor("value".to_string())
+/// );
+/// ```
+#[must_use]
+pub fn pack_at(
+  anchor: &PackAnchor,
+  layout: impl Into<Box<Layout>>
+) -> Box<Layout> {
+  Box::new(Layout::PackAt(anchor.clone(), layout.into()))
+}
+
+/// Constructs a layout of lines, each a left and a right side joined by a separator, where the separators line up in one column.
+///
+/// The column is found when rendering, as the furthest column any of the separators would start at, and the separator is padded by a space on either side; the lines share a `pack_group` of their own, so nested alignments get columns of their own.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, align_on, compile, render};
+///
+/// let layout = align_on(vec![
+///   (text("x".to_string()), text("1".to_string())),
+///   (text("width".to_string()), text("80".to_string())),
+///   (text("tab".to_string()), text("2".to_string()))
+/// ], text("=".to_string()));
+/// let document = compile(layout);
+/// assert_eq!(render(document, 2, 80), "x     = 1\nwidth = 80\ntab   = 2");
+/// ```
+#[must_use]
+pub fn align_on(
+  items: Vec<(Box<Layout>, Box<Layout>)>,
+  separator: Box<Layout>
+) -> Box<Layout> {
+  let anchor = PackAnchor::new("align_on");
+  let layout = items.into_iter().rev()
+    .map(|(left, right)| comp(
+      comp(left, pack_at(&anchor, separator.clone()), true, false),
+      right,
+      true, false
+    ))
+    .reduce(|result, item| line(item, result));
+  match layout {
+    None => null(),
+    Some(layout) => pack_group(&[anchor], layout)
+  }
 }
 
 /// Constructs a new Line layout.
@@ -215,18 +993,20 @@ pub fn pack(
 ///
 /// # Examples
 /// ```
-/// use typeset::{text, line};
+/// use typeset::{text, line, compile, render};
 ///
 /// let layout = line(
 ///   text("foo".to_string()),
 ///   text("bar".to_string())
 /// );
+/// assert_eq!(render(compile(layout), 2, 80), "foo\nbar");
 /// ```
+#[must_use]
 pub fn line(
-  left: Box<Layout>,
-  right: Box<Layout>
+  left: impl Into<Box<Layout>>,
+  right: impl Into<Box<Layout>>
 ) -> Box<Layout> {
-  Box::new(Layout::Line(left, right))
+  Box::new(Layout::Line(left.into(), right.into()))
 }
 
 /// Constructs a new Comp layout.
@@ -235,48 +1015,507 @@ pub fn line(
 ///
 /// # Examples
 /// ```
-/// use typeset::{text, comp};
+/// use typeset::{text, comp, compile, render};
 ///
 /// let layout = comp(
 ///   text("foo".to_string()),
 ///   text("bar".to_string()),
 ///   false, false
 /// );
+/// let document = compile(layout);
+/// assert_eq!(render(document.clone(), 2, 80), "foobar");
+/// assert_eq!(render(document, 2, 3), "foo\nbar");
 /// ```
+#[must_use]
 pub fn comp(
-  left: Box<Layout>,
-  right: Box<Layout>,
+  left: impl Into<Box<Layout>>,
+  right: impl Into<Box<Layout>>,
   pad: bool,
   fix: bool
 ) -> Box<Layout> {
-  Box::new(Layout::Comp(left, right, Attr {
-    pad: pad,
-    fix: fix
+  Box::new(Layout::Comp(left.into(), right.into(), Attr {
+    pad,
+    fix
   }))
 }
 
-#[derive(Debug)]
-enum Broken<'a> {
-  Null,
-  Text(&'a str),
-  Fix(&'a Broken<'a>),
-  Grp(&'a Broken<'a>),
-  Seq(bool, &'a Broken<'a>),
-  Nest(&'a Broken<'a>),
-  Pack(&'a Broken<'a>),
-  Line(&'a Broken<'a>, &'a Broken<'a>),
+/// Where the operators of a `chain` are placed when the chain is broken, at the start of the line of their operand or at the end of the line before it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum OperatorStyle {
+  #[default]
+  Leading,
+  Trailing
+}
+
+/// Constructs a layout of an infix operator chain, a first operand followed by pairs of an operator and an operand.
+///
+/// The chain is either laid out flat on one line, or broken with each of the other operands on a line of its own, nested under the first operand; the operators are kept with their operands, before them or after the operand before them depending on the style.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, chain, compile, render, OperatorStyle};
+///
+/// let operands = || vec![
+///   (text("+".to_string()), text("bar".to_string())),
+///   (text("+".to_string()), text("baz".to_string()))
+/// ];
+/// let layout = chain(text("foo".to_string()), operands(), OperatorStyle::Leading);
+/// assert_eq!(render(compile(layout.clone()), 2, 80), "foo + bar + baz");
+/// assert_eq!(render(compile(layout), 2, 10), "foo\n  + bar\n  + baz");
+///
+/// let layout = chain(text("foo".to_string()), operands(), OperatorStyle::Trailing);
+/// assert_eq!(render(compile(layout), 2, 10), "foo +\n  bar +\n  baz");
+/// ```
+#[must_use]
+pub fn chain(
+  first: impl Into<Box<Layout>>,
+  rest: Vec<(Box<Layout>, Box<Layout>)>,
+  style: OperatorStyle
+) -> Box<Layout> {
+  let first = first.into();
+  if rest.is_empty() { return first }
+  let (first1, items) = match style {
+    OperatorStyle::Leading => {
+      let items = rest.into_iter()
+        .map(|(operator, operand)| comp(operator, operand, true, true))
+        .collect::<Vec<_>>();
+      (first, items)
+    }
+    OperatorStyle::Trailing => {
+      let mut rest1 = rest.into_iter();
+      let (operator, mut operand) = rest1.next().expect("The rest is not empty");
+      let mut items = Vec::new();
+      for (operator1, operand1) in rest1 {
+        items.push(comp(operand, operator1, true, true));
+        operand = operand1
+      }
+      items.push(operand);
+      (comp(first, operator, true, true), items)
+    }
+  };
+  let items1 = items.into_iter().rev()
+    .reduce(|result, item| comp(item, result, true, false))
+    .expect("The rest is not empty");
+  seq(comp(first1, nest(items1), true, false))
+}
+
+/// Constructs a layout of a method chain, a receiver followed by calls, where each call carries its own leading `.` or other accessor.
+///
+/// The chain is either laid out flat on one line, or broken with each call on a line of its own, nested under the receiver; when `keep_first` is set, the first call stays on the line of the receiver.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, method_chain, compile, render};
+///
+/// let calls = || vec![
+///   text(".iter()".to_string()),
+///   text(".map(f)".to_string()),
+///   text(".sum()".to_string())
+/// ];
+/// let layout = method_chain(text("xs".to_string()), calls(), false);
+/// assert_eq!(render(compile(layout.clone()), 2, 80), "xs.iter().map(f).sum()");
+/// assert_eq!(render(compile(layout), 2, 10), "xs\n  .iter()\n  .map(f)\n  .sum()");
+///
+/// let layout = method_chain(text("xs".to_string()), calls(), true);
+/// assert_eq!(render(compile(layout), 2, 10), "xs.iter()\n  .map(f)\n  .sum()");
+/// ```
+#[must_use]
+pub fn method_chain(
+  receiver: impl Into<Box<Layout>>,
+  calls: Vec<Box<Layout>>,
+  keep_first: bool
+) -> Box<Layout> {
+  let mut calls1 = calls.into_iter();
+  let mut receiver1 = receiver.into();
+  if keep_first {
+    if let Some(call) = calls1.next() {
+      receiver1 = comp(receiver1, call, false, true)
+    }
+  }
+  match calls1.rev().reduce(|result, call| comp(call, result, false, false)) {
+    None => receiver1,
+    Some(calls2) => seq(comp(receiver1, nest(calls2), false, false))
+  }
+}
+
+/// Constructs a layout of items joined by soft lines, unpadded compositions in a seq, such that the items are either all on one line or each on a line of its own.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, join_with_soft_lines, compile, render};
+///
+/// let items = || vec![text("foo,"), text("bar,"), text("baz")];
+/// let layout = join_with_soft_lines(items());
+/// assert_eq!(render(compile(layout.clone()), 2, 80), "foo,bar,baz");
+/// assert_eq!(render(compile(layout), 2, 8), "foo,\nbar,\nbaz");
+/// assert_eq!(render(compile(join_with_soft_lines(vec![])), 2, 80), "");
+/// ```
+#[must_use]
+pub fn join_with_soft_lines(
+  items: Vec<Box<Layout>>
+) -> Box<Layout> {
+  if items.is_empty() { return null() }
+  seq(items)
+}
+
+/// Constructs a layout of items joined as paragraphs, each item on lines of its own and separated from the next by a blank line.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, join_paragraphs, compile, render};
+///
+/// let layout = join_paragraphs(vec![
+///   comp(text("foo"), text("bar"), true, false),
+///   text("baz")
+/// ]);
+/// assert_eq!(render(compile(layout.clone()), 2, 80), "foo bar\n\nbaz");
+/// assert_eq!(render(compile(layout), 2, 4), "foo\nbar\n\nbaz");
+/// ```
+#[must_use]
+pub fn join_paragraphs(
+  items: Vec<Box<Layout>>
+) -> Box<Layout> {
+  items.into_iter().rev()
+    .reduce(|result, item| line(item, line(null(), result)))
+    .unwrap_or_else(null)
+}
+
+/// Validates a layout for whitespace-sensitive output, e.g. Python or YAML, where a line that is indented wrongly changes the meaning of the output, and gives back the layout.
+///
+/// The layout is valid when breaking any of its compositions can only give a line that is indented deeper than the line it breaks from started, i.e. when the right side of every breakable composition starts under more nests than the line it is on; then breaks never give a line at the indentation of a sibling, and only line layouts start lines at the indentation of their siblings.
+/// Packs do not count as indentation, since their column depends on where they are rendered; compositions under a fix can not be broken, and are not checked.
+/// An invalid layout gives a `CompilerError::DedentingBreak` with the first composition that could break to a dedented line.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, line, nest, indent_sensitive, compile, render, CompilerError};
+///
+/// let call = || comp(text("foo("), nest(comp(text("x,"), text("y)"), true, false)), false, false);
+/// let layout = indent_sensitive(line(text("if a:"), nest(call()))).unwrap();
+/// assert_eq!(render(compile(layout), 4, 10), "if a:\n    foo(x,\n        y)");
+///
+/// let layout = comp(text("x ="), text("1"), true, false);
+/// assert!(matches!(indent_sensitive(layout), Err(CompilerError::DedentingBreak(_))));
+/// let layout = line(text("if a:"), nest(comp(text("x ="), text("1"), true, false)));
+/// assert!(matches!(indent_sensitive(layout), Err(CompilerError::DedentingBreak(_))));
+/// ```
+pub fn indent_sensitive(
+  layout: impl Into<Box<Layout>>
+) -> Result<Box<Layout>, CompilerError> {
+  /* The number of nests over the first text of a layout */
+  fn _leading_nests(
+    layout: &Layout
+  ) -> usize {
+    let mut result = 0;
+    let mut layout = layout;
+    loop {
+      layout = match layout {
+        Layout::Null | Layout::Text(_, _) | Layout::PackReset | Layout::Slot(_) => return result,
+        Layout::Nest(layout1) => { result += 1; layout1 }
+        Layout::Annotate(_, layout1) | Layout::Fix(layout1) |
+        Layout::Grp(_, layout1) | Layout::Seq(_, layout1) |
+        Layout::Pack(layout1) | Layout::PackGroup(_, layout1) | Layout::PackAt(_, layout1) |
+        Layout::Line(layout1, _) | Layout::Comp(layout1, _, _) => layout1
+      }
+    }
+  }
+  /* The nests of the last line started within a layout, given the nests of the layout, if it has a line */
+  fn _trailing_line(
+    layout: &Layout,
+    depth: usize
+  ) -> Option<usize> {
+    let mut stack: Vec<Result<(&Layout, usize), usize>> = vec![Ok((layout, depth))];
+    while let Some(item) = stack.pop() {
+      let (layout1, depth1) = match item {
+        Err(line_depth) => return Some(line_depth),
+        Ok(item) => item
+      };
+      match layout1 {
+        Layout::Null | Layout::Text(_, _) | Layout::PackReset | Layout::Slot(_) => (),
+        Layout::Nest(layout2) => stack.push(Ok((layout2, depth1 + 1))),
+        Layout::Annotate(_, layout2) | Layout::Fix(layout2) |
+        Layout::Grp(_, layout2) | Layout::Seq(_, layout2) |
+        Layout::Pack(layout2) | Layout::PackGroup(_, layout2) | Layout::PackAt(_, layout2) =>
+          stack.push(Ok((layout2, depth1))),
+        Layout::Line(_, right) => {
+          stack.push(Err(depth1 + _leading_nests(right)));
+          stack.push(Ok((right, depth1)))
+        }
+        Layout::Comp(left, right, _) => {
+          stack.push(Ok((left, depth1)));
+          stack.push(Ok((right, depth1)))
+        }
+      }
+    }
+    None
+  }
+  let layout = layout.into();
+  /* The layouts to check, by their nests, the nests of the line they are on, and whether they are fixed */
+  let mut stack: Vec<(&Layout, usize, usize, bool)> = vec![(&layout, 0, _leading_nests(&layout), false)];
+  while let Some((layout1, depth, line_depth, fixed)) = stack.pop() {
+    match layout1 {
+      Layout::Null | Layout::Text(_, _) | Layout::PackReset | Layout::Slot(_) => (),
+      Layout::Nest(layout2) =>
+        stack.push((layout2, depth + 1, line_depth, fixed)),
+      Layout::Fix(layout2) =>
+        stack.push((layout2, depth, line_depth, true)),
+      Layout::Annotate(_, layout2) |
+      Layout::Grp(_, layout2) | Layout::Seq(_, layout2) |
+      Layout::Pack(layout2) | Layout::PackGroup(_, layout2) | Layout::PackAt(_, layout2) =>
+        stack.push((layout2, depth, line_depth, fixed)),
+      Layout::Line(left, right) => {
+        stack.push((right, depth, depth + _leading_nests(right), fixed));
+        stack.push((left, depth, line_depth, fixed))
+      }
+      Layout::Comp(left, right, attr) => {
+        let breakable = !fixed && !attr.fix;
+        let line_depth1 = _trailing_line(left, depth).unwrap_or(line_depth);
+        if breakable && depth + _leading_nests(right) <= line_depth1 {
+          return Err(CompilerError::DedentingBreak(layout1.to_string()))
+        }
+        stack.push((right, depth, line_depth1, fixed));
+        stack.push((left, depth, line_depth, fixed))
+      }
+    }
+  }
+  Ok(layout)
+}
+
+/// Constructs a layout from text structured by indentation, such as a hand-formatted snippet, inferring the nesting of each line from its leading whitespace.
+///
+/// Every `indent_unit` leading spaces, or a leading tab, of a line is one level of nesting, and the lines are composed with `line`, the lines of each level nested under the line before them.
+/// Leading spaces short of a whole unit are kept in the text of the line, as is all leading whitespace when `indent_unit` is 0; blank lines are kept as empty lines.
+/// Rendering the layout gives back the text, re-indented with the tab indentation size of the render options.
+///
+/// # Examples
+/// ```
+/// use typeset::{from_indented_text, compile, render};
+///
+/// let layout = from_indented_text("fn foo() {\n    bar();\n\n    baz(\n        1);\n}", 4);
+/// let document = compile(layout);
+/// assert_eq!(render(document.clone(), 4, 80), "fn foo() {\n    bar();\n\n    baz(\n        1);\n}");
+/// assert_eq!(render(document, 2, 80), "fn foo() {\n  bar();\n\n  baz(\n    1);\n}");
+/// ```
+#[must_use]
+pub fn from_indented_text(
+  data: &str,
+  indent_unit: usize
+) -> Box<Layout> {
+  fn _depth(
+    data: &str,
+    indent_unit: usize
+  ) -> (usize, &str) {
+    if indent_unit == 0 { return (0, data) }
+    let mut depth = 0;
+    let mut rest = data;
+    loop {
+      if let Some(rest1) = rest.strip_prefix('\t') {
+        depth += 1;
+        rest = rest1
+      } else if rest.len() >= indent_unit && rest.bytes().take(indent_unit).all(|c| c == b' ') {
+        depth += 1;
+        rest = &rest[indent_unit..]
+      } else {
+        return (depth, rest)
+      }
+    }
+  }
+  fn _block(
+    lines: &[(usize, &str)],
+    depth: usize
+  ) -> Box<Layout> {
+    let mut items = Vec::new();
+    let mut index = 0;
+    while index < lines.len() {
+      let (depth1, data) = lines[index];
+      if depth1 <= depth {
+        items.push(if data.is_empty() { null() } else { text(data.to_string()) });
+        index += 1;
+        continue
+      }
+      let length = lines[index..].iter()
+        .take_while(|(depth2, _)| depth < *depth2)
+        .count();
+      items.push(nest(_block(&lines[index..index + length], depth + 1)));
+      index += length
+    }
+    items.into_iter().rev()
+      .reduce(|result, item| line(item, result))
+      .unwrap_or_else(null)
+  }
+  let mut lines: Vec<(usize, &str)> = Vec::new();
+  for data1 in data.split('\n') {
+    let line1 = if data1.trim().is_empty() {
+      (lines.last().map_or(0, |(depth, _)| *depth), "")
+    } else {
+      _depth(data1, indent_unit)
+    };
+    lines.push(line1)
+  }
+  _block(&lines, 0)
+}
+
+/// Constructs a new link annotation of a layout.
+///
+/// Annotations take up no width and do not change how the layout is broken; the markers of an annotation are placed around its first and last text, and an annotation without any text is dropped.
+/// The ANSI and HTML backends render links as OSC 8 hyperlinks and `<a>` elements respectively, while `render` ignores them.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, link, compile, render, render_ansi, render_html, RenderOptions, Theme};
+///
+/// let layout = comp(
+///   text("see".to_string()),
+///   link("https://example.com".to_string(), text("a<b".to_string())),
+///   true, false
+/// );
+/// let document = compile(layout);
+/// let options = RenderOptions::default();
+/// let theme = Theme::default();
+/// assert_eq!(render(document.clone(), 2, 80), "see a<b");
+/// assert_eq!(
+///   render_ansi(&document, &options, &theme),
+///   "see \x1b]8;;https://example.com\x1b\\a<b\x1b]8;;\x1b\\"
+/// );
+/// assert_eq!(
+///   render_html(&document, &options, &theme),
+///   "see <a href=\"https://example.com\">a&lt;b</a>"
+/// );
+/// ```
+#[must_use]
+pub fn link(
+  url: impl Into<Cow<'static, str>>,
+  layout: impl Into<Box<Layout>>
+) -> Box<Layout> {
+  Box::new(Layout::Annotate(Annotation::Link(url.into()), layout.into()))
+}
+
+/// Constructs a new tag annotation of a layout.
+///
+/// Tags are styled by the ANSI and HTML backends according to their theme, while `render` ignores them.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, tag, compile, render_ansi, render_html, RenderOptions, Theme};
+///
+/// let layout = comp(
+///   tag("keyword".to_string(), text("let".to_string())),
+///   text("x".to_string()),
+///   true, false
+/// );
+/// let document = compile(layout);
+/// let options = RenderOptions::default();
+/// let theme = Theme::default();
+/// assert_eq!(render_ansi(&document, &options, &theme), "\x1b[1;34mlet\x1b[0m x");
+/// assert_eq!(render_html(&document, &options, &theme), "<span class=\"keyword\">let</span> x");
+/// ```
+#[must_use]
+pub fn tag(
+  name: impl Into<Cow<'static, str>>,
+  layout: impl Into<Box<Layout>>
+) -> Box<Layout> {
+  Box::new(Layout::Annotate(Annotation::Tag(name.into()), layout.into()))
+}
+
+/*
+  The attributes of a text are kept as 32 bit numbers, such that a text node
+  of the intermediate representations fits in 32 bytes; display widths beyond
+  u32::MAX are capped.
+*/
+#[derive(Debug, Copy, Clone)]
+pub struct TextAttr {
+  width: Option<u32>,
+  markers: Option<u32>
+}
+
+impl TextAttr {
+  fn _width(&self) -> Option<usize> {
+    self.width.map(|width| width as usize)
+  }
+
+  fn _markers(&self) -> Option<usize> {
+    self.markers.map(|index| index as usize)
+  }
+}
+
+pub(crate) fn _text_attr(
+  width: Option<usize>
+) -> TextAttr {
+  let width1 = width.map(|width| u32::try_from(width).unwrap_or(u32::MAX));
+  TextAttr { width: width1, markers: None }
+}
+
+fn _name<'a>(
+  mem: &'a Bump,
+  name: Option<&str>
+) -> Option<&'a str> {
+  name.map(|name| &*mem.alloc_str(name))
+}
+
+fn _print_name(
+  name: Option<&str>
+) -> String {
+  match name {
+    None => String::new(),
+    Some(name) => format!("{:?} ", name)
+  }
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LeafMarkers {
+  open: Vec<Marker>,
+  close: Vec<Marker>
+}
+
+/* The panic of the compiler when a layout has more scopes or packs than can be indexed, given as a `CompilerError::IndexOverflow` by the safe entry points */
+const INDEX_OVERFLOW: &str = "A layout has at most MAX_SCOPE_INDEX scopes and MAX_PACK_INDEX packs";
+
+/*
+  The indices of scopes, packs and anchors are given by counters, which
+  panic with `INDEX_OVERFLOW` rather than wrap around, since a wrapped index
+  would silently merge two scopes or align two packs to the same mark; a
+  counter runs out one short of the largest value of the index type, which
+  is why the largest indices of the limits are below it.
+*/
+const _: () = assert!(MAX_SCOPE_INDEX < Index::MAX && MAX_PACK_INDEX < Index::MAX);
+
+fn _fresh(
+  counter: &mut Index
+) -> Index {
+  let index = *counter;
+  *counter = index.checked_add(1)
+    .unwrap_or_else(|| panic::panic_any(INDEX_OVERFLOW));
+  index
+}
+
+#[derive(Debug)]
+struct AnchorScope {
+  bound: Vec<(PackAnchor, Index)>,
+  next: Index
+}
+
+#[derive(Debug)]
+enum Broken<'a> {
+  Null,
+  Text(&'a str, TextAttr),
+  Fix(&'a Broken<'a>),
+  Grp(Option<&'a str>, &'a Broken<'a>),
+  Seq(bool, Option<&'a str>, &'a Broken<'a>),
+  Nest(&'a Broken<'a>),
+  Pack(Option<Index>, &'a Broken<'a>),
+  Line(&'a Broken<'a>, &'a Broken<'a>),
   Comp(&'a Broken<'a>, &'a Broken<'a>, Attr)
 }
 
 #[derive(Debug)]
-enum EDSL<'a> {
+pub enum EDSL<'a> {
   Null,
-  Text(&'a str),
+  Text(&'a str, TextAttr),
   Fix(&'a EDSL<'a>),
-  Grp(&'a EDSL<'a>),
-  Seq(&'a EDSL<'a>),
+  Grp(Option<&'a str>, &'a EDSL<'a>),
+  Seq(Option<&'a str>, &'a EDSL<'a>),
   Nest(&'a EDSL<'a>),
-  Pack(&'a EDSL<'a>),
+  Pack(Option<Index>, &'a EDSL<'a>),
   Line(&'a EDSL<'a>, &'a EDSL<'a>),
   Comp(&'a EDSL<'a>, &'a EDSL<'a>, Attr)
 }
@@ -284,123 +1523,398 @@ enum EDSL<'a> {
 /*
   Collapse broken sequences
 */
-fn _broken<'b, 'a: 'b>(
+pub(crate) fn _broken<'b, 's, S: LayoutSource<'s>>(
   mem: &'b Bump,
-  layout: Box<Layout>
-) -> &'b EDSL<'b> {
-  fn _mark<'b, 'a: 'b>(
+  layout: S,
+  options: CompileOptions,
+  markers: &mut Vec<LeafMarkers>
+) -> Result<&'b EDSL<'b>, CompilerError> {
+  fn _mark<'b, 's, S: LayoutSource<'s>>(
     mem: &'b Bump,
-    layout: Box<Layout>
-  ) -> &'b Broken<'b> {
-    fn _visit<'b, 'a: 'b>(
-      mem: &'b Bump,
-      layout: Box<Layout>
-    ) -> (bool, &'b Broken<'b>) {
-      fn _null<'a>(
-        mem: &'a Bump
-      ) -> &'a Broken<'a> {
-        mem.alloc(Broken::Null)
-      }
-      fn _text<'a>(
-        mem: &'a Bump,
-        data: &'a str
-      ) -> &'a Broken<'a> {
-        mem.alloc(Broken::Text(data))
-      }
-      fn _fix<'a>(
-        mem: &'a Bump,
-        layout: &'a Broken<'a>
-      ) -> &'a Broken<'a> {
-        mem.alloc(Broken::Fix(layout))
-      }
-      fn _grp<'a>(
-        mem: &'a Bump,
-        layout: &'a Broken<'a>
-      ) -> &'a Broken<'a> {
-        mem.alloc(Broken::Grp(layout))
-      }
-      fn _seq<'a>(
-        mem: &'a Bump,
-        broken: bool,
-        layout: &'a Broken<'a>
-      ) -> &'a Broken<'a> {
-        mem.alloc(Broken::Seq(broken, layout))
-      }
-      fn _nest<'a>(
-        mem: &'a Bump,
-        layout: &'a Broken<'a>
-      ) -> &'a Broken<'a> {
-        mem.alloc(Broken::Nest(layout))
-      }
-      fn _pack<'a>(
-        mem: &'a Bump,
-        layout: &'a Broken<'a>
-      ) -> &'a Broken<'a> {
-        mem.alloc(Broken::Pack(layout))
-      }
-      fn _line<'a>(
-        mem: &'a Bump,
-        left: &'a Broken<'a>,
-        right: &'a Broken<'a>
-      ) -> &'a Broken<'a> {
-        mem.alloc(Broken::Line(left, right))
-      }
-      fn _comp<'a>(
-        mem: &'a Bump,
-        left: &'a Broken<'a>,
-        right: &'a Broken<'a>,
-        attr: Attr
-      ) -> &'a Broken<'a> {
-        mem.alloc(Broken::Comp(left, right, attr))
+    layout: S,
+    options: CompileOptions,
+    markers: &mut Vec<LeafMarkers>
+  ) -> Result<&'b Broken<'b>, CompilerError> {
+    fn _null<'a>(
+      mem: &'a Bump
+    ) -> &'a Broken<'a> {
+      mem.alloc(Broken::Null)
+    }
+    fn _text<'a>(
+      mem: &'a Bump,
+      data: &'a str,
+      attr: TextAttr
+    ) -> &'a Broken<'a> {
+      mem.alloc(Broken::Text(data, attr))
+    }
+    fn _fix<'a>(
+      mem: &'a Bump,
+      layout: &'a Broken<'a>
+    ) -> &'a Broken<'a> {
+      mem.alloc(Broken::Fix(layout))
+    }
+    fn _grp<'a>(
+      mem: &'a Bump,
+      name: Option<&'a str>,
+      layout: &'a Broken<'a>
+    ) -> &'a Broken<'a> {
+      mem.alloc(Broken::Grp(name, layout))
+    }
+    fn _seq<'a>(
+      mem: &'a Bump,
+      broken: bool,
+      name: Option<&'a str>,
+      layout: &'a Broken<'a>
+    ) -> &'a Broken<'a> {
+      mem.alloc(Broken::Seq(broken, name, layout))
+    }
+    fn _nest<'a>(
+      mem: &'a Bump,
+      layout: &'a Broken<'a>
+    ) -> &'a Broken<'a> {
+      mem.alloc(Broken::Nest(layout))
+    }
+    fn _pack<'a>(
+      mem: &'a Bump,
+      anchor: Option<Index>,
+      layout: &'a Broken<'a>
+    ) -> &'a Broken<'a> {
+      mem.alloc(Broken::Pack(anchor, layout))
+    }
+    fn _line<'a>(
+      mem: &'a Bump,
+      left: &'a Broken<'a>,
+      right: &'a Broken<'a>
+    ) -> &'a Broken<'a> {
+      mem.alloc(Broken::Line(left, right))
+    }
+    fn _comp<'a>(
+      mem: &'a Bump,
+      left: &'a Broken<'a>,
+      right: &'a Broken<'a>,
+      attr: Attr
+    ) -> &'a Broken<'a> {
+      mem.alloc(Broken::Comp(left, right, attr))
+    }
+    /*
+      Attach a marker to the first or the last non-empty text of a layout,
+      descending with an explicit stack of the layouts passed through rather
+      than by recursion, and trying the other side of a composition when the
+      side tried first has no such text; the layouts passed through are then
+      rebuilt around the marked text.
+    */
+    fn _attach<'a>(
+      mem: &'a Bump,
+      layout: &'a Broken<'a>,
+      marker: Marker,
+      first: bool,
+      markers: &mut Vec<LeafMarkers>
+    ) -> Option<&'a Broken<'a>> {
+      enum Step<'a> {
+        Fix,
+        Grp(Option<&'a str>),
+        Seq(bool, Option<&'a str>),
+        Nest,
+        Pack(Option<Index>),
+        Line(&'a Broken<'a>, &'a Broken<'a>, bool),
+        Comp(&'a Broken<'a>, &'a Broken<'a>, Attr, bool)
       }
-      match layout {
-        box Layout::Null => (false, _null(mem)),
-        box Layout::Text(data) => {
-          let data1 = mem.alloc_str(data.as_str());
-          (false, _text(mem, data1))
-        }
-        box Layout::Fix(layout1) => {
-          let (broken, layout2) = _visit(mem, layout1.clone());
-          (broken, _fix(mem, layout2))
-        }
-        box Layout::Grp(layout1) => {
-          let (broken, layout2) = _visit(mem, layout1.clone());
-          (broken, _grp(mem, layout2))
-        }
-        box Layout::Seq(layout1) => {
-          let (broken, layout2) = _visit(mem, layout1.clone());
-          (broken, _seq(mem, broken, layout2))
-        }
-        box Layout::Nest(layout1) => {
-          let (broken, layout2) = _visit(mem, layout1.clone());
-          (broken, _nest(mem, layout2))
+      let mut steps = Vec::new();
+      let mut layout = layout;
+      let (data, attr) = loop {
+        match layout {
+          Broken::Null | Broken::Text("", _) => {
+            layout = loop {
+              match steps.pop() {
+                None => return None,
+                Some(Step::Line(left, right, in_left)) if in_left == first => {
+                  steps.push(Step::Line(left, right, !in_left));
+                  break if in_left { right } else { left }
+                }
+                Some(Step::Comp(left, right, attr, in_left)) if in_left == first => {
+                  steps.push(Step::Comp(left, right, attr, !in_left));
+                  break if in_left { right } else { left }
+                }
+                Some(_) => ()
+              }
+            }
+          }
+          Broken::Text(data, attr) => break (data, attr),
+          Broken::Fix(layout1) => { steps.push(Step::Fix); layout = layout1 }
+          Broken::Grp(name, layout1) => { steps.push(Step::Grp(*name)); layout = layout1 }
+          Broken::Seq(broken, name, layout1) => { steps.push(Step::Seq(*broken, *name)); layout = layout1 }
+          Broken::Nest(layout1) => { steps.push(Step::Nest); layout = layout1 }
+          Broken::Pack(anchor, layout1) => { steps.push(Step::Pack(*anchor)); layout = layout1 }
+          Broken::Line(left, right) => {
+            steps.push(Step::Line(left, right, first));
+            layout = if first { left } else { right }
+          }
+          Broken::Comp(left, right, attr) => {
+            steps.push(Step::Comp(left, right, *attr, first));
+            layout = if first { left } else { right }
+          }
         }
-        box Layout::Pack(layout1) => {
-          let (broken, layout2) = _visit(mem, layout1.clone());
-          (broken, _pack(mem, layout2))
+      };
+      let index = attr._markers().unwrap_or_else(|| {
+        markers.push(LeafMarkers::default());
+        markers.len() - 1
+      });
+      let leaf = &mut markers[index];
+      if first { leaf.open.insert(0, marker) } else { leaf.close.push(marker) }
+      let index1 = u32::try_from(index)
+        .expect("A layout has at most MAX_ANNOTATED_TEXTS annotated texts");
+      let attr1 = TextAttr { markers: Some(index1), ..*attr };
+      let text = _text(mem, data, attr1);
+      Some(steps.into_iter().rev().fold(text, |layout1, step| match step {
+        Step::Fix => _fix(mem, layout1),
+        Step::Grp(name) => _grp(mem, name, layout1),
+        Step::Seq(broken, name) => _seq(mem, broken, name, layout1),
+        Step::Nest => _nest(mem, layout1),
+        Step::Pack(anchor) => _pack(mem, anchor, layout1),
+        Step::Line(left, right, in_left) =>
+          if in_left { _line(mem, layout1, right) } else { _line(mem, left, layout1) },
+        Step::Comp(left, right, attr, in_left) =>
+          if in_left { _comp(mem, layout1, right, attr) } else { _comp(mem, left, layout1, attr) }
+      }))
+    }
+    fn _lines<'a>(
+      mem: &'a Bump,
+      data: &str,
+      width: Option<usize>,
+      options: CompileOptions
+    ) -> Result<(bool, &'a Broken<'a>, bool), CompilerError> {
+      if options.reject_tabs && data.contains('\t') {
+        return Err(CompilerError::TabInText(data.to_string()))
+      }
+      if !data.contains(['\n', '\r']) {
+        return Ok((false, _text(mem, mem.alloc_str(data), _text_attr(width)), false))
+      }
+      match options.newlines {
+        NewlinePolicy::Reject =>
+          Err(CompilerError::NewlineInText(data.to_string())),
+        NewlinePolicy::Escape => {
+          let data1 = data.replace('\r', "\\r").replace('\n', "\\n");
+          Ok((false, _text(mem, mem.alloc_str(data1.as_str()), _text_attr(width)), false))
         }
-        box Layout::Line(left, right) => {
-          let (_l_broken, left1) = _visit(mem, left.clone());
-          let (_r_broken, right1) = _visit(mem, right.clone());
-          (true, _line(mem, left1, right1))
+        NewlinePolicy::SplitIntoLines => {
+          let lines: Vec<&str> = data
+            .split("\r\n")
+            .flat_map(|part| part.split(['\n', '\r']))
+            .collect();
+          let mut lines = lines.into_iter().rev();
+          let last = lines.next().unwrap_or("");
+          let result = lines.fold(
+            _text(mem, mem.alloc_str(last), _text_attr(None)),
+            |result, line| _line(mem, _text(mem, mem.alloc_str(line), _text_attr(None)), result)
+          );
+          Ok((true, result, false))
         }
-        box Layout::Comp(left, right, attr) => {
-          let (l_broken, left1) = _visit(mem, left.clone());
-          let (r_broken, right1) = _visit(mem, right.clone());
-          let broken = l_broken || r_broken;
-          (broken, _comp(mem, left1, right1, attr.clone()))
+      }
+    }
+    fn _reset<'a>(
+      mem: &'a Bump,
+      left: &'a Broken<'a>,
+      l_reset: bool,
+      r_reset: bool,
+      markers: &mut Vec<LeafMarkers>
+    ) -> (&'a Broken<'a>, bool) {
+      if !r_reset { return (left, l_reset) }
+      match _attach(mem, left, Marker::PackReset, false, markers) {
+        Some(left1) => (left1, l_reset),
+        None => (left, true)
+      }
+    }
+    fn _fill<'a>(
+      mem: &'a Bump,
+      layout: &'a Broken<'a>,
+      slots: &mut Vec<Marker>,
+      markers: &mut Vec<LeafMarkers>
+    ) -> &'a Broken<'a> {
+      let mut result = layout;
+      while let Some(marker) = slots.last() {
+        match _attach(mem, result, marker.clone(), true, markers) {
+          None => break,
+          Some(result1) => { result = result1; slots.pop(); }
         }
       }
+      result
     }
-    let (_break, layout) = _visit(mem, layout);
-    layout
-  }
-  fn _remove<'b, 'a: 'b, R>(
+    /*
+      Visit the layout with an explicit stack rather than by recursion, such
+      that deeply nested layouts do not overflow the call stack; the results
+      of the visited layouts are kept on a stack of their own, and are
+      combined by the items pushed after them.
+    */
+    fn _visit<'b, 's, S: LayoutSource<'s>>(
+      mem: &'b Bump,
+      layout: S,
+      options: CompileOptions,
+      markers: &mut Vec<LeafMarkers>,
+      anchors: &mut AnchorScope,
+      slots: &mut Vec<Marker>
+    ) -> Result<(bool, &'b Broken<'b>, bool), CompilerError> {
+      enum Item<'b, 's, S> {
+        Visit(S),
+        Annotate(&'s Annotation, Vec<Marker>),
+        Fix,
+        Grp(Option<&'b str>),
+        Seq(Option<&'b str>),
+        Nest,
+        Pack(Option<Index>),
+        Unbind(usize),
+        Line,
+        Comp(Attr)
+      }
+      let mut results: Vec<(bool, &'b Broken<'b>, bool)> = Vec::new();
+      let pop = |results: &mut Vec<(bool, &'b Broken<'b>, bool)>|
+        results.pop().unwrap_or_else(|| invariant!());
+      let mut stack = vec![Item::Visit(layout)];
+      while let Some(item) = stack.pop() {
+        let result = match item {
+          Item::Visit(layout1) => match layout1.view() {
+            LayoutView::Null => (false, _null(mem), false),
+            LayoutView::Text(data, width) => {
+              let (broken, layout2, reset) = _lines(mem, data, width, options)?;
+              (broken, _fill(mem, layout2, slots, markers), reset)
+            }
+            LayoutView::PackReset => (false, _null(mem), true),
+            LayoutView::Slot(name) => {
+              if !options.minified {
+                slots.push(Marker::Slot(Cow::Owned(name.to_string())))
+              }
+              (false, _null(mem), false)
+            }
+            LayoutView::Annotate(annotation, layout2) => {
+              if !options.minified || matches!(annotation, Annotation::Hint(_) | Annotation::Hyphen) {
+                stack.push(Item::Annotate(annotation, std::mem::take(slots)))
+              }
+              stack.push(Item::Visit(layout2));
+              continue
+            }
+            LayoutView::Fix(layout2) => {
+              stack.push(Item::Fix);
+              stack.push(Item::Visit(layout2));
+              continue
+            }
+            LayoutView::Grp(name, layout2) => {
+              stack.push(Item::Grp(if options.minified { None } else { _name(mem, name) }));
+              stack.push(Item::Visit(layout2));
+              continue
+            }
+            LayoutView::Seq(name, layout2) => {
+              stack.push(Item::Seq(if options.minified { None } else { _name(mem, name) }));
+              stack.push(Item::Visit(layout2));
+              continue
+            }
+            LayoutView::Nest(layout2) => {
+              stack.push(Item::Nest);
+              stack.push(Item::Visit(layout2));
+              continue
+            }
+            LayoutView::Pack(layout2) => {
+              stack.push(Item::Pack(None));
+              stack.push(Item::Visit(layout2));
+              continue
+            }
+            LayoutView::PackGroup(group, layout2) => {
+              stack.push(Item::Unbind(anchors.bound.len()));
+              for anchor in group {
+                let index = _fresh(&mut anchors.next);
+                anchors.bound.push((anchor.clone(), index))
+              }
+              stack.push(Item::Visit(layout2));
+              continue
+            }
+            LayoutView::PackAt(anchor, layout2) => {
+              let Some(&(_, index)) = anchors.bound.iter().rev()
+                .find(|(bound, _)| bound == anchor) else {
+                return Err(CompilerError::UnboundAnchor(anchor.0.clone()))
+              };
+              stack.push(Item::Pack(Some(index)));
+              stack.push(Item::Visit(layout2));
+              continue
+            }
+            LayoutView::Line(left, right) => {
+              stack.push(Item::Line);
+              stack.push(Item::Visit(right));
+              stack.push(Item::Visit(left));
+              continue
+            }
+            LayoutView::Comp(left, right, attr) => {
+              stack.push(Item::Comp(attr));
+              stack.push(Item::Visit(right));
+              stack.push(Item::Visit(left));
+              continue
+            }
+          },
+          Item::Annotate(annotation, mut pending) => {
+            let (broken, layout2, reset) = pop(&mut results);
+            let open = Marker::Open(annotation.clone());
+            let close = Marker::Close(annotation.clone());
+            let layout3 = _attach(mem, layout2, open, true, markers)
+              .and_then(|layout3| _attach(mem, layout3, close, false, markers))
+              .unwrap_or(layout2);
+            let layout4 = _fill(mem, layout3, &mut pending, markers);
+            pending.append(slots);
+            *slots = pending;
+            (broken, layout4, reset)
+          }
+          Item::Fix => {
+            let (broken, layout2, reset) = pop(&mut results);
+            (broken, _fix(mem, layout2), reset)
+          }
+          Item::Grp(name) => {
+            let (broken, layout2, reset) = pop(&mut results);
+            (broken, _grp(mem, name, layout2), reset)
+          }
+          Item::Seq(name) => {
+            let (broken, layout2, reset) = pop(&mut results);
+            (broken, _seq(mem, broken, name, layout2), reset)
+          }
+          Item::Nest => {
+            let (broken, layout2, reset) = pop(&mut results);
+            (broken, _nest(mem, layout2), reset)
+          }
+          Item::Pack(anchor) => {
+            let (broken, layout2, reset) = pop(&mut results);
+            (broken, _pack(mem, anchor, layout2), reset)
+          }
+          Item::Unbind(bound) => {
+            anchors.bound.truncate(bound);
+            continue
+          }
+          Item::Line => {
+            let (_r_broken, right1, r_reset) = pop(&mut results);
+            let (_l_broken, left1, l_reset) = pop(&mut results);
+            let (left2, reset) = _reset(mem, left1, l_reset, r_reset, markers);
+            (true, _line(mem, left2, right1), reset)
+          }
+          Item::Comp(attr) => {
+            let (r_broken, right1, r_reset) = pop(&mut results);
+            let (l_broken, left1, l_reset) = pop(&mut results);
+            let (left2, reset) = _reset(mem, left1, l_reset, r_reset, markers);
+            let broken = l_broken || r_broken;
+            (broken, _comp(mem, left2, right1, attr), reset)
+          }
+        };
+        results.push(result)
+      }
+      Ok(pop(&mut results))
+    }
+    let mut anchors = AnchorScope { bound: Vec::new(), next: 0 };
+    let mut slots = Vec::new();
+    let (_break, layout, _reset) = _visit(mem, layout, options, markers, &mut anchors, &mut slots)?;
+    Ok(slots.into_iter().fold(layout, |layout, marker|
+      _attach(mem, layout, marker, false, markers).unwrap_or(layout)))
+  }
+  /*
+    Remove the broken sequences with an explicit stack rather than by
+    recursion, such that deeply nested layouts do not overflow the call stack
+  */
+  fn _remove<'b, 'a: 'b>(
     mem: &'b Bump,
-    layout: &'a Broken<'a>,
-    broken: bool,
-    cont: &'b dyn Fn(&'b Bump, &'b EDSL<'b>) -> R
-  ) -> R {
+    layout: &'a Broken<'a>
+  ) -> &'b EDSL<'b> {
     fn _null<'a>(
       mem: &'a Bump
     ) -> &'a EDSL<'a> {
@@ -408,9 +1922,10 @@ fn _broken<'b, 'a: 'b>(
     }
     fn _text<'a>(
       mem: &'a Bump,
-      data: &'a str
+      data: &'a str,
+      attr: TextAttr
     ) -> &'a EDSL<'a> {
-      mem.alloc(EDSL::Text(data))
+      mem.alloc(EDSL::Text(data, attr))
     }
     fn _fix<'a>(
       mem: &'a Bump,
@@ -420,15 +1935,17 @@ fn _broken<'b, 'a: 'b>(
     }
     fn _grp<'a>(
       mem: &'a Bump,
+      name: Option<&'a str>,
       layout: &'a EDSL<'a>
     ) -> &'a EDSL<'a> {
-      mem.alloc(EDSL::Grp(layout))
+      mem.alloc(EDSL::Grp(name, layout))
     }
     fn _seq<'a>(
       mem: &'a Bump,
+      name: Option<&'a str>,
       layout: &'a EDSL<'a>
     ) -> &'a EDSL<'a> {
-      mem.alloc(EDSL::Seq(layout))
+      mem.alloc(EDSL::Seq(name, layout))
     }
     fn _nest<'a>(
       mem: &'a Bump,
@@ -438,9 +1955,10 @@ fn _broken<'b, 'a: 'b>(
     }
     fn _pack<'a>(
       mem: &'a Bump,
+      anchor: Option<Index>,
       layout: &'a EDSL<'a>
     ) -> &'a EDSL<'a> {
-      mem.alloc(EDSL::Pack(layout))
+      mem.alloc(EDSL::Pack(anchor, layout))
     }
     fn _line<'a>(
       mem: &'a Bump,
@@ -457,75 +1975,161 @@ fn _broken<'b, 'a: 'b>(
     ) -> &'a EDSL<'a> {
       mem.alloc(EDSL::Comp(left, right, attr))
     }
-    match layout {
-      Broken::Null => cont(mem, _null(mem)),
-      Broken::Text(data) => cont(mem, _text(mem, data)),
-      Broken::Fix(layout1) =>
-        _remove(mem, layout1, false,
-          compose(mem, cont, mem.alloc(|mem, layout1|
-            _fix(mem, layout1)))),
-      Broken::Grp(layout1) =>
-        _remove(mem, layout1, false,
-          compose(mem, cont, mem.alloc(|mem, layout1|
-            _grp(mem, layout1)))),
-      Broken::Seq(broken, layout1) =>
-        if *broken { _remove(mem, layout1, true, cont) } else {
-        _remove(mem, layout1, false,
-          compose(mem, cont, mem.alloc(|mem, layout2|
-            _seq(mem, layout2))))},
-      Broken::Nest(layout1) =>
-        _remove(mem, layout1, broken,
-          compose(mem, cont, mem.alloc(|mem, layout2|
-            _nest(mem, layout2)))),
-      Broken::Pack(layout1) =>
-        _remove(mem, layout1, broken,
-          compose(mem, cont, mem.alloc(|mem, layout2|
-            _pack(mem, layout2)))),
-      Broken::Line(left, right) =>
-        _remove(mem, left, broken, mem.alloc(move |mem, left1|
-        _remove(mem, right, broken, mem.alloc(move |mem, right1|
-        cont(mem, _line(mem, left1, right1)))))),
-      Broken::Comp(left, right, attr) =>
-        _remove(mem, left, broken, mem.alloc(move |mem, left1|
-        _remove(mem, right, broken, mem.alloc(move |mem, right1|
-        if broken && !attr.fix { cont(mem, _line(mem, left1, right1)) }
-        else { cont(mem, _comp(mem, left1, right1, *attr)) }))))
-    }
-  }
-  let layout1 = _mark(mem, layout);
-  _remove(mem, layout1, false, mem.alloc(|_mem, result| result))
+    enum Item<'a> {
+      Visit(&'a Broken<'a>, bool),
+      Fix,
+      Grp(Option<&'a str>),
+      Seq(Option<&'a str>),
+      Nest,
+      Pack(Option<Index>),
+      Line,
+      Comp(Attr)
+    }
+    let mut results: Vec<&'b EDSL<'b>> = Vec::new();
+    let pop = |results: &mut Vec<&'b EDSL<'b>>|
+      results.pop().unwrap_or_else(|| invariant!());
+    let mut stack = vec![Item::Visit(layout, false)];
+    while let Some(item) = stack.pop() {
+      let result = match item {
+        Item::Visit(layout1, broken) => match layout1 {
+          Broken::Null => _null(mem),
+          Broken::Text(data, attr) => _text(mem, data, *attr),
+          Broken::Fix(layout2) => {
+            stack.push(Item::Fix);
+            stack.push(Item::Visit(layout2, false));
+            continue
+          }
+          Broken::Grp(name, layout2) => {
+            stack.push(Item::Grp(*name));
+            stack.push(Item::Visit(layout2, false));
+            continue
+          }
+          Broken::Seq(broken1, name, layout2) => {
+            if !*broken1 { stack.push(Item::Seq(*name)) }
+            stack.push(Item::Visit(layout2, *broken1));
+            continue
+          }
+          Broken::Nest(layout2) => {
+            stack.push(Item::Nest);
+            stack.push(Item::Visit(layout2, broken));
+            continue
+          }
+          Broken::Pack(anchor, layout2) => {
+            stack.push(Item::Pack(*anchor));
+            stack.push(Item::Visit(layout2, broken));
+            continue
+          }
+          Broken::Line(left, right) => {
+            stack.push(Item::Line);
+            stack.push(Item::Visit(right, broken));
+            stack.push(Item::Visit(left, broken));
+            continue
+          }
+          Broken::Comp(left, right, attr) => {
+            if broken && !attr.fix { stack.push(Item::Line) }
+            else { stack.push(Item::Comp(*attr)) }
+            stack.push(Item::Visit(right, broken));
+            stack.push(Item::Visit(left, broken));
+            continue
+          }
+        },
+        Item::Fix => _fix(mem, pop(&mut results)),
+        Item::Grp(name) => _grp(mem, name, pop(&mut results)),
+        Item::Seq(name) => _seq(mem, name, pop(&mut results)),
+        Item::Nest => _nest(mem, pop(&mut results)),
+        Item::Pack(anchor) => _pack(mem, anchor, pop(&mut results)),
+        Item::Line => {
+          let right1 = pop(&mut results);
+          let left1 = pop(&mut results);
+          _line(mem, left1, right1)
+        }
+        Item::Comp(attr) => {
+          let right1 = pop(&mut results);
+          let left1 = pop(&mut results);
+          _comp(mem, left1, right1, attr)
+        }
+      };
+      results.push(result)
+    }
+    pop(&mut results)
+  }
+  let layout1 = _mark(mem, layout, options, markers)?;
+  Ok(_remove(mem, layout1))
 }
 
 #[derive(Debug)]
-enum Serial<'a> {
+pub enum Serial<'a> {
   Next(&'a SerialTerm<'a>, &'a SerialComp<'a>, &'a Serial<'a>),
   Last(&'a SerialTerm<'a>, &'a Serial<'a>),
   Past
 }
 
 #[derive(Debug)]
-enum SerialTerm<'a> {
+pub enum SerialTerm<'a> {
   Null,
-  Text(&'a str),
+  Text(&'a str, TextAttr),
   Nest(&'a SerialTerm<'a>),
-  Pack(u64, &'a SerialTerm<'a>)
+  Pack(Index, &'a SerialTerm<'a>)
 }
 
 #[derive(Debug)]
-enum SerialComp<'a> {
+pub enum SerialComp<'a> {
   Line,
-  Comp(Attr),
-  Grp(u64, &'a SerialComp<'a>),
-  Seq(u64, &'a SerialComp<'a>)
+  Comp(Attr, &'a Scopes<'a>)
+}
+
+/*
+  The groups and sequences around a composition, innermost first; the
+  scopes are shared by every composition under them, such that deeply nested
+  layouts take linear space.
+*/
+#[derive(Debug)]
+pub enum Scopes<'a> {
+  Top,
+  Grp(Index, &'a Scopes<'a>),
+  Seq(Index, &'a Scopes<'a>)
+}
+
+impl<'a> Scopes<'a> {
+  fn _index(&self) -> Option<Index> {
+    match self {
+      Scopes::Top => None,
+      Scopes::Grp(index, _) | Scopes::Seq(index, _) => Some(*index)
+    }
+  }
+
+  fn _outer(&self) -> &'a Scopes<'a> {
+    match self {
+      Scopes::Top => invariant!(),
+      Scopes::Grp(_, outer) | Scopes::Seq(_, outer) => outer
+    }
+  }
 }
 
 /*
   Serialize in order to normalize
 */
-fn _serialize<'b, 'a: 'b>(
+pub(crate) fn _serialize<'b, 'a: 'b>(
   mem: &'b Bump,
-  layout: &'a EDSL<'a>
+  layout: &'a EDSL<'a>,
+  names: &mut BTreeMap<Index, &'b str>,
+  anchors: &mut BTreeMap<Index, Index>
 ) -> &'b Serial<'b> {
+  enum Scope {
+    Nest,
+    Pack(Index)
+  }
+  #[derive(Copy, Clone)]
+  enum Glue<'a> {
+    Last,
+    Line,
+    Comp(Attr, &'a Scopes<'a>)
+  }
+  enum Item<'a, 'b> {
+    Visit(&'a EDSL<'a>, bool, Glue<'b>),
+    PopTerm,
+    PopComp
+  }
   fn _next<'a>(
     mem: &'a Bump,
     term: &'a SerialTerm<'a>,
@@ -553,9 +2157,10 @@ fn _serialize<'b, 'a: 'b>(
   }
   fn _text<'a>(
     mem: &'a Bump,
-    data: &'a str
+    data: &'a str,
+    attr: TextAttr
   ) -> &'a SerialTerm<'a> {
-    mem.alloc(SerialTerm::Text(data))
+    mem.alloc(SerialTerm::Text(data, attr))
   }
   fn _nest<'a>(
     mem: &'a Bump,
@@ -565,189 +2170,149 @@ fn _serialize<'b, 'a: 'b>(
   }
   fn _pack<'a>(
     mem: &'a Bump,
-    index: u64,
+    index: Index,
     term: &'a SerialTerm<'a>
   ) -> &'a SerialTerm<'a> {
     mem.alloc(SerialTerm::Pack(index, term))
   }
+  fn _line<'a>(
+    mem: &'a Bump
+  ) -> &'a SerialComp<'a> {
+    mem.alloc(SerialComp::Line)
+  }
   fn _comp<'a>(
     mem: &'a Bump,
-    attr: Attr
+    attr: Attr,
+    scopes: &'a Scopes<'a>
   ) -> &'a SerialComp<'a> {
-    mem.alloc(SerialComp::Comp(attr))
+    mem.alloc(SerialComp::Comp(attr, scopes))
   }
-  fn _grp<'a>(
+  fn _wrap_term<'a>(
     mem: &'a Bump,
-    index: u64,
-    comp: &'a SerialComp<'a>
-  ) -> &'a SerialComp<'a> {
-    mem.alloc(SerialComp::Grp(index, comp))
-  }
-  fn _seq<'a>(
-    mem: &'a Bump,
-    index: u64,
-    comp: &'a SerialComp<'a>
-  ) -> &'a SerialComp<'a> {
-    mem.alloc(SerialComp::Seq(index, comp))
-  }
-  fn __line<'a>(
-    mem: &'a Bump,
-    term: &'a SerialTerm<'a>,
-    serial: &'a Serial<'a>
-  ) -> &'a Serial<'a> {
-    _next(mem, term, mem.alloc(SerialComp::Line), serial)
-  }
-  fn __comp<'a>(
-    mem: &'a Bump,
-    comps: &'a dyn Fn(&'a Bump, &'a SerialComp<'a>) -> &'a SerialComp<'a>,
-    attr: Attr,
-    term: &'a SerialTerm<'a>,
-    serial: &'a Serial<'a>
-  ) -> &'a Serial<'a> {
-    _next(mem, term, comps(mem, _comp(mem, attr)), serial)
+    terms: &[Scope],
+    term: &'a SerialTerm<'a>
+  ) -> &'a SerialTerm<'a> {
+    terms.iter().rev().fold(term, |term, scope| match scope {
+      Scope::Nest => _nest(mem, term),
+      Scope::Pack(index) => _pack(mem, *index, term)
+    })
   }
-  fn _visit<'b, 'a: 'b, R>(
-    mem: &'b Bump,
-    i: u64,
-    j: u64,
-    fixed: bool,
-    terms: &'b dyn Fn(&'b Bump, &'b SerialTerm<'b>) -> &'b SerialTerm<'b>,
-    comps: &'b dyn Fn(&'b Bump, &'b SerialComp<'b>) -> &'b SerialComp<'b>,
-    glue: &'b dyn Fn(&'b Bump, &'b SerialTerm<'b>, &'b Serial<'b>) -> &'b Serial<'b>,
-    result: &'b dyn Fn(&'b Bump, &'b Serial<'b>) -> R,
-    layout: &'a EDSL<'a>
-  ) -> (
-    u64, u64, &'b dyn Fn(&'b Bump, &'b Serial<'b>) -> R
-  ) {
-    match layout {
-      EDSL::Null =>
-        (i, j, compose(mem, result, mem.alloc(|mem, serial|
-        glue(mem, _null(mem), serial)))),
-      EDSL::Text(data) =>
-        (i, j, compose(mem, result, mem.alloc(|mem, serial|
-        glue(mem, terms(mem, _text(mem, data)), serial)))),
-      EDSL::Fix(layout1) =>
-        _visit(mem, i, j, true, terms, comps, glue, result, layout1),
-      EDSL::Grp(layout1) =>
-        _visit(
-          mem,
-          i + 1, j,
-          fixed,
-          terms,
-          compose(mem, comps, mem.alloc(move |mem, comp| _grp(mem, i, comp))),
-          glue,
-          result,
-          layout1
-        ),
-      EDSL::Seq(layout1) =>
-        _visit(
-          mem,
-          i + 1, j,
-          fixed,
-          terms,
-          compose(mem, comps, mem.alloc(move |mem, comp| _seq(mem, i, comp))),
-          glue,
-          result,
-          layout1
-        ),
-      EDSL::Nest(layout1) =>
-        _visit(
-          mem,
-          i, j,
-          fixed,
-          compose(mem, terms, mem.alloc(|mem, term| _nest(mem, term))),
-          comps,
-          glue,
-          result,
-          layout1
-        ),
-      EDSL::Pack(layout1) =>
-        _visit(
-          mem,
-          i, j + 1,
-          fixed,
-          compose(mem, terms, mem.alloc(move |mem, term| _pack(mem, j, term))),
-          comps,
-          glue,
-          result,
-          layout1
-        ),
+
+  /*
+    The leaves are visited from left to right with an explicit stack, keeping
+    the term scopes (nests and packs) and the composition scopes (groups and
+    sequences) around the current node; each leaf is glued to the next by the
+    composition whose left side it ends, under the scopes of the composition.
+  */
+  let mut i = 0;
+  let mut j = 0;
+  let mut terms = Vec::new();
+  let mut comps: &'b Scopes<'b> = &Scopes::Top;
+  let mut cells = Vec::new();
+  let mut stack = vec![Item::Visit(layout, false, Glue::Last)];
+  while let Some(item) = stack.pop() {
+    let (layout1, fixed, glue) = match item {
+      Item::PopTerm => { terms.pop(); continue }
+      Item::PopComp => { comps = comps._outer(); continue }
+      Item::Visit(layout1, fixed, glue) => (layout1, fixed, glue)
+    };
+    let leaf = match layout1 {
+      EDSL::Null => _null(mem),
+      EDSL::Text(data, attr) => _text(mem, data, *attr),
+      EDSL::Fix(layout2) => {
+        stack.push(Item::Visit(layout2, true, glue));
+        continue
+      }
+      EDSL::Grp(name, layout2) => {
+        let index = _fresh(&mut i);
+        if let Some(name) = name { names.insert(index, name); }
+        comps = mem.alloc(Scopes::Grp(index, comps));
+        stack.push(Item::PopComp);
+        stack.push(Item::Visit(layout2, fixed, glue));
+        continue
+      }
+      EDSL::Seq(name, layout2) => {
+        let index = _fresh(&mut i);
+        if let Some(name) = name { names.insert(index, name); }
+        comps = mem.alloc(Scopes::Seq(index, comps));
+        stack.push(Item::PopComp);
+        stack.push(Item::Visit(layout2, fixed, glue));
+        continue
+      }
+      EDSL::Nest(layout2) => {
+        terms.push(Scope::Nest);
+        stack.push(Item::PopTerm);
+        stack.push(Item::Visit(layout2, fixed, glue));
+        continue
+      }
+      EDSL::Pack(anchor, layout2) => {
+        let index = match anchor {
+          None => _fresh(&mut j),
+          Some(anchor) => *anchors.entry(*anchor).or_insert_with(|| _fresh(&mut j))
+        };
+        terms.push(Scope::Pack(index));
+        stack.push(Item::PopTerm);
+        stack.push(Item::Visit(layout2, fixed, glue));
+        continue
+      }
       EDSL::Line(left, right) => {
-        let (i1, j1, result1) = _visit(
-          mem,
-          i, j,
-          fixed,
-          terms,
-          comps,
-          mem.alloc(|mem, term, serial| __line(mem, term, serial)),
-          result,
-          left
-        );
-        _visit(
-          mem, i1, j1, fixed, terms, comps, glue, result1, right
-        )
+        stack.push(Item::Visit(right, fixed, glue));
+        stack.push(Item::Visit(left, fixed, Glue::Line));
+        continue
       }
       EDSL::Comp(left, right, attr) => {
-        let glue1 = mem.alloc(move |mem, term, serial| {
-          let attr1 = Attr {
-            pad: attr.pad,
-            fix: fixed || attr.fix
-          };
-          __comp(mem, comps, attr1, term, serial)
-        });
-        let (i1, j1, result1) = _visit(
-          mem, i, j, fixed, terms, comps, glue1, result, left
-        );
-        _visit(
-          mem, i1, j1, fixed, terms, comps, glue, result1, right
-        )
+        let attr1 = Attr {
+          pad: attr.pad,
+          fix: fixed || attr.fix
+        };
+        stack.push(Item::Visit(right, fixed, glue));
+        stack.push(Item::Visit(left, fixed, Glue::Comp(attr1, comps)));
+        continue
       }
-    }
-  }
-  let (_i, _j, result) = _visit(
-    mem,
-    0, 0,
-    false,
-    mem.alloc(|_mem, x| x),
-    mem.alloc(|_mem, x| x),
-    mem.alloc(|mem, term, serial| _last(mem, term, serial)),
-    mem.alloc(|_mem, x| x),
-    layout
-  );
-  result(mem, _past(mem))
+    };
+    let term = _wrap_term(mem, &terms, leaf);
+    let comp = match glue {
+      Glue::Last => None,
+      Glue::Line => Some(_line(mem)),
+      Glue::Comp(attr, scopes) => Some(_comp(mem, attr, scopes))
+    };
+    cells.push((term, comp))
+  }
+  cells.into_iter().rev().fold(_past(mem), |serial, (term, comp)| match comp {
+    None => _last(mem, term, serial),
+    Some(comp) => _next(mem, term, comp, serial)
+  })
 }
 
 #[derive(Debug)]
-enum LinearDoc<'a> {
+pub enum LinearDoc<'a> {
   Nil,
   Cons(&'a LinearObj<'a>, &'a LinearDoc<'a>)
 }
 
 #[derive(Debug)]
-enum LinearObj<'a> {
+pub enum LinearObj<'a> {
   Next(&'a LinearTerm<'a>, &'a LinearComp<'a>, &'a LinearObj<'a>),
   Last(&'a LinearTerm<'a>)
 }
 
 #[derive(Debug)]
-enum LinearTerm<'a> {
+pub enum LinearTerm<'a> {
   Null,
-  Text(&'a str),
+  Text(&'a str, TextAttr),
   Nest(&'a LinearTerm<'a>),
-  Pack(u64, &'a LinearTerm<'a>)
+  Pack(Index, &'a LinearTerm<'a>)
 }
 
 #[derive(Debug)]
-enum LinearComp<'a> {
-  Comp(Attr),
-  Grp(u64, &'a LinearComp<'a>),
-  Seq(u64, &'a LinearComp<'a>)
+pub enum LinearComp<'a> {
+  Comp(Attr, &'a Scopes<'a>)
 }
 
 /*
   Lift newlines to spine
 */
-fn _linearize<'b, 'a: 'b>(
+pub(crate) fn _linearize<'b, 'a: 'b>(
   mem: &'b Bump,
   serial: &'a Serial<'a>
 ) -> &'b LinearDoc<'b> {
@@ -784,9 +2349,10 @@ fn _linearize<'b, 'a: 'b>(
   }
   fn _text<'a>(
     mem: &'a Bump,
-    data: &'a str
+    data: &'a str,
+    attr: TextAttr
   ) -> &'a LinearTerm<'a> {
-    mem.alloc(LinearTerm::Text(data))
+    mem.alloc(LinearTerm::Text(data, attr))
   }
   fn _nest<'a>(
     mem: &'a Bump,
@@ -796,138 +2362,117 @@ fn _linearize<'b, 'a: 'b>(
   }
   fn _pack<'a>(
     mem: &'a Bump,
-    index: u64,
+    index: Index,
     term: &'a LinearTerm<'a>
   ) -> &'a LinearTerm<'a> {
     mem.alloc(LinearTerm::Pack(index, term))
   }
   fn _comp<'a>(
     mem: &'a Bump,
-    attr: Attr
-  ) -> &'a LinearComp<'a> {
-    mem.alloc(LinearComp::Comp(attr))
-  }
-  fn _grp<'a>(
-    mem: &'a Bump,
-    index: u64,
-    comp: &'a LinearComp<'a>
-  ) -> &'a LinearComp<'a> {
-    mem.alloc(LinearComp::Grp(index, comp))
-  }
-  fn _seq<'a>(
-    mem: &'a Bump,
-    index: u64,
-    comp: &'a LinearComp<'a>
+    attr: Attr,
+    scopes: &'a Scopes<'a>
   ) -> &'a LinearComp<'a> {
-    mem.alloc(LinearComp::Seq(index, comp))
+    mem.alloc(LinearComp::Comp(attr, scopes))
   }
-  fn _visit_serial<'b, 'a: 'b, R>(
-    mem: &'b Bump,
-    serial: &'a Serial<'a>,
-    line: &'b dyn Fn(&'b Bump, &'b LinearObj<'b>) -> &'b LinearObj<'b>,
-    cont: &'b dyn Fn(&'b Bump, &'b LinearDoc<'b>) -> R
-  ) -> R {
-    match serial {
-      Serial::Next(term, SerialComp::Line, serial1) =>
-        _visit_term(mem, term, mem.alloc(move |mem, term1|
-        _visit_serial(
-          mem,
-          serial1,
-          mem.alloc(|_mem, obj| obj),
-          mem.alloc(move |mem, serial2|
-            cont(mem, _cons(mem, line(mem, _last(mem, term1)), serial2))
-        )))),
-      Serial::Next(term, comp, serial1) =>
-        _visit_term(mem, term, mem.alloc(move |mem, term1|
-        _visit_comp(mem, comp, mem.alloc(move |mem, comp1|
-        _visit_serial(
-          mem,
-          serial1,
-          compose(mem, line, mem.alloc(move |mem, obj|
-            _next(mem, term1, comp1, obj))),
-          cont
-        ))))),
-      Serial::Last(term, Serial::Past) =>
-        _visit_term(mem, term, mem.alloc(|mem, term1|
-        cont(mem, _cons(mem, line(mem, _last(mem, term1)), _nil(mem))))),
-      _ => unreachable!("Invariant")
-    }
-  }
-  fn _visit_term<'b, 'a: 'b, R>(
+  fn _visit_term<'b, 'a: 'b>(
     mem: &'b Bump,
-    term: &'a SerialTerm<'a>,
-    cont: &'b dyn Fn(&'b Bump, &'b LinearTerm<'b>) -> R
-  ) -> R {
-    match term {
-      SerialTerm::Null => cont(mem, _null(mem)),
-      SerialTerm::Text(data) => cont(mem, _text(mem, data)),
-      SerialTerm::Nest(term1) =>
-        _visit_term(mem, term1, compose(mem, cont,
-          mem.alloc(|mem, term2| _nest(mem, term2)))),
-      SerialTerm::Pack(index, term1) =>
-        _visit_term(mem, term1, compose(mem, cont,
-          mem.alloc(|mem, term2| _pack(mem, *index, term2))))
-    }
-  }
-  fn _visit_comp<'b, 'a: 'b, R>(
+    term: &'a SerialTerm<'a>
+  ) -> &'b LinearTerm<'b> {
+    let mut scopes = Vec::new();
+    let mut term = term;
+    let leaf = loop {
+      match term {
+        SerialTerm::Null => break _null(mem),
+        SerialTerm::Text(data, attr) => break _text(mem, data, *attr),
+        SerialTerm::Nest(term1) => { scopes.push(None); term = term1 }
+        SerialTerm::Pack(index, term1) => { scopes.push(Some(*index)); term = term1 }
+      }
+    };
+    scopes.into_iter().rev().fold(leaf, |term1, scope| match scope {
+      None => _nest(mem, term1),
+      Some(index) => _pack(mem, index, term1)
+    })
+  }
+  fn _visit_comp<'b, 'a: 'b>(
     mem: &'b Bump,
-    comp: &'a SerialComp<'a>,
-    cont: &'b dyn Fn(&'b Bump, &'b LinearComp<'b>) -> R
-  ) -> R {
+    comp: &'a SerialComp<'a>
+  ) -> &'b LinearComp<'b> {
     match comp {
-      SerialComp::Line => unreachable!("Invariant"),
-      SerialComp::Comp(attr) => cont(mem, _comp(mem, *attr)),
-      SerialComp::Grp(index, comp1) =>
-        _visit_comp(mem, comp1, compose(mem, cont,
-          mem.alloc(|mem, comp1| _grp(mem, *index, comp1)))),
-      SerialComp::Seq(index, comp1) =>
-        _visit_comp(mem, comp1, compose(mem, cont,
-          mem.alloc(|mem, comp1| _seq(mem, *index, comp1))))
+      SerialComp::Line => invariant!(comp),
+      SerialComp::Comp(attr, scopes) => _comp(mem, *attr, scopes)
+    }
+  }
+  fn _visit_line<'a>(
+    mem: &'a Bump,
+    items: &mut Vec<(&'a LinearTerm<'a>, &'a LinearComp<'a>)>,
+    last: &'a LinearTerm<'a>
+  ) -> &'a LinearObj<'a> {
+    items.drain(..).rev().fold(_last(mem, last), |obj, (term, comp)|
+      _next(mem, term, comp, obj))
+  }
+  /*
+    The serial is visited with a loop rather than by recursion, such that long
+    documents do not overflow the call stack; the terms and compositions of a
+    line are kept until the newline or the end that closes it.
+  */
+  let mut objs = Vec::new();
+  let mut items = Vec::new();
+  let mut serial = serial;
+  loop {
+    match serial {
+      Serial::Next(term, SerialComp::Line, serial1) => {
+        let term1 = _visit_term(mem, term);
+        objs.push(_visit_line(mem, &mut items, term1));
+        serial = serial1
+      }
+      Serial::Next(term, comp, serial1) => {
+        items.push((_visit_term(mem, term), _visit_comp(mem, comp)));
+        serial = serial1
+      }
+      Serial::Last(term, Serial::Past) => {
+        let term1 = _visit_term(mem, term);
+        objs.push(_visit_line(mem, &mut items, term1));
+        break
+      }
+      _ => invariant!(serial)
     }
   }
-  _visit_serial(
-    mem,
-    serial,
-    mem.alloc(|_mem, obj| obj),
-    mem.alloc(|_mem, doc| doc),
-  )
+  objs.into_iter().rev().fold(_nil(mem), |doc, obj| _cons(mem, obj, doc))
 }
 
 #[derive(Debug)]
-enum FixedDoc<'a> {
+pub enum FixedDoc<'a> {
   EOD,
   Break(&'a FixedObj<'a>, &'a FixedDoc<'a>)
 }
 
 #[derive(Debug)]
-enum FixedObj<'a> {
+pub enum FixedObj<'a> {
   Next(&'a FixedItem<'a>, &'a FixedComp<'a>, &'a FixedObj<'a>),
   Last(&'a FixedItem<'a>)
 }
 
 #[derive(Debug)]
-enum FixedItem<'a> {
+pub enum FixedItem<'a> {
   Fix(&'a FixedFix<'a>),
   Term(&'a FixedTerm<'a>)
 }
 
 #[derive(Debug)]
-enum FixedTerm<'a> {
+pub enum FixedTerm<'a> {
   Null,
-  Text(&'a str),
+  Text(&'a str, TextAttr),
   Nest(&'a FixedTerm<'a>),
-  Pack(u64, &'a FixedTerm<'a>)
+  Pack(Index, &'a FixedTerm<'a>)
 }
 
 #[derive(Debug)]
-enum FixedComp<'a> {
-  Comp(bool),
-  Grp(u64, &'a FixedComp<'a>),
-  Seq(u64, &'a FixedComp<'a>)
+pub enum FixedComp<'a> {
+  Comp(bool, &'a Scopes<'a>)
 }
 
 #[derive(Debug)]
-enum FixedFix<'a> {
+pub enum FixedFix<'a> {
   Next(&'a FixedTerm<'a>, &'a FixedComp<'a>, &'a FixedFix<'a>),
   Last(&'a FixedTerm<'a>)
 }
@@ -935,7 +2480,7 @@ enum FixedFix<'a> {
 /*
   Coalesce fixed comps
 */
-fn _fixed<'b, 'a: 'b>(
+pub(crate) fn _fixed<'b, 'a: 'b>(
   mem: &'b Bump,
   doc: &'a LinearDoc<'a>
 ) -> &'b FixedDoc<'b> {
@@ -984,9 +2529,10 @@ fn _fixed<'b, 'a: 'b>(
   }
   fn _text<'a>(
     mem: &'a Bump,
-    data: &'a str
+    data: &'a str,
+    attr: TextAttr
   ) -> &'a FixedTerm<'a> {
-    mem.alloc(FixedTerm::Text(data))
+    mem.alloc(FixedTerm::Text(data, attr))
   }
   fn _nest<'a>(
     mem: &'a Bump,
@@ -996,30 +2542,17 @@ fn _fixed<'b, 'a: 'b>(
   }
   fn _pack<'a>(
     mem: &'a Bump,
-    index: u64,
+    index: Index,
     term: &'a FixedTerm<'a>
   ) -> &'a FixedTerm<'a> {
     mem.alloc(FixedTerm::Pack(index, term))
   }
   fn _comp<'a>(
     mem: &'a Bump,
-    pad: bool
-  ) -> &'a FixedComp<'a> {
-    mem.alloc(FixedComp::Comp(pad))
-  }
-  fn _grp<'a>(
-    mem: &'a Bump,
-    index: u64,
-    comp: &'a FixedComp<'a>
-  ) -> &'a FixedComp<'a> {
-    mem.alloc(FixedComp::Grp(index, comp))
-  }
-  fn _seq<'a>(
-    mem: &'a Bump,
-    index: u64,
-    comp: &'a FixedComp<'a>
+    pad: bool,
+    scopes: &'a Scopes<'a>
   ) -> &'a FixedComp<'a> {
-    mem.alloc(FixedComp::Seq(index, comp))
+    mem.alloc(FixedComp::Comp(pad, scopes))
   }
   fn _fix_next<'a>(
     mem: &'a Bump,
@@ -1035,113 +2568,87 @@ fn _fixed<'b, 'a: 'b>(
   ) -> &'a FixedFix<'a> {
     mem.alloc(FixedFix::Last(term))
   }
-  fn _visit_doc<'b, 'a: 'b>(
-    mem: &'b Bump,
-    doc: &'a LinearDoc<'a>
-  ) -> &'b FixedDoc<'b> {
-    match doc {
-      LinearDoc::Nil => _eod(mem),
-      LinearDoc::Cons(obj, doc1) =>
-        _visit_obj(mem, obj, mem.alloc(move |mem, obj1| {
-        let doc2 = _visit_doc(mem, doc1);
-        _break(mem, obj1, doc2)}))
-    }
-  }
-  fn _visit_obj<'b, 'a: 'b, R>(
-    mem: &'b Bump,
-    obj: &'a LinearObj<'a>,
-    cont: &'b dyn Fn(&'b Bump, &'b FixedObj<'b>) -> R
-  ) -> R {
-    match obj {
-      LinearObj::Next(term, comp, obj1) =>
-        _visit_term(mem, term, mem.alloc(move |mem, term1| {
-        let (is_fixed, comp1) = _visit_comp(mem, comp);
-        if is_fixed {
-          _visit_fix(
-            mem,
-            obj1,
-            mem.alloc(move |mem, fix| _fix_next(mem, term1, comp1, fix)),
-            cont
-          )
-        } else {
-          _visit_obj(
-            mem,
-            obj1,
-            compose(mem, cont, mem.alloc(|mem, obj2|
-              _next(mem, _term(mem, term1), comp1, obj2))
-            ))
-        }})),
-      LinearObj::Last(term) =>
-        _visit_term(mem, term, mem.alloc(|mem, term1|
-        cont(mem, _last(mem, _term(mem, term1)))))
-    }
-  }
-  fn _visit_fix<'b, 'a: 'b, R>(
-    mem: &'b Bump,
-    obj: &'a LinearObj<'a>,
-    line: &'b dyn Fn(&'b Bump, &'b FixedFix<'b>) -> &'b FixedFix<'b>,
-    cont: &'b dyn Fn(&'b Bump, &'b FixedObj<'b>) -> R
-  ) -> R {
-    match obj {
-      LinearObj::Next(term, comp, obj1) =>
-        _visit_term(mem, term, mem.alloc(move |mem, term1| {
-        let (is_fixed, comp1) = _visit_comp(mem, comp);
-        if is_fixed {
-          _visit_fix(
-            mem,
-            obj1,
-            compose(mem, line, mem.alloc(move |mem, fix|
-              _fix_next(mem, term1, comp1, fix))),
-            cont
-          )
-        } else {
-          _visit_obj(mem, obj1, compose(mem, cont,
-            mem.alloc(|mem, obj2|
-              _next(
-                mem,
-                _fix(mem, line(mem, _fix_last(mem, term1))),
-                comp1,
-                obj2
-              ))))
-        }})),
-      LinearObj::Last(term) =>
-        _visit_term(mem, term, mem.alloc(|mem, term1|
-        cont(mem, _last(mem, _fix(mem, line(mem, _fix_last(mem, term1)))))))
-    }
-  }
-  fn _visit_term<'b, 'a: 'b, R>(
+  fn _visit_term<'b, 'a: 'b>(
     mem: &'b Bump,
-    term: &'a LinearTerm<'a>,
-    cont: &'b dyn Fn(&'b Bump, &'b FixedTerm<'b>) -> R
-  ) -> R {
-    match term {
-      LinearTerm::Null => cont(mem, _null(mem)),
-      LinearTerm::Text(data) => cont(mem, _text(mem, data)),
-      LinearTerm::Nest(term1) =>
-        _visit_term(mem, term1, compose(mem, cont,
-          mem.alloc(|mem, term2| _nest(mem, term2)))),
-      LinearTerm::Pack(index, term1) =>
-        _visit_term(mem, term1, compose(mem, cont,
-          mem.alloc(|mem, term2| _pack(mem, *index, term2))))
-    }
+    term: &'a LinearTerm<'a>
+  ) -> &'b FixedTerm<'b> {
+    let mut scopes = Vec::new();
+    let mut term = term;
+    let leaf = loop {
+      match term {
+        LinearTerm::Null => break _null(mem),
+        LinearTerm::Text(data, attr) => break _text(mem, data, *attr),
+        LinearTerm::Nest(term1) => { scopes.push(None); term = term1 }
+        LinearTerm::Pack(index, term1) => { scopes.push(Some(*index)); term = term1 }
+      }
+    };
+    scopes.into_iter().rev().fold(leaf, |term1, scope| match scope {
+      None => _nest(mem, term1),
+      Some(index) => _pack(mem, index, term1)
+    })
   }
   fn _visit_comp<'b, 'a: 'b>(
     mem: &'b Bump,
     comp: &'a LinearComp<'a>
   ) -> (bool, &'b FixedComp<'b>) {
     match comp {
-      LinearComp::Comp(attr) => (attr.fix, _comp(mem, attr.pad)),
-      LinearComp::Grp(index, comp1) => {
-        let (is_fixed, comp2) = _visit_comp(mem, comp1);
-        (is_fixed, _grp(mem, *index, comp2))
-      }
-      LinearComp::Seq(index, comp1) => {
-        let (is_fixed, comp2) = _visit_comp(mem, comp1);
-        (is_fixed, _seq(mem, *index, comp2))
-      }
+      LinearComp::Comp(attr, scopes) => (attr.fix, _comp(mem, attr.pad, scopes))
     }
   }
-  _visit_doc(mem, doc)
+  /*
+    The item of a term, the fix of the terms before it under fixed
+    compositions if there are any, or else the term itself
+  */
+  fn _visit_item<'a>(
+    mem: &'a Bump,
+    fixed: &mut Vec<(&'a FixedTerm<'a>, &'a FixedComp<'a>)>,
+    term: &'a FixedTerm<'a>
+  ) -> &'a FixedItem<'a> {
+    if fixed.is_empty() { return _term(mem, term) }
+    let fix = fixed.drain(..).rev().fold(_fix_last(mem, term), |fix, (term1, comp)|
+      _fix_next(mem, term1, comp, fix));
+    _fix(mem, fix)
+  }
+  /*
+    The objects are visited with a loop rather than by recursion, such that
+    long lines do not overflow the call stack; the terms under fixed
+    compositions are kept until the term that ends their fix.
+  */
+  fn _visit_obj<'b, 'a: 'b>(
+    mem: &'b Bump,
+    obj: &'a LinearObj<'a>
+  ) -> &'b FixedObj<'b> {
+    let mut items = Vec::new();
+    let mut fixed = Vec::new();
+    let mut obj = obj;
+    let last = loop {
+      match obj {
+        LinearObj::Next(term, comp, obj1) => {
+          let term1 = _visit_term(mem, term);
+          let (is_fixed, comp1) = _visit_comp(mem, comp);
+          if is_fixed {
+            fixed.push((term1, comp1))
+          } else {
+            items.push((_visit_item(mem, &mut fixed, term1), comp1))
+          }
+          obj = obj1
+        }
+        LinearObj::Last(term) => {
+          let term1 = _visit_term(mem, term);
+          break _visit_item(mem, &mut fixed, term1)
+        }
+      }
+    };
+    items.into_iter().rev().fold(_last(mem, last), |obj1, (item, comp)|
+      _next(mem, item, comp, obj1))
+  }
+  let mut objs = Vec::new();
+  let mut doc = doc;
+  while let LinearDoc::Cons(obj, doc1) = doc {
+    objs.push(_visit_obj(mem, obj));
+    doc = doc1
+  }
+  objs.into_iter().rev().fold(_eod(mem), |doc1, obj| _break(mem, obj, doc1))
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -1168,7 +2675,7 @@ struct GraphNode<'a> {
 
 #[derive(Debug)]
 struct GraphEdge<'a> {
-  prop: Property<()>,
+  prop: Property<Option<&'a str>>,
   ins_next: Cell<Option<&'a GraphEdge<'a>>>,
   ins_prev: Cell<Option<&'a GraphEdge<'a>>>,
   outs_next: Cell<Option<&'a GraphEdge<'a>>>,
@@ -1180,10 +2687,10 @@ struct GraphEdge<'a> {
 #[derive(Debug)]
 enum GraphTerm<'a> {
   Null,
-  Text(&'a str),
+  Text(&'a str, TextAttr),
   Fix(&'a GraphFix<'a>),
   Nest(&'a GraphTerm<'a>),
-  Pack(u64, &'a GraphTerm<'a>)
+  Pack(Index, &'a GraphTerm<'a>)
 }
 
 #[derive(Debug)]
@@ -1192,53 +2699,14 @@ enum GraphFix<'a> {
   Next(&'a GraphTerm<'a>, &'a GraphFix<'a>, bool)
 }
 
-fn copy_graph_term<'b, 'a: 'b>(
-  mem: &'b Bump,
-  term: &'a GraphTerm<'a>
-) -> &'b GraphTerm<'b> {
-  match term {
-    GraphTerm::Null => mem.alloc(GraphTerm::Null),
-    GraphTerm::Text(data) => mem.alloc(GraphTerm::Text(data)),
-    GraphTerm::Fix(fix) => {
-      let fix1 = copy_graph_fix(mem, fix);
-      mem.alloc(GraphTerm::Fix(fix1))
-    },
-    GraphTerm::Nest(term1) => {
-      let term2 = copy_graph_term(mem, term1);
-      mem.alloc(GraphTerm::Nest(term2))
-    },
-    GraphTerm::Pack(index, term1) => {
-      let term2 = copy_graph_term(mem, term1);
-      mem.alloc(GraphTerm::Pack(*index, term2))
-    }
-  }
-}
-
-fn copy_graph_fix<'b, 'a: 'b>(
-  mem: &'b Bump,
-  fix: &'a GraphFix<'a>
-) -> &'b GraphFix<'b> {
-  match fix {
-    GraphFix::Last(term) => {
-      let term1 = copy_graph_term(mem, term);
-      mem.alloc(GraphFix::Last(term1))
-    },
-    GraphFix::Next(term, fix1, pad) => {
-      let term1 = copy_graph_term(mem, term);
-      let fix2 = copy_graph_fix(mem, fix1);
-      mem.alloc(GraphFix::Next(term1, fix2, *pad))
-    },
-  }
-}
-
 fn make_node<'a>(
   mem: &'a Bump,
   index: u64,
   term: &'a GraphTerm<'a>
 ) -> &'a GraphNode<'a> {
   mem.alloc(GraphNode {
-    index: index,
-    term: term,
+    index,
+    term,
     ins_head: Cell::new(None),
     ins_tail: Cell::new(None),
     outs_head: Cell::new(None),
@@ -1248,12 +2716,12 @@ fn make_node<'a>(
 
 fn make_edge<'a>(
   mem: &'a Bump,
-  prop: Property<()>,
+  prop: Property<Option<&'a str>>,
   source: &'a GraphNode<'a>,
   target: &'a GraphNode<'a>
 ) -> &'a GraphEdge<'a> {
   mem.alloc(GraphEdge {
-    prop: prop,
+    prop,
     ins_next: Cell::new(None),
     ins_prev: Cell::new(None),
     outs_next: Cell::new(None),
@@ -1264,46 +2732,38 @@ fn make_edge<'a>(
 }
 
 #[derive(Debug)]
-enum RebuildDoc<'a> {
+pub enum RebuildDoc<'a> {
   EOD,
   Break(&'a RebuildObj<'a>, &'a RebuildDoc<'a>)
 }
 
 #[derive(Debug)]
-enum RebuildObj<'a> {
+pub enum RebuildObj<'a> {
   Term(&'a RebuildTerm<'a>),
   Fix(&'a RebuildFix<'a>),
-  Grp(&'a RebuildObj<'a>),
-  Seq(&'a RebuildObj<'a>),
+  Grp(Option<&'a str>, &'a RebuildObj<'a>),
+  Seq(Option<&'a str>, &'a RebuildObj<'a>),
   Comp(&'a RebuildObj<'a>, &'a RebuildObj<'a>, bool)
 }
 
 #[derive(Debug)]
-enum RebuildFix<'a> {
+pub enum RebuildFix<'a> {
   Term(&'a RebuildTerm<'a>),
   Comp(&'a RebuildFix<'a>, &'a RebuildFix<'a>, bool)
 }
 
 #[derive(Debug)]
-enum RebuildTerm<'a> {
+pub enum RebuildTerm<'a> {
   Null,
-  Text(&'a str),
+  Text(&'a str, TextAttr),
   Nest(&'a RebuildTerm<'a>),
-  Pack(u64, &'a RebuildTerm<'a>)
-}
-
-#[derive(Copy, Clone)]
-struct RebuildCont<'a>(&'a dyn Fn(&'a Bump, &'a RebuildObj<'a>) -> &'a RebuildObj<'a>);
-
-impl<'a> fmt::Debug for RebuildCont<'a> {
-  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-    write!(f, "RebuildCont(<fn>)")
-  }
+  Pack(Index, &'a RebuildTerm<'a>)
 }
 
-fn _structurize<'b, 'a: 'b>(
+pub(crate) fn _structurize<'b, 'a: 'b>(
   mem: &'b Bump,
-  doc: &'a FixedDoc<'a>
+  doc: &'a FixedDoc<'a>,
+  names: &BTreeMap<Index, &'b str>
 ) -> &'b RebuildDoc<'b> {
   fn _eod<'a>(
     mem: &'a Bump
@@ -1325,9 +2785,10 @@ fn _structurize<'b, 'a: 'b>(
   }
   fn _text<'a>(
     mem: &'a Bump,
-    data: &'a str
+    data: &'a str,
+    attr: TextAttr
   ) -> &'a GraphTerm<'a> {
-    mem.alloc(GraphTerm::Text(data))
+    mem.alloc(GraphTerm::Text(data, attr))
   }
   fn _fix<'a>(
     mem: &'a Bump,
@@ -1343,7 +2804,7 @@ fn _structurize<'b, 'a: 'b>(
   }
   fn _pack<'a>(
     mem: &'a Bump,
-    index: u64,
+    index: Index,
     term: &'a GraphTerm<'a>
   ) -> &'a GraphTerm<'a> {
     mem.alloc(GraphTerm::Pack(index, term))
@@ -1362,10 +2823,8 @@ fn _structurize<'b, 'a: 'b>(
   ) -> &'a GraphFix<'a> {
     mem.alloc(GraphFix::Next(left, right, pad))
   }
-  fn _unit_grp() -> Property<()> { Property::Grp(()) }
-  fn _unit_seq() -> Property<()> { Property::Seq(()) }
-  fn _unary_grp(index: u64) -> Property<u64> { Property::Grp(index) }
-  fn _unary_seq(index: u64) -> Property<u64> { Property::Seq(index) }
+  fn _named_grp(name: Option<&str>) -> Property<Option<&str>> { Property::Grp(name) }
+  fn _named_seq(name: Option<&str>) -> Property<Option<&str>> { Property::Seq(name) }
   fn _binary_grp(
     from_index: u64,
     to_index: Option<u64>
@@ -1380,125 +2839,95 @@ fn _structurize<'b, 'a: 'b>(
   }
   fn _graphify<'b, 'a: 'b>(
     mem: &'b Bump,
-    doc: &'a FixedDoc<'a>
+    doc: &'a FixedDoc<'a>,
+    names: &BTreeMap<Index, &'b str>
   ) -> &'b GraphDoc<'b> {
-    fn _lift_stack<'b, 'a: 'b>(
-      mem: &'b Bump,
-      comp: &'a FixedComp<'a>
-    ) -> (&'b List<'b, Property<u64>>, bool) {
-      match comp {
-        FixedComp::Comp(pad) => (_list::nil(mem), *pad),
-        FixedComp::Grp(index, comp1) => {
-          let (props, pad) = _lift_stack(mem, comp1);
-          (_list::cons(mem, _unary_grp(*index), props), pad)
-        }
-        FixedComp::Seq(index, comp1) => {
-          let (props, pad) = _lift_stack(mem, comp1);
-          (_list::cons(mem, _unary_seq(*index), props), pad)
-        }
+    type Graph<'a> = Map<'a, Index, Property<(u64, Option<u64>)>>;
+    /* The scopes from the given scopes out to, but not including, the given outer scopes, outermost first */
+    fn _between<'a>(
+      scopes: &'a Scopes<'a>,
+      outer: &'a Scopes<'a>
+    ) -> Vec<&'a Scopes<'a>> {
+      let mut result = Vec::new();
+      let mut scopes = scopes;
+      while scopes._index() != outer._index() {
+        result.push(scopes);
+        scopes = scopes._outer()
       }
+      result.reverse();
+      result
     }
-    type Graph<'a> = Map<'a, u64, Property<(u64, Option<u64>)>>;
     fn _close<'b, 'a: 'b>(
       mem: &'b Bump,
       to_node: u64,
       props: &'a Graph<'a>,
-      stack: &'a List<'a, Property<u64>>
+      scopes: &[&'a Scopes<'a>]
     ) -> &'b Graph<'b> {
-      match stack {
-        List::Nil => props,
-        List::Cons(_, Property::Grp(index), stack1) =>
+      scopes.iter().fold(props, |props, scope| match scope {
+        Scopes::Top => invariant!(to_node),
+        Scopes::Grp(index, _) =>
           match props.lookup_unsafe(&total, *index) {
-            Property::Seq(_) => unreachable!("Invariant"),
+            Property::Seq(_) => invariant!(index),
             Property::Grp((from_node, _to_node)) => {
               let prop1 = _binary_grp(from_node, Some(to_node));
-              let props1 = props.insert(mem, &total, *index, prop1);
-              _close(mem, to_node, props1, stack1)
+              props.insert(mem, &total, *index, prop1)
             }
           }
-        List::Cons(_, Property::Seq(index), stack1) => {
+        Scopes::Seq(index, _) =>
           match props.lookup_unsafe(&total, *index) {
-            Property::Grp(_) => unreachable!("Invariant"),
+            Property::Grp(_) => invariant!(index),
             Property::Seq((from_node, _to_node)) => {
               let prop1 = _binary_seq(from_node, Some(to_node));
-              let props1 = props.insert(mem, &total, *index, prop1);
-              _close(mem, to_node, props1, stack1)
+              props.insert(mem, &total, *index, prop1)
             }
           }
-        }
-      }
+      })
     }
     fn _open<'b, 'a: 'b>(
       mem: &'b Bump,
       from_node: u64,
       props: &'a Graph<'a>,
-      stack: &'a List<Property<u64>>
+      scopes: &[&'a Scopes<'a>]
     ) -> &'b Graph<'b> {
-      match stack {
-        List::Nil => props,
-        List::Cons(_, Property::Grp(index), stack1) => {
-          let prop1 = _binary_grp(from_node, None);
-          let props1 = props.insert(mem, &total, *index, prop1);
-          _open(mem, from_node, props1, stack1)
-        }
-        List::Cons(_, Property::Seq(index), stack1) => {
-          let prop1 = _binary_seq(from_node, None);
-          let props1 = props.insert(mem, &total, *index, prop1);
-          _open(mem, from_node, props1, stack1)
-        }
-      }
+      scopes.iter().fold(props, |props, scope| match scope {
+        Scopes::Top => invariant!(from_node),
+        Scopes::Grp(index, _) =>
+          props.insert(mem, &total, *index, _binary_grp(from_node, None)),
+        Scopes::Seq(index, _) =>
+          props.insert(mem, &total, *index, _binary_seq(from_node, None))
+      })
     }
+    /*
+      Close the scopes of the previous composition that the next composition
+      is not under, and open the scopes of the next composition that the
+      previous one was not under; the scopes are numbered in the order they
+      are opened, so the innermost scope common to both is found by stepping
+      out of the scope with the greater number.
+    */
     fn _update<'b, 'a: 'b>(
       mem: &'b Bump,
       node: u64,
       props: &'a Graph<'a>,
-      scope: &'b List<'b, Property<u64>>,
-      stack: &'b List<'b, Property<u64>>
-    ) -> (&'b List<'b, Property<u64>>, &'b Graph<'b>) {
-      match (scope, stack) {
-        (_, List::Nil) => {
-          let props1 = _close(mem, node, props, scope);
-          (_list::nil(mem), props1)
-        }
-        (List::Nil, _) => {
-          let props1 = _open(mem, node, props, stack);
-          (stack, props1)
-        }
-        ( List::Cons(_, Property::Grp(left), scope1)
-        , List::Cons(_, Property::Grp(right), stack1)) => {
-          if left > right { unreachable!("Invariant") }
-          if left == right {
-            let (stack2, props1) = _update(mem, node, props, scope1, stack1);
-            (_list::cons(mem, _unary_grp(*left), stack2), props1)
-          } else {
-            let props1 = _close(mem, node, props, scope);
-            let props2 = _open(mem, node, props1, stack);
-            (stack, props2)
-          }
-        }
-        ( List::Cons(_, Property::Seq(left), scope1)
-        , List::Cons(_, Property::Seq(right), stack1)) => {
-          if left > right { unreachable!("Invariant") }
-          if left == right {
-            let (stack2, props1) = _update(mem, node, props, scope1, stack1);
-            (_list::cons(mem, _unary_seq(*left), stack2), props1)
-          } else {
-            let props1 = _close(mem, node, props, scope);
-            let props2 = _open(mem, node, props1, stack);
-            (stack, props2)
-          }
-        }
-        _ => {
-          let props1 = _close(mem, node, props, scope);
-          let props2 = _open(mem, node, props1, stack);
-          (stack, props2)
+      scope: &'b Scopes<'b>,
+      stack: &'b Scopes<'b>
+    ) -> (&'b Scopes<'b>, &'b Graph<'b>) {
+      let mut common = (scope, stack);
+      while common.0._index() != common.1._index() {
+        common = if common.0._index() > common.1._index() {
+          (common.0._outer(), common.1)
+        } else {
+          (common.0, common.1._outer())
         }
       }
+      let props1 = _close(mem, node, props, &_between(scope, common.0));
+      let props2 = _open(mem, node, props1, &_between(stack, common.0));
+      (stack, props2)
     }
     fn _transpose<'a>(
       mem: &'a Bump,
-      nodes: &'a List<'a, &'a GraphNode<'a>>,
-      props: &'a List<'a, Property<(u64, Option<u64>)>>
+      nodes: &[&'a GraphNode<'a>],
+      props: &'a List<'a, (Index, Property<(u64, Option<u64>)>)>,
+      names: &BTreeMap<Index, &'a str>
     ) {
       fn _push_ins<'a>(
         edge: &'a GraphEdge<'a>,
@@ -1532,185 +2961,134 @@ fn _structurize<'b, 'a: 'b>(
           }
         }
       }
-      match props {
-        List::Nil => (),
-        List::Cons(_, Property::Grp((from_index, Some(to_index))), props1) => {
-          if from_index == to_index {
-            _transpose(mem, nodes, props1)
-          } else {
-            let from_node = nodes.get_unsafe(*from_index);
-            let to_node = nodes.get_unsafe(*to_index);
-            let curr = make_edge(mem, _unit_grp(), from_node, to_node);
-            _push_ins(curr, to_node);
-            _push_outs(curr, from_node);
-            _transpose(mem, nodes, props1)
-          }
-        }
-        List::Cons(_, Property::Seq((from_index, Some(to_index))), props1) => {
-          if from_index == to_index {
-            _transpose(mem, nodes, props1)
-          } else {
-            let from_node = nodes.get_unsafe(*from_index);
-            let to_node = nodes.get_unsafe(*to_index);
-            let curr = make_edge(mem, _unit_seq(), from_node, to_node);
-            _push_ins(curr, to_node);
-            _push_outs(curr, from_node);
-            _transpose(mem, nodes, props1)
-          }
-        }
-        _ => unreachable!("Invariant")
-      }
-    }
-    fn _visit_doc<'b, 'a: 'b>(
-      mem: &'b Bump,
-      doc: &'a FixedDoc<'a>
-    ) -> &'b GraphDoc<'b> {
-      match doc {
-        FixedDoc::EOD => _eod(mem),
-        FixedDoc::Break(obj, doc1) => {
-          let scope = _list::nil(mem);
-          let nodes = mem.alloc(|_mem, nodes| nodes);
-          let pads = mem.alloc(|_mem, pads| pads);
-          let props = _map::empty(mem);
-          let (nodes1, pads1, props1) = _visit_obj(
-            mem, obj, 0, scope, nodes, pads, props
-          );
-          let nodes2 = nodes1(mem, _list::nil(mem));
-          let props2 = props1.values(mem).fold(
-            mem,
-            _list::nil(mem),
-            mem.alloc(|mem, item: Property<(u64, Option<u64>)>, items|
-            _list::cons(mem, item, items))
-          );
-          _transpose(mem, nodes2, props2);
-          let doc2 = _visit_doc(mem, doc1);
-          _break(mem, nodes2, pads1(mem, _list::nil(mem)), doc2)
-        }
+      for (scope, prop) in props.iter() {
+        let (prop1, from_index, to_index) = match prop {
+          Property::Grp((from_index, Some(to_index))) =>
+            (_named_grp(names.get(&scope).copied()), from_index, to_index),
+          Property::Seq((from_index, Some(to_index))) =>
+            (_named_seq(names.get(&scope).copied()), from_index, to_index),
+          _ => invariant!(props)
+        };
+        if from_index == to_index { continue }
+        let from_node = nodes[from_index as usize];
+        let to_node = nodes[to_index as usize];
+        let curr = make_edge(mem, prop1, from_node, to_node);
+        _push_ins(curr, to_node);
+        _push_outs(curr, from_node)
       }
     }
-    fn _visit_obj<'b, 'a: 'b>(
+    /*
+      The lines, their items and the terms of their fixes are visited with
+      loops rather than by recursion, such that long documents and lines do
+      not overflow the call stack.
+    */
+    fn _visit_line<'b, 'a: 'b>(
       mem: &'b Bump,
       obj: &'a FixedObj<'a>,
-      index: u64,
-      scope: &'a List<'a, Property<u64>>,
-      nodes: &'b dyn Fn(&'b Bump, &'b List<'b, &'b GraphNode<'b>>) -> &'b List<'b, &'b GraphNode<'b>>,
-      pads: &'b dyn Fn(&'b Bump, &'b List<'b, bool>) -> &'b List<'b, bool>,
-      props: &'a Graph<'a>
-    ) -> (
-      &'b dyn Fn(&'b Bump, &'b List<'b, &'b GraphNode<'b>>) -> &'b List<'b, &'b GraphNode<'b>>,
-      &'b dyn Fn(&'b Bump, &'b List<'b, bool>) -> &'b List<'b, bool>,
-      &'b Graph<'b>
-    ) {
-      match obj {
-        FixedObj::Next(term, comp, obj1) => {
-          match term {
-            FixedItem::Term(term) =>
-              _visit_term(mem, term, mem.alloc(move |mem, term1| {
-              let nodes2 = compose(mem, nodes, mem.alloc(move |mem, nodes1|
-                _list::cons(mem, make_node(mem, index, term1), nodes1)
-              ));
-              let (stack, pad) = _lift_stack(mem, comp);
-              let pads2 = compose(mem, pads, mem.alloc(move |mem, pads1|
-                _list::cons(mem, pad, pads1)
-              ));
-              let (scope1, props1) = _update(mem, index, props, scope, stack);
-              _visit_obj(
-                mem,
-                obj1,
-                index + 1,
-                scope1,
-                nodes2,
-                pads2,
-                props1
-              )})),
-            FixedItem::Fix(fix) => {
-              let (fix1, scope1, props1) = _visit_fix(
-                mem, fix, index, scope, props
-              );
-              let nodes2 = compose(mem, nodes, mem.alloc(move |mem, nodes1|
-                _list::cons(mem, make_node(mem, index, _fix(mem, fix1)), nodes1)
-              ));
-              let (stack, pad) = _lift_stack(mem, comp);
-              let pads2 = compose(mem, pads, mem.alloc(move |mem, pads1|
-                _list::cons(mem, pad, pads1)
-              ));
-              let (scope2, props2) = _update(mem, index, props1, scope1, stack);
-              _visit_obj(
-                mem,
-                obj1,
-                index + 1,
-                scope2,
-                nodes2,
-                pads2,
-                props2
-              )
-            }
+      names: &BTreeMap<Index, &'b str>
+    ) -> (&'b List<'b, &'b GraphNode<'b>>, &'b List<'b, bool>) {
+      let mut nodes = Vec::new();
+      let mut pads = Vec::new();
+      let mut index = 0;
+      let mut scope: &'b Scopes<'b> = &Scopes::Top;
+      let mut props: &'b Graph<'b> = _map::empty(mem);
+      let mut obj = obj;
+      loop {
+        let item = match obj {
+          FixedObj::Next(item, _comp, _obj1) => item,
+          FixedObj::Last(item) => item
+        };
+        let term = match item {
+          FixedItem::Term(term) => _visit_term(mem, term),
+          FixedItem::Fix(fix) => {
+            let (fix1, scope1, props1) = _visit_fix(mem, fix, index, scope, props);
+            scope = scope1;
+            props = props1;
+            _fix(mem, fix1)
           }
-        }
-        FixedObj::Last(term) => {
-          match term {
-            FixedItem::Term(term) =>
-              _visit_term(mem, term, mem.alloc(move |mem, term1| {
-              let nodes2 = compose(mem, nodes, mem.alloc(move |mem, nodes1|
-                _list::cons(mem, make_node(mem, index, term1), nodes1)
-              ));
-              let props1 = _close(mem, index, props, scope);
-              (nodes2, pads, props1)})),
-            FixedItem::Fix(fix) => {
-              let (fix1, scope1, props1) = _visit_fix(mem, fix, index, scope, props);
-              let nodes2 = compose(mem, nodes, mem.alloc(move |mem, nodes1|
-                _list::cons(mem, make_node(mem, index, _fix(mem, fix1)), nodes1)
-              ));
-              let props2 = _close(mem, index, props1, scope1);
-              (nodes2, pads, props2)
-            }
+        };
+        nodes.push(make_node(mem, index, term));
+        match obj {
+          FixedObj::Next(_item, FixedComp::Comp(pad, stack), obj1) => {
+            pads.push(*pad);
+            (scope, props) = _update(mem, index, props, scope, stack);
+            index += 1;
+            obj = obj1
+          }
+          FixedObj::Last(_item) => {
+            props = _close(mem, index, props, &_between(scope, &Scopes::Top));
+            break
           }
         }
       }
+      /*
+        The edges are made in the order of the entries of the map, that of
+        avl::to_list, since the order of the edges of a node decides how the
+        scopes over the same items are nested when rebuilt; a grp and a seq
+        over the same items are told apart by it, so it is pinned by the
+        corpus and the structurize runner.
+      */
+      _transpose(mem, &nodes, props.entries(mem), names);
+      (_list::from_iter(mem, nodes), _list::from_iter(mem, pads))
     }
-    fn _visit_term<'b, 'a: 'b, R>(
+    fn _visit_term<'b, 'a: 'b>(
       mem: &'b Bump,
-      term: &'a FixedTerm<'a>,
-      cont: &'b dyn Fn(&'b Bump, &'b GraphTerm<'b>) -> R
-    ) -> R {
-      match term {
-        FixedTerm::Null => cont(mem, _null(mem)),
-        FixedTerm::Text(data) => cont(mem, _text(mem, data)),
-        FixedTerm::Nest(term1) =>
-          _visit_term(mem, term1, compose(mem, cont, mem.alloc(|mem, term2|
-          _nest(mem, term2)))),
-        FixedTerm::Pack(index, term1) =>
-          _visit_term(mem, term1, compose(mem, cont, mem.alloc(|mem, term2|
-          _pack(mem, *index, term2))))
-      }
+      term: &'a FixedTerm<'a>
+    ) -> &'b GraphTerm<'b> {
+      let mut scopes = Vec::new();
+      let mut term = term;
+      let leaf = loop {
+        match term {
+          FixedTerm::Null => break _null(mem),
+          FixedTerm::Text(data, attr) => break _text(mem, data, *attr),
+          FixedTerm::Nest(term1) => { scopes.push(None); term = term1 }
+          FixedTerm::Pack(index, term1) => { scopes.push(Some(*index)); term = term1 }
+        }
+      };
+      scopes.into_iter().rev().fold(leaf, |term1, scope| match scope {
+        None => _nest(mem, term1),
+        Some(index) => _pack(mem, index, term1)
+      })
     }
     fn _visit_fix<'b, 'a: 'b>(
       mem: &'b Bump,
       fix: &'a FixedFix<'a>,
       index: u64,
-      scope: &'a List<'a, Property<u64>>,
-      props: &'a Graph<'a>
+      scope: &'b Scopes<'b>,
+      props: &'b Graph<'b>
     ) -> (
       &'b GraphFix<'b>,
-      &'b List<'b, Property<u64>>,
+      &'b Scopes<'b>,
       &'b Graph<'b>
     ) {
-      match fix {
-        FixedFix::Next(term, comp, fix1) =>
-          _visit_term(mem, term, mem.alloc(move |mem, term1| {
-          let (stack, pad) = _lift_stack(mem, comp);
-          let (scope1, props1) = _update(mem, index, props, scope, stack);
-          let (fix2, scope2, props2) = _visit_fix(mem, fix1, index, scope1, props1);
-          (_fix_next(mem, term1, fix2, pad), scope2, props2)})),
-        FixedFix::Last(term) =>
-          _visit_term(mem, term, mem.alloc(move |mem, term1|
-          (_fix_last(mem, term1), scope, props)))
-      }
+      let mut terms = Vec::new();
+      let mut scope = scope;
+      let mut props = props;
+      let mut fix = fix;
+      let last = loop {
+        match fix {
+          FixedFix::Next(term, FixedComp::Comp(pad, stack), fix1) => {
+            terms.push((_visit_term(mem, term), *pad));
+            (scope, props) = _update(mem, index, props, scope, stack);
+            fix = fix1
+          }
+          FixedFix::Last(term) => break _fix_last(mem, _visit_term(mem, term))
+        }
+      };
+      let fix1 = terms.into_iter().rev().fold(last, |fix2, (term, pad)|
+        _fix_next(mem, term, fix2, pad));
+      (fix1, scope, props)
     }
-    _visit_doc(mem, doc)
+    let mut lines = Vec::new();
+    let mut doc = doc;
+    while let FixedDoc::Break(obj, doc1) = doc {
+      lines.push(_visit_line(mem, obj, names));
+      doc = doc1
+    }
+    lines.into_iter().rev().fold(_eod(mem), |doc1, (nodes, pads)|
+      _break(mem, nodes, pads, doc1))
   }
   fn _solve<'a>(
-    mem: &'a Bump,
     doc: &'a GraphDoc<'a>
   ) -> &'a GraphDoc<'a> {
     fn _move_ins<'a>(
@@ -1728,20 +3106,9 @@ fn _structurize<'b, 'a: 'b>(
         tail: &'a GraphEdge<'a>,
         edge: &'a GraphEdge<'a>
       ) {
-        fn _set_targets<'a>(
-          node: &'a GraphNode<'a>,
-          ins: Option<&'a GraphEdge<'a>>
-        ) {
-          match ins {
-            None => (),
-            Some(edge) => {
-              edge.target.set(node);
-              _set_targets(node, edge.ins_next.get())
-            }
-          }
-        }
         let node = edge.target.get();
-        _set_targets(node, Some(head));
+        let ins = std::iter::successors(Some(head), |edge1| edge1.ins_next.get());
+        ins.for_each(|edge1| edge1.target.set(node));
         match edge.ins_next.get() {
           None => {
             edge.ins_next.set(Some(head));
@@ -1811,102 +3178,62 @@ fn _structurize<'b, 'a: 'b>(
       _remove_out(curr);
       _prepend_out(curr, edge)
     }
-    fn _resolve<'a, R>(
-      mem: &'a Bump,
+    /*
+      The first grp of the outgoing edges from the given one, moving the seqs
+      before it out of the way under the given edge
+    */
+    fn _resolve<'a>(
       edge: &'a GraphEdge<'a>,
-      outs: &'a GraphEdge<'a>,
-      none: &'a dyn Fn(&'a Bump) -> R,
-      some: &'a dyn Fn(&'a Bump, &'a GraphEdge<'a>) -> R
-    ) -> R {
-      fn _visit<'a, R>(
-        mem: &'a Bump,
-        maybe_curr: Option<&'a GraphEdge<'a>>,
-        edge: &'a GraphEdge<'a>,
-        none: &'a dyn Fn(&'a Bump) -> R,
-        some: &'a dyn Fn(&'a Bump, &'a GraphEdge<'a>) -> R
-      ) -> R{
-        match maybe_curr {
-          None => none(mem),
-          Some(curr) =>
-            match curr.prop {
-            | Property::Grp(()) => some(mem, curr),
-            | Property::Seq(()) => {
-              let curr1 = curr.outs_next.get();
-              _move_out(curr, edge);
-              _visit(mem, curr1, curr, none, some)
-            }
+      outs: &'a GraphEdge<'a>
+    ) -> Option<&'a GraphEdge<'a>> {
+      let mut edge = edge;
+      let mut maybe_curr = Some(outs);
+      while let Some(curr) = maybe_curr {
+        match curr.prop {
+          Property::Grp(_) => return Some(curr),
+          Property::Seq(_) => {
+            maybe_curr = curr.outs_next.get();
+            _move_out(curr, edge);
+            edge = curr
           }
         }
       }
-      _visit(mem, Some(outs), edge, none, some)
+      None
     }
     fn _leftmost<'a>(
-      mem: &'a Bump,
       head: &'a GraphEdge<'a>
     ) -> &'a GraphEdge<'a> {
-      fn _visit<'a>(
-        mem: &'a Bump,
-        curr: &'a GraphEdge<'a>,
-        index: u64,
-        result: &'a GraphEdge<'a>
-      ) -> &'a GraphEdge<'a> {
-        match curr.ins_next.get() {
-          None => result,
-          Some(next) => {
-            let index1 = next.source.get().index;
-            if index1 < index {
-              _visit(mem, next, index1, next)
-            } else {
-              _visit(mem, next, index, result)
-            }
-          }
-        }
-      }
-      _visit(mem, head, head.source.get().index, head)
-    }
-    fn _visit_doc<'a>(
-      mem: &'a Bump,
-      doc: &'a GraphDoc<'a>
-    ) -> &'a GraphDoc<'a> {
-      match doc {
-        GraphDoc::EOD => _eod(mem),
-        GraphDoc::Break(nodes, pads, doc1) => {
-          let count = nodes.length();
-          _visit_node(mem, count, 0, nodes);
-          let doc2 = _visit_doc(mem, doc1);
-          _break(mem, nodes, pads, doc2)
-        }
-      }
+      let ins = std::iter::successors(Some(head), |edge| edge.ins_next.get());
+      ins.fold(head, |result, edge|
+        if edge.source.get().index < result.source.get().index { edge } else { result })
     }
     fn _visit_node<'a>(
-      mem: &'a Bump,
-      count: u64,
-      index: u64,
-      nodes: &'a List<'a, &'a GraphNode<'a>>
+      index: usize,
+      node: &'a GraphNode<'a>
     ) {
-      if count == index { return }
-      let node = nodes.get_unsafe(index);
       match (
         (node.ins_head.get(), node.ins_tail.get()),
         (node.outs_head.get(), node.outs_tail.get())
       ) {
         ( (Some(ins_head), Some(ins_tail))
         , (Some(outs_head), Some(_outs_tail))) => {
-          let ins_first = _leftmost(mem, ins_head);
-          _resolve(mem, ins_first, outs_head,
-            mem.alloc(move |mem| _visit_node(mem, count, index + 1, nodes)),
-            mem.alloc(move |mem, outs_head1| {
-              _move_ins(ins_head, ins_tail, outs_head1);
-              _visit_node(mem, count, index + 1, nodes)
-            }))
+          let ins_first = _leftmost(ins_head);
+          if let Some(outs_head1) = _resolve(ins_first, outs_head) {
+            _move_ins(ins_head, ins_tail, outs_head1)
+          }
         }
         ((Some(_), None), _) | ((None, Some(_)), _)
         | (_, (Some(_), None)) | (_, (None, Some(_))) =>
-          unreachable!("Invariant"),
-        (_, _) => _visit_node(mem, count, index + 1, nodes)
+          invariant!(index, node.index),
+        (_, _) => ()
       }
     }
-    _visit_doc(mem, doc)
+    let mut doc1 = doc;
+    while let GraphDoc::Break(nodes, _pads, doc2) = doc1 {
+      nodes.iter().enumerate().for_each(|(index, node)| _visit_node(index, node));
+      doc1 = doc2
+    }
+    doc
   }
   fn _rebuild<'b, 'a: 'b>(
     mem: &'b Bump,
@@ -1938,15 +3265,17 @@ fn _structurize<'b, 'a: 'b>(
     }
     fn _grp<'a>(
       mem: &'a Bump,
+      name: Option<&'a str>,
       obj: &'a RebuildObj<'a>
     ) -> &'a RebuildObj<'a> {
-      mem.alloc(RebuildObj::Grp(obj))
+      mem.alloc(RebuildObj::Grp(name, obj))
     }
     fn _seq<'a>(
       mem: &'a Bump,
+      name: Option<&'a str>,
       obj: &'a RebuildObj<'a>
     ) -> &'a RebuildObj<'a> {
-      mem.alloc(RebuildObj::Seq(obj))
+      mem.alloc(RebuildObj::Seq(name, obj))
     }
     fn _comp<'a>(
       mem: &'a Bump,
@@ -1977,9 +3306,10 @@ fn _structurize<'b, 'a: 'b>(
     }
     fn _text<'a>(
       mem: &'a Bump,
-      data: &'a str
+      data: &'a str,
+      attr: TextAttr
     ) -> &'a RebuildTerm<'a> {
-      mem.alloc(RebuildTerm::Text(data))
+      mem.alloc(RebuildTerm::Text(data, attr))
     }
     fn _nest<'a>(
       mem: &'a Bump,
@@ -1989,356 +3319,160 @@ fn _structurize<'b, 'a: 'b>(
     }
     fn _pack<'a>(
       mem: &'a Bump,
-      index: u64,
+      index: Index,
       term: &'a RebuildTerm<'a>
     ) -> &'a RebuildTerm<'a> {
       mem.alloc(RebuildTerm::Pack(index, term))
     }
-    fn __comp<'a>(
-      mem: &'a Bump,
-      left: &'a RebuildObj<'a>,
-      pad: bool,
-      right: &'a RebuildObj<'a>
-    ) -> &'a RebuildObj<'a> {
-      _comp(mem, left, right, pad)
-    }
-    fn _topology<'b, 'a: 'b>(
-      mem: &'b Bump,
-      nodes: &'a List<'a, &'a GraphNode<'a>>
-    ) -> (
-      &'b List<'b, &'b GraphTerm<'b>>,
-      &'b List<'b, u64>,
-      &'b List<'b, &'b List<'b, Property<()>>>
-    ) {
-      fn _num_ins<'a>(
-        node: &'a GraphNode<'a>
-      ) -> u64 {
-        fn _visit<'a>(
-          maybe_edge: Option<&'a GraphEdge<'a>>,
-          num: u64
-        ) -> u64 {
-          match maybe_edge {
-            None => num,
-            Some(edge) => _visit(edge.ins_next.get(), num + 1)
-          }
-        }
-        _visit(node.ins_head.get(), 0)
-      }
-      fn _prop_outs<'b, 'a: 'b>(
-        mem: &'b Bump,
-        node: &'a GraphNode<'a>
-      ) -> &'b List<'b, Property<()>> {
-        fn _visit<'b, 'a: 'b>(
-          mem: &'b Bump,
-          maybe_edge: Option<&'a GraphEdge<'a>>,
-          props: &'b dyn Fn(&'b Bump, &'b List<'b, Property<()>>) -> &'b List<'b, Property<()>>
-        ) -> &'b List<'b, Property<()>> {
-          match maybe_edge {
-            None => props(mem, _list::nil(mem)),
-            Some(edge) =>
-              _visit(mem, edge.outs_next.get(),
-                compose(mem, props, mem.alloc(|mem, props1|
-                  _list::cons(mem, edge.prop, props1))))
-          }
-        }
-        _visit(
-          mem,
-          node.outs_head.get(),
-          mem.alloc(|_mem, props| props)
-        )
-      }
-      fn _visit<'b, 'a: 'b>(
-        mem: &'b Bump,
-        nodes: &'a List<'a, &'a GraphNode<'a>>,
-        index: u64,
-        terms: &'b dyn Fn(&'b Bump, &'b List<'b, &'b GraphTerm<'b>>) -> &'b List<'b, &'b GraphTerm<'b>>,
-        ins: &'b dyn Fn(&'b Bump, &'b List<'b, u64>) -> &'b List<'b, u64>,
-        outs: &'b dyn Fn(&'b Bump, &'b List<'b, &'b List<'b, Property<()>>>) -> &'b List<'b, &'b List<'b, Property<()>>>
-      ) -> (
-        &'b List<'b, &'b GraphTerm<'b>>,
-        &'b List<'b, u64>,
-        &'b List<'b, &'b List<'b, Property<()>>>
-      ) {
-        if index == nodes.length() {
-          (
-            terms(mem, _list::nil(mem)),
-            ins(mem, _list::nil(mem)),
-            outs(mem, _list::nil(mem))
-          )
-        } else {
-          let node = nodes.get_unsafe(index);
-          let term1 = copy_graph_term(mem, node.term);
-          let num_ins = _num_ins(node);
-          let prop_outs = _prop_outs(mem, node);
-          _visit(mem, nodes, index + 1,
-            compose(mem, terms, mem.alloc(move |mem, term2|
-              _list::cons(mem, term1, term2))),
-            compose(mem, ins, mem.alloc(move |mem, ins1|
-              _list::cons(mem, num_ins, ins1))),
-            compose(mem, outs, mem.alloc(move |mem, outs1|
-              _list::cons(mem, prop_outs, outs1)))
-          )
-        }
-      }
-      _visit(
-        mem,
-        nodes,
-        0,
-        mem.alloc(|_mem, terms| terms),
-        mem.alloc(|_mem, ins| ins),
-        mem.alloc(|_mem, outs| outs)
-      )
+    /*
+      A scope of a line being rebuilt, the grp or seq that it is closed with,
+      none for the line itself, and the objects that its content is composed
+      to the right of, with their paddings, outermost first
+    */
+    struct Frame<'a> {
+      prop: Option<Property<Option<&'a str>>>,
+      partial: Vec<(&'a RebuildObj<'a>, bool)>
     }
-    fn _open<'a>(
+    fn _apply<'a>(
       mem: &'a Bump,
-      props: &'a List<'a, Property<()>>,
-      stack: &'a List<'a, RebuildCont<'a>>,
-      partial: &'a dyn Fn(&'a Bump, &'a RebuildObj<'a>) -> &'a RebuildObj<'a>
-    ) -> &'a List<'a, RebuildCont<'a>> {
-      fn _visit<'a>(
-        mem: &'a Bump,
-        props: &'a List<'a, Property<()>>,
-        stack: &'a List<'a, RebuildCont<'a>>
-      ) -> &'a List<'a, RebuildCont<'a>> {
-        match props {
-          List::Nil => stack,
-          List::Cons(_, Property::Grp(()), props1) =>
-            _visit(
-              mem,
-              props1,
-              _list::cons(mem, RebuildCont(mem.alloc(|mem, obj| _grp(mem, obj))), stack)
-            ),
-          List::Cons(_, Property::Seq(()), props1) =>
-            _visit(
-              mem,
-              props1,
-              _list::cons(mem, RebuildCont(mem.alloc(|mem, obj| _seq(mem, obj))), stack)
-            )
-        }
-      }
-      match stack {
-        List::Cons(_, top, stack1) =>
-          _visit(
-            mem,
-            props,
-            _list::cons(
-              mem,
-              RebuildCont(mem.alloc(|mem, obj| top.0(mem, partial(mem, obj)))),
-              stack1
-            )
-          ),
-        _ => unreachable!("Invariant")
-      }
+      partial: Vec<(&'a RebuildObj<'a>, bool)>,
+      obj: &'a RebuildObj<'a>
+    ) -> &'a RebuildObj<'a> {
+      partial.into_iter().rev().fold(obj, |obj1, (left, pad)| _comp(mem, left, obj1, pad))
     }
     fn _close<'a>(
       mem: &'a Bump,
-      count: u64,
-      stack: &'a List<'a, RebuildCont<'a>>,
-      term: &'a RebuildObj<'a>
-    ) -> (
-      &'a List<'a, RebuildCont<'a>>,
-      &'a RebuildObj<'a>
-    ) {
-      fn _visit<'a>(
-        mem: &'a Bump,
-        count: u64,
-        stack: &'a List<'a, RebuildCont<'a>>,
-        result: &'a RebuildObj<'a>
-      ) -> (
-        &'a List<'a, RebuildCont<'a>>,
-        &'a RebuildObj<'a>
-      ) {
-        if count == 0 { (stack, result) } else {
-          match stack {
-            List::Cons(_, top, stack1) =>
-              _visit(mem, count - 1, stack1, top.0(mem, result)),
-            _ => unreachable!("Invariant")
-          }
-        }
+      frame: Frame<'a>,
+      obj: &'a RebuildObj<'a>
+    ) -> &'a RebuildObj<'a> {
+      let obj1 = _apply(mem, frame.partial, obj);
+      match frame.prop {
+        None => obj1,
+        Some(Property::Grp(name)) => _grp(mem, name, obj1),
+        Some(Property::Seq(name)) => _seq(mem, name, obj1)
       }
-      _visit(mem, count, stack, term)
     }
-    fn _final<'a>(
+    fn _close_ins<'a>(
       mem: &'a Bump,
-      stack: &'a List<'a, RebuildCont<'a>>,
-      term: &'a RebuildObj<'a>
+      count: usize,
+      stack: &mut Vec<Frame<'a>>,
+      obj: &'a RebuildObj<'a>
     ) -> &'a RebuildObj<'a> {
-      match stack {
-        List::Cons(_, last, List::Nil) => last.0(mem, term),
-        _ => unreachable!("Invariant")
-      }
+      (0..count).fold(obj, |obj1, _| match stack.pop() {
+        Some(frame) => _close(mem, frame, obj1),
+        None => invariant!(count)
+      })
     }
-    fn _visit_doc<'b, 'a: 'b>(
+    /*
+      The nodes of a line are visited with a loop rather than by recursion,
+      such that long lines do not overflow the call stack; the scopes entered
+      at a node are pushed on a stack, and are closed at the node of their
+      incoming edges.
+    */
+    fn _visit_line<'b, 'a: 'b>(
       mem: &'b Bump,
-      doc: &'a GraphDoc<'a>
-    ) -> &'b RebuildDoc<'b> {
-      match doc {
-        GraphDoc::EOD => _eod(mem),
-        GraphDoc::Break(nodes, pads, doc1) => {
-          let (terms, ins, outs) = _topology(mem, nodes);
-          let stack: &'b List<'b, RebuildCont<'b>> = _list::cons(
-            mem,
-            RebuildCont(mem.alloc(|_mem, obj| obj)),
-            _list::nil(mem)
-          );
-          let partial = mem.alloc(|_mem, obj| obj);
-          let obj = _visit_line(mem, terms, pads, ins, outs, stack, partial);
-          let doc2 = _visit_doc(mem, doc1);
-          _break(mem, obj, doc2)
-        }
-      }
-    }
-    fn _visit_line<'a>(
-      mem: &'a Bump,
-      terms: &'a List<'a, &'a GraphTerm<'a>>,
-      pads: &'a List<'a, bool>,
-      ins: &'a List<'a, u64>,
-      outs: &'a List<'a, &'a List<'a, Property<()>>>,
-      stack: &'a List<'a, RebuildCont<'a>>,
-      partial: &'a dyn Fn(&'a Bump, &'a RebuildObj<'a>) -> &'a RebuildObj<'a>
-    ) -> &'a RebuildObj<'a> {
-      match (terms, pads) {
-        ( List::Cons(_, GraphTerm::Fix(fix), List::Nil)
-        , List::Nil) =>
-          _visit_fix(mem, fix, mem.alloc(move |mem, fix1|
-          match (ins, outs) {
-            ( List::Cons(_, 0, List::Nil)
-            , List::Cons(_, List::Nil, List::Nil)) =>
-              _final(mem, stack, partial(mem, _fix(mem, fix1))),
-            ( List::Cons(_, in_props, List::Nil)
-            , List::Cons(_, List::Nil, List::Nil)) => {
-              let (stack1, fix2) = _close(
-                mem, *in_props, stack, partial(mem, _fix(mem, fix1))
-              );
-              _final(mem, stack1, fix2)
-            }
-            (_, _) => unreachable!("Invariant")
-          })),
-        ( List::Cons(_, term, List::Nil)
-        , List::Nil) =>
-          _visit_term(mem, term, mem.alloc(move |mem, term1|
-          match (ins, outs) {
-            ( List::Cons(_, 0, List::Nil)
-            , List::Cons(_, List::Nil, List::Nil)) =>
-              _final(mem, stack, partial(mem, _term(mem, term1))),
-            ( List::Cons(_, in_props, List::Nil)
-            , List::Cons(_, List::Nil, List::Nil)) => {
-              let (stack1, term2) = _close(
-                mem, *in_props, stack, partial(mem, _term(mem, term1))
-              );
-              _final(mem, stack1, term2)
-            }
-            (_, _) => unreachable!("Invariant")
-          })),
-        ( List::Cons(_, GraphTerm::Fix(fix), terms1)
-        , List::Cons(_, pad, pads1)) =>
-          _visit_fix(mem, fix, mem.alloc(move |mem, fix1|
-          match (ins, outs) {
-            ( List::Cons(_, 0, ins1)
-            , List::Cons(_, List::Nil, outs1)) => {
-              let partial1 = compose(mem, partial, mem.alloc(move |mem, obj|
-                __comp(mem, _fix(mem, fix1), *pad, obj)
-              ));
-              _visit_line(mem, terms1, pads1, ins1, outs1, stack, partial1)
-            }
-            ( List::Cons(_, in_props, ins1)
-            , List::Cons(_, List::Nil, outs1)) => {
-              let (stack1, fix2) = _close(
-                mem, *in_props, stack, partial(mem, _fix(mem, fix1))
-              );
-              let partial1 = mem.alloc(move |mem, obj|
-                __comp(mem, fix2, *pad, obj)
-              );
-              _visit_line(mem, terms1, pads1, ins1, outs1, stack1, partial1)
-            }
-            ( List::Cons(_, 0, ins1)
-            , List::Cons(_, out_props, outs1)) => {
-              let stack1 = _open(mem, out_props, stack, partial);
-              let partial1 = mem.alloc(|mem, obj|
-                __comp(mem, _fix(mem, fix1), *pad, obj)
-              );
-              _visit_line(mem, terms1, pads1, ins1, outs1, stack1, partial1)
-            }
-            (_, _) => unreachable!("Invariant")
-          })),
-        ( List::Cons(_, term, terms1)
-        , List::Cons(_, pad, pads1)) =>
-          _visit_term(mem, term, mem.alloc(move |mem, term1|
-          match (ins, outs) {
-            ( List::Cons(_, 0, ins1)
-            , List::Cons(_, List::Nil, outs1)) => {
-              let partial1 = compose(mem, partial, mem.alloc(move |mem, obj|
-                __comp(mem, _term(mem, term1), *pad, obj)
-              ));
-              _visit_line(mem, terms1, pads1, ins1, outs1, stack, partial1)
-            }
-            ( List::Cons(_, in_props, ins1)
-            , List::Cons(_, List::Nil, outs1)) => {
-              let (stack1, term2) = _close(
-                mem, *in_props, stack, partial(mem, _term(mem, term1))
-              );
-              let partial1 = mem.alloc(move |mem, obj|
-                __comp(mem, term2, *pad, obj)
-              );
-              _visit_line(mem, terms1, pads1, ins1, outs1, stack1, partial1)
-            }
-            ( List::Cons(_, 0, ins1)
-            , List::Cons(_, out_props, outs1)) => {
-              let stack1 = _open(mem, out_props, stack, partial);
-              let partial1 = mem.alloc(|mem, obj|
-                __comp(mem, _term(mem, term1), *pad, obj)
-              );
-              _visit_line(mem, terms1, pads1, ins1, outs1, stack1, partial1)
+      nodes: &'a List<'a, &'a GraphNode<'a>>,
+      pads: &'a List<'a, bool>
+    ) -> &'b RebuildObj<'b> {
+      let mut stack = vec![Frame { prop: None, partial: Vec::new() }];
+      let mut partial = Vec::new();
+      let mut pads = pads.iter();
+      let mut nodes = nodes.iter().peekable();
+      while let Some(node) = nodes.next() {
+        let obj = match node.term {
+          GraphTerm::Fix(fix) => _fix(mem, _visit_fix(mem, fix)),
+          term => _term(mem, _visit_term(mem, term))
+        };
+        let ins = std::iter::successors(node.ins_head.get(), |edge| edge.ins_next.get());
+        let outs = std::iter::successors(node.outs_head.get(), |edge| edge.outs_next.get());
+        let num_ins = ins.count();
+        let props: Vec<_> = outs.map(|edge| edge.prop).collect();
+        match (nodes.peek(), pads.next()) {
+          (None, None) => {
+            if !props.is_empty() { invariant!(node.index, props) }
+            let obj1 = _close_ins(mem, num_ins, &mut stack, _apply(mem, partial, obj));
+            return match (stack.pop(), stack.is_empty()) {
+              (Some(frame), true) => _close(mem, frame, obj1),
+              (_, _) => invariant!(node.index)
             }
-            (_, _) => unreachable!("Invariant")
-          })),
-        (_, _) => unreachable!("Invariant")
+          }
+          (Some(_), Some(pad)) =>
+            match (num_ins, props.is_empty()) {
+              (0, true) => partial.push((obj, pad)),
+              (_, true) => {
+                let obj1 = _apply(mem, std::mem::take(&mut partial), obj);
+                let obj2 = _close_ins(mem, num_ins, &mut stack, obj1);
+                partial.push((obj2, pad))
+              }
+              (0, false) => {
+                match stack.last_mut() {
+                  Some(top) => top.partial.append(&mut partial),
+                  None => invariant!(node.index)
+                }
+                stack.extend(props.into_iter().map(|prop|
+                  Frame { prop: Some(prop), partial: Vec::new() }));
+                partial.push((obj, pad))
+              }
+              (_, false) => invariant!(node.index, num_ins, props)
+            },
+          (_, _) => invariant!(node.index)
+        }
       }
+      invariant!(nodes, pads)
     }
-    fn _visit_term<'b, 'a: 'b, R>(
+    fn _visit_term<'b, 'a: 'b>(
       mem: &'b Bump,
-      term: &'a GraphTerm<'a>,
-      cont: &'b dyn Fn(&'b Bump, &'b RebuildTerm<'b>) -> R
-    ) -> R {
-      match term {
-        GraphTerm::Null =>
-          cont(mem, _null(mem)),
-        GraphTerm::Text(data) =>
-          cont(mem, _text(mem, data)),
-        GraphTerm::Nest(term1) =>
-          _visit_term(mem, term1, compose(mem, cont, mem.alloc(|mem, term2|
-          _nest(mem, term2)))),
-        GraphTerm::Pack(index, term1) =>
-          _visit_term(mem, term1, compose(mem, cont, mem.alloc(|mem, term2|
-          _pack(mem, *index, term2)))),
-        GraphTerm::Fix(_fix) =>
-          unreachable!("Invariant")
-      }
-    }
-    fn _visit_fix<'b, 'a: 'b, R>(
+      term: &'a GraphTerm<'a>
+    ) -> &'b RebuildTerm<'b> {
+      let mut scopes = Vec::new();
+      let mut term1 = term;
+      let leaf = loop {
+        match term1 {
+          GraphTerm::Null => break _null(mem),
+          GraphTerm::Text(data, attr) => break _text(mem, data, *attr),
+          GraphTerm::Nest(term2) => { scopes.push(None); term1 = term2 }
+          GraphTerm::Pack(index, term2) => { scopes.push(Some(*index)); term1 = term2 }
+          GraphTerm::Fix(_fix) => invariant!(term)
+        }
+      };
+      scopes.into_iter().rev().fold(leaf, |term2, scope| match scope {
+        None => _nest(mem, term2),
+        Some(index) => _pack(mem, index, term2)
+      })
+    }
+    fn _visit_fix<'b, 'a: 'b>(
       mem: &'b Bump,
-      fix: &'a GraphFix<'a>,
-      cont: &'b dyn Fn(&'b Bump, &'b RebuildFix<'b>) -> R
-    ) -> R {
-      match fix {
-        GraphFix::Last(term) =>
-          _visit_term(mem, term, compose(mem, cont, mem.alloc(|mem, term1|
-          _fix_term(mem, term1)))),
-        GraphFix::Next(term, fix1, pad) =>
-          _visit_term(mem, term, mem.alloc(move |mem, term1|
-          _visit_fix(mem, fix1, mem.alloc(move |mem, fix2|
-          cont(mem, _fix_comp(mem, _fix_term(mem, term1), fix2, *pad))))))
-      }
+      fix: &'a GraphFix<'a>
+    ) -> &'b RebuildFix<'b> {
+      let mut terms = Vec::new();
+      let mut fix = fix;
+      let last = loop {
+        match fix {
+          GraphFix::Last(term) => break _fix_term(mem, _visit_term(mem, term)),
+          GraphFix::Next(term, fix1, pad) => {
+            terms.push((_visit_term(mem, term), *pad));
+            fix = fix1
+          }
+        }
+      };
+      terms.into_iter().rev().fold(last, |fix1, (term, pad)|
+        _fix_comp(mem, _fix_term(mem, term), fix1, pad))
     }
-    _visit_doc(mem, doc)
+    let mut objs = Vec::new();
+    let mut doc = doc;
+    while let GraphDoc::Break(nodes, pads, doc1) = doc {
+      objs.push(_visit_line(mem, nodes, pads));
+      doc = doc1
+    }
+    objs.into_iter().rev().fold(_eod(mem), |doc1, obj| _break(mem, obj, doc1))
   }
-  let doc1 = _graphify(mem, doc);
-  let doc2 = _solve(mem, doc1);
+  let doc1 = _graphify(mem, doc, names);
+  let doc2 = _solve(doc1);
   _rebuild(mem, doc2)
 }
 
 #[derive(Debug)]
-enum DenullDoc<'a> {
+pub enum DenullDoc<'a> {
   EOD,
   Line(&'a DenullObj<'a>),
   Empty(&'a DenullDoc<'a>),
@@ -2346,31 +3480,31 @@ enum DenullDoc<'a> {
 }
 
 #[derive(Debug)]
-enum DenullObj<'a> {
+pub enum DenullObj<'a> {
   Term(&'a DenullTerm<'a>),
   Fix(&'a DenullFix<'a>),
-  Grp(&'a DenullObj<'a>),
-  Seq(&'a DenullObj<'a>),
+  Grp(Option<&'a str>, &'a DenullObj<'a>),
+  Seq(Option<&'a str>, &'a DenullObj<'a>),
   Comp(&'a DenullObj<'a>, &'a DenullObj<'a>, bool)
 }
 
 #[derive(Debug)]
-enum DenullFix<'a> {
+pub enum DenullFix<'a> {
   Term(&'a DenullTerm<'a>),
   Comp(&'a DenullFix<'a>, &'a DenullFix<'a>, bool)
 }
 
 #[derive(Debug)]
-enum DenullTerm<'a> {
-  Text(&'a str),
+pub enum DenullTerm<'a> {
+  Text(&'a str, TextAttr),
   Nest(&'a DenullTerm<'a>),
-  Pack(u64, &'a DenullTerm<'a>)
+  Pack(Index, &'a DenullTerm<'a>)
 }
 
 /*
   Remove null identities
 */
-fn _denull<'b, 'a: 'b>(
+pub(crate) fn _denull<'b, 'a: 'b>(
   mem: &'b Bump,
   doc: &'a RebuildDoc<'a>
 ) -> &'b DenullDoc<'b> {
@@ -2412,15 +3546,17 @@ fn _denull<'b, 'a: 'b>(
   }
   fn _grp<'a>(
     mem: &'a Bump,
+    name: Option<&'a str>,
     obj: &'a DenullObj<'a>
   ) -> &'a DenullObj<'a> {
-    mem.alloc(DenullObj::Grp(obj))
+    mem.alloc(DenullObj::Grp(name, obj))
   }
   fn _seq<'a>(
     mem: &'a Bump,
+    name: Option<&'a str>,
     obj: &'a DenullObj<'a>
   ) -> &'a DenullObj<'a> {
-    mem.alloc(DenullObj::Seq(obj))
+    mem.alloc(DenullObj::Seq(name, obj))
   }
   fn _comp<'a>(
     mem: &'a Bump,
@@ -2446,9 +3582,10 @@ fn _denull<'b, 'a: 'b>(
   }
   fn _text<'a>(
     mem: &'a Bump,
-    data: &'a str
+    data: &'a str,
+    attr: TextAttr
   ) -> &'a DenullTerm<'a> {
-    mem.alloc(DenullTerm::Text(data))
+    mem.alloc(DenullTerm::Text(data, attr))
   }
   fn _nest<'a>(
     mem: &'a Bump,
@@ -2458,139 +3595,175 @@ fn _denull<'b, 'a: 'b>(
   }
   fn _pack<'a>(
     mem: &'a Bump,
-    index: u64,
+    index: Index,
     term: &'a DenullTerm<'a>
   ) -> &'a DenullTerm<'a> {
     mem.alloc(DenullTerm::Pack(index, term))
   }
-  fn _visit_doc<'b, 'a: 'b, R>(
-    mem: &'b Bump,
-    doc: &'a RebuildDoc<'a>,
-    none: &'b dyn Fn(&'b Bump) -> R,
-    some: &'b dyn Fn(&'b Bump, &'b DenullDoc<'b>) -> R
-  ) -> R {
-    match doc {
-      RebuildDoc::EOD => none(mem),
-      RebuildDoc::Break(obj, doc1) =>
-        _visit_obj(mem, obj,
-          mem.alloc(|mem|
-            _visit_doc(mem, doc1,
-              mem.alloc(|mem| some(mem, _eod(mem))),
-              mem.alloc(|mem, doc2| some(mem, _empty(mem, doc2))))),
-          mem.alloc(move |mem, obj1|
-            _visit_doc(mem, doc1,
-              mem.alloc(move |mem| some(mem, _line(mem, obj1))),
-              mem.alloc(|mem, doc2| some(mem, _break(mem, obj1, doc2))))),
-          mem.alloc(move |mem, _pad, obj1|
-            _visit_doc(mem, doc1,
-              mem.alloc(move |mem| some(mem, _line(mem, obj1))),
-              mem.alloc(|mem, doc2| some(mem, _break(mem, obj1, doc2))))))
-    }
-  }
-  fn _visit_obj<'b, 'a: 'b, R>(
-    mem: &'b Bump,
-    obj: &'a RebuildObj<'a>,
-    last_none: &'b dyn Fn(&'b Bump) -> R,
-    last_some: &'b dyn Fn(&'b Bump, &'b DenullObj<'b>) -> R,
-    next_none: &'b dyn Fn(&'b Bump, bool, &'b DenullObj<'b>) -> R
-  ) -> R {
-    match obj {
-      RebuildObj::Term(term) =>
-        _visit_term(mem, term, last_none, compose(mem, last_some,
-          mem.alloc(|mem, term1| _term(mem, term1)))),
-      RebuildObj::Fix(fix) =>
-        _visit_fix(mem, fix, last_none,
-          compose(mem, last_some, mem.alloc(|mem, fix1| _fix(mem, fix1))),
-          mem.alloc(|mem, _comp, fix1| last_some(mem, _fix(mem, fix1)))),
-      RebuildObj::Grp(obj1) =>
-        _visit_obj(mem, obj1,
-          last_none,
-          compose(mem, last_some, mem.alloc(|mem, obj2| _grp(mem, obj2))),
-          mem.alloc(|mem, _pad, obj2|
-            last_some(mem, _grp(mem, obj2)))),
-      RebuildObj::Seq(obj1) =>
-        _visit_obj(mem, obj1,
-          last_none,
-          compose(mem, last_some, mem.alloc(|mem, obj2| _seq(mem, obj2))),
-          mem.alloc(|mem, _pad, obj2|
-            last_some(mem, _seq(mem, obj2)))),
-      RebuildObj::Comp(left, right, l_pad) =>
-        _visit_obj(mem, left,
-          mem.alloc(|mem|
-            _visit_obj(mem, right,
-              last_none,
-              mem.alloc(|mem, right1| next_none(mem, *l_pad, right1)),
-              mem.alloc(|mem, r_pad, right1| {
-                let pad = *l_pad || r_pad;
-                next_none(mem, pad, right1)}))),
-          mem.alloc(move |mem, left1|
-            _visit_obj(mem, right,
-              mem.alloc(move |mem| last_some(mem, left1)),
-              mem.alloc(|mem, right1| last_some(mem, _comp(mem, left1, right1, *l_pad))),
-              mem.alloc(|mem, r_pad, right1| {
-                let pad = *l_pad || r_pad;
-                last_some(mem, _comp(mem, left1, right1, pad))}))),
-          mem.alloc(|_mem, _pad, _left1| unreachable!("Invariant")))
-    }
-  }
-  fn _visit_fix<'b, 'a: 'b, R>(
+  /*
+    The objects left of an object once its nulls are removed: nothing, the
+    object, or the object after a removed composition with the given padding
+  */
+  #[derive(Copy, Clone)]
+  enum Denulled<T> {
+    None,
+    Last(T),
+    Next(bool, T)
+  }
+  fn _join<T>(
+    left: Denulled<T>,
+    right: Denulled<T>,
+    l_pad: bool,
+    comp: impl Fn(T, T, bool) -> T
+  ) -> Denulled<T> {
+    match (left, right) {
+      (Denulled::Next(..), _) => invariant!(l_pad),
+      (Denulled::None, Denulled::None) => Denulled::None,
+      (Denulled::None, Denulled::Last(right1)) => Denulled::Next(l_pad, right1),
+      (Denulled::None, Denulled::Next(r_pad, right1)) => Denulled::Next(l_pad || r_pad, right1),
+      (Denulled::Last(left1), Denulled::None) => Denulled::Last(left1),
+      (Denulled::Last(left1), Denulled::Last(right1)) => Denulled::Last(comp(left1, right1, l_pad)),
+      (Denulled::Last(left1), Denulled::Next(r_pad, right1)) => Denulled::Last(comp(left1, right1, l_pad || r_pad))
+    }
+  }
+  fn _pop<T>(results: &mut Vec<Denulled<T>>) -> Denulled<T> {
+    results.pop().unwrap_or_else(|| invariant!())
+  }
+  /*
+    The objects, fixes and terms are visited with explicit stacks rather than
+    by recursion, such that deeply nested documents do not overflow the call
+    stack
+  */
+  fn _visit_obj<'b, 'a: 'b>(
     mem: &'b Bump,
-    fix: &'a RebuildFix<'a>,
-    last_none: &'b dyn Fn(&'b Bump) -> R,
-    last_some: &'b dyn Fn(&'b Bump, &'b DenullFix<'b>) -> R,
-    next_none: &'b dyn Fn(&'b Bump, bool, &'b DenullFix<'b>) -> R
-  ) -> R {
-    match fix {
-      RebuildFix::Term(term) =>
-        _visit_term(mem, term, last_none, compose(mem, last_some,
-          mem.alloc(|mem, term1| _fix_term(mem, term1)))),
-      RebuildFix::Comp(left, right, l_pad) =>
-        _visit_fix(mem, left,
-          mem.alloc(|mem|
-            _visit_fix(mem, right,
-              last_none,
-              mem.alloc(|mem, right1| next_none(mem, *l_pad, right1)),
-              mem.alloc(|mem, r_pad, right1| {
-                let pad = *l_pad || r_pad;
-                next_none(mem, pad, right1)}))),
-          mem.alloc(move |mem, left1|
-            _visit_fix(mem, right,
-              mem.alloc(move |mem| last_some(mem, left1)),
-              mem.alloc(|mem, right1| last_some(mem, _fix_comp(mem, left1, right1, *l_pad))),
-              mem.alloc(|mem, r_pad, right1| {
-                let pad = *l_pad || r_pad;
-                last_some(mem, _fix_comp(mem, left1, right1, pad))}))),
-          mem.alloc(|_mem, _pad, _left1| unreachable!("Invariant")))
-    }
-  }
-  fn _visit_term<'b, 'a: 'b, R>(
+    obj: &'a RebuildObj<'a>
+  ) -> Denulled<&'b DenullObj<'b>> {
+    enum Item<'a> {
+      Visit(&'a RebuildObj<'a>),
+      Grp(Option<&'a str>),
+      Seq(Option<&'a str>),
+      Comp(bool)
+    }
+    let mut results = Vec::new();
+    let mut stack = vec![Item::Visit(obj)];
+    while let Some(item) = stack.pop() {
+      let result = match item {
+        Item::Visit(RebuildObj::Term(term)) =>
+          match _visit_term(mem, term) {
+            None => Denulled::None,
+            Some(term1) => Denulled::Last(_term(mem, term1))
+          },
+        Item::Visit(RebuildObj::Fix(fix)) =>
+          match _visit_fix(mem, fix) {
+            Denulled::None => Denulled::None,
+            Denulled::Last(fix1) | Denulled::Next(_, fix1) =>
+              Denulled::Last(_fix(mem, fix1))
+          },
+        Item::Visit(RebuildObj::Grp(name, obj1)) => {
+          stack.push(Item::Grp(*name));
+          stack.push(Item::Visit(obj1));
+          continue
+        }
+        Item::Visit(RebuildObj::Seq(name, obj1)) => {
+          stack.push(Item::Seq(*name));
+          stack.push(Item::Visit(obj1));
+          continue
+        }
+        Item::Visit(RebuildObj::Comp(left, right, pad)) => {
+          stack.push(Item::Comp(*pad));
+          stack.push(Item::Visit(right));
+          stack.push(Item::Visit(left));
+          continue
+        }
+        Item::Grp(name) =>
+          match _pop(&mut results) {
+            Denulled::None => Denulled::None,
+            Denulled::Last(obj1) | Denulled::Next(_, obj1) =>
+              Denulled::Last(_grp(mem, name, obj1))
+          },
+        Item::Seq(name) =>
+          match _pop(&mut results) {
+            Denulled::None => Denulled::None,
+            Denulled::Last(obj1) | Denulled::Next(_, obj1) =>
+              Denulled::Last(_seq(mem, name, obj1))
+          },
+        Item::Comp(pad) => {
+          let right = _pop(&mut results);
+          let left = _pop(&mut results);
+          _join(left, right, pad, |left1, right1, pad1| _comp(mem, left1, right1, pad1))
+        }
+      };
+      results.push(result)
+    }
+    _pop(&mut results)
+  }
+  fn _visit_fix<'b, 'a: 'b>(
     mem: &'b Bump,
-    term: &'a RebuildTerm<'a>,
-    none: &'b dyn Fn(&'b Bump) -> R,
-    some: &'b dyn Fn(&'b Bump, &'b DenullTerm<'b>) -> R
-  ) -> R {
-    match term {
-      RebuildTerm::Null => none(mem),
-      RebuildTerm::Text(data) =>
-        if data.len() == 0 {
-          none(mem)
-        } else {
-          some(mem, _text(mem, data))
-        },
-      RebuildTerm::Nest(term1) =>
-        _visit_term(mem, term1, none, compose(mem, some,
-          mem.alloc(|mem, term2| _nest(mem, term2)))),
-      RebuildTerm::Pack(index, term1) =>
-        _visit_term(mem, term1, none, compose(mem, some,
-          mem.alloc(|mem, term2| _pack(mem, *index, term2))))
+    fix: &'a RebuildFix<'a>
+  ) -> Denulled<&'b DenullFix<'b>> {
+    enum Item<'a> {
+      Visit(&'a RebuildFix<'a>),
+      Comp(bool)
+    }
+    let mut results = Vec::new();
+    let mut stack = vec![Item::Visit(fix)];
+    while let Some(item) = stack.pop() {
+      let result = match item {
+        Item::Visit(RebuildFix::Term(term)) =>
+          match _visit_term(mem, term) {
+            None => Denulled::None,
+            Some(term1) => Denulled::Last(_fix_term(mem, term1))
+          },
+        Item::Visit(RebuildFix::Comp(left, right, pad)) => {
+          stack.push(Item::Comp(*pad));
+          stack.push(Item::Visit(right));
+          stack.push(Item::Visit(left));
+          continue
+        }
+        Item::Comp(pad) => {
+          let right = _pop(&mut results);
+          let left = _pop(&mut results);
+          _join(left, right, pad, |left1, right1, pad1| _fix_comp(mem, left1, right1, pad1))
+        }
+      };
+      results.push(result)
     }
+    _pop(&mut results)
   }
-  _visit_doc(
-    mem,
-    doc,
-    mem.alloc(|mem| _eod(mem)),
-    mem.alloc(|_mem, doc1| doc1)
-  )
+  fn _visit_term<'b, 'a: 'b>(
+    mem: &'b Bump,
+    term: &'a RebuildTerm<'a>
+  ) -> Option<&'b DenullTerm<'b>> {
+    let mut scopes = Vec::new();
+    let mut term = term;
+    let text = loop {
+      match term {
+        RebuildTerm::Null => return None,
+        RebuildTerm::Text("", _attr) => return None,
+        RebuildTerm::Text(data, attr) => break _text(mem, data, *attr),
+        RebuildTerm::Nest(term1) => { scopes.push(None); term = term1 }
+        RebuildTerm::Pack(index, term1) => { scopes.push(Some(*index)); term = term1 }
+      }
+    };
+    Some(scopes.iter().rev().fold(text, |term1, scope| match scope {
+      None => _nest(mem, term1),
+      Some(index) => _pack(mem, *index, term1)
+    }))
+  }
+  let mut objs = Vec::new();
+  let mut doc = doc;
+  while let RebuildDoc::Break(obj, doc1) = doc {
+    objs.push(*obj);
+    doc = doc1
+  }
+  objs.into_iter().rev().fold(None, |rest, obj| Some(
+    match (_visit_obj(mem, obj), rest) {
+      (Denulled::None, None) => _eod(mem),
+      (Denulled::None, Some(doc1)) => _empty(mem, doc1),
+      (Denulled::Last(obj1) | Denulled::Next(_, obj1), None) => _line(mem, obj1),
+      (Denulled::Last(obj1) | Denulled::Next(_, obj1), Some(doc1)) => _break(mem, obj1, doc1)
+    }
+  )).unwrap_or_else(|| _eod(mem))
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -2603,7 +3776,7 @@ enum Count {
 /*
   Remove grp and seq identities
 */
-fn _identities<'b, 'a: 'b>(
+pub(crate) fn _identities<'b, 'a: 'b>(
   mem: &'b Bump,
   doc: &'a DenullDoc<'a>
 ) -> &'b DenullDoc<'b> {
@@ -2645,15 +3818,17 @@ fn _identities<'b, 'a: 'b>(
   }
   fn _grp<'a>(
     mem: &'a Bump,
+    name: Option<&'a str>,
     obj: &'a DenullObj<'a>
   ) -> &'a DenullObj<'a> {
-    mem.alloc(DenullObj::Grp(obj))
+    mem.alloc(DenullObj::Grp(name, obj))
   }
   fn _seq<'a>(
     mem: &'a Bump,
+    name: Option<&'a str>,
     obj: &'a DenullObj<'a>
   ) -> &'a DenullObj<'a> {
-    mem.alloc(DenullObj::Seq(obj))
+    mem.alloc(DenullObj::Seq(name, obj))
   }
   fn _comp<'a>(
     mem: &'a Bump,
@@ -2671,135 +3846,157 @@ fn _identities<'b, 'a: 'b>(
       (Count::One, Count::One) => Count::Many
     }
   }
-  fn _elim_seqs<'b, 'a: 'b>(
+  fn _pop<'a>(
+    results: &mut Vec<(Count, &'a DenullObj<'a>)>
+  ) -> (Count, &'a DenullObj<'a>) {
+    results.pop().unwrap_or_else(|| invariant!())
+  }
+  /*
+    The lines of a document are visited with a loop, and their objects with
+    an explicit stack, rather than by recursion, such that long or deeply
+    nested documents do not overflow the call stack; the objects are visited
+    in the given mode, under a seq for eliminating seqs, and in the head of a
+    composition for eliminating grps.
+  */
+  fn _visit_doc<'b, 'a: 'b>(
     mem: &'b Bump,
-    doc: &'a DenullDoc<'a>
+    doc: &'a DenullDoc<'a>,
+    mode: bool,
+    visit_obj: fn(&'b Bump, &'a DenullObj<'a>, bool) -> &'b DenullObj<'b>
   ) -> &'b DenullDoc<'b> {
-    fn _visit_doc<'b, 'a: 'b>(
-      mem: &'b Bump,
-      doc: &'a DenullDoc<'a>
-    ) -> &'b DenullDoc<'b> {
+    let mut lines = Vec::new();
+    let mut doc = doc;
+    let last = loop {
       match doc {
-        DenullDoc::EOD => _eod(mem),
-        DenullDoc::Empty(doc1) => {
-          let doc2 = _visit_doc(mem, doc1);
-          _empty(mem, doc2)
-        }
-        DenullDoc::Break(obj, doc1) => {
-          let (_count, obj1) = _visit_obj(mem, obj, false);
-          let doc2 = _visit_doc(mem, doc1);
-          _break(mem, obj1, doc2)
-        }
-        DenullDoc::Line(obj) => {
-          let (_count, obj1) = _visit_obj(mem, obj, false);
-          _line(mem, obj1)
-        }
+        DenullDoc::EOD => break _eod(mem),
+        DenullDoc::Empty(doc1) => { lines.push(None); doc = doc1 }
+        DenullDoc::Break(obj, doc1) => { lines.push(Some(visit_obj(mem, obj, mode))); doc = doc1 }
+        DenullDoc::Line(obj) => break _line(mem, visit_obj(mem, obj, mode))
       }
+    };
+    lines.into_iter().rev().fold(last, |doc1, line| match line {
+      None => _empty(mem, doc1),
+      Some(obj) => _break(mem, obj, doc1)
+    })
+  }
+  fn _elim_seqs<'b, 'a: 'b>(
+    mem: &'b Bump,
+    obj: &'a DenullObj<'a>,
+    under_seq: bool
+  ) -> &'b DenullObj<'b> {
+    enum Item<'a> {
+      Visit(&'a DenullObj<'a>, bool),
+      Grp(Option<&'a str>),
+      Seq(Option<&'a str>),
+      Comp(bool)
     }
-    fn _visit_obj<'b, 'a: 'b>(
-      mem: &'b Bump,
-      obj: &'a DenullObj<'a>,
-      under_seq: bool
-    ) -> (Count, &'b DenullObj<'b>) {
-      match obj {
-        DenullObj::Term(term) |
-        DenullObj::Fix(DenullFix::Term(term)) =>
+    let mut results = Vec::new();
+    let mut stack = vec![Item::Visit(obj, under_seq)];
+    while let Some(item) = stack.pop() {
+      let result = match item {
+        Item::Visit(DenullObj::Term(term) | DenullObj::Fix(DenullFix::Term(term)), _) =>
           (Count::Zero, _term(mem, term)),
-        DenullObj::Fix(fix) =>
+        Item::Visit(DenullObj::Fix(fix), _) =>
           (Count::Zero, _fix(mem, fix)),
-        DenullObj::Grp(obj1) => {
-          let (_count, obj2) = _visit_obj(mem, obj1, false);
-          (Count::Zero, _grp(mem, obj2))
+        Item::Visit(DenullObj::Grp(name, obj1), _) => {
+          stack.push(Item::Grp(*name));
+          stack.push(Item::Visit(obj1, false));
+          continue
         }
-        DenullObj::Seq(obj1) =>
-          if under_seq {
-            _visit_obj(mem, obj1, true)
-          } else {
-            let (count, obj2) = _visit_obj(mem, obj1, true);
-            match count {
-              Count::Zero | Count::One => (count, obj2),
-              Count::Many => (Count::Many, _seq(mem, obj2))
-            }
+        Item::Visit(DenullObj::Seq(name, obj1), under_seq1) => {
+          if !under_seq1 { stack.push(Item::Seq(*name)) }
+          stack.push(Item::Visit(obj1, true));
+          continue
+        }
+        Item::Visit(DenullObj::Comp(left, right, pad), under_seq1) => {
+          stack.push(Item::Comp(*pad));
+          stack.push(Item::Visit(right, under_seq1));
+          stack.push(Item::Visit(left, under_seq1));
+          continue
+        }
+        Item::Grp(name) => {
+          let (_count, obj1) = _pop(&mut results);
+          (Count::Zero, _grp(mem, name, obj1))
+        }
+        Item::Seq(name) =>
+          match _pop(&mut results) {
+            (count @ (Count::Zero | Count::One), obj1) => (count, obj1),
+            (Count::Many, obj1) => (Count::Many, _seq(mem, name, obj1))
           },
-        DenullObj::Comp(left, right, pad) => {
-          let (l_count, left1) = _visit_obj(mem, left, under_seq);
-          let (r_count, right1) = _visit_obj(mem, right, under_seq);
+        Item::Comp(pad) => {
+          let (r_count, right) = _pop(&mut results);
+          let (l_count, left) = _pop(&mut results);
           let count = _add(Count::One, _add(l_count, r_count));
-          (count, _comp(mem, left1, right1, *pad))
+          (count, _comp(mem, left, right, pad))
         }
-      }
+      };
+      results.push(result)
     }
-    _visit_doc(mem, doc)
+    _pop(&mut results).1
   }
   fn _elim_grps<'b, 'a: 'b>(
     mem: &'b Bump,
-    doc: &'a DenullDoc<'a>
-  ) -> &'b DenullDoc<'b> {
-    fn _visit_doc<'b, 'a: 'b>(
-      mem: &'b Bump,
-      doc: &'a DenullDoc<'a>
-    ) -> &'b DenullDoc<'b> {
-      match doc {
-        DenullDoc::EOD =>
-          _eod(mem),
-        DenullDoc::Empty(doc1) => {
-          let doc2 = _visit_doc(mem, doc1);
-          _empty(mem, doc2)
-        }
-        DenullDoc::Break(obj, doc1) => {
-          let (_count, obj1) = _visit_obj(mem, obj, true);
-          let doc2 = _visit_doc(mem, doc1);
-          _break(mem, obj1, doc2)
-        }
-        DenullDoc::Line(obj) => {
-          let (_count, obj1) = _visit_obj(mem, obj, true);
-          _line(mem, obj1)
-        }
-      }
+    obj: &'a DenullObj<'a>,
+    in_head: bool
+  ) -> &'b DenullObj<'b> {
+    enum Item<'a> {
+      Visit(&'a DenullObj<'a>, bool),
+      Grp(Option<&'a str>),
+      Seq(Option<&'a str>),
+      Comp(bool)
     }
-    fn _visit_obj<'b, 'a: 'b>(
-      mem: &'b Bump,
-      obj: &'a DenullObj<'a>,
-      in_head: bool
-    ) -> (Count, &'b DenullObj<'b>) {
-      match obj {
-        DenullObj::Term(term) |
-        DenullObj::Fix(DenullFix::Term(term)) =>
+    let mut results = Vec::new();
+    let mut stack = vec![Item::Visit(obj, in_head)];
+    while let Some(item) = stack.pop() {
+      let result = match item {
+        Item::Visit(DenullObj::Term(term) | DenullObj::Fix(DenullFix::Term(term)), _) =>
           (Count::Zero, _term(mem, term)),
-        DenullObj::Fix(fix) =>
+        Item::Visit(DenullObj::Fix(fix), _) =>
           (Count::Zero, _fix(mem, fix)),
-        DenullObj::Grp(obj1) =>
-          if in_head  {
-            _visit_obj(mem, obj1, true)
-          } else {
-            let (count, obj2) = _visit_obj(mem, obj1, false);
-            match count {
-              Count::Zero => (Count::Zero, obj2),
-              Count::One | Count::Many => (Count::Zero, _grp(mem, obj2))
-            }
-          }
-        DenullObj::Seq(obj1) => {
-          let (count, obj2) = _visit_obj(mem, obj1, false);
-          (count, _seq(mem, obj2))
+        Item::Visit(DenullObj::Grp(name, obj1), in_head1) => {
+          if !in_head1 { stack.push(Item::Grp(*name)) }
+          stack.push(Item::Visit(obj1, in_head1));
+          continue
         }
-        DenullObj::Comp(left, right, pad) => {
-          let (l_count, left1) = _visit_obj(mem, left, in_head);
-          let (r_count, right1) = _visit_obj(mem, right, false);
+        Item::Visit(DenullObj::Seq(name, obj1), _) => {
+          stack.push(Item::Seq(*name));
+          stack.push(Item::Visit(obj1, false));
+          continue
+        }
+        Item::Visit(DenullObj::Comp(left, right, pad), in_head1) => {
+          stack.push(Item::Comp(*pad));
+          stack.push(Item::Visit(right, false));
+          stack.push(Item::Visit(left, in_head1));
+          continue
+        }
+        Item::Grp(name) =>
+          match _pop(&mut results) {
+            (Count::Zero, obj1) => (Count::Zero, obj1),
+            (Count::One | Count::Many, obj1) => (Count::Zero, _grp(mem, name, obj1))
+          },
+        Item::Seq(name) => {
+          let (count, obj1) = _pop(&mut results);
+          (count, _seq(mem, name, obj1))
+        }
+        Item::Comp(pad) => {
+          let (r_count, right) = _pop(&mut results);
+          let (l_count, left) = _pop(&mut results);
           let count = _add(Count::One, _add(l_count, r_count));
-          (count, _comp(mem, left1, right1, *pad))
+          (count, _comp(mem, left, right, pad))
         }
-      }
+      };
+      results.push(result)
     }
-    _visit_doc(mem, doc)
+    _pop(&mut results).1
   }
-  let doc1 = _elim_seqs(mem, doc);
-  _elim_grps(mem, doc1)
+  let doc1 = _visit_doc(mem, doc, false, _elim_seqs);
+  _visit_doc(mem, doc1, true, _elim_grps)
 }
 
 /*
   Reassociate after grp and seq removals
 */
-fn _reassociate<'b, 'a: 'b>(
+pub(crate) fn _reassociate<'b, 'a: 'b>(
   mem: &'b Bump,
   doc: &'a DenullDoc<'a>
 ) -> &'b DenullDoc<'b> {
@@ -2841,15 +4038,17 @@ fn _reassociate<'b, 'a: 'b>(
   }
   fn _grp<'a>(
     mem: &'a Bump,
+    name: Option<&'a str>,
     obj: &'a DenullObj<'a>
   ) -> &'a DenullObj<'a> {
-    mem.alloc(DenullObj::Grp(obj))
+    mem.alloc(DenullObj::Grp(name, obj))
   }
   fn _seq<'a>(
     mem: &'a Bump,
+    name: Option<&'a str>,
     obj: &'a DenullObj<'a>
   ) -> &'a DenullObj<'a> {
-    mem.alloc(DenullObj::Seq(obj))
+    mem.alloc(DenullObj::Seq(name, obj))
   }
   fn _comp<'a>(
     mem: &'a Bump,
@@ -2859,70 +4058,100 @@ fn _reassociate<'b, 'a: 'b>(
   ) -> &'a DenullObj<'a> {
     mem.alloc(DenullObj::Comp(left, right, pad))
   }
-  fn __comp<'a>(
-    mem: &'a Bump,
-    pad: bool,
-    right: &'a DenullObj<'a>,
-    left: &'a DenullObj<'a>
-  ) -> &'a DenullObj<'a> {
-    _comp(mem, left, right, pad)
-  }
-  fn _visit_doc<'b, 'a: 'b>(
+  /*
+    The objects of a line are visited in order with an explicit stack rather
+    than by recursion, such that long or deeply nested lines do not overflow
+    the call stack; the items of the compositions under a grp or seq, or at
+    the top of the line, are kept with the paddings between them on a chain
+    of their own, and are composed to the right once the chain is complete.
+  */
+  fn _visit_obj<'b, 'a: 'b>(
     mem: &'b Bump,
-    doc: &'a DenullDoc<'a>
-  ) -> &'b DenullDoc<'b> {
-    match doc {
-      DenullDoc::EOD =>
-        _eod(mem),
-      DenullDoc::Empty(doc1) => {
-        let doc2 = _visit_doc(mem, doc1);
-        _empty(mem, doc2)
-      }
-      DenullDoc::Break(obj, doc1) => {
-        let partial = mem.alloc(move |_mem, obj1| obj1);
-        _visit_obj(mem, obj, partial, mem.alloc(move |mem, obj2| {
-        let doc2 = _visit_doc(mem, doc1);
-        _break(mem, obj2, doc2)}))
-      }
-      DenullDoc::Line(obj) => {
-        let partial = mem.alloc(|_mem, obj1| obj1);
-        _visit_obj(mem, obj, partial, mem.alloc(|mem, obj2|
-        _line(mem, obj2)))
+    obj: &'a DenullObj<'a>
+  ) -> &'b DenullObj<'b> {
+    enum Item<'a> {
+      Visit(&'a DenullObj<'a>),
+      Pad(bool),
+      Grp(Option<&'a str>),
+      Seq(Option<&'a str>)
+    }
+    fn _chain<'a>(
+      mem: &'a Bump,
+      chain: (Vec<&'a DenullObj<'a>>, Vec<bool>)
+    ) -> &'a DenullObj<'a> {
+      let (mut objs, pads) = chain;
+      let last = objs.pop().unwrap_or_else(|| invariant!());
+      objs.into_iter().zip(pads).rev().fold(last, |right, (left, pad)|
+        _comp(mem, left, right, pad))
+    }
+    let mut chains = vec![(Vec::new(), Vec::new())];
+    let mut stack = vec![Item::Visit(obj)];
+    while let Some(item) = stack.pop() {
+      let result = match item {
+        Item::Visit(DenullObj::Term(term)) => _term(mem, term),
+        Item::Visit(DenullObj::Fix(fix)) => _fix(mem, fix),
+        Item::Visit(DenullObj::Grp(name, obj1)) => {
+          chains.push((Vec::new(), Vec::new()));
+          stack.push(Item::Grp(*name));
+          stack.push(Item::Visit(obj1));
+          continue
+        }
+        Item::Visit(DenullObj::Seq(name, obj1)) => {
+          chains.push((Vec::new(), Vec::new()));
+          stack.push(Item::Seq(*name));
+          stack.push(Item::Visit(obj1));
+          continue
+        }
+        Item::Visit(DenullObj::Comp(left, right, pad)) => {
+          stack.push(Item::Visit(right));
+          stack.push(Item::Pad(*pad));
+          stack.push(Item::Visit(left));
+          continue
+        }
+        Item::Pad(pad) => {
+          match chains.last_mut() {
+            Some((_objs, pads)) => pads.push(pad),
+            None => invariant!(pad)
+          }
+          continue
+        }
+        Item::Grp(name) => {
+          let chain = chains.pop().unwrap_or_else(|| invariant!(name));
+          _grp(mem, name, _chain(mem, chain))
+        }
+        Item::Seq(name) => {
+          let chain = chains.pop().unwrap_or_else(|| invariant!(name));
+          _seq(mem, name, _chain(mem, chain))
+        }
+      };
+      match chains.last_mut() {
+        Some((objs, _pads)) => objs.push(result),
+        None => invariant!(result)
       }
     }
+    match (chains.pop(), chains.is_empty()) {
+      (Some(chain), true) => _chain(mem, chain),
+      (_, _) => invariant!()
+    }
   }
-  fn _visit_obj<'b, 'a: 'b, R>(
-    mem: &'b Bump,
-    obj: &'a DenullObj<'a>,
-    partial: &'b dyn Fn(&'b Bump, &'b DenullObj<'b>) -> &'b DenullObj<'b>,
-    cont: &'b dyn Fn(&'b Bump, &'b DenullObj<'b>) -> R
-  ) -> R {
-    match obj {
-      DenullObj::Term(term) =>
-        cont(mem, partial(mem, _term(mem, term))),
-      DenullObj::Fix(fix) =>
-        cont(mem, partial(mem, _fix(mem, fix))),
-      DenullObj::Grp(obj1) =>
-        _visit_obj(mem, obj1, mem.alloc(|_mem, obj2| obj2),
-        compose(mem, cont, compose(mem, partial, mem.alloc(|mem, obj3|
-        _grp(mem, obj3))))),
-      DenullObj::Seq(obj1) =>
-        _visit_obj(mem, obj1, mem.alloc(|_mem, obj2| obj2),
-        compose(mem, cont, compose(mem, partial, mem.alloc(|mem, obj3|
-        _seq(mem, obj3))))),
-      DenullObj::Comp(left, right, pad) =>
-        _visit_obj(mem, right, partial, mem.alloc(move |mem, result|
-        _visit_obj(mem, left,
-          mem.alloc(move |mem, obj1| __comp(mem, *pad, result, obj1)),
-          cont
-        )))
-    }
-  }
-  _visit_doc(mem, doc)
+  let mut lines = Vec::new();
+  let mut doc = doc;
+  let last = loop {
+    match doc {
+      DenullDoc::EOD => break _eod(mem),
+      DenullDoc::Empty(doc1) => { lines.push(None); doc = doc1 }
+      DenullDoc::Break(obj, doc1) => { lines.push(Some(_visit_obj(mem, obj))); doc = doc1 }
+      DenullDoc::Line(obj) => break _line(mem, _visit_obj(mem, obj))
+    }
+  };
+  lines.into_iter().rev().fold(last, |doc1, line| match line {
+    None => _empty(mem, doc1),
+    Some(obj) => _break(mem, obj, doc1)
+  })
 }
 
 #[derive(Debug)]
-enum FinalDoc<'a> {
+pub enum FinalDoc<'a> {
   EOD,
   Empty(&'a FinalDoc<'a>),
   Break(&'a FinalDocObj<'a>, &'a FinalDoc<'a>),
@@ -2930,32 +4159,32 @@ enum FinalDoc<'a> {
 }
 
 #[derive(Debug)]
-enum FinalDocObj<'a> {
-  Text(&'a str),
+pub enum FinalDocObj<'a> {
+  Text(&'a str, TextAttr),
   Fix(&'a FinalDocObjFix<'a>),
-  Grp(&'a FinalDocObj<'a>),
-  Seq(&'a FinalDocObj<'a>),
+  Grp(Option<&'a str>, &'a FinalDocObj<'a>),
+  Seq(Option<&'a str>, &'a FinalDocObj<'a>),
   Nest(&'a FinalDocObj<'a>),
-  Pack(u64, &'a FinalDocObj<'a>),
+  Pack(Index, &'a FinalDocObj<'a>),
   Comp(&'a FinalDocObj<'a>, &'a FinalDocObj<'a>, bool)
 }
 
 #[derive(Debug)]
-enum FinalDocObjFix<'a> {
-  Text(&'a str),
+pub enum FinalDocObjFix<'a> {
+  Text(&'a str, TextAttr),
   Comp(&'a FinalDocObjFix<'a>, &'a FinalDocObjFix<'a>, bool)
 }
 
 #[derive(Debug, Copy, Clone)]
 enum Prop {
   Nest,
-  Pack(u64)
+  Pack(Index)
 }
 
 /*
   Rescope nest and pack.
 */
-fn _rescope<'b, 'a: 'b>(
+pub(crate) fn _rescope<'b, 'a: 'b>(
   mem: &'b Bump,
   doc: &'a DenullDoc<'a>
 ) -> &'b FinalDoc<'b> {
@@ -2985,9 +4214,10 @@ fn _rescope<'b, 'a: 'b>(
   }
   fn _text<'a>(
     mem: &'a Bump,
-    data: &'a str
+    data: &'a str,
+    attr: TextAttr
   ) -> &'a FinalDocObj<'a> {
-    mem.alloc(FinalDocObj::Text(data))
+    mem.alloc(FinalDocObj::Text(data, attr))
   }
   fn _fix<'a>(
     mem: &'a Bump,
@@ -2997,15 +4227,17 @@ fn _rescope<'b, 'a: 'b>(
   }
   fn _grp<'a>(
     mem: &'a Bump,
+    name: Option<&'a str>,
     obj: &'a FinalDocObj<'a>
   ) -> &'a FinalDocObj<'a> {
-    mem.alloc(FinalDocObj::Grp(obj))
+    mem.alloc(FinalDocObj::Grp(name, obj))
   }
   fn _seq<'a>(
     mem: &'a Bump,
+    name: Option<&'a str>,
     obj: &'a FinalDocObj<'a>
   ) -> &'a FinalDocObj<'a> {
-    mem.alloc(FinalDocObj::Seq(obj))
+    mem.alloc(FinalDocObj::Seq(name, obj))
   }
   fn _nest<'a>(
     mem: &'a Bump,
@@ -3015,7 +4247,7 @@ fn _rescope<'b, 'a: 'b>(
   }
   fn _pack<'a>(
     mem: &'a Bump,
-    index: u64,
+    index: Index,
     obj: &'a FinalDocObj<'a>
   ) -> &'a FinalDocObj<'a> {
     mem.alloc(FinalDocObj::Pack(index, obj))
@@ -3030,9 +4262,10 @@ fn _rescope<'b, 'a: 'b>(
   }
   fn _fix_text<'a>(
     mem: &'a Bump,
-    data: &'a str
+    data: &'a str,
+    attr: TextAttr
   ) -> &'a FinalDocObjFix<'a> {
-    mem.alloc(FinalDocObjFix::Text(data))
+    mem.alloc(FinalDocObjFix::Text(data, attr))
   }
   fn _fix_comp<'a>(
     mem: &'a Bump,
@@ -3042,9 +4275,12 @@ fn _rescope<'b, 'a: 'b>(
   ) -> &'a FinalDocObjFix<'a> {
     mem.alloc(FinalDocObjFix::Comp(left, right, pad))
   }
-  fn _prop_pack(index: u64) -> Prop {
+  fn _prop_pack(index: Index) -> Prop {
     Prop::Pack(index)
   }
+  /*
+    The props common to the start of both lists, and the rest of each list
+  */
   fn _join_props<'b, 'a: 'b>(
     mem: &'b Bump,
     l: &'a List<'a, Prop>,
@@ -3054,78 +4290,62 @@ fn _rescope<'b, 'a: 'b>(
     &'b List<'b, Prop>,
     &'b List<'b, Prop>
   ) {
-    fn _visit<'b, 'a: 'b>(
-      mem: &'b Bump,
-      l: &'a List<'a, Prop>,
-      r: &'a List<'a, Prop>,
-      c: &'a dyn Fn(&'b Bump, &'a List<'a, Prop>) -> &'a List<'a, Prop>
-    ) -> (
-      &'b List<'b, Prop>,
-      &'b List<'b, Prop>,
-      &'b List<'b, Prop>
-    ) {
+    let mut common = Vec::new();
+    let (mut l, mut r) = (l, r);
+    loop {
       match (l, r) {
         ( List::Cons(_, Prop::Nest, l1)
         , List::Cons(_, Prop::Nest, r1)) => {
-          let c1 = compose(mem, c, mem.alloc(|mem, props|
-            _list::cons(mem, Prop::Nest, props)));
-          _visit(mem, l1, r1, c1)
+          common.push(Prop::Nest);
+          (l, r) = (l1, r1)
         }
         ( List::Cons(_, Prop::Pack(l_index), l1)
-        , List::Cons(_, Prop::Pack(r_index), r1)) =>
-          if l_index != r_index {
-            (l, r, c(mem, _list::nil(mem)))
-          } else {
-            let c1 = compose(mem, c, mem.alloc(|mem, props|
-              _list::cons(mem, _prop_pack(*l_index), props)));
-            _visit(mem, l1, r1, c1)
-          }
-        (_, _) =>
-          (l, r, c(mem, _list::nil(mem)))
+        , List::Cons(_, Prop::Pack(r_index), r1)) if l_index == r_index => {
+          common.push(_prop_pack(*l_index));
+          (l, r) = (l1, r1)
+        }
+        (_, _) => return (l, r, _list::from_iter(mem, common))
       }
     }
-    _visit(mem, l, r, mem.alloc(|_mem, props| props))
   }
-  fn _apply_props<'b, 'a: 'b, R>(
+  fn _apply_props<'b, 'a: 'b>(
     mem: &'b Bump,
     props: &'a List<'a, Prop>,
-    term: &'a FinalDocObj<'a>,
-    cont: &'b dyn Fn(&'b Bump, &'b FinalDocObj<'b>) -> R
-  ) -> R {
-    match props {
-      List::Nil => cont(mem, term),
-      List::Cons(_, Prop::Nest, props1) =>
-        _apply_props(mem, props1, term, compose(mem, cont, mem.alloc(|mem, obj|
-        _nest(mem, obj)))),
-      List::Cons(_, Prop::Pack(index), props1) =>
-        _apply_props(mem, props1, term, compose(mem, cont, mem.alloc(|mem, obj|
-        _pack(mem, *index, obj))))
-    }
-  }
-  fn _visit_doc<'b, 'a: 'b>(
+    obj: &'a FinalDocObj<'a>
+  ) -> &'b FinalDocObj<'b> {
+    let props1: Vec<Prop> = props.iter().collect();
+    props1.into_iter().rev().fold(obj, |obj1, prop| match prop {
+      Prop::Nest => _nest(mem, obj1),
+      Prop::Pack(index) => _pack(mem, index, obj1)
+    })
+  }
+  /*
+    The props around a term, outermost first
+  */
+  fn _term_props<'b, 'a: 'b>(
     mem: &'b Bump,
-    doc: &'a DenullDoc<'a>
-  ) -> &'b FinalDoc<'b> {
-    match doc {
-      DenullDoc::EOD =>
-        _eod(mem),
-      DenullDoc::Empty(doc1) => {
-        let doc2 = _visit_doc(mem, doc1);
-        _empty(mem, doc2)
-      }
-      DenullDoc::Break(obj, doc1) => {
-        let (props, obj1) = _visit_obj(mem, obj);
-        _apply_props(mem, props, obj1, mem.alloc(move |mem, obj2| {
-        let doc2 = _visit_doc(mem, doc1);
-        _break(mem, obj2, doc2)}))
-      }
-      DenullDoc::Line(obj) => {
-        let (props, obj1) = _visit_obj(mem, obj);
-        _apply_props(mem, props, obj1, mem.alloc(|mem, obj2|
-        _line(mem, obj2)))
+    term: &'a DenullTerm<'a>
+  ) -> (
+    &'b List<'b, Prop>,
+    &'a str,
+    TextAttr
+  ) {
+    let mut props = Vec::new();
+    let mut term = term;
+    loop {
+      match term {
+        DenullTerm::Text(data, attr) => return (_list::from_iter(mem, props), data, *attr),
+        DenullTerm::Nest(term1) => { props.push(Prop::Nest); term = term1 }
+        DenullTerm::Pack(index, term1) => { props.push(_prop_pack(*index)); term = term1 }
       }
     }
   }
+  /*
+    The objects and fixes are visited with explicit stacks rather than by
+    recursion, such that deeply nested lines do not overflow the call stack;
+    an object is given with the props common to all of its terms, which are
+    lifted out of it.
+  */
   fn _visit_obj<'b, 'a: 'b>(
     mem: &'b Bump,
     obj: &'a DenullObj<'a>
@@ -3133,31 +4353,69 @@ fn _rescope<'b, 'a: 'b>(
     &'b List<'b, Prop>,
     &'b FinalDocObj<'b>
   ) {
-    match obj {
-      DenullObj::Term(term) =>
-        _visit_term(mem, term, mem.alloc(|_mem, props| props)),
-      DenullObj::Fix(fix) => {
-        let (props, fix1) = _visit_fix(mem, fix);
-        (props, _fix(mem, fix1))
-      }
-      DenullObj::Grp(obj1) => {
-        let (props, obj2) = _visit_obj(mem, obj1);
-        (props, _grp(mem, obj2))
-      }
-      DenullObj::Seq(obj1) => {
-        let (props, obj2) = _visit_obj(mem, obj1);
-        (props, _seq(mem, obj2))
-      }
-      DenullObj::Comp(left, right, pad) => {
-        let (l_props, left1) = _visit_obj(mem, left);
-        let (r_props, right1) = _visit_obj(mem, right);
-        let (l_props1, r_props1, c_props) = _join_props(mem, l_props, r_props);
-        _apply_props(mem, l_props1, left1, mem.alloc(move |mem, left2|
-        _apply_props(mem, r_props1, right1, mem.alloc(move |mem, right2|
-        (c_props, _comp(mem, left2, right2, *pad))))))
-      }
+    enum Item<'a> {
+      Visit(&'a DenullObj<'a>),
+      Grp(Option<&'a str>),
+      Seq(Option<&'a str>),
+      Comp(bool)
+    }
+    fn _pop<'a>(
+      results: &mut Vec<(&'a List<'a, Prop>, &'a FinalDocObj<'a>)>
+    ) -> (&'a List<'a, Prop>, &'a FinalDocObj<'a>) {
+      results.pop().unwrap_or_else(|| invariant!())
+    }
+    let mut results = Vec::new();
+    let mut stack = vec![Item::Visit(obj)];
+    while let Some(item) = stack.pop() {
+      let result = match item {
+        Item::Visit(DenullObj::Term(term)) => {
+          let (props, data, attr) = _term_props(mem, term);
+          (props, _text(mem, data, attr))
+        }
+        Item::Visit(DenullObj::Fix(fix)) => {
+          let (props, fix1) = _visit_fix(mem, fix);
+          (props, _fix(mem, fix1))
+        }
+        Item::Visit(DenullObj::Grp(name, obj1)) => {
+          stack.push(Item::Grp(*name));
+          stack.push(Item::Visit(obj1));
+          continue
+        }
+        Item::Visit(DenullObj::Seq(name, obj1)) => {
+          stack.push(Item::Seq(*name));
+          stack.push(Item::Visit(obj1));
+          continue
+        }
+        Item::Visit(DenullObj::Comp(left, right, pad)) => {
+          stack.push(Item::Comp(*pad));
+          stack.push(Item::Visit(right));
+          stack.push(Item::Visit(left));
+          continue
+        }
+        Item::Grp(name) => {
+          let (props, obj1) = _pop(&mut results);
+          (props, _grp(mem, name, obj1))
+        }
+        Item::Seq(name) => {
+          let (props, obj1) = _pop(&mut results);
+          (props, _seq(mem, name, obj1))
+        }
+        Item::Comp(pad) => {
+          let (r_props, right) = _pop(&mut results);
+          let (l_props, left) = _pop(&mut results);
+          let (l_props1, r_props1, c_props) = _join_props(mem, l_props, r_props);
+          let left1 = _apply_props(mem, l_props1, left);
+          let right1 = _apply_props(mem, r_props1, right);
+          (c_props, _comp(mem, left1, right1, pad))
+        }
+      };
+      results.push(result)
     }
+    _pop(&mut results)
   }
+  /*
+    A fix is given with the props of its leftmost term
+  */
   fn _visit_fix<'b, 'a: 'b>(
     mem: &'b Bump,
     fix: &'a DenullFix<'a>
@@ -3165,66 +4423,64 @@ fn _rescope<'b, 'a: 'b>(
     &'b List<'b, Prop>,
     &'b FinalDocObjFix<'b>
   ) {
-    match fix {
-      DenullFix::Term(term) =>
-        _visit_fix_term(mem, term, mem.alloc(|_mem, props| props)),
-      DenullFix::Comp(left, right, pad) => {
-        let (l_props, left1) = _visit_fix(mem, left);
-        let (_r_props, right1) = _visit_fix(mem, right);
-        (l_props, _fix_comp(mem, left1, right1, *pad))
-      }
+    enum Item<'a> {
+      Visit(&'a DenullFix<'a>),
+      Comp(bool)
     }
-  }
-  fn _visit_term<'b, 'a: 'b>(
-    mem: &'b Bump,
-    term: &'a DenullTerm<'a>,
-    result: &'b dyn Fn(&'b Bump, &'b List<'b, Prop>) -> &'b List<'b, Prop>
-  ) -> (
-    &'b List<'b, Prop>,
-    &'b FinalDocObj<'b>
-  ) {
-    match term {
-      DenullTerm::Text(data) =>
-        (result(mem, _list::nil(mem)), _text(mem, data)),
-      DenullTerm::Nest(term1) => {
-        let result1 = compose(mem, result, mem.alloc(|mem, props|
-          _list::cons(mem, Prop::Nest, props)));
-        _visit_term(mem, term1, result1)
-      }
-      DenullTerm::Pack (index, term1) => {
-        let result1 = compose(mem, result, mem.alloc(|mem, props|
-          _list::cons(mem, _prop_pack(*index), props)));
-        _visit_term(mem, term1, result1)
-      }
+    fn _pop<'a>(
+      results: &mut Vec<(&'a List<'a, Prop>, &'a FinalDocObjFix<'a>)>
+    ) -> (&'a List<'a, Prop>, &'a FinalDocObjFix<'a>) {
+      results.pop().unwrap_or_else(|| invariant!())
+    }
+    let mut results = Vec::new();
+    let mut stack = vec![Item::Visit(fix)];
+    while let Some(item) = stack.pop() {
+      let result = match item {
+        Item::Visit(DenullFix::Term(term)) => {
+          let (props, data, attr) = _term_props(mem, term);
+          (props, _fix_text(mem, data, attr))
+        }
+        Item::Visit(DenullFix::Comp(left, right, pad)) => {
+          stack.push(Item::Comp(*pad));
+          stack.push(Item::Visit(right));
+          stack.push(Item::Visit(left));
+          continue
+        }
+        Item::Comp(pad) => {
+          let (_r_props, right) = _pop(&mut results);
+          let (l_props, left) = _pop(&mut results);
+          (l_props, _fix_comp(mem, left, right, pad))
+        }
+      };
+      results.push(result)
     }
+    _pop(&mut results)
   }
-  fn _visit_fix_term<'b, 'a: 'b>(
-    mem: &'b Bump,
-    term: &'a DenullTerm<'a>,
-    result: &'b dyn Fn(&'b Bump, &'b List<'b, Prop>) -> &'b List<'b, Prop>
-  ) -> (
-    &'b List<'b, Prop>,
-    &'b FinalDocObjFix<'b>
-  ) {
-    match term {
-      DenullTerm::Text(data) =>
-        (result(mem, _list::nil(mem)), _fix_text(mem, data)),
-      DenullTerm::Nest(term1) => {
-        let result1 = compose(mem, result, mem.alloc(|mem, props|
-          _list::cons(mem, Prop::Nest, props)));
-        _visit_fix_term(mem, term1, result1)
+  let mut lines = Vec::new();
+  let mut doc = doc;
+  let last = loop {
+    match doc {
+      DenullDoc::EOD => break _eod(mem),
+      DenullDoc::Empty(doc1) => { lines.push(None); doc = doc1 }
+      DenullDoc::Break(obj, doc1) => {
+        let (props, obj1) = _visit_obj(mem, obj);
+        lines.push(Some(_apply_props(mem, props, obj1)));
+        doc = doc1
       }
-      DenullTerm::Pack(index, term1) => {
-        let result1 = compose(mem, result, mem.alloc(|mem, props|
-          _list::cons(mem, _prop_pack(*index), props)));
-        _visit_fix_term(mem, term1, result1)
+      DenullDoc::Line(obj) => {
+        let (props, obj1) = _visit_obj(mem, obj);
+        break _line(mem, _apply_props(mem, props, obj1))
       }
     }
-  }
-  _visit_doc(mem, doc)
+  };
+  lines.into_iter().rev().fold(last, |doc1, line| match line {
+    None => _empty(mem, doc1),
+    Some(obj) => _break(mem, obj, doc1)
+  })
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
+#[non_exhaustive]
 pub enum Doc {
   EOD,
   Empty(Box<Doc>),
@@ -3233,71 +4489,64 @@ pub enum Doc {
 }
 
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum DocObj {
-  Text(String),
+  Text(String, Option<usize>),
+  Marker(Marker),
   Fix(Box<DocObjFix>),
-  Grp(Box<DocObj>),
-  Seq(Box<DocObj>),
+  Grp(Option<String>, Box<DocObj>),
+  Seq(Option<String>, Box<DocObj>),
   Nest(Box<DocObj>),
-  Pack(u64, Box<DocObj>),
+  Pack(Index, Box<DocObj>),
+  PackAt(Index, Box<DocObj>),
   Comp(Box<DocObj>, Box<DocObj>, bool)
 }
 
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum DocObjFix {
-  Text(String),
+  Text(String, Option<usize>),
+  Marker(Marker),
   Comp(Box<DocObjFix>, Box<DocObjFix>, bool)
 }
 
 impl fmt::Display for Doc {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-    fn _print_doc(
-      doc: Box<Doc>
-    ) -> String {
-      match doc {
-        box Doc::EOD => "EOD".to_string(),
-        box Doc::Empty(doc1) => {
-          let doc_s = _print_doc(doc1);
-          format!("Empty\n{}", doc_s)
-        }
-        box Doc::Break(obj, doc1) => {
-          let obj_s = _print_obj(obj);
-          let doc1_s = _print_doc(doc1);
-          format!("Break {}\n{}", obj_s, doc1_s)
-        }
-        box Doc::Line(obj) => {
-          let obj_s = _print_obj(obj);
-          format!("Line {}", obj_s)
-        }
-      }
-    }
     fn _print_obj(
-      obj: Box<DocObj>
+      obj: &DocObj
     ) -> String {
       match obj {
-        box DocObj::Text(data) =>
+        DocObj::Text(data, None) =>
           format!("(Text \"{}\")", data),
-        box DocObj::Fix(obj1) => {
+        DocObj::Text(data, Some(width)) =>
+          format!("(Text \"{}\" {})", data, width),
+        DocObj::Marker(marker) =>
+          format!("(Marker {:?})", marker),
+        DocObj::Fix(obj1) => {
           let obj_s = _print_fix(obj1);
           format!("(Fix {})", obj_s)
         }
-        box DocObj::Grp(obj1) => {
+        DocObj::Grp(name, obj1) => {
           let obj_s = _print_obj(obj1);
-          format!("(Grp {})", obj_s)
+          format!("(Grp {}{})", _print_name(name.as_deref()), obj_s)
         }
-        box DocObj::Seq(obj1) => {
+        DocObj::Seq(name, obj1) => {
           let obj_s = _print_obj(obj1);
-          format!("(Seq {})", obj_s)
+          format!("(Seq {}{})", _print_name(name.as_deref()), obj_s)
         }
-        box DocObj::Nest(obj1) => {
+        DocObj::Nest(obj1) => {
           let obj_s = _print_obj(obj1);
           format!("(Nest {})", obj_s)
         }
-        box DocObj::Pack(index, obj1) => {
+        DocObj::Pack(index, obj1) => {
           let obj_s = _print_obj(obj1);
           format!("(Pack {} {})", index, obj_s)
         }
-        box DocObj::Comp(left, right, pad) => {
+        DocObj::PackAt(index, obj1) => {
+          let obj_s = _print_obj(obj1);
+          format!("(PackAt {} {})", index, obj_s)
+        }
+        DocObj::Comp(left, right, pad) => {
           let left_s = _print_obj(left);
           let right_s = _print_obj(right);
           format!("(Comp {} {} {})", left_s, right_s, pad)
@@ -3305,100 +4554,324 @@ impl fmt::Display for Doc {
       }
     }
     fn _print_fix(
-      obj: Box<DocObjFix>
+      obj: &DocObjFix
     ) -> String {
       match obj {
-        box DocObjFix::Text(data) =>
+        DocObjFix::Text(data, None) =>
           format!("(Text \"{}\")", data),
-        box DocObjFix::Comp(left, right, pad) => {
+        DocObjFix::Text(data, Some(width)) =>
+          format!("(Text \"{}\" {})", data, width),
+        DocObjFix::Marker(marker) =>
+          format!("(Marker {:?})", marker),
+        DocObjFix::Comp(left, right, pad) => {
           let left_s = _print_fix(left);
           let right_s = _print_fix(right);
           format!("(Comp {} {} {})", left_s, right_s, pad)
         }
       }
     }
-    write!(f, "{}", _print_doc(Box::new(self.clone())))
+    let mut doc = self;
+    loop {
+      match doc {
+        Doc::EOD => return write!(f, "EOD"),
+        Doc::Empty(doc1) => { writeln!(f, "Empty")?; doc = doc1 }
+        Doc::Break(obj, doc1) => { writeln!(f, "Break {}", _print_obj(obj))?; doc = doc1 }
+        Doc::Line(obj) => return write!(f, "Line {}", _print_obj(obj))
+      }
+    }
   }
 }
 
-fn _move_to_heap<'a>(
-  doc: &'a FinalDoc<'a>
-) -> Box<Doc> {
-  fn _visit_doc<'a>(
-    doc: &'a FinalDoc<'a>
-  ) -> Box<Doc> {
-    match doc {
-      FinalDoc::EOD => Box::new(Doc::EOD),
-      FinalDoc::Empty(doc1) => {
-        let doc2 = _visit_doc(doc1);
-        Box::new(Doc::Empty(doc2))
+/* Clone the lines of a document with a loop, such that cloning a long document does not overflow the call stack */
+impl Clone for Doc {
+  fn clone(&self) -> Self {
+    let mut lines = Vec::new();
+    let mut doc = self;
+    let last = loop {
+      match doc {
+        Doc::EOD => break Doc::EOD,
+        Doc::Empty(doc1) => { lines.push(None); doc = doc1 }
+        Doc::Break(obj, doc1) => { lines.push(Some(obj.clone())); doc = doc1 }
+        Doc::Line(obj) => break Doc::Line(obj.clone())
       }
-      FinalDoc::Break(obj, doc1) => {
-        let obj1 = _visit_obj(obj);
-        let doc2 = _visit_doc(doc1);
-        Box::new(Doc::Break(obj1, doc2))
+    };
+    lines.into_iter().rev().fold(last, |doc1, line| match line {
+      None => Doc::Empty(Box::new(doc1)),
+      Some(obj) => Doc::Break(obj, Box::new(doc1))
+    })
+  }
+}
+
+/*
+  Drop the documents, objects and fixed objects under a document with explicit
+  stacks rather than by recursion, such that dropping a long or deeply nested
+  document does not overflow the call stack; the children are moved out of
+  their boxes, which are left holding an empty document or an empty text.
+*/
+impl Drop for Doc {
+  fn drop(&mut self) {
+    fn _take(
+      doc: &mut Doc,
+      stack: &mut Vec<Doc>
+    ) {
+      match doc {
+        Doc::EOD | Doc::Line(_) => (),
+        Doc::Empty(doc1) | Doc::Break(_, doc1) =>
+          stack.push(std::mem::replace(&mut **doc1, Doc::EOD))
       }
-      FinalDoc::Line(obj) => {
-        let obj1 = _visit_obj(obj);
-        Box::new(Doc::Line(obj1))
+    }
+    let mut stack = Vec::new();
+    _take(self, &mut stack);
+    while let Some(mut doc) = stack.pop() {
+      _take(&mut doc, &mut stack)
+    }
+  }
+}
+
+impl Drop for DocObj {
+  fn drop(&mut self) {
+    fn _take(
+      obj: &mut DocObj,
+      stack: &mut Vec<DocObj>
+    ) {
+      match obj {
+        DocObj::Text(_, _) | DocObj::Marker(_) | DocObj::Fix(_) => (),
+        DocObj::Grp(_, obj1) | DocObj::Seq(_, obj1) |
+        DocObj::Nest(obj1) | DocObj::Pack(_, obj1) | DocObj::PackAt(_, obj1) =>
+          stack.push(std::mem::replace(&mut **obj1, DocObj::Text(String::new(), None))),
+        DocObj::Comp(left, right, _) => {
+          stack.push(std::mem::replace(&mut **left, DocObj::Text(String::new(), None)));
+          stack.push(std::mem::replace(&mut **right, DocObj::Text(String::new(), None)))
+        }
+      }
+    }
+    let mut stack = Vec::new();
+    _take(self, &mut stack);
+    while let Some(mut obj) = stack.pop() {
+      _take(&mut obj, &mut stack)
+    }
+  }
+}
+
+impl Drop for DocObjFix {
+  fn drop(&mut self) {
+    fn _take(
+      fix: &mut DocObjFix,
+      stack: &mut Vec<DocObjFix>
+    ) {
+      match fix {
+        DocObjFix::Text(_, _) | DocObjFix::Marker(_) => (),
+        DocObjFix::Comp(left, right, _) => {
+          stack.push(std::mem::replace(&mut **left, DocObjFix::Text(String::new(), None)));
+          stack.push(std::mem::replace(&mut **right, DocObjFix::Text(String::new(), None)))
+        }
       }
     }
+    let mut stack = Vec::new();
+    _take(self, &mut stack);
+    while let Some(mut fix) = stack.pop() {
+      _take(&mut fix, &mut stack)
+    }
   }
+}
+
+/*
+  Move a child out of a document, object or fixed object that is taken apart,
+  leaving an empty document or an empty text in its place, as the children of
+  a type with a destructor can not be moved out by a pattern
+*/
+fn _take_doc(
+  doc: &mut Box<Doc>
+) -> Box<Doc> {
+  std::mem::replace(doc, Box::new(Doc::EOD))
+}
+
+fn _take_obj(
+  obj: &mut Box<DocObj>
+) -> Box<DocObj> {
+  std::mem::replace(obj, Box::new(DocObj::Text(String::new(), None)))
+}
+
+fn _take_fix(
+  fix: &mut Box<DocObjFix>
+) -> Box<DocObjFix> {
+  std::mem::replace(fix, Box::new(DocObjFix::Text(String::new(), None)))
+}
+
+pub(crate) fn _move_to_heap<'a>(
+  doc: &'a FinalDoc<'a>,
+  markers: &[LeafMarkers],
+  anchored: &BTreeSet<Index>
+) -> Box<Doc> {
+  fn _marked(
+    data: &str,
+    width: Option<usize>,
+    leaf: &LeafMarkers
+  ) -> Box<DocObjFix> {
+    let text = Box::new(DocObjFix::Text(data.to_string(), width));
+    leaf.open.iter()
+      .map(|marker| Box::new(DocObjFix::Marker(marker.clone())))
+      .chain(std::iter::once(text))
+      .chain(leaf.close.iter().map(|marker| Box::new(DocObjFix::Marker(marker.clone()))))
+      .rev()
+      .reduce(|right, left| Box::new(DocObjFix::Comp(left, right, false)))
+      .expect("A marked text has at least one item")
+  }
+  /*
+    The objects and fixes are visited with explicit stacks rather than by
+    recursion, such that deeply nested lines do not overflow the call stack
+  */
   fn _visit_obj<'a>(
-    obj: &'a FinalDocObj<'a>
+    obj: &'a FinalDocObj<'a>,
+    markers: &[LeafMarkers],
+    anchored: &BTreeSet<Index>
   ) -> Box<DocObj> {
-    match obj {
-      FinalDocObj::Text(data) =>
-        Box::new(DocObj::Text(data.to_string())),
-      FinalDocObj::Fix(fix) => {
-        let fix1 = _visit_fix(fix);
-        Box::new(DocObj::Fix(fix1))
-      }
-      FinalDocObj::Grp(obj1) => {
-        let obj2 = _visit_obj(obj1);
-        Box::new(DocObj::Grp(obj2))
-      }
-      FinalDocObj::Seq(obj1) => {
-        let obj2 = _visit_obj(obj1);
-        Box::new(DocObj::Seq(obj2))
-      }
-      FinalDocObj::Nest(obj1) => {
-        let obj2 = _visit_obj(obj1);
-        Box::new(DocObj::Nest(obj2))
-      }
-      FinalDocObj::Pack(index, obj1) => {
-        let obj2 = _visit_obj(obj1);
-        Box::new(DocObj::Pack(*index, obj2))
-      }
-      FinalDocObj::Comp(left, right, pad) => {
-        let left1 = _visit_obj(left);
-        let right1 = _visit_obj(right);
-        Box::new(DocObj::Comp(left1, right1, *pad))
-      }
+    enum Item<'a> {
+      Visit(&'a FinalDocObj<'a>),
+      Grp(Option<&'a str>),
+      Seq(Option<&'a str>),
+      Nest,
+      Pack(Index),
+      Comp(bool)
+    }
+    fn _pop(
+      results: &mut Vec<DocObj>
+    ) -> Box<DocObj> {
+      Box::new(results.pop().unwrap_or_else(|| invariant!()))
+    }
+    let mut results = Vec::new();
+    let mut stack = vec![Item::Visit(obj)];
+    while let Some(item) = stack.pop() {
+      let result = match item {
+        Item::Visit(FinalDocObj::Text(data, attr)) => match attr._markers() {
+          None => DocObj::Text(data.to_string(), attr._width()),
+          Some(index) => DocObj::Fix(_marked(data, attr._width(), &markers[index]))
+        },
+        Item::Visit(FinalDocObj::Fix(fix)) =>
+          DocObj::Fix(_visit_fix(fix, markers)),
+        Item::Visit(FinalDocObj::Grp(name, obj1)) => {
+          stack.push(Item::Grp(*name));
+          stack.push(Item::Visit(obj1));
+          continue
+        }
+        Item::Visit(FinalDocObj::Seq(name, obj1)) => {
+          stack.push(Item::Seq(*name));
+          stack.push(Item::Visit(obj1));
+          continue
+        }
+        Item::Visit(FinalDocObj::Nest(obj1)) => {
+          stack.push(Item::Nest);
+          stack.push(Item::Visit(obj1));
+          continue
+        }
+        Item::Visit(FinalDocObj::Pack(index, obj1)) => {
+          stack.push(Item::Pack(*index));
+          stack.push(Item::Visit(obj1));
+          continue
+        }
+        Item::Visit(FinalDocObj::Comp(left, right, pad)) => {
+          stack.push(Item::Comp(*pad));
+          stack.push(Item::Visit(right));
+          stack.push(Item::Visit(left));
+          continue
+        }
+        Item::Grp(name) =>
+          DocObj::Grp(name.map(str::to_string), _pop(&mut results)),
+        Item::Seq(name) =>
+          DocObj::Seq(name.map(str::to_string), _pop(&mut results)),
+        Item::Nest =>
+          DocObj::Nest(_pop(&mut results)),
+        Item::Pack(index) =>
+          if anchored.contains(&index) {
+            DocObj::PackAt(index, _pop(&mut results))
+          } else {
+            DocObj::Pack(index, _pop(&mut results))
+          },
+        Item::Comp(pad) => {
+          let right = _pop(&mut results);
+          let left = _pop(&mut results);
+          DocObj::Comp(left, right, pad)
+        }
+      };
+      results.push(result)
     }
+    _pop(&mut results)
   }
   fn _visit_fix<'a>(
-    fix: &'a FinalDocObjFix<'a>
+    fix: &'a FinalDocObjFix<'a>,
+    markers: &[LeafMarkers]
   ) -> Box<DocObjFix> {
-    match fix {
-      FinalDocObjFix::Text(data) =>
-        Box::new(DocObjFix::Text(data.to_string())),
-      FinalDocObjFix::Comp(left, right, pad) => {
-        let left1 = _visit_fix(left);
-        let right1 = _visit_fix(right);
-        Box::new(DocObjFix::Comp(left1, right1, *pad))
-      }
+    enum Item<'a> {
+      Visit(&'a FinalDocObjFix<'a>),
+      Comp(bool)
+    }
+    fn _pop(
+      results: &mut Vec<DocObjFix>
+    ) -> Box<DocObjFix> {
+      Box::new(results.pop().unwrap_or_else(|| invariant!()))
+    }
+    let mut results = Vec::new();
+    let mut stack = vec![Item::Visit(fix)];
+    while let Some(item) = stack.pop() {
+      let result = match item {
+        Item::Visit(FinalDocObjFix::Text(data, attr)) => match attr._markers() {
+          None => DocObjFix::Text(data.to_string(), attr._width()),
+          Some(index) => *_marked(data, attr._width(), &markers[index])
+        },
+        Item::Visit(FinalDocObjFix::Comp(left, right, pad)) => {
+          stack.push(Item::Comp(*pad));
+          stack.push(Item::Visit(right));
+          stack.push(Item::Visit(left));
+          continue
+        }
+        Item::Comp(pad) => {
+          let right = _pop(&mut results);
+          let left = _pop(&mut results);
+          DocObjFix::Comp(left, right, pad)
+        }
+      };
+      results.push(result)
     }
+    _pop(&mut results)
   }
-  _visit_doc(doc)
+  let mut lines = Vec::new();
+  let mut doc = doc;
+  let last = loop {
+    match doc {
+      FinalDoc::EOD => break Box::new(Doc::EOD),
+      FinalDoc::Empty(doc1) => { lines.push(None); doc = doc1 }
+      FinalDoc::Break(obj, doc1) => {
+        lines.push(Some(_visit_obj(obj, markers, anchored)));
+        doc = doc1
+      }
+      FinalDoc::Line(obj) => break Box::new(Doc::Line(_visit_obj(obj, markers, anchored)))
+    }
+  };
+  lines.into_iter().rev().fold(last, |doc1, line| match line {
+    None => Box::new(Doc::Empty(doc1)),
+    Some(obj) => Box::new(Doc::Break(obj, doc1))
+  })
 }
 
 /// A function for compiling layouts into documents optimized for rendering, takes a `Box<Layout>` and gives a `Box<Doc>`.
 ///
+/// Layouts of only texts under fixed compositions are compiled directly to a single line, without running the compiler passes.
+/// It panics on the errors that `compile_safe` gives as a `CompilerError`, i.e. an unbound anchor, running out of memory or overflowing an index; with the `deny-panics` feature this function is deprecated, such that applications are pointed to `compile_safe`.
+///
 /// # Examples
 /// ```
 /// use typeset::{text, comp, compile};
 ///
+/// let fixed = comp(
+///   text("foo".to_string()),
+///   text("bar".to_string()),
+///   true, true
+/// );
+/// assert_eq!(
+///   format!("{}", compile(fixed)),
+///   "Line (Fix (Comp (Text \"foo\") (Text \"bar\") true))"
+/// );
+///
 /// let layout = comp(
 ///   text("foo".to_string()),
 ///   text("bar".to_string()),
@@ -3406,72 +4879,688 @@ fn _move_to_heap<'a>(
 /// );
 /// let document = compile(layout);
 /// ```
+#[must_use]
+#[cfg_attr(feature = "deny-panics", deprecated(note = "compile panics on compile errors, use compile_safe instead"))]
 pub fn compile(
   layout: Box<Layout>
 ) -> Box<Doc> {
-  let mem = Bump::new();
-  let layout1 = _broken(&mem, layout);
-  let layout2 = _serialize(&mem, layout1);
-  let doc = _linearize(&mem, layout2);
-  let doc1 = _fixed(&mem, doc);
-  let doc2 = _structurize(&mem, doc1);
-  let doc3 = _denull(&mem, doc2);
-  let doc4 = _identities(&mem, doc3);
-  let doc5 = _reassociate(&mem, doc4);
-  let doc6 = _rescope(&mem, doc5);
-  _move_to_heap(doc6)
+  _compile_default(layout)
+}
+
+pub(crate) fn _compile_default(
+  layout: Box<Layout>
+) -> Box<Doc> {
+  _compile(layout, &CompileOptions::default())
+    .expect("Compiling with the default options only fails on unbound anchors, running out of memory or overflowing an index")
+}
+
+/// A function for compiling layouts that catches panics of the compiler, takes a `Box<Layout>` and gives either a `Box<Doc>` or a `CompilerError`.
+///
+/// The compiler is not expected to panic, but if it does, e.g. due to a bug violating an internal invariant, the panic is caught and returned as a `CompilerError::InternalPanic` with the panic message, instead of unwinding into the caller.
+/// Note that the panic hook is still invoked, and that aborts, such as stack overflows, can not be caught.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, compile_catch, render};
+///
+/// let layout = comp(
+///   text("foo".to_string()),
+///   text("bar".to_string()),
+///   true, false
+/// );
+/// let document = compile_catch(layout).unwrap();
+/// assert_eq!(render(document, 2, 80), "foo bar");
+/// ```
+pub fn compile_catch(
+  layout: Box<Layout>
+) -> Result<Box<Doc>, CompilerError> {
+  panic::catch_unwind(AssertUnwindSafe(|| _compile_default(layout)))
+    .map_err(CompilerError::from_panic)
+}
+
+/// A function for compiling layouts with the default compile options, takes a `Box<Layout>` and gives either a `Box<Doc>` or a `CompilerError`.
+///
+/// The panics of running out of memory and of overflowing the scope or pack indices are caught and given as a `CompilerError::AllocationFailed` and a `CompilerError::IndexOverflow`, and a `pack_at` without an enclosing anchor gives a `CompilerError::UnboundAnchor`.
+/// Any other panic of the compiler, e.g. due to a bug violating an internal invariant, unwinds into the caller; see `compile_catch` for catching those as well.
+///
+/// Layouts without any text, i.e. nulls, empty texts and any scopes or compositions of them, compile to the empty document, which renders as the empty string; whitespace-only text is rendered as is, and forced linebreaks are kept even between empty layouts.
+///
+/// # Examples
+/// ```
+/// use typeset::{null, text, grp, nest, line, comp, compile_safe, render};
+///
+/// let empty = grp(nest(comp(null(), text("".to_string()), true, false)));
+/// assert_eq!(render(compile_safe(empty).unwrap(), 2, 80), "");
+///
+/// let blank = text("  ".to_string());
+/// assert_eq!(render(compile_safe(blank).unwrap(), 2, 80), "  ");
+///
+/// let breaks = line(null(), null());
+/// assert_eq!(render(compile_safe(breaks).unwrap(), 2, 80), "\n");
+/// ```
+pub fn compile_safe(
+  layout: Box<Layout>
+) -> Result<Box<Doc>, CompilerError> {
+  compile_with(layout, &CompileOptions::default())
+}
+
+/// The policy for text containing newlines, which would otherwise break the width computations of the renderer.
+///
+/// A text can either be rejected with a `CompilerError::NewlineInText`, be split into lines joined by forced linebreaks, or have its newlines escaped as `\\n` and `\\r`; a `\r\n` pair counts as a single newline when splitting.
+/// The default policy is to split the text into lines.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum NewlinePolicy {
+  Reject,
+  #[default]
+  SplitIntoLines,
+  Escape
+}
+
+/// Limits on the size of layouts to compile, the number of layout nodes, the total number of bytes of text, the depth of the layout tree, and the number of bytes allocated by the compiler; each limit is disabled when `None`.
+///
+/// The limits on the layout are checked by a walk over the layout before any pass is run, so that layouts from untrusted input can be rejected before they are compiled.
+/// The limit on allocated bytes is a soft one, checked between the passes of the compiler, so a single pass can allocate past it before the compilation is stopped.
+/// The default limits are all disabled.
+/// New limits may be added in minor releases, disabled by default, so the limits are built from `CompileLimits::default()` with struct update syntax, as below.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, nest, compile_with, CompileLimits, CompileOptions, CompilerError};
+///
+/// let layout = nest(nest(comp(
+///   text("foo".to_string()),
+///   text("bar".to_string()),
+///   true, false
+/// )));
+/// let limits = CompileLimits { max_depth: Some(3), ..CompileLimits::default() };
+/// let options = CompileOptions { limits: limits, ..CompileOptions::default() };
+/// assert_eq!(compile_with(layout.clone(), &options).unwrap_err(), CompilerError::TooDeep(3));
+///
+/// let limits = CompileLimits { max_nodes: Some(4), ..CompileLimits::default() };
+/// let options = CompileOptions { limits: limits, ..CompileOptions::default() };
+/// assert_eq!(compile_with(layout.clone(), &options).unwrap_err(), CompilerError::TooManyNodes(4));
+///
+/// let limits = CompileLimits { max_text_bytes: Some(6), ..CompileLimits::default() };
+/// let options = CompileOptions { limits: limits, ..CompileOptions::default() };
+/// assert!(compile_with(layout, &options).is_ok());
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CompileLimits {
+  pub max_nodes: Option<usize>,
+  pub max_text_bytes: Option<usize>,
+  pub max_depth: Option<usize>,
+  pub max_allocated_bytes: Option<usize>
+}
+
+impl Default for CompileLimits {
+  fn default() -> Self {
+    CompileLimits {
+      max_nodes: DEFAULT_MAX_NODES,
+      max_text_bytes: DEFAULT_MAX_TEXT_BYTES,
+      max_depth: DEFAULT_MAX_DEPTH,
+      max_allocated_bytes: DEFAULT_MAX_ALLOCATED_BYTES
+    }
+  }
+}
+
+fn _check_limits<'s, S: LayoutSource<'s>>(
+  layout: S,
+  limits: CompileLimits
+) -> Result<(), CompilerError> {
+  let CompileLimits { max_nodes, max_text_bytes, max_depth, .. } = limits;
+  if max_nodes.is_none() && max_text_bytes.is_none() && max_depth.is_none() {
+    return Ok(())
+  }
+  let mut nodes = 0;
+  let mut text_bytes = 0;
+  let mut stack = vec![(layout, 1)];
+  while let Some((layout, depth)) = stack.pop() {
+    nodes += 1;
+    if let Some(limit) = max_nodes.filter(|limit| *limit < nodes) {
+      return Err(CompilerError::TooManyNodes(limit))
+    }
+    if let Some(limit) = max_depth.filter(|limit| *limit < depth) {
+      return Err(CompilerError::TooDeep(limit))
+    }
+    match layout.view() {
+      LayoutView::Null | LayoutView::PackReset | LayoutView::Slot(_) => (),
+      LayoutView::Text(data, _) => {
+        text_bytes += data.len();
+        if let Some(limit) = max_text_bytes.filter(|limit| *limit < text_bytes) {
+          return Err(CompilerError::TextTooLarge(limit))
+        }
+      }
+      LayoutView::Annotate(_, layout1) | LayoutView::Fix(layout1) |
+      LayoutView::Grp(_, layout1) | LayoutView::Seq(_, layout1) |
+      LayoutView::Nest(layout1) | LayoutView::Pack(layout1) |
+      LayoutView::PackGroup(_, layout1) | LayoutView::PackAt(_, layout1) =>
+        stack.push((layout1, depth + 1)),
+      LayoutView::Line(left, right) | LayoutView::Comp(left, right, _) => {
+        stack.push((right, depth + 1));
+        stack.push((left, depth + 1))
+      }
+    }
+  }
+  Ok(())
+}
+
+/// Options for compiling layouts, the newline policy for text, whether to reject text containing tabs, the optional passes to run, the limits on the size of layouts, and whether to minify the document.
+///
+/// Tabs are otherwise kept in the text, and measured by the renderer according to the `tabs` render option; rejected text gives a `CompilerError::TabInText`.
+///
+/// A minified document is compiled without the metadata of the layout, for rendering services that only render plain text: the names of groups and seqs, the links and tags, and the slots, which can then not be filled.
+/// It renders the same as the full document with `render`, since what remains is what decides the output: the break hints of groups, the soft hyphens, and the pack resets; page breaks are only added after compilation, see `Doc::append_page`.
+/// Minifying is a runtime option, so the document keeps its representation: groups and seqs keep their name fields, as `None`, and take the same memory as in a document compiled from a layout without names; what is saved are the strings and markers of the metadata.
+///
+/// Options may be added in minor releases, with defaults that compile as before; set the options you need and take the rest from `..CompileOptions::default()`.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, grp_named, tag, slot, compile_with, render, CompileOptions, CompilerError, NewlinePolicy};
+///
+/// let options = CompileOptions {
+///   newlines: NewlinePolicy::Reject,
+///   reject_tabs: true,
+///   ..CompileOptions::default()
+/// };
+/// let error = compile_with(text("\tfoo".to_string()), &options).unwrap_err();
+/// assert_eq!(error, CompilerError::TabInText("\tfoo".to_string()));
+///
+/// let layout = comp(text("{"), grp_named("decl", comp(
+///   tag("keyword", text("let")),
+///   comp(slot("note"), text("x"), false, false),
+///   true, false
+/// )), true, false);
+/// let full = compile_with(layout.clone(), &CompileOptions::default()).unwrap();
+/// let options = CompileOptions { minified: true, ..CompileOptions::default() };
+/// let minified = compile_with(layout, &options).unwrap();
+/// assert_eq!(render(minified.clone(), 2, 80), render(full.clone(), 2, 80));
+/// for metadata in ["decl", "keyword", "note"] {
+///   assert!(format!("{:?}", full).contains(metadata));
+///   assert!(!format!("{:?}", minified).contains(metadata));
+/// }
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct CompileOptions {
+  pub newlines: NewlinePolicy,
+  pub reject_tabs: bool,
+  pub passes: PassMask,
+  pub limits: CompileLimits,
+  pub minified: bool
+}
+
+/// A function for compiling layouts with compile options, takes a `Box<Layout>` and the options, and gives either a `Box<Doc>` or a `CompilerError`.
+///
+/// Like `compile_safe`, panics of the compiler are caught and returned as errors.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, compile_with, render, CompileOptions, CompilerError, NewlinePolicy};
+///
+/// let layout = text("foo\nbar".to_string());
+/// let split = compile_with(layout.clone(), &CompileOptions::default()).unwrap();
+/// assert_eq!(render(split, 2, 80), "foo\nbar");
+///
+/// let options = CompileOptions { newlines: NewlinePolicy::Escape, ..CompileOptions::default() };
+/// let escaped = compile_with(layout.clone(), &options).unwrap();
+/// assert_eq!(render(escaped, 2, 80), "foo\\nbar");
+///
+/// let options = CompileOptions { newlines: NewlinePolicy::Reject, ..CompileOptions::default() };
+/// let error = compile_with(layout, &options).unwrap_err();
+/// assert_eq!(error, CompilerError::NewlineInText("foo\nbar".to_string()));
+/// ```
+pub fn compile_with(
+  layout: Box<Layout>,
+  options: &CompileOptions
+) -> Result<Box<Doc>, CompilerError> {
+  panic::catch_unwind(AssertUnwindSafe(|| _compile(layout, options)))
+    .map_err(CompilerError::from_panic)?
+}
+
+/// A function for compiling layouts with a soft limit on the number of bytes allocated by the compiler, takes a `Box<Layout>` and the limit, and gives either a `Box<Doc>` or a `CompilerError`.
+///
+/// The allocated bytes are checked between the passes of the compiler, see `CompileLimits`; when the limit is exceeded, or an allocation of the compiler fails, a `CompilerError::AllocationFailed` is given with the number of bytes allocated so far.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, compile_with_memory_limit, render, CompilerError};
+///
+/// let layout = comp(
+///   text("foo".to_string()),
+///   text("bar".to_string()),
+///   true, false
+/// );
+/// let document = compile_with_memory_limit(layout.clone(), 1 << 20).unwrap();
+/// assert_eq!(render(document, 2, 80), "foo bar");
+///
+/// let error = compile_with_memory_limit(layout, 16).unwrap_err();
+/// assert!(matches!(error, CompilerError::AllocationFailed(allocated) if 16 < allocated));
+/// ```
+pub fn compile_with_memory_limit(
+  layout: Box<Layout>,
+  bytes: usize
+) -> Result<Box<Doc>, CompilerError> {
+  let limits = CompileLimits { max_allocated_bytes: Some(bytes), ..CompileLimits::default() };
+  compile_with(layout, &CompileOptions { limits, ..CompileOptions::default() })
+}
+
+/// The optional optimization passes of the compiler, each field tells whether the pass is run.
+///
+/// The reassociate pass rebalances compositions; skipping it changes the shape of the compiled document, but should never change how it renders, which makes it useful for bisecting a rendering regression.
+/// All other passes are semantically required and always run: null removal can not be skipped, as the later passes have no representation of null layouts, and the removal of `grp` and `seq` identities decides which scopes take effect, and as such changes the rendering when skipped.
+/// Optional passes may be added in minor releases, so a mask is built from `PassMask::all()` or `PassMask::none()` rather than by listing every field.
+///
+/// # Examples
+/// ```
+/// use typeset::PassMask;
+///
+/// let passes = PassMask { reassociate: false, ..PassMask::all() };
+/// assert_eq!(passes, PassMask::none());
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PassMask {
+  pub reassociate: bool
+}
+
+impl PassMask {
+  /// Enables all of the optional passes, as done by `compile`.
+  pub fn all() -> Self {
+    PassMask { reassociate: true }
+  }
+
+  /// Disables all of the optional passes, leaving only the required ones.
+  pub fn none() -> Self {
+    PassMask { reassociate: false }
+  }
+}
+
+impl Default for PassMask {
+  fn default() -> Self {
+    PassMask::all()
+  }
+}
+
+/// A function for compiling layouts with some of the optional passes disabled, takes a `Box<Layout>` and a `PassMask`, and gives a `Box<Doc>`.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, compile, compile_with_passes, render, PassMask};
+///
+/// let layout = comp(
+///   text("foo".to_string()),
+///   text("bar".to_string()),
+///   true, false
+/// );
+/// let document = compile(layout.clone());
+/// let unoptimized = compile_with_passes(layout, PassMask::none());
+/// assert_eq!(render(document, 2, 80), render(unoptimized, 2, 80));
+/// ```
+pub fn compile_with_passes(
+  layout: Box<Layout>,
+  passes: PassMask
+) -> Box<Doc> {
+  let options = CompileOptions { passes, ..CompileOptions::default() };
+  _compile(layout, &options)
+    .expect("Compiling with the default options only fails on unbound anchors, running out of memory or overflowing an index")
+}
+
+/// The trace of a compilation, the debug dumps of the intermediate representations given by each pass in order, and the compiled document.
+#[derive(Debug, Clone)]
+pub struct CompileTrace {
+  pub passes: Vec<(&'static str, String)>,
+  pub doc: Box<Doc>
+}
+
+impl fmt::Display for CompileTrace {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    for (pass, dump) in &self.passes {
+      writeln!(f, "{}: {}", pass, dump)?
+    }
+    write!(f, "{}", self.doc)
+  }
+}
+
+/// A function for compiling layouts while tracing the passes of the compiler, takes a `Box<Layout>` and compile options, and gives either a `CompileTrace` or a `CompilerError`.
+///
+/// Like `compile_with`, panics of the compiler are caught and returned as errors.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, compile_trace, render, CompileOptions};
+///
+/// let layout = comp(
+///   text("foo".to_string()),
+///   text("bar".to_string()),
+///   true, false
+/// );
+/// let trace = compile_trace(layout, &CompileOptions::default()).unwrap();
+/// assert_eq!(trace.passes[0].0, "broken");
+/// assert_eq!(trace.passes.last().unwrap().0, "rescope");
+/// assert_eq!(render(trace.doc, 2, 80), "foo bar");
+/// ```
+pub fn compile_trace(
+  layout: Box<Layout>,
+  options: &CompileOptions
+) -> Result<CompileTrace, CompilerError> {
+  let mut passes = Vec::new();
+  panic::catch_unwind(AssertUnwindSafe(|| {
+    _compile_traced(&*layout, options, &mut |pass, dump, _mem| {
+      passes.push((pass, format!("{:?}", dump)))
+    })
+  }))
+    .map_err(CompilerError::from_panic)?
+    .map(|doc| CompileTrace { passes, doc })
+}
+
+/// The memory report of a compilation, the bytes in use by the intermediate representations after each pass of the compiler in order, and the compiled document.
+///
+/// The bytes are counted in the arena of the compiler, which keeps the intermediate representations of all passes until the compilation ends; the report is meant for comparing the memory use of layouts and of compiler versions.
+#[derive(Debug, Clone)]
+pub struct CompileReport {
+  pub passes: Vec<(&'static str, usize)>,
+  pub doc: Box<Doc>
+}
+
+impl CompileReport {
+  /// Gives the bytes in use after the last pass, the peak memory use of the intermediate representations.
+  pub fn total_bytes(&self) -> usize {
+    self.passes.last().map_or(0, |(_pass, bytes)| *bytes)
+  }
+}
+
+impl fmt::Display for CompileReport {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let mut before = 0;
+    for (pass, bytes) in &self.passes {
+      writeln!(f, "{:<12} {:>10} bytes {:>+10}", pass, bytes, *bytes as i64 - before as i64)?;
+      before = *bytes
+    }
+    write!(f, "{:<12} {:>10} bytes", "total", self.total_bytes())
+  }
+}
+
+fn _used_bytes(
+  mem: &Bump
+) -> usize {
+  // The chunks are only measured, their data is never read
+  unsafe { mem.iter_allocated_chunks_raw() }
+    .map(|(_data, length)| length)
+    .sum()
+}
+
+/// A function for compiling layouts while reporting the memory used by the passes of the compiler, takes a `Box<Layout>` and compile options, and gives either a `CompileReport` or a `CompilerError`.
+///
+/// Like `compile_with`, panics of the compiler are caught and returned as errors.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, compile_with_report, render, CompileOptions};
+///
+/// let layout = comp(
+///   text("foo".to_string()),
+///   text("bar".to_string()),
+///   true, false
+/// );
+/// let report = compile_with_report(layout, &CompileOptions::default()).unwrap();
+/// assert_eq!(report.passes[0].0, "broken");
+/// assert!(report.passes.windows(2).all(|passes| passes[0].1 <= passes[1].1));
+/// assert_eq!(render(report.doc, 2, 80), "foo bar");
+/// ```
+pub fn compile_with_report(
+  layout: Box<Layout>,
+  options: &CompileOptions
+) -> Result<CompileReport, CompilerError> {
+  let mut passes = Vec::new();
+  panic::catch_unwind(AssertUnwindSafe(|| {
+    _compile_traced(&*layout, options, &mut |pass, _dump, mem| {
+      passes.push((pass, _used_bytes(mem)))
+    })
+  }))
+    .map_err(CompilerError::from_panic)?
+    .map(|doc| CompileReport { passes, doc })
+}
+
+fn _compile(
+  layout: Box<Layout>,
+  options: &CompileOptions
+) -> Result<Box<Doc>, CompilerError> {
+  _compile_in(&Bump::new(), &layout, options)
+}
+
+pub(crate) fn _compile_in(
+  mem: &Bump,
+  layout: &Layout,
+  options: &CompileOptions
+) -> Result<Box<Doc>, CompilerError> {
+  if options.passes.reassociate {
+    if let Some(doc) = _trivial(layout) {
+      _check_limits(layout, options.limits)?;
+      return Ok(doc)
+    }
+  }
+  _compile_traced_in(mem, layout, options, &mut |_pass, _dump, _mem| {})
+}
+
+/*
+  Compile fixed layouts of text directly, skipping the passes; these are
+  the layouts without any breakable compositions or modal layouts, whose
+  documents are a single line of the non-empty texts, composed to the right
+  with the paddings between them.
+*/
+fn _trivial(
+  layout: &Layout
+) -> Option<Box<Doc>> {
+  enum Item<'a> {
+    Layout(&'a Layout, bool),
+    Pad(bool)
+  }
+  let mut texts: Vec<(&str, Option<usize>, bool)> = Vec::new();
+  let mut pad = false;
+  let mut stack = vec![Item::Layout(layout, false)];
+  while let Some(item) = stack.pop() {
+    match item {
+      Item::Pad(pad1) =>
+        pad = pad || pad1,
+      Item::Layout(Layout::Null, _) => (),
+      Item::Layout(Layout::Text(data, width), _) => {
+        if data.contains(['\n', '\r', '\t']) { return None }
+        if data.is_empty() { continue }
+        texts.push((data, *width, pad && !texts.is_empty()));
+        pad = false
+      }
+      Item::Layout(Layout::Fix(layout1), _) =>
+        stack.push(Item::Layout(layout1, true)),
+      Item::Layout(Layout::Comp(left, right, attr), fixed) => {
+        if !fixed && !attr.fix { return None }
+        stack.push(Item::Layout(right, fixed));
+        stack.push(Item::Pad(attr.pad));
+        stack.push(Item::Layout(left, fixed))
+      }
+      Item::Layout(_, _) => return None
+    }
+  }
+  let mut texts = texts.into_iter().rev();
+  let Some((data, width, pad)) = texts.next() else {
+    return Some(Box::new(Doc::EOD))
+  };
+  if texts.len() == 0 {
+    return Some(Box::new(Doc::Line(Box::new(DocObj::Text(data.to_string(), width)))))
+  }
+  let last = Box::new(DocObjFix::Text(data.to_string(), width));
+  let (fix, _pad) = texts.fold((last, pad), |(right, pad), (data, width, pad1)| {
+    let left = Box::new(DocObjFix::Text(data.to_string(), width));
+    (Box::new(DocObjFix::Comp(left, right, pad)), pad1)
+  });
+  Some(Box::new(Doc::Line(Box::new(DocObj::Fix(fix)))))
+}
+
+pub(crate) fn _compile_traced<'s, S: LayoutSource<'s>>(
+  layout: S,
+  options: &CompileOptions,
+  trace: &mut dyn FnMut(&'static str, &dyn fmt::Debug, &Bump)
+) -> Result<Box<Doc>, CompilerError> {
+  _compile_traced_in(&Bump::new(), layout, options, trace)
+}
+
+fn _compile_traced_in<'s, S: LayoutSource<'s>>(
+  mem: &Bump,
+  layout: S,
+  options: &CompileOptions,
+  trace: &mut dyn FnMut(&'static str, &dyn fmt::Debug, &Bump)
+) -> Result<Box<Doc>, CompilerError> {
+  _check_limits(layout, options.limits)?;
+  panic::catch_unwind(AssertUnwindSafe(|| _passes(mem, layout, options, trace)))
+    .unwrap_or_else(|payload| match payload.downcast_ref::<&str>() {
+      Some(&"out of memory") =>
+        Err(CompilerError::AllocationFailed(mem.allocated_bytes())),
+      Some(&INDEX_OVERFLOW) =>
+        Err(CompilerError::IndexOverflow(MAX_PACK_INDEX)),
+      _ => panic::resume_unwind(payload)
+    })
+}
+
+fn _passes<'s, S: LayoutSource<'s>>(
+  mem: &Bump,
+  layout: S,
+  options: &CompileOptions,
+  trace: &mut dyn FnMut(&'static str, &dyn fmt::Debug, &Bump)
+) -> Result<Box<Doc>, CompilerError> {
+  let mut pass = |name, dump: &dyn fmt::Debug| {
+    trace(name, dump, mem);
+    let allocated = mem.allocated_bytes();
+    match options.limits.max_allocated_bytes {
+      Some(limit) if limit < allocated =>
+        Err(CompilerError::AllocationFailed(allocated)),
+      _ => Ok(())
+    }
+  };
+  let mut markers = Vec::new();
+  let mut names = BTreeMap::new();
+  let mut anchors = BTreeMap::new();
+  let layout1 = _broken(mem, layout, *options, &mut markers)?;
+  pass("broken", layout1)?;
+  let layout2 = _serialize(mem, layout1, &mut names, &mut anchors);
+  pass("serialize", layout2)?;
+  let doc = _linearize(mem, layout2);
+  pass("linearize", doc)?;
+  let doc1 = _fixed(mem, doc);
+  pass("fixed", doc1)?;
+  let doc2 = _structurize(mem, doc1, &names);
+  pass("structurize", doc2)?;
+  let doc3 = _denull(mem, doc2);
+  pass("denull", doc3)?;
+  let doc4 = _identities(mem, doc3);
+  pass("identities", doc4)?;
+  let doc5 = if options.passes.reassociate {
+    let doc5 = _reassociate(mem, doc4);
+    pass("reassociate", doc5)?;
+    doc5
+  } else { doc4 };
+  let doc6 = _rescope(mem, doc5);
+  pass("rescope", doc6)?;
+  let anchored = anchors.into_values().collect();
+  Ok(_move_to_heap(doc6, &markers, &anchored))
 }
 
 #[derive(Debug, Copy, Clone)]
-struct State<'a> {
+struct State {
   width: usize,
+  ribbon: Option<usize>,
+  narrow: NarrowPolicy,
   tab: usize,
+  tabs: TabPolicy,
   head: bool,
   broken: bool,
   lvl: usize,
-  pos: usize,
-  marks: &'a Map<'a, usize, usize>
+  pos: usize
 }
 
-fn _make_state<'a>(
-  mem: &'a Bump,
+fn _make_state(
   width: usize,
-  tab: usize
-) -> State<'a> {
+  ribbon: Option<usize>,
+  narrow: NarrowPolicy,
+  tab: usize,
+  tabs: TabPolicy
+) -> State {
   State {
-    width: width,
-    tab: tab,
+    width,
+    ribbon,
+    narrow,
+    tab,
+    tabs,
     head: true,
     broken: false,
     lvl: 0,
-    pos: 0,
-    marks: _map::empty(mem)
+    pos: 0
   }
 }
 
-fn _inc_pos<'a>(
+fn _inc_pos(
   n: usize,
-  state: State<'a>
-) -> State<'a> {
+  state: State
+) -> State {
   State {
     pos: state.pos + n,
     ..state
   }
 }
 
-fn _indent<'a>(
+fn _tab_stop(
+  tab: usize,
+  pos: usize
+) -> usize {
+  if tab == 0 { pos } else {
+  pos + (tab - (pos % tab))}
+}
+
+/* The width of a text starting at a column, in bytes with tabs measured by the tab policy; the backends and the testing helpers measure lines by it as well */
+pub(crate) fn _text_columns(
+  data: &str,
+  pos: usize,
+  tabs: TabPolicy
+) -> usize {
+  if !data.contains('\t') { return data.len() }
+  match tabs {
+    TabPolicy::Width(n) => {
+      let tabs = data.matches('\t').count();
+      data.len() - tabs + tabs * n
+    }
+    TabPolicy::Expand(n) => {
+      let pos1 = data.chars().fold(pos, |pos1, c|
+        if c == '\t' { _tab_stop(n, pos1) } else { pos1 + c.len_utf8() });
+      pos1 - pos
+    }
+  }
+}
+
+fn _text_width(
+  data: &str,
+  state: State
+) -> usize {
+  _text_columns(data, state.pos, state.tabs)
+}
+
+fn _inc_text(
+  data: &str,
+  width: Option<usize>,
+  state: State
+) -> State {
+  _inc_pos(width.unwrap_or_else(|| _text_width(data, state)), state)
+}
+
+fn _indent(
   tab: usize,
-  state: State<'a>
-) -> State<'a> {
-  if tab <= 0 { state } else {
+  state: State
+) -> State {
+  if tab == 0 { state } else {
   let lvl = state.lvl;
   let lvl1 = lvl + (tab - (lvl % tab));
   State { lvl: lvl1, ..state }}
 }
 
-fn _newline<'a>(
-  state: State<'a>
-) -> State<'a> {
+fn _newline(
+  state: State
+) -> State {
   State {
     head: true,
     pos: 0,
@@ -3479,9 +5568,9 @@ fn _newline<'a>(
   }
 }
 
-fn _reset<'a>(
-  state: State<'a>
-) -> State<'a> {
+fn _reset(
+  state: State
+) -> State {
   State {
     head: true,
     broken: false,
@@ -3490,334 +5579,2647 @@ fn _reset<'a>(
   }
 }
 
-fn _get_offset<'a>(
-  state: State<'a>
+/*
+  The column a line may extend to, at the current indentation level
+*/
+fn _limit(
+  state: State
+) -> usize {
+  let width = match state.narrow {
+    NarrowPolicy::Strict => state.width,
+    NarrowPolicy::MinContent(min_content) => max(state.width, state.lvl + min_content)
+  };
+  match state.ribbon {
+    None => width,
+    Some(ribbon) => min(width, state.lvl + ribbon)
+  }
+}
+
+/* The limit that break decisions are made against under the hint of the innermost hinted group */
+fn _hinted_limit(
+  state: State,
+  hint: Option<Hint>,
+  tolerance: usize
+) -> usize {
+  match hint {
+    None => _limit(state),
+    Some(Hint::PreferFlat) => _limit(state) + tolerance,
+    Some(Hint::PreferBroken) => _limit(state).saturating_sub(tolerance)
+  }
+}
+
+/* The limit of a break decision biased towards the verdict of the same decision in the previous rendering */
+fn _stable_limit(
+  limit: usize,
+  recalled: Option<Verdict>,
+  hysteresis: usize
+) -> usize {
+  match recalled {
+    Some(Verdict::Fit) => limit + hysteresis,
+    Some(Verdict::Overflow) => limit.saturating_sub(hysteresis),
+    Some(Verdict::Seq) | None => limit
+  }
+}
+
+fn _get_offset(
+  state: State
 ) -> usize {
   if !state.head { 0 } else {
-  max(0, state.lvl - state.pos)}
+  state.lvl.saturating_sub(state.pos)}
 }
 
-/// A function for rendering documents, takes a `Box<Doc>`, a tab indentation size and a output buffer target width, and gives a `String`.
-///
-/// # Examples
-/// ```
-/// use typeset::{text, comp, compile, render};
-///
-/// let layout = comp(
-///   text("foo".to_string()),
-///   text("bar".to_string()),
-///   false, false
-/// );
-/// let document = compile(layout);
-/// println!("{}", render(document, 2, 80));
-/// ```
-pub fn render(
-  doc: Box<Doc>,
-  tab: usize,
-  width: usize
-) -> String {
-  fn _whitespace(n: usize) -> String { " ".repeat(n) }
-  fn _pad<'a>(
-    n: usize,
-    result: String
-  ) -> String {
-    result + &_whitespace(n)
+fn _get_mark_offset(
+  mark: usize,
+  state: State
+) -> usize {
+  max(_get_offset(state), mark.saturating_sub(state.pos))
+}
+
+/*
+  The indentation model, shared by measuring and rendering:
+  - `lvl` is the column that the texts of a line head are padded to, it is 0 at the start of every line of the document;
+  - a nest raises `lvl` to the next tab stop, and a pack raises it to the mark of its index, or records the current column as the mark;
+  - both restore `lvl` when their scope ends, also when a composition in their scope was broken;
+  - padding is only emitted at a line head, up to `lvl`, or up to the mark for a pack;
+  - a break of the document resets `head`, `pos` and `broken`, but the marks are kept, unless the pack scope is per line.
+  Entering a nest or a pack gives the padding to emit and the state within it.
+*/
+fn _enter_nest(
+  state: State
+) -> (usize, State) {
+  let state1 = _indent(state.tab, state);
+  let offset = _get_offset(state1);
+  (offset, _inc_pos(offset, state1))
+}
+
+fn _enter_pack(
+  mark: Option<usize>,
+  state: State
+) -> (usize, State) {
+  match mark {
+    None => (0, State { lvl: max(state.lvl, state.pos), ..state }),
+    Some(mark) => {
+      let state1 = State { lvl: max(state.lvl, mark), ..state };
+      let offset = _get_mark_offset(mark, state1);
+      (offset, _inc_pos(offset, state1))
+    }
   }
-  fn _measure<'b, 'a: 'b>(
-    mem: &'b Bump,
-    obj: &Box<DocObj>,
-    state: State<'a>
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Task<'a> {
+  Doc(&'a Doc),
+  Obj(&'a DocObj),
+  Fix(&'a DocObjFix),
+  Comp(&'a DocObj, bool),
+  Pad(usize),
+  Newline,
+  Head(bool),
+  Broken(bool),
+  Lvl(usize),
+  Name(Option<&'a str>)
+}
+
+/*
+  The indentation and markers of a line without text so far, held back
+  when rendering lazily until the line is given a text; lines that end
+  without any text then get their markers but not their indentation.
+*/
+#[derive(Debug, Copy, Clone)]
+enum Deferred<'a> {
+  Pad(usize),
+  Marker(&'a Marker)
+}
+
+#[derive(Debug, Clone)]
+struct Indentation<'a> {
+  lazy: bool,
+  blank: bool,
+  deferred: Vec<Deferred<'a>>
+}
+
+impl<'a> Indentation<'a> {
+  fn _pad<B: Backend>(
+    &mut self,
+    n: usize,
+    out: &mut B
+  ) -> fmt::Result {
+    if !(self.lazy && self.blank) { return out.pad(n) }
+    if 0 < n { self.deferred.push(Deferred::Pad(n)) }
+    Ok(())
+  }
+
+  fn _marker<B: Backend>(
+    &mut self,
+    marker: &'a Marker,
+    out: &mut B
+  ) -> fmt::Result {
+    if !(self.lazy && self.blank) { return out.marker(marker) }
+    self.deferred.push(Deferred::Marker(marker));
+    Ok(())
+  }
+
+  fn _text<B: Backend>(
+    &mut self,
+    data: &str,
+    out: &mut B
+  ) -> fmt::Result {
+    if data.is_empty() { return Ok(()) }
+    self.blank = false;
+    for item in self.deferred.drain(..) {
+      match item {
+        Deferred::Pad(n) => out.pad(n)?,
+        Deferred::Marker(marker) => out.marker(marker)?
+      }
+    }
+    Ok(())
+  }
+
+  fn _end_line<B: Backend>(
+    &mut self,
+    out: &mut B
+  ) -> fmt::Result {
+    for item in self.deferred.drain(..) {
+      if let Deferred::Marker(marker) = item { out.marker(marker)? }
+    }
+    self.blank = true;
+    Ok(())
+  }
+
+  fn _line_end(
+    &self,
+    pos: usize
   ) -> usize {
-    fn _visit_obj<'b, 'a: 'b>(
-      mem: &'b Bump,
-      obj: &Box<DocObj>,
-      state: State<'a>
-    ) -> State<'b> {
-      match obj {
-        box DocObj::Text(data) => _inc_pos(data.len(), state),
-        box DocObj::Fix(fix) => _visit_fix(fix, state),
-        box DocObj::Grp(obj1) => _visit_obj(mem, obj1, state),
-        box DocObj::Seq(obj1) => _visit_obj(mem, obj1, state),
-        box DocObj::Nest(obj1) => {
-          let lvl = state.lvl;
-          let state1 = _indent(state.tab, state);
-          let offset = _get_offset(state1);
-          let state2 = _inc_pos(offset, state1);
-          let state3 = _visit_obj(mem, obj1, state2);
-          State { lvl: lvl, ..state3 }
-        }
-        box DocObj::Pack(index, obj1) => {
-          let index = *index as usize;
-          let lvl = state.lvl;
-          let marks = state.marks;
-          match marks.lookup(&total, index) {
-            None => {
-              let pos = state.pos;
-              let marks1 = marks.insert(mem, &total, index, pos);
-              let state1 = State { marks: marks1, ..state };
-              let state2 = State { lvl: max(lvl, pos), ..state1 };
-              let state3 = _visit_obj(mem, obj1, state2);
-              State { lvl: lvl, ..state3 }
-            }
-            Some(lvl1) => {
-              let state1 = State { lvl: max(lvl, lvl1), ..state };
-              let offset = _get_offset(state1);
-              let state2 = _inc_pos(offset, state1);
-              let state3 = _visit_obj(mem, obj1, state2);
-              State { lvl: lvl, ..state3 }
+    if self.lazy && self.blank { 0 } else { pos }
+  }
+}
+
+fn _write_text<B: Backend>(
+  data: &str,
+  width: Option<usize>,
+  state: State,
+  out: &mut B
+) -> fmt::Result {
+  let (None, TabPolicy::Expand(n)) = (width, state.tabs) else {
+    return out.text(data)
+  };
+  let mut pos = state.pos;
+  for (index, part) in data.split('\t').enumerate() {
+    if index != 0 {
+      let pos1 = _tab_stop(n, pos);
+      out.pad(pos1 - pos)?;
+      pos = pos1
+    }
+    out.text(part)?;
+    pos += part.len()
+  }
+  Ok(())
+}
+
+/*
+  The pack marks of a render, kept in a vector while there are few of them,
+  as in most lines and measures, and in a B-tree once there are more.
+*/
+const SMALL_MARKS: usize = 16;
+
+#[derive(Debug, Clone)]
+enum Marks {
+  Small(Vec<(Index, usize)>),
+  Large(BTreeMap<Index, usize>)
+}
+
+impl Marks {
+  fn new() -> Self {
+    Marks::Small(Vec::new())
+  }
+
+  fn get(&self, index: &Index) -> Option<&usize> {
+    match self {
+      Marks::Small(marks) => marks.iter()
+        .find(|(index1, _pos)| index1 == index)
+        .map(|(_index, pos)| pos),
+      Marks::Large(marks) => marks.get(index)
+    }
+  }
+
+  fn insert(&mut self, index: Index, pos: usize) {
+    match self {
+      Marks::Small(marks) => {
+        if let Some(mark) = marks.iter_mut().find(|(index1, _pos)| *index1 == index) {
+          mark.1 = pos
+        } else if marks.len() < SMALL_MARKS {
+          marks.push((index, pos))
+        } else {
+          let mut marks1: BTreeMap<Index, usize> = marks.drain(..).collect();
+          marks1.insert(index, pos);
+          *self = Marks::Large(marks1)
+        }
+      }
+      Marks::Large(marks) => {
+        marks.insert(index, pos);
+      }
+    }
+  }
+
+  fn retain(&mut self, mut keep: impl FnMut(&Index) -> bool) {
+    match self {
+      Marks::Small(marks) => marks.retain(|(index, _pos)| keep(index)),
+      Marks::Large(marks) => marks.retain(|index, _pos| keep(index))
+    }
+  }
+
+  fn clear(&mut self) {
+    *self = Marks::new()
+  }
+}
+
+impl From<&BTreeMap<Index, usize>> for Marks {
+  fn from(marks: &BTreeMap<Index, usize>) -> Self {
+    if SMALL_MARKS < marks.len() { Marks::Large(marks.clone()) } else {
+    Marks::Small(marks.iter().map(|(index, pos)| (*index, *pos)).collect()) }
+  }
+}
+
+fn _measure(
+  marks: &Marks,
+  obj: &DocObj,
+  state: State,
+  bound: usize
+) -> usize {
+  _measure_tasks(marks, vec![Task::Obj(obj)], &[], state, bound)
+}
+
+/*
+  Measures the tasks of a stack flat, followed by the pending tasks of the
+  rest, up to the end of the line; positions only grow along a line, so the
+  measuring stops as soon as it is past the bound, such that measuring deeply
+  nested groups and sequences against the width does not take quadratic time.
+*/
+fn _measure_tasks(
+  marks: &Marks,
+  stack: Vec<Task>,
+  rest: &[Task],
+  state: State,
+  bound: usize
+) -> usize {
+  let mut state = state;
+  let mut marks1 = Marks::new();
+  let mut reset = false;
+  let mut stack = stack;
+  let mut rest = rest;
+  loop {
+    if bound < state.pos { break }
+    let task = match stack.pop() {
+      Some(task) => task,
+      None => match rest.split_last() {
+        None => break,
+        Some((task, rest1)) => { rest = rest1; *task }
+      }
+    };
+    match task {
+      Task::Obj(DocObj::Text(data, width)) =>
+        state = _inc_text(data, *width, state),
+      Task::Obj(DocObj::Marker(Marker::PackReset)) |
+      Task::Fix(DocObjFix::Marker(Marker::PackReset)) => {
+        marks1.clear();
+        reset = true
+      }
+      Task::Obj(DocObj::Marker(_)) | Task::Fix(DocObjFix::Marker(_)) =>
+        (),
+      Task::Obj(DocObj::Fix(fix)) =>
+        stack.push(Task::Fix(fix)),
+      Task::Obj(DocObj::Grp(_name, obj1)) =>
+        stack.push(Task::Obj(obj1)),
+      Task::Obj(DocObj::Seq(_name, obj1)) =>
+        stack.push(Task::Obj(obj1)),
+      Task::Obj(DocObj::Nest(obj1)) => {
+        stack.push(Task::Lvl(state.lvl));
+        state = _enter_nest(state).1;
+        stack.push(Task::Obj(obj1))
+      }
+      Task::Obj(obj1 @ (DocObj::Pack(index, obj2) | DocObj::PackAt(index, obj2))) => {
+        let index = *index;
+        let keep = !reset || matches!(obj1, DocObj::PackAt(..));
+        stack.push(Task::Lvl(state.lvl));
+        let mark = marks1.get(&index)
+          .or_else(|| if keep { marks.get(&index) } else { None })
+          .copied();
+        if mark.is_none() { marks1.insert(index, state.pos); }
+        state = _enter_pack(mark, state).1;
+        stack.push(Task::Obj(obj2))
+      }
+      Task::Obj(DocObj::Comp(left, right, pad)) => {
+        stack.push(Task::Comp(right, *pad));
+        stack.push(Task::Obj(left))
+      }
+      Task::Comp(right, pad) => {
+        let state1 = _inc_pos(if pad { 1 } else { 0 }, state);
+        stack.push(Task::Head(state1.head));
+        state = State { head: false, ..state1 };
+        stack.push(Task::Obj(right))
+      }
+      Task::Fix(DocObjFix::Text(data, width)) =>
+        state = _inc_text(data, *width, state),
+      Task::Fix(DocObjFix::Comp(left, right, pad)) => {
+        stack.push(Task::Fix(right));
+        stack.push(Task::Pad(if *pad { 1 } else { 0 }));
+        stack.push(Task::Fix(left))
+      }
+      Task::Pad(n) =>
+        state = _inc_pos(n, state),
+      Task::Head(head) =>
+        state = State { head, ..state },
+      Task::Lvl(lvl) =>
+        state = State { lvl, ..state },
+      Task::Broken(_) | Task::Name(_) =>
+        (),
+      Task::Doc(_) | Task::Newline =>
+        break
+    }
+  }
+  state.pos
+}
+
+fn _next_comp(
+  marks: &Marks,
+  obj: &DocObj,
+  state: State,
+  bound: usize
+) -> (usize, bool) {
+  let mut state = state;
+  let mut grouped = false;
+  let mut hyphen = false;
+  let mut marks1 = Marks::new();
+  let mut reset = false;
+  let mut stack = vec![Task::Obj(obj)];
+  while let Some(task) = stack.pop() {
+    match task {
+      Task::Obj(DocObj::Text(data, width)) => {
+        hyphen = hyphen && data.is_empty();
+        state = _inc_text(data, *width, state)
+      }
+      Task::Obj(DocObj::Marker(Marker::Close(Annotation::Hyphen))) |
+      Task::Fix(DocObjFix::Marker(Marker::Close(Annotation::Hyphen))) =>
+        hyphen = true,
+      Task::Obj(DocObj::Marker(Marker::PackReset)) |
+      Task::Fix(DocObjFix::Marker(Marker::PackReset)) => {
+        marks1.clear();
+        reset = true
+      }
+      Task::Obj(DocObj::Marker(_)) | Task::Fix(DocObjFix::Marker(_)) =>
+        (),
+      Task::Obj(DocObj::Fix(fix)) =>
+        stack.push(Task::Fix(fix)),
+      Task::Obj(DocObj::Grp(_name, obj1)) =>
+        if state.head { stack.push(Task::Obj(obj1)) } else {
+        let obj_end_pos = _measure(marks, obj1, state, bound);
+        grouped = true;
+        state = State { pos: obj_end_pos, ..state }}
+      Task::Obj(DocObj::Seq(_name, obj1)) =>
+        stack.push(Task::Obj(obj1)),
+      Task::Obj(DocObj::Nest(obj1)) => {
+        stack.push(Task::Lvl(state.lvl));
+        state = _enter_nest(state).1;
+        stack.push(Task::Obj(obj1))
+      }
+      Task::Obj(obj1 @ (DocObj::Pack(index, obj2) | DocObj::PackAt(index, obj2))) => {
+        let index = *index;
+        let keep = !reset || matches!(obj1, DocObj::PackAt(..));
+        stack.push(Task::Lvl(state.lvl));
+        let mark = marks1.get(&index)
+          .or_else(|| if keep { marks.get(&index) } else { None })
+          .copied();
+        if mark.is_none() { marks1.insert(index, state.pos); }
+        state = _enter_pack(mark, state).1;
+        stack.push(Task::Obj(obj2))
+      }
+      Task::Obj(DocObj::Comp(left, _right, _pad)) =>
+        stack.push(Task::Obj(left)),
+      Task::Fix(DocObjFix::Text(data, width)) => {
+        hyphen = hyphen && data.is_empty();
+        state = _inc_text(data, *width, state)
+      }
+      Task::Fix(DocObjFix::Comp(left, right, pad)) => {
+        stack.push(Task::Fix(right));
+        stack.push(Task::Pad(if *pad { 1 } else { 0 }));
+        stack.push(Task::Fix(left))
+      }
+      Task::Pad(n) =>
+        state = _inc_pos(n, state),
+      Task::Lvl(lvl) =>
+        state = State { lvl, ..state },
+      Task::Doc(_) | Task::Comp(_, _) | Task::Newline |
+      Task::Head(_) | Task::Broken(_) | Task::Name(_) =>
+        invariant!(task, stack)
+    }
+  }
+  /* A soft hyphen right before the next composition is emitted when the line is broken there */
+  (state.pos + if hyphen { 1 } else { 0 }, grouped)
+}
+
+/* The position a composition is measured to, given the pending tasks of the line when groups are measured to the end of the line; any position past the bound may be given for a composition that is measured past it */
+fn _break_pos(
+  marks: &Marks,
+  obj: &DocObj,
+  state: State,
+  rest: Option<&[Task]>,
+  bound: usize
+) -> usize {
+  let (next_comp_pos, grouped) = _next_comp(marks, obj, state, bound);
+  match rest {
+    Some(rest) if grouped =>
+      _measure_tasks(marks, vec![Task::Obj(obj)], rest, state, bound),
+    _ => next_comp_pos
+  }
+}
+
+fn _should_break(
+  marks: &Marks,
+  obj: &DocObj,
+  state: State,
+  rest: Option<&[Task]>,
+  limit: usize
+) -> bool {
+  let broken = state.broken;
+  if broken { true } else {
+  let break_pos = _break_pos(marks, obj, state, rest, limit);
+  limit < break_pos }
+}
+
+fn _anchors(
+  doc: &Doc
+) -> usize {
+  let mut anchors = BTreeSet::new();
+  let mut docs = vec![doc];
+  let mut objs: Vec<&DocObj> = Vec::new();
+  while let Some(doc) = docs.pop() {
+    match doc {
+      Doc::EOD => (),
+      Doc::Empty(doc1) => docs.push(doc1),
+      Doc::Break(obj, doc1) => {
+        objs.push(obj);
+        docs.push(doc1)
+      }
+      Doc::Line(obj) => objs.push(obj)
+    }
+  }
+  while let Some(obj) = objs.pop() {
+    match obj {
+      DocObj::Text(_, _) | DocObj::Marker(_) | DocObj::Fix(_) => (),
+      DocObj::Grp(_, obj1) | DocObj::Seq(_, obj1) |
+      DocObj::Nest(obj1) | DocObj::Pack(_, obj1) =>
+        objs.push(obj1),
+      DocObj::PackAt(index, obj1) => {
+        anchors.insert(*index);
+        objs.push(obj1)
+      }
+      DocObj::Comp(left, right, _) => {
+        objs.push(left);
+        objs.push(right)
+      }
+    }
+  }
+  anchors.len()
+}
+
+/*
+  Solve the columns of anchored packs, by rendering the document with the
+  columns found so far until no anchor is entered past its column; each
+  pass can only move the anchors that depend on the ones before them.
+*/
+fn _solve_anchors(
+  doc: &Doc,
+  options: RenderOptions,
+  recalled: &[Verdict],
+  anchors: usize
+) -> BTreeMap<Index, usize> {
+  let mut anchored = BTreeMap::new();
+  for _ in 0..=anchors {
+    let mut renderer = Renderer::_anchored(doc, options, recalled, anchored.clone());
+    renderer.entries = Some(BTreeMap::new());
+    renderer._run(&mut Discard)
+      .expect("Discarding output can not fail");
+    let entries = renderer.entries.unwrap_or_default();
+    if entries.iter().all(|(index, pos)| anchored.get(index) == Some(pos)) { break }
+    for (index, pos) in entries {
+      let column = anchored.entry(index).or_insert(pos);
+      *column = max(*column, pos)
+    }
+  }
+  anchored
+}
+
+/// The kind of a break decision, whether a seq is broken, measured to the end of the seq, or whether a composition is broken, measured to the next composition that could be broken.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BreakKind {
+  Seq,
+  Comp
+}
+
+/// The verdict of a break decision, either the measured width fits and nothing is broken, the measured width overflows the available width and the seq or composition is broken, or the composition is broken since it is under a broken seq, regardless of whether it fits.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Verdict {
+  Fit,
+  Overflow,
+  Seq
+}
+
+/// A break decision made by the renderer, as given by `explain`.
+///
+/// The line and column are zero-based and give the position in the rendered output at which the decision is made; the name is the name of the innermost enclosing named group or seq, see `grp_named` and `seq_named`, and the measured and available widths are the columns that were compared.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BreakDecision {
+  pub kind: BreakKind,
+  pub name: Option<String>,
+  pub line: usize,
+  pub column: usize,
+  pub measured: usize,
+  pub available: usize,
+  pub verdict: Verdict
+}
+
+/// The origin of a rendered line, either a forced linebreak of the document or a broken composition.
+///
+/// The first line of a document counts as forced.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LineKind {
+  Forced,
+  Break
+}
+
+/// Metadata of a rendered line, its kind, its indentation, i.e. the column of its first non-empty text, and the tags active at that text, outermost first.
+///
+/// Lines without any text are indented by their full length, which is zero with lazy indentation, and have the tags active at their end.
+/// The struct is non-exhaustive, such that more metadata can be added without breaking applications.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LineInfo {
+  pub indent: usize,
+  pub kind: LineKind,
+  pub tags: Vec<String>
+}
+
+/// An incremental renderer for documents, which renders a document in chunks of bounded size.
+///
+/// The render state is kept between chunks, such that rendering can be spread over several calls, e.g. across the frames of a GUI application; concatenating all the chunks gives the same output as `render`.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, compile, RenderOptions, Renderer};
+///
+/// let layout = comp(
+///   text("foo".to_string()),
+///   text("bar".to_string()),
+///   true, false
+/// );
+/// let document = compile(layout);
+/// let mut renderer = Renderer::new(&document, RenderOptions::default());
+/// assert_eq!(renderer.next_chunk(4), Some("foo "));
+/// assert_eq!(renderer.next_chunk(4), Some("bar"));
+/// assert_eq!(renderer.next_chunk(4), None);
+///
+/// let mut renderer = Renderer::new(&document, RenderOptions { width: 4, ..RenderOptions::default() });
+/// assert_eq!(renderer.next_chunk(0), Some("foo\n"));
+/// assert_eq!(renderer.next_chunk(0), Some("bar"));
+/// assert_eq!(renderer.next_chunk(0), None);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Renderer<'a> {
+  state: State,
+  marks: Marks,
+  anchored: BTreeMap<Index, usize>,
+  entries: Option<BTreeMap<Index, usize>>,
+  pack_scope: PackScope,
+  group_measure: GroupMeasure,
+  max_blank_lines: Option<usize>,
+  blank_lines: usize,
+  stack: Vec<Task<'a>>,
+  lines: Option<Vec<LineInfo>>,
+  decisions: Option<Vec<BreakDecision>>,
+  name: Option<&'a str>,
+  tags: Vec<&'a str>,
+  hints: Vec<Hint>,
+  hyphen: bool,
+  hint_tolerance: usize,
+  recalled: &'a [Verdict],
+  verdicts: Option<Vec<Verdict>>,
+  hysteresis: usize,
+  ordinal: usize,
+  line: usize,
+  indented: bool,
+  indentation: Indentation<'a>,
+  pending: String,
+  chunk: String
+}
+
+impl<'a> Renderer<'a> {
+  /// Constructs a new renderer for a document with the given render options.
+  pub fn new(
+    doc: &'a Doc,
+    options: RenderOptions
+  ) -> Self {
+    Renderer::_recalling(doc, options, &[])
+  }
+
+  fn _recalling(
+    doc: &'a Doc,
+    options: RenderOptions,
+    recalled: &'a [Verdict]
+  ) -> Self {
+    let anchors = _anchors(doc);
+    let anchored = if anchors == 0 { BTreeMap::new() } else {
+      _solve_anchors(doc, options, recalled, anchors)
+    };
+    Renderer::_anchored(doc, options, recalled, anchored)
+  }
+
+  fn _anchored(
+    doc: &'a Doc,
+    options: RenderOptions,
+    recalled: &'a [Verdict],
+    anchored: BTreeMap<Index, usize>
+  ) -> Self {
+    Renderer {
+      state: _make_state(options.width, options.ribbon, options.narrow, options.tab, options.tabs),
+      marks: Marks::from(&anchored),
+      anchored,
+      entries: None,
+      pack_scope: options.pack_scope,
+      group_measure: options.group_measure,
+      max_blank_lines: options.max_blank_lines,
+      blank_lines: 0,
+      stack: vec![Task::Doc(doc)],
+      lines: if options.line_info { Some(Vec::new()) } else { None },
+      decisions: None,
+      name: None,
+      tags: Vec::new(),
+      hints: Vec::new(),
+      hyphen: false,
+      hint_tolerance: options.hint_tolerance,
+      recalled,
+      verdicts: None,
+      hysteresis: options.hysteresis,
+      ordinal: 0,
+      line: 0,
+      indented: true,
+      indentation: Indentation {
+        lazy: options.lazy_indent,
+        blank: true,
+        deferred: Vec::new()
+      },
+      pending: String::new(),
+      chunk: String::new()
+    }
+  }
+
+  /// Renders the next chunk of the document, gives `None` once the whole document has been rendered.
+  ///
+  /// A chunk holds at most `max_bytes` bytes, unless a single character is longer than that, and is never split within a character.
+  /// A `max_bytes` of zero asks for the next line instead, including its linebreak if any, such that a chunk is never empty before the end of the document.
+  pub fn next_chunk(
+    &mut self,
+    max_bytes: usize
+  ) -> Option<&str> {
+    let mut pending = std::mem::take(&mut self.pending);
+    let mut split = if max_bytes == 0 {
+      let mut searched = 0;
+      loop {
+        if let Some(index) = pending[searched..].find('\n') { break searched + index + 1 }
+        searched = pending.len();
+        if !self._step(&mut Plain(&mut pending))
+          .expect("Writing to a String can not fail") { break pending.len() }
+      }
+    } else {
+      while pending.len() < max_bytes && self._step(&mut Plain(&mut pending))
+        .expect("Writing to a String can not fail") {}
+      max_bytes.min(pending.len())
+    };
+    while !pending.is_char_boundary(split) { split -= 1 }
+    if split == 0 {
+      split = pending.chars().next().map_or(0, char::len_utf8)
+    }
+    self.chunk.clear();
+    self.chunk.extend(pending.drain(..split));
+    self.pending = pending;
+    if self.chunk.is_empty() { None } else { Some(&self.chunk) }
+  }
+
+  /// Gives the metadata of the lines rendered so far, if requested by the `line_info` render option.
+  pub fn lines(&self) -> Option<&[LineInfo]> {
+    self.lines.as_deref()
+  }
+
+  fn _decide(
+    &mut self,
+    kind: BreakKind,
+    line: usize,
+    column: usize,
+    measured: usize,
+    available: usize,
+    verdict: Verdict
+  ) {
+    self.ordinal += 1;
+    if let Some(verdicts) = &mut self.verdicts {
+      verdicts.push(verdict)
+    }
+    if let Some(decisions) = &mut self.decisions {
+      decisions.push(BreakDecision {
+        kind,
+        name: self.name.map(str::to_string),
+        line,
+        column,
+        measured,
+        available,
+        verdict
+      })
+    }
+  }
+
+  fn _begin_line(
+    &mut self,
+    kind: LineKind
+  ) {
+    if let Some(lines) = &mut self.lines {
+      lines.push(LineInfo { indent: 0, kind, tags: Vec::new() });
+      self.indented = false
+    }
+  }
+
+  fn _end_indent(
+    &mut self,
+    pos: usize
+  ) {
+    if self.indented { return }
+    if let Some(line) = self.lines.as_mut().and_then(|lines| lines.last_mut()) {
+      line.indent = pos;
+      line.tags = self.tags.iter().map(|tag| tag.to_string()).collect()
+    }
+    self.indented = true
+  }
+
+  pub(crate) fn _run<B: Backend>(
+    &mut self,
+    out: &mut B
+  ) -> fmt::Result {
+    while self._step(out)? {}
+    Ok(())
+  }
+
+  /* Render the next line of the document, up to the forced linebreak after it */
+  fn _run_line<B: Backend>(
+    &mut self,
+    out: &mut B
+  ) -> fmt::Result {
+    while let Some(Task::Newline) = self.stack.last() {
+      self._step(&mut Discard)?;
+    }
+    if !self._step(out)? { return Ok(()) }
+    while !matches!(self.stack.last(), None | Some(Task::Newline | Task::Doc(_))) {
+      self._step(out)?;
+    }
+    Ok(())
+  }
+
+  fn _step<B: Backend>(
+    &mut self,
+    out: &mut B
+  ) -> Result<bool, fmt::Error> {
+    let Some(task) = self.stack.pop() else {
+      self._end_indent(self.indentation._line_end(self.state.pos));
+      self.indentation._end_line(out)?;
+      return Ok(false)
+    };
+    match task {
+      Task::Doc(_) =>
+        self._begin_line(LineKind::Forced),
+      Task::Newline => {
+        self.hyphen = false;
+        self._end_indent(self.indentation._line_end(self.state.pos))
+      }
+      Task::Obj(DocObj::Text(data, _)) | Task::Fix(DocObjFix::Text(data, _))
+        if !data.is_empty() => {
+        self.hyphen = false;
+        self._end_indent(self.state.pos)
+      }
+      _ => ()
+    }
+    let stack = &mut self.stack;
+    let marks = &mut self.marks;
+    let indentation = &mut self.indentation;
+    let state = self.state;
+    let explain = self.decisions.is_some();
+    let mut line_break = None;
+    let mut decision = None;
+    self.state = match task {
+      Task::Doc(doc) => {
+        let state1 = _reset(state);
+        if self.pack_scope == PackScope::Line {
+          marks.retain(|index| self.anchored.contains_key(index))
+        }
+        match doc {
+          Doc::EOD => (),
+          Doc::Empty(doc1) => {
+            stack.push(Task::Doc(doc1));
+            if self.max_blank_lines.is_none_or(|max| self.blank_lines < max) {
+              self.blank_lines += 1;
+              stack.push(Task::Newline)
             }
           }
+          Doc::Break(obj, doc1) => {
+            self.blank_lines = 0;
+            stack.push(Task::Doc(doc1));
+            stack.push(Task::Newline);
+            stack.push(Task::Obj(obj))
+          }
+          Doc::Line(obj) => {
+            self.blank_lines = 0;
+            stack.push(Task::Obj(obj))
+          }
+        }
+        state1
+      }
+      Task::Newline => {
+        indentation._end_line(out)?;
+        out.newline()?;
+        self.line += 1;
+        state
+      }
+      Task::Obj(DocObj::Text(data, width)) => {
+        indentation._text(data, out)?;
+        _write_text(data, *width, state, out)?;
+        _inc_text(data, *width, state)
+      }
+      Task::Obj(DocObj::Marker(Marker::PackReset)) |
+      Task::Fix(DocObjFix::Marker(Marker::PackReset)) => {
+        marks.retain(|index| self.anchored.contains_key(index));
+        state
+      }
+      Task::Obj(DocObj::Marker(marker)) | Task::Fix(DocObjFix::Marker(marker)) => {
+        match marker {
+          Marker::Open(Annotation::Hint(hint)) => self.hints.push(*hint),
+          Marker::Close(Annotation::Hint(_)) => { self.hints.pop(); }
+          Marker::Open(Annotation::Tag(tag)) => self.tags.push(tag),
+          Marker::Close(Annotation::Tag(_)) => { self.tags.pop(); }
+          Marker::Close(Annotation::Hyphen) => self.hyphen = true,
+          _ => ()
+        }
+        indentation._marker(marker, out)?;
+        state
+      }
+      Task::Obj(DocObj::Fix(fix)) => {
+        stack.push(Task::Fix(fix));
+        state
+      }
+      Task::Obj(DocObj::Grp(name, obj1)) => {
+        stack.push(Task::Broken(state.broken));
+        if explain && name.is_some() {
+          stack.push(Task::Name(self.name));
+          self.name = name.as_deref()
+        }
+        stack.push(Task::Obj(obj1));
+        State { broken: false, ..state }
+      }
+      Task::Obj(DocObj::Seq(name, obj1)) => {
+        let limit = _stable_limit(
+          _hinted_limit(state, self.hints.last().copied(), self.hint_tolerance),
+          self.recalled.get(self.ordinal).copied(),
+          self.hysteresis
+        );
+        let bound = if explain { usize::MAX } else { limit };
+        let obj_end_pos = _measure(marks, obj1, state, bound);
+        let fits = obj_end_pos <= limit;
+        if !fits { stack.push(Task::Broken(state.broken)) }
+        if explain && name.is_some() {
+          stack.push(Task::Name(self.name));
+          self.name = name.as_deref()
+        }
+        stack.push(Task::Obj(obj1));
+        if fits {
+          decision = Some((BreakKind::Seq, obj_end_pos, limit, Verdict::Fit));
+          state
+        } else {
+          decision = Some((BreakKind::Seq, obj_end_pos, limit, Verdict::Overflow));
+          State { broken: true, ..state }
+        }
+      }
+      Task::Obj(DocObj::Nest(obj1)) => {
+        stack.push(Task::Lvl(state.lvl));
+        stack.push(Task::Obj(obj1));
+        let (offset, state1) = _enter_nest(state);
+        indentation._pad(offset, out)?;
+        state1
+      }
+      Task::Obj(obj @ (DocObj::Pack(index, obj1) | DocObj::PackAt(index, obj1))) => {
+        let index = *index;
+        if let (DocObj::PackAt(..), Some(entries)) = (obj, &mut self.entries) {
+          let entry = entries.entry(index).or_insert(state.pos);
+          *entry = max(*entry, state.pos)
+        }
+        stack.push(Task::Lvl(state.lvl));
+        stack.push(Task::Obj(obj1));
+        debug_assert!(
+          self.entries.is_some() || matches!(obj, DocObj::PackAt(..)) == self.anchored.contains_key(&index),
+          "Pack index {} is used both with and without an anchor, or is never anchored", index
+        );
+        let mark = marks.get(&index).copied();
+        if mark.is_none() { marks.insert(index, state.pos); }
+        let (offset, state1) = _enter_pack(mark, state);
+        indentation._pad(offset, out)?;
+        state1
+      }
+      Task::Obj(DocObj::Comp(left, right, pad)) => {
+        stack.push(Task::Comp(right, *pad));
+        stack.push(Task::Obj(left));
+        state
+      }
+      Task::Comp(right, pad) => {
+        let rest = match self.group_measure {
+          GroupMeasure::End => None,
+          GroupMeasure::LineEnd => Some(&stack[..])
+        };
+        let padding = if pad { 1 } else { 0 };
+        let state1 = _inc_pos(padding, state);
+        let state2 = State { head: false, ..state1 };
+        let limit = _stable_limit(
+          _hinted_limit(state2, self.hints.last().copied(), self.hint_tolerance),
+          self.recalled.get(self.ordinal).copied(),
+          self.hysteresis
+        );
+        /* A word is only broken at a soft hyphen when the rest of it does not fit, and not by the broken sequences it is in */
+        let hyphen = std::mem::take(&mut self.hyphen);
+        let measured = if hyphen { State { broken: false, ..state2 } } else { state2 };
+        let broken = _should_break(marks, right, measured, rest, limit);
+        let next_comp_pos = if explain { _break_pos(marks, right, measured, rest, usize::MAX) } else { 0 };
+        let verdict =
+          if measured.broken { Verdict::Seq }
+          else if broken { Verdict::Overflow }
+          else { Verdict::Fit };
+        decision = Some((BreakKind::Comp, next_comp_pos, limit, verdict));
+        stack.push(Task::Obj(right));
+        if broken {
+          let state3 = if hyphen {
+            indentation._text("-", out)?;
+            out.text("-")?;
+            _inc_pos(1, state)
+          } else { state };
+          line_break = Some(indentation._line_end(state3.pos));
+          self.line += 1;
+          let state4 = _newline(state3);
+          let offset = _get_offset(state4);
+          indentation._end_line(out)?;
+          out.newline()?;
+          indentation._pad(offset, out)?;
+          _inc_pos(offset, state4)
+        } else {
+          indentation._pad(padding, out)?;
+          state2
+        }
+      }
+      Task::Fix(DocObjFix::Text(data, width)) => {
+        indentation._text(data, out)?;
+        _write_text(data, *width, state, out)?;
+        _inc_text(data, *width, state)
+      }
+      Task::Fix(DocObjFix::Comp(left, right, pad)) => {
+        stack.push(Task::Fix(right));
+        stack.push(Task::Pad(if *pad { 1 } else { 0 }));
+        stack.push(Task::Fix(left));
+        state
+      }
+      Task::Pad(n) => {
+        indentation._pad(n, out)?;
+        _inc_pos(n, state)
+      }
+      Task::Broken(broken) =>
+        State { broken, ..state },
+      Task::Lvl(lvl) =>
+        State { lvl, ..state },
+      Task::Name(name) => {
+        self.name = name;
+        state
+      }
+      Task::Head(_) =>
+        invariant!(task, stack)
+    };
+    if let Some((kind, measured, available, verdict)) = decision {
+      let line = self.line - if line_break.is_some() { 1 } else { 0 };
+      self._decide(kind, line, state.pos, measured, available, verdict)
+    }
+    if let Some(pos) = line_break {
+      self._end_indent(pos);
+      self._begin_line(LineKind::Break)
+    }
+    Ok(true)
+  }
+}
+
+fn _render<W: fmt::Write>(
+  doc: &Doc,
+  options: RenderOptions,
+  out: &mut W
+) -> fmt::Result {
+  Renderer::new(doc, options)._run(&mut Plain(out))
+}
+
+/// A function for rendering documents, takes a `Box<Doc>`, a tab indentation size and a output buffer target width, and gives a `String`.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, compile, render};
+///
+/// let layout = comp(
+///   text("foo".to_string()),
+///   text("bar".to_string()),
+///   false, false
+/// );
+/// let document = compile(layout);
+/// assert_eq!(render(document, 2, 80), "foobar");
+/// ```
+pub fn render(
+  doc: Box<Doc>,
+  tab: usize,
+  width: usize
+) -> String {
+  let mut result = String::new();
+  _render(&doc, RenderOptions::new(tab, width), &mut result)
+    .expect("Writing to a String can not fail");
+  result
+}
+
+/// A function for rendering documents directly into a `fmt::Write` target, takes a `&Doc`, render options and the target.
+///
+/// The rendered output is written piece by piece into the target, without building any intermediate strings.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, compile, render_to, RenderOptions};
+///
+/// let layout = comp(
+///   text("foo".to_string()),
+///   text("bar".to_string()),
+///   true, false
+/// );
+/// let document = compile(layout);
+/// let mut output = String::new();
+/// render_to(&document, &RenderOptions::default(), &mut output).unwrap();
+/// assert_eq!(output, "foo bar");
+/// ```
+pub fn render_to<W: fmt::Write>(
+  doc: &Doc,
+  options: &RenderOptions,
+  out: &mut W
+) -> fmt::Result {
+  _render(doc, *options, out)
+}
+
+/// A function for rendering documents into a `String`, takes a `&Doc`, render options and the string, and appends the rendered output to it.
+///
+/// The string is reserved the estimated length of the output up front, see `Doc::estimated_output_len`, such that rendering large documents does not reallocate the string over and over.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, compile, render_into, RenderOptions};
+///
+/// let layout = comp(
+///   text("foo".to_string()),
+///   text("bar".to_string()),
+///   true, false
+/// );
+/// let document = compile(layout);
+/// let mut output = String::from("> ");
+/// render_into(&document, &RenderOptions::default(), &mut output);
+/// assert_eq!(output, "> foo bar");
+/// ```
+pub fn render_into(
+  doc: &Doc,
+  options: &RenderOptions,
+  out: &mut String
+) {
+  out.reserve(doc.estimated_output_len(options));
+  _render(doc, *options, out)
+    .expect("Writing to a String can not fail")
+}
+
+/// The result of rendering a document with `render_with`, the rendered text and, if requested by the `line_info` render option, the metadata of each of its lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderOutput {
+  pub text: String,
+  pub lines: Option<Vec<LineInfo>>
+}
+
+/// A function for rendering documents with render options, takes a `&Doc` and the options, and gives a `RenderOutput`.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, nest, tag, compile, render_with, LineKind, RenderOptions};
+///
+/// let layout = comp(
+///   text("foo".to_string()),
+///   tag("body", nest(text("bar".to_string()))),
+///   true, false
+/// );
+/// let document = compile(layout);
+/// let options = RenderOptions { width: 4, line_info: true, ..RenderOptions::default() };
+/// let output = render_with(&document, &options);
+/// assert_eq!(output.text, "foo\n  bar");
+/// let lines: Vec<_> = output.lines.unwrap().into_iter()
+///   .map(|line| (line.indent, line.kind, line.tags))
+///   .collect();
+/// assert_eq!(lines, vec![
+///   (0, LineKind::Forced, vec![]),
+///   (2, LineKind::Break, vec!["body".to_string()])
+/// ]);
+/// ```
+pub fn render_with(
+  doc: &Doc,
+  options: &RenderOptions
+) -> RenderOutput {
+  let mut renderer = Renderer::new(doc, *options);
+  let mut result = String::new();
+  renderer._run(&mut Plain(&mut result))
+    .expect("Writing to a String can not fail");
+  RenderOutput { text: result, lines: renderer.lines }
+}
+
+/// The break decisions of the last rendering of a document by `render_stable`, which biases the next rendering of the document towards them.
+///
+/// A memory is meant for renderings of one document; the decisions are recalled by the order in which they are made, so recalling the decisions of another document only biases rendering where the two agree in shape.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BreakMemory {
+  verdicts: Vec<Verdict>
+}
+
+impl BreakMemory {
+  /// Constructs a new empty memory, with which a document renders as with `render_with`.
+  #[must_use]
+  pub fn new() -> Self {
+    BreakMemory::default()
+  }
+
+  /// Forgets the decisions of the last rendering.
+  pub fn clear(&mut self) {
+    self.verdicts.clear()
+  }
+}
+
+/// A function for rendering a document again with hysteresis, e.g. on every resize of an editor, takes a `&Doc`, render options and the memory of the last rendering, and gives a `RenderOutput`.
+///
+/// With a `hysteresis` render option of `n` columns, a seq or composition that was kept flat in the last rendering stays flat until it overflows the available width by more than `n`, and one that was broken stays broken until it fits with more than `n` columns to spare, such that a document measuring close to the width does not flip between breaking and not as the width changes by a column.
+/// The decisions of the rendering are then kept in the memory for the next one.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, grp, compile, render_with, render_stable, BreakMemory, RenderOptions};
+///
+/// let document = compile(grp(comp(text("foo"), text("bar"), true, false)));
+/// let options = |width| RenderOptions { width: width, hysteresis: 2, ..RenderOptions::default() };
+/// let mut memory = BreakMemory::new();
+/// assert_eq!(render_stable(&document, &options(7), &mut memory).text, "foo bar");
+/// assert_eq!(render_with(&document, &options(6)).text, "foo\nbar");
+/// assert_eq!(render_stable(&document, &options(6), &mut memory).text, "foo bar");
+/// assert_eq!(render_stable(&document, &options(4), &mut memory).text, "foo\nbar");
+/// assert_eq!(render_stable(&document, &options(7), &mut memory).text, "foo\nbar");
+/// assert_eq!(render_stable(&document, &options(10), &mut memory).text, "foo bar");
+/// ```
+pub fn render_stable(
+  doc: &Doc,
+  options: &RenderOptions,
+  memory: &mut BreakMemory
+) -> RenderOutput {
+  let mut renderer = Renderer::_recalling(doc, *options, &memory.verdicts);
+  renderer.verdicts = Some(Vec::new());
+  let mut result = String::new();
+  renderer._run(&mut Plain(&mut result))
+    .expect("Writing to a String can not fail");
+  let lines = renderer.lines;
+  let verdicts = renderer.verdicts.unwrap_or_default();
+  memory.verdicts = verdicts;
+  RenderOutput { text: result, lines }
+}
+
+static DEFAULT_OPTIONS: OnceLock<RenderOptions> = OnceLock::new();
+
+/// A function for setting the default render options of the application, which are used by `format_layout` and as the base of `RenderOptions::from_env`.
+///
+/// The default options can be set once, typically at startup, and are shared by all threads; setting them again gives back the given options as an error.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, set_default_options, default_options, format_layout, RenderOptions};
+///
+/// assert_eq!(default_options(), RenderOptions::default());
+/// let options = RenderOptions { width: 4, ..RenderOptions::default() };
+/// assert_eq!(set_default_options(options), Ok(()));
+/// assert_eq!(set_default_options(RenderOptions::default()), Err(RenderOptions::default()));
+/// assert_eq!(default_options(), options);
+///
+/// let layout = comp(text("foo"), text("bar"), true, false);
+/// assert_eq!(format_layout(layout), "foo\nbar");
+/// ```
+pub fn set_default_options(
+  options: RenderOptions
+) -> Result<(), RenderOptions> {
+  DEFAULT_OPTIONS.set(options)
+}
+
+/// A function giving the default render options of the application, as set by `set_default_options`, or `RenderOptions::default()` if they have not been set.
+///
+/// # Examples
+/// ```
+/// use typeset::{default_options, RenderOptions};
+///
+/// assert_eq!(default_options(), RenderOptions::default());
+/// ```
+pub fn default_options() -> RenderOptions {
+  DEFAULT_OPTIONS.get().copied().unwrap_or_default()
+}
+
+/// A function for compiling and rendering a layout with the default render options of the application, takes a `Box<Layout>` and gives a `String`.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, format_layout};
+///
+/// let layout = comp(text("foo"), text("bar"), true, false);
+/// assert_eq!(format_layout(layout), "foo bar");
+/// ```
+pub fn format_layout(
+  layout: Box<Layout>
+) -> String {
+  render_with(&_compile_default(layout), &default_options()).text
+}
+
+/// A function for explaining the line breaks of a rendered document, takes a `&Doc` and render options, and gives the break decisions made by the renderer in the order they were made.
+///
+/// Each seq is measured to its end, and each composition is measured to the next composition that could be broken; the measured column is compared against the available width, and the verdict tells whether it fit, overflowed, or whether the composition was broken by its enclosing seq.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, seq_named, compile, render, explain, RenderOptions, BreakDecision, BreakKind, Verdict};
+///
+/// let layout = seq_named("args".to_string(), comp(
+///   text("foo,".to_string()),
+///   comp(
+///     text("bar,".to_string()),
+///     text("baz".to_string()),
+///     true, false
+///   ),
+///   true, false
+/// ));
+/// let document = compile(layout);
+/// assert_eq!(render(document.clone(), 2, 8), "foo,\nbar,\nbaz");
+/// let decisions = explain(&document, &RenderOptions::new(2, 8));
+/// assert_eq!(decisions[0], BreakDecision {
+///   kind: BreakKind::Seq,
+///   name: Some("args".to_string()),
+///   line: 0, column: 0, measured: 13, available: 8,
+///   verdict: Verdict::Overflow
+/// });
+/// assert_eq!(decisions[1].verdict, Verdict::Seq);
+/// assert_eq!(decisions.len(), 3);
+/// ```
+pub fn explain(
+  doc: &Doc,
+  options: &RenderOptions
+) -> Vec<BreakDecision> {
+  let mut renderer = Renderer::new(doc, *options);
+  renderer.decisions = Some(Vec::new());
+  let mut result = String::new();
+  renderer._run(&mut Plain(&mut result))
+    .expect("Writing to a String can not fail");
+  renderer.decisions.unwrap_or_default()
+}
+
+/// The policy for measuring tab characters in text, either counting each tab as a fixed width, or expanding each tab into spaces up to the next multiple of the given tab stop.
+///
+/// Expanded tabs depend on the column at which the text is rendered, and are written as spaces; tabs counted as a fixed width are written as is.
+/// The default policy counts a tab as width 1, like any other character.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, compile, render_with, RenderOptions, TabPolicy};
+///
+/// let layout = comp(
+///   text("a".to_string()),
+///   text("\tb".to_string()),
+///   false, false
+/// );
+/// let document = compile(layout);
+/// let options = RenderOptions { tabs: TabPolicy::Expand(4), ..RenderOptions::default() };
+/// assert_eq!(render_with(&document, &options).text, "a   b");
+///
+/// let layout = comp(
+///   text("\tfoo".to_string()),
+///   text("bar".to_string()),
+///   true, false
+/// );
+/// let document = compile(layout);
+/// let options = RenderOptions { width: 8, tabs: TabPolicy::Width(4), ..RenderOptions::default() };
+/// assert_eq!(render_with(&document, &options).text, "\tfoo\nbar");
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TabPolicy {
+  Width(usize),
+  Expand(usize)
+}
+
+impl Default for TabPolicy {
+  fn default() -> Self {
+    TabPolicy::Width(1)
+  }
+}
+
+/// The scope of pack marks, either kept for the whole document, or forgotten at the start of every line, such that a pack spanning several lines is marked anew on each of them.
+///
+/// Only lines separated by line layouts start a new scope; the broken compositions of a line keep the marks of the line.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, line, pack, compile, render_with, RenderOptions, PackScope};
+///
+/// let layout = comp(
+///   text("let".to_string()),
+///   pack(line(
+///     text("x".to_string()),
+///     text("y".to_string())
+///   )),
+///   true, false
+/// );
+/// let document = compile(layout);
+/// let options = RenderOptions::default();
+/// assert_eq!(render_with(&document, &options).text, "let x\n    y");
+/// let options = RenderOptions { pack_scope: PackScope::Line, ..RenderOptions::default() };
+/// assert_eq!(render_with(&document, &options).text, "let x\ny");
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum PackScope {
+  #[default]
+  Document,
+  Line
+}
+
+/// How far a group that follows a breakable composition is measured, when deciding whether to break the composition.
+///
+/// By default a group is measured to its own end, so the composition before it is kept when the group fits, even if the text after the group then overflows on the same line.
+/// Measuring to the end of the line instead keeps the composition only when everything up to the next forced linebreak fits as well, as Oppen's printer does.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, grp, compile, render_with, RenderOptions, GroupMeasure};
+///
+/// let layout = comp(
+///   text("foo"),
+///   comp(
+///     grp(comp(text("bar"), text("baz"), true, false)),
+///     text(";;;;"),
+///     false, false
+///   ),
+///   true, false
+/// );
+/// let document = compile(layout);
+/// let options = RenderOptions { width: 12, ..RenderOptions::default() };
+/// assert_eq!(render_with(&document, &options).text, "foo bar baz\n;;;;");
+/// let options = RenderOptions { group_measure: GroupMeasure::LineEnd, ..options };
+/// assert_eq!(render_with(&document, &options).text, "foo\nbar baz;;;;");
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum GroupMeasure {
+  #[default]
+  End,
+  LineEnd
+}
+
+/// What to do when the indentation of a line reaches the buffer width, such that no text fits on the line.
+///
+/// With `Strict`, the buffer width is kept, every breakable composition past it is broken, and the output degenerates to one text per line.
+/// With `MinContent(n)`, the width of a line is widened to at least its indentation plus `n` columns, such that deeply indented lines still have room for `n` columns of text and break as they would at a shallow indentation.
+/// Lines indented short of the buffer width by at least `n` columns are rendered the same under either policy.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, nest, compile, render_with, RenderOptions, NarrowPolicy};
+///
+/// let layout = nest(comp(
+///   text("foo"),
+///   comp(text("bar"), text("baz"), true, false),
+///   true, false
+/// ));
+/// let document = compile(layout);
+/// let options = RenderOptions::new(8, 4);
+/// assert_eq!(render_with(&document, &options).text, "        foo\n        bar\n        baz");
+/// let options = RenderOptions { narrow: NarrowPolicy::MinContent(8), ..options };
+/// assert_eq!(render_with(&document, &options).text, "        foo bar\n        baz");
+/// let options = RenderOptions { narrow: NarrowPolicy::MinContent(8), ..RenderOptions::new(8, 40) };
+/// assert_eq!(render_with(&document, &options).text, "        foo bar baz");
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum NarrowPolicy {
+  #[default]
+  Strict,
+  MinContent(usize)
+}
+
+/// A named combination of render options, for good output without setting every option, see `RenderOptions::preset`.
+///
+/// - `Compact` renders wide and dense, a buffer width of 100, a tab indentation size of 2, no blank lines, and groups measured to their end, such that as little as possible is broken.
+/// - `Expanded` renders narrow and airy, a buffer width of 80, a tab indentation size of 4, a ribbon width of 40, at most one blank line in a row, and groups measured to the end of the line, such that deeply indented and crowded lines are broken.
+/// - `Aligned` renders for alignment, a buffer width of 80, a tab indentation size of 2, no ribbon width, at most one blank line in a row, pack marks kept for the whole document, and groups measured to the end of the line, such that packs line up across lines and a line is broken before a group rather than after it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Preset {
+  Compact,
+  Expanded,
+  Aligned
+}
+
+/// Options for rendering documents, the tab indentation size, the output buffer target width, the ribbon width, the policy for lines indented past the buffer width, the limit on consecutive blank lines, whether to collect per-line metadata, the policy for tab characters in text, the scope of pack marks, how far groups are measured, whether styling backends may use color, whether indentation is emitted lazily, the tolerance of break hints, and the hysteresis of break decisions.
+///
+/// The ribbon width limits the columns of a line past the current indentation level, so that deeply indented lines break as readily as shallow ones.
+/// With lazy indentation, the indentation of a line is only emitted once the line is given a non-empty text, such that lines without any text are rendered empty rather than as indentation only.
+/// The hint tolerance is the number of columns by which the hint of a group, see `grp_hint`, may widen or narrow the available width of its compositions.
+/// The hysteresis is the number of columns by which a break decision may keep the verdict of the last rendering, and only applies when rendering again with `render_stable`.
+/// The policy for narrow lines, see `NarrowPolicy`, decides whether a line indented past the buffer width is given room for its text.
+/// The default options are a tab indentation size of 2, a buffer width of 80, no ribbon width, a strict buffer width, no limit on blank lines, no line metadata, tabs counted as width 1, pack marks kept for the whole document, groups measured to their end, color enabled, lazy indentation, a hint tolerance of 4, and no hysteresis.
+/// Options may be added in minor releases, with defaults that render as before, so the options are built with struct update syntax from `RenderOptions::new` or `RenderOptions::default()`.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, nest, compile, render_with, Doc, DocObj, RenderOptions};
+///
+/// let options = RenderOptions { width: 40, ..RenderOptions::default() };
+///
+/// let layout = nest(nest(comp(
+///   text("foo".to_string()),
+///   text("bar".to_string()),
+///   true, false
+/// )));
+/// let document = compile(layout);
+/// let options = RenderOptions { ribbon: Some(6), ..RenderOptions::default() };
+/// assert_eq!(render_with(&document, &options).text, "    foo\n    bar");
+///
+/// let document = Doc::from_plain_text("foo\n\n\n\nbar");
+/// let options = RenderOptions { max_blank_lines: Some(1), ..RenderOptions::default() };
+/// assert_eq!(render_with(&document, &options).text, "foo\n\nbar");
+///
+/// let empty = Box::new(DocObj::Nest(Box::new(DocObj::Text(String::new(), None))));
+/// let document = Doc::Break(empty, Doc::from_plain_text("foo"));
+/// assert_eq!(render_with(&document, &RenderOptions::default()).text, "\nfoo");
+/// let options = RenderOptions { lazy_indent: false, ..RenderOptions::default() };
+/// assert_eq!(render_with(&document, &options).text, "  \nfoo");
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RenderOptions {
+  pub tab: usize,
+  pub width: usize,
+  pub ribbon: Option<usize>,
+  pub narrow: NarrowPolicy,
+  pub max_blank_lines: Option<usize>,
+  pub line_info: bool,
+  pub tabs: TabPolicy,
+  pub pack_scope: PackScope,
+  pub group_measure: GroupMeasure,
+  pub color: bool,
+  pub lazy_indent: bool,
+  pub hint_tolerance: usize,
+  pub hysteresis: usize
+}
+
+impl RenderOptions {
+  /// Constructs new render options from a tab indentation size and a buffer width.
+  pub fn new(
+    tab: usize,
+    width: usize
+  ) -> Self {
+    RenderOptions {
+      tab,
+      width,
+      ribbon: None,
+      narrow: NarrowPolicy::default(),
+      max_blank_lines: None,
+      line_info: false,
+      tabs: TabPolicy::default(),
+      pack_scope: PackScope::default(),
+      group_measure: GroupMeasure::default(),
+      color: true,
+      lazy_indent: true,
+      hint_tolerance: DEFAULT_HINT_TOLERANCE,
+      hysteresis: DEFAULT_HYSTERESIS
+    }
+  }
+
+  /// Constructs render options from the environment, for end users to tweak the output of tools without flags.
+  ///
+  /// The tab indentation size and buffer width are read from the `TYPESET_TAB` and `TYPESET_WIDTH` environment variables, and color is disabled when the `NO_COLOR` environment variable is set to a non-empty value; unset or invalid variables keep the default options of the application, see `set_default_options`.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::RenderOptions;
+  ///
+  /// std::env::set_var("TYPESET_WIDTH", "40");
+  /// std::env::set_var("TYPESET_TAB", "four");
+  /// let options = RenderOptions::from_env();
+  /// assert_eq!(options.width, 40);
+  /// assert_eq!(options.tab, 2);
+  /// ```
+  pub fn from_env() -> Self {
+    fn _var(name: &str) -> Option<usize> {
+      std::env::var(name).ok()?.trim().parse().ok()
+    }
+    let defaults = default_options();
+    RenderOptions {
+      tab: _var("TYPESET_TAB").unwrap_or(defaults.tab),
+      width: _var("TYPESET_WIDTH").unwrap_or(defaults.width),
+      color: defaults.color && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()),
+      ..defaults
+    }
+  }
+
+  /// Constructs render options from a preset, with the options the preset does not set at their defaults.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{text, comp, nest, compile, render_with, RenderOptions, Preset};
+  ///
+  /// let layout = comp(
+  ///   text("foo".to_string()),
+  ///   nest(comp(text("bar".to_string()), text("baz".to_string()), true, false)),
+  ///   true, false
+  /// );
+  /// let document = compile(layout);
+  /// let options = RenderOptions::preset(Preset::Compact);
+  /// assert_eq!(options.width, 100);
+  /// assert_eq!(render_with(&document, &options).text, "foo bar baz");
+  /// let options = RenderOptions { width: 8, ..RenderOptions::preset(Preset::Expanded) };
+  /// assert_eq!(render_with(&document, &options).text, "foo bar\n    baz");
+  /// ```
+  pub fn preset(
+    preset: Preset
+  ) -> Self {
+    match preset {
+      Preset::Compact => RenderOptions {
+        max_blank_lines: Some(0),
+        ..RenderOptions::new(2, 100)
+      },
+      Preset::Expanded => RenderOptions {
+        ribbon: Some(40),
+        max_blank_lines: Some(1),
+        group_measure: GroupMeasure::LineEnd,
+        ..RenderOptions::new(4, 80)
+      },
+      Preset::Aligned => RenderOptions {
+        max_blank_lines: Some(1),
+        pack_scope: PackScope::Document,
+        group_measure: GroupMeasure::LineEnd,
+        ..RenderOptions::default()
+      }
+    }
+  }
+}
+
+impl Default for RenderOptions {
+  fn default() -> Self {
+    RenderOptions::new(DEFAULT_TAB, DEFAULT_WIDTH)
+  }
+}
+
+/// A document paired with render options, which renders the document when displayed.
+///
+/// A width given in the format specification overrides the buffer width of the options.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, compile, RenderOptions};
+///
+/// let layout = comp(
+///   text("foo".to_string()),
+///   text("bar".to_string()),
+///   true, false
+/// );
+/// let document = compile(layout);
+/// let options = RenderOptions::default();
+/// assert_eq!(format!("{}", document.display(options)), "foo bar");
+/// assert_eq!(format!("{:4}", document.display(options)), "foo\nbar");
+/// ```
+#[derive(Debug, Copy, Clone)]
+pub struct Rendered<'a>(&'a Doc, RenderOptions);
+
+/// An iterator over the rendered lines of a document, see `Doc::render_lines`.
+///
+/// Skipping lines with `nth` renders them without output, such that pack marks carry over as in a full render.
+#[derive(Debug, Clone)]
+pub struct RenderedLines<'a> {
+  renderer: Renderer<'a>,
+  remaining: usize
+}
+
+impl<'a> Iterator for RenderedLines<'a> {
+  type Item = String;
+
+  fn next(&mut self) -> Option<String> {
+    if self.remaining == 0 { return None }
+    self.remaining -= 1;
+    let mut result = String::new();
+    self.renderer._run_line(&mut Plain(&mut result))
+      .expect("Writing to a String can not fail");
+    Some(result)
+  }
+
+  fn nth(&mut self, index: usize) -> Option<String> {
+    for _ in 0..index.min(self.remaining) {
+      self.remaining -= 1;
+      self.renderer._run_line(&mut Discard)
+        .expect("Discarding output can not fail");
+    }
+    self.next()
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    (self.remaining, Some(self.remaining))
+  }
+}
+
+impl<'a> ExactSizeIterator for RenderedLines<'a> {}
+
+impl<'a> fmt::Display for Rendered<'a> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let Rendered(doc, options) = self;
+    let width = f.width().unwrap_or(options.width);
+    _render(doc, RenderOptions { width, ..*options }, f)
+  }
+}
+
+impl Doc {
+  /// Gives the lines of the document in order, each the object laid out on the line, or `None` for an empty line.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{text, line, compile};
+  ///
+  /// let document = compile(line(text("foo"), line(text(""), text("bar"))));
+  /// let lines = document.lines();
+  /// assert_eq!(lines.len(), 3);
+  /// assert!(lines[1].is_none());
+  /// ```
+  pub fn lines(&self) -> Vec<Option<&DocObj>> {
+    let mut result = Vec::new();
+    let mut cursor = self;
+    loop {
+      cursor = match cursor {
+        Doc::EOD => return result,
+        Doc::Empty(doc1) => {
+          result.push(None);
+          doc1
+        }
+        Doc::Break(obj, doc1) => {
+          result.push(Some(&**obj));
+          doc1
         }
-        box DocObj::Comp(left, right, pad) => {
-          let state1 = _visit_obj(mem, left, state);
-          let state2 = _inc_pos(if *pad { 1 } else { 0 }, state1);
-          let head = state2.head;
-          let state3 = State { head: false, ..state2 };
-          let state4 = _visit_obj(mem, right, state3);
-          State { head: head, ..state4 }
+        Doc::Line(obj) => {
+          result.push(Some(&**obj));
+          return result
         }
       }
     }
-    fn _visit_fix<'b, 'a: 'b>(
-      fix: &Box<DocObjFix>,
-      state: State<'a>
-    ) -> State<'a> {
-      match fix {
-        box DocObjFix::Text(data) =>
-          _inc_pos(data.len(), state),
-        box DocObjFix::Comp(left, right, pad) => {
-          let state1 = _visit_fix(left, state);
-          let state2 = _inc_pos(if *pad { 1 } else { 0 }, state1);
-          _visit_fix(right, state2)
+  }
+
+  /// Gives the number of lines of the document, as in `lines`, without collecting them.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{text, line, compile};
+  ///
+  /// let document = compile(line(text("foo"), line(text(""), text("bar"))));
+  /// assert_eq!(document.line_count(), 3);
+  /// ```
+  pub fn line_count(&self) -> usize {
+    let mut result = 0;
+    let mut cursor = self;
+    loop {
+      cursor = match cursor {
+        Doc::EOD => return result,
+        Doc::Empty(doc1) | Doc::Break(_, doc1) => {
+          result += 1;
+          doc1
         }
+        Doc::Line(_) => return result + 1
       }
     }
-    let state1 = _visit_obj(mem, obj, state);
-    state1.pos
   }
-  fn _next_comp<'b, 'a: 'b>(
-    mem: &'b Bump,
-    obj: &Box<DocObj>,
-    state: State<'a>
+
+  /// Gives an estimate of the length in bytes of the document rendered with the given options, for reserving the output buffer, see `render_into`.
+  ///
+  /// The estimate is the length of the output when no composition is broken, the texts, paddings and forced linebreaks of the document, with tabs expanded by the `tabs` render option and blank lines limited by the `max_blank_lines` render option; broken compositions only add their indentation to it.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{text, comp, line, nest, compile, render, RenderOptions};
+  ///
+  /// let layout = line(comp(text("foo"), nest(text("bar")), true, false), text("baz"));
+  /// let document = compile(layout);
+  /// let options = RenderOptions::default();
+  /// assert_eq!(document.estimated_output_len(&options), 11);
+  /// assert_eq!(render(document.clone(), 2, 80).len(), 11);
+  /// assert_eq!(render(document, 2, 4).len(), 13);
+  /// ```
+  pub fn estimated_output_len(
+    &self,
+    options: &RenderOptions
   ) -> usize {
-    fn _visit_obj<'b, 'a: 'b>(
-      mem: &'b Bump,
-      obj: &Box<DocObj>,
-      state: State<'a>
-    ) -> State<'b> {
+    let text = |data: &str| match options.tabs {
+      TabPolicy::Width(_) => data.len(),
+      TabPolicy::Expand(n) => data.len() + data.matches('\t').count() * n.saturating_sub(1)
+    };
+    let mut result = 0;
+    let mut lines: usize = 0;
+    let mut blank_lines = 0;
+    let mut objs: Vec<&DocObj> = Vec::new();
+    for line in self.lines() {
+      match line {
+        None => {
+          if options.max_blank_lines.is_some_and(|max| max <= blank_lines) { continue }
+          blank_lines += 1
+        }
+        Some(obj) => {
+          blank_lines = 0;
+          objs.push(obj)
+        }
+      }
+      lines += 1
+    }
+    let mut fixes: Vec<&DocObjFix> = Vec::new();
+    while let Some(obj) = objs.pop() {
       match obj {
-        box DocObj::Text(data) =>
-          _inc_pos(data.len(), state),
-        box DocObj::Fix(fix) =>
-          _visit_fix(mem, fix, state),
-        box DocObj::Grp(obj1) => {
-          let head = state.head;
-          if head { _visit_obj(mem, obj1, state) } else {
-          let obj_end_pos = _measure(mem, &obj1, state);
-          State { pos: obj_end_pos, ..state }}
-        }
-        box DocObj::Seq(obj1) =>
-          _visit_obj(mem, obj1, state),
-        box DocObj::Nest(obj1) => {
-          let lvl = state.lvl;
-          let state1 = _indent(state.tab, state);
-          let offset = _get_offset(state1);
-          let state2 = _inc_pos(offset, state1);
-          let state3 = _visit_obj(mem, obj1, state2);
-          State { lvl: lvl, ..state3 }
-        }
-        box DocObj::Pack(index, obj1) => {
-          let index = *index as usize;
-          let lvl = state.lvl;
-          let marks = state.marks;
-          match marks.lookup(&total, index) {
-            None => {
-              let pos = state.pos;
-              let marks1 = marks.insert(mem, &total, index, pos);
-              let state1 = State { marks: marks1, ..state };
-              let state2 = State { lvl: max(lvl, pos), ..state1 };
-              let state3 = _visit_obj(mem, obj1, state2);
-              State { lvl: lvl, ..state3 }
-            }
-            Some(lvl1) => {
-              let state1 = State { lvl: max(lvl, lvl1), ..state };
-              let offset = _get_offset(state1);
-              let state2 = _inc_pos(offset, state1);
-              let state3 = _visit_obj(mem, obj1, state2);
-              State { lvl: lvl, ..state3 }
-            }
-          }
+        DocObj::Text(data, _) => result += text(data),
+        DocObj::Marker(_) => (),
+        DocObj::Fix(fix) => fixes.push(fix),
+        DocObj::Grp(_, obj1) | DocObj::Seq(_, obj1) |
+        DocObj::Nest(obj1) | DocObj::Pack(_, obj1) | DocObj::PackAt(_, obj1) =>
+          objs.push(obj1),
+        DocObj::Comp(left, right, pad) => {
+          if *pad { result += 1 }
+          objs.push(left);
+          objs.push(right)
         }
-        box DocObj::Comp(left, _right, _pad) =>
-          _visit_obj(mem, left, state)
       }
     }
-    fn _visit_fix<'b, 'a: 'b>(
-      mem: &'b Bump,
-      fix: &Box<DocObjFix>,
-      state: State<'a>
-    ) -> State<'a> {
+    while let Some(fix) = fixes.pop() {
       match fix {
-        box DocObjFix::Text(data) =>
-          _inc_pos(data.len(), state),
-        box DocObjFix::Comp(left, right, pad) => {
-          let state1 = _visit_fix(mem, left, state);
-          let state2 = _inc_pos(if *pad { 1 } else { 0 }, state1);
-          _visit_fix(mem, right, state2)
+        DocObjFix::Text(data, _) => result += text(data),
+        DocObjFix::Marker(_) => (),
+        DocObjFix::Comp(left, right, pad) => {
+          if *pad { result += 1 }
+          fixes.push(left);
+          fixes.push(right)
         }
       }
     }
-    let state1 = _visit_obj(mem, obj, state);
-    state1.pos
+    result + lines.saturating_sub(1)
   }
-  fn _will_fit<'b, 'a: 'b>(
-    mem: &'b Bump,
-    obj: &Box<DocObj>,
-    state: State
-  ) -> bool {
-    let obj_end_pos = _measure(mem, obj, state);
-    obj_end_pos <= state.width
+
+  /// Renders a single line of the document by its index, as in `lines`, or gives `None` if there is no such line.
+  ///
+  /// The lines before it are rendered without output, such that pack marks carry over as in a full render, and the lines after it are not rendered at all; a line may still render over several lines of output where its compositions are broken.
+  /// Every call renders from the start of the document, so use `render_lines` to render several lines in order.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{text, comp, line, compile, render_with, RenderOptions};
+  ///
+  /// let layout = line(
+  ///   text("foo".to_string()),
+  ///   comp(text("bar".to_string()), text("baz".to_string()), true, false)
+  /// );
+  /// let document = compile(layout);
+  /// let options = RenderOptions { width: 4, ..RenderOptions::default() };
+  /// assert_eq!(document.render_line(0, &options).unwrap(), "foo");
+  /// assert_eq!(document.render_line(1, &options).unwrap(), "bar\nbaz");
+  /// assert_eq!(document.render_line(2, &options), None);
+  /// assert_eq!(
+  ///   document.render_line(1, &options),
+  ///   document.render_lines(&options).nth(1)
+  /// );
+  /// ```
+  pub fn render_line(
+    &self,
+    index: usize,
+    options: &RenderOptions
+  ) -> Option<String> {
+    self.render_lines(options).nth(index)
   }
-  fn _should_break<'b, 'a: 'b>(
-    mem: &'b Bump,
-    obj: &Box<DocObj>,
-    state: State
-  ) -> bool {
-    let broken = state.broken;
-    if broken { true } else {
-    let next_comp_pos = _next_comp(mem, obj, state);
-    state.width < next_comp_pos }
+
+  /// Renders the lines of the document in order, as in `lines`, one at a time with a single renderer, such that rendering every line takes as long as rendering the document.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{text, comp, line, compile, render_with, RenderOptions};
+  ///
+  /// let layout = line(
+  ///   text("foo".to_string()),
+  ///   comp(text("bar".to_string()), text("baz".to_string()), true, false)
+  /// );
+  /// let document = compile(layout);
+  /// let options = RenderOptions { width: 4, ..RenderOptions::default() };
+  /// let lines: Vec<String> = document.render_lines(&options).collect();
+  /// assert_eq!(lines, ["foo", "bar\nbaz"]);
+  /// assert_eq!(lines.join("\n"), render_with(&document, &options).text);
+  /// ```
+  pub fn render_lines(
+    &self,
+    options: &RenderOptions
+  ) -> RenderedLines<'_> {
+    RenderedLines {
+      renderer: Renderer::new(self, *options),
+      remaining: self.line_count()
+    }
   }
-  fn _visit_doc<'b, 'a: 'b>(
-    mem: &'b Bump,
-    doc: Box<Doc>,
-    state: State<'a>
-  ) -> (State<'b>, String) {
-    let state1 = _reset(state);
-    match doc {
-      box Doc::EOD =>
-        (state1, "".to_string()),
-      box Doc::Empty(doc1) => {
-        let (state2, doc2) = _visit_doc(mem, doc1, state1);
-        (state2, format!("\n{}", doc2))
-      }
-      box Doc::Break(obj, doc1) => {
-        let (state2, obj1) = _visit_obj(mem, obj, state1, "".to_string());
-        let state3 = _reset(state2);
-        let (state4, doc2) = _visit_doc(mem, doc1, state3);
-        (state4, format!("{}\n{}", obj1, doc2))
+
+  /// Pairs the document with render options for use with `format!` and friends.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{text, compile, RenderOptions};
+  ///
+  /// let document = compile(text("foo".to_string()));
+  /// println!("{}", document.display(RenderOptions::default()));
+  /// ```
+  pub fn display(
+    &self,
+    options: RenderOptions
+  ) -> Rendered<'_> {
+    Rendered(self, options)
+  }
+
+  /// Constructs a document from plain text, which is rendered as is.
+  ///
+  /// The text is split into lines on newlines, and each line is kept as fixed text, including its indentation.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{Doc, render};
+  ///
+  /// let document = Doc::from_plain_text("foo\n  bar\n\nbaz");
+  /// assert_eq!(render(document, 2, 4), "foo\n  bar\n\nbaz");
+  /// ```
+  pub fn from_plain_text(
+    data: &str
+  ) -> Box<Doc> {
+    let mut lines = data.split('\n').rev();
+    let last = lines.next().unwrap_or("");
+    let mut result = Box::new(
+      if last.is_empty() { Doc::EOD } else {
+      Doc::Line(Box::new(DocObj::Fix(Box::new(
+        DocObjFix::Text(last.to_string(), None)
+      ))))}
+    );
+    for line in lines {
+      result = Box::new(
+        if line.is_empty() { Doc::Empty(result) } else {
+        Doc::Break(Box::new(DocObj::Fix(Box::new(
+          DocObjFix::Text(line.to_string(), None)
+        ))), result)}
+      )
+    }
+    result
+  }
+
+  /// Decomposes the document into its lines in order, each the object laid out on the line, or `None` for an empty line, giving up the document without copying its objects.
+  ///
+  /// Unlike `lines`, the parts are the lines of the document as separated by its forced linebreaks, such that a document ending in a forced linebreak has an empty last part, and the empty document has a single empty part; rebuilding the parts with `from_parts` gives the document back.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{null, text, line, compile, render, Doc};
+  ///
+  /// let document = compile(line(text("foo"), line(text("bar"), text("baz"))));
+  /// let parts: Vec<_> = document.into_parts().into_iter()
+  ///   .enumerate()
+  ///   .filter(|(index, _part)| *index != 1)
+  ///   .map(|(_index, part)| part)
+  ///   .collect();
+  /// assert_eq!(render(Doc::from_parts(parts), 2, 80), "foo\nbaz");
+  ///
+  /// let document = compile(line(text("foo"), null()));
+  /// assert_eq!(render(document.clone(), 2, 80), "foo\n");
+  /// let parts = document.into_parts();
+  /// assert_eq!(parts.len(), 2);
+  /// assert!(parts[1].is_none());
+  /// assert_eq!(render(Doc::from_parts(parts), 2, 80), "foo\n");
+  /// ```
+  pub fn into_parts(self) -> Vec<Option<Box<DocObj>>> {
+    let mut result = Vec::new();
+    let mut cursor = self;
+    loop {
+      cursor = match &mut cursor {
+        Doc::EOD => {
+          result.push(None);
+          return result
+        }
+        Doc::Empty(doc1) => {
+          result.push(None);
+          std::mem::replace(&mut **doc1, Doc::EOD)
+        }
+        Doc::Break(obj, doc1) => {
+          result.push(Some(_take_obj(obj)));
+          std::mem::replace(&mut **doc1, Doc::EOD)
+        }
+        Doc::Line(obj) => {
+          result.push(Some(_take_obj(obj)));
+          return result
+        }
       }
-      box Doc::Line(obj) =>
-        _visit_obj(mem, obj, state1, "".to_string())
     }
   }
-  fn _visit_obj<'b, 'a: 'b>(
-    mem: &'b Bump,
-    obj: Box<DocObj>,
-    state: State<'a>,
-    result: String
-  ) -> (State<'b>, String) {
-    match obj {
-      box DocObj::Text(data) => {
-        let state1 = _inc_pos(data.len(), state);
-        (state1, result.clone() + &data)
-      }
-      box DocObj::Fix(fix) =>
-        _visit_fix(mem, fix, state, result),
-      box DocObj::Grp(obj1) => {
-        let broken = state.broken;
-        let state1 = State { broken: false, ..state };
-        let (state2, result1) = _visit_obj(mem, obj1, state1, result.clone());
-        let state3 = State { broken: broken, ..state2 };
-        (state3, result1.clone())
-      }
-      box DocObj::Seq(obj1) =>
-        if _will_fit(mem, &obj1, state) {
-          _visit_obj(mem, obj1, state, result)
-        } else {
-          let broken = state.broken;
-          let state1 = State { broken: true, ..state };
-          let (state2, result1) = _visit_obj(mem, obj1, state1, result.clone());
-          let state3 = State { broken: broken, ..state2 };
-          (state3, result1.clone())
-        }
-      box DocObj::Nest(obj1) => {
-        let lvl = state.lvl;
-        let state1 = _indent(state.tab, state);
-        let offset = _get_offset(state1);
-        let state2 = _inc_pos(offset, state1);
-        let result1 = _pad(offset, result.clone());
-        let (state3, result2) = _visit_obj(mem, obj1, state2, result1.clone());
-        let state4 = State { lvl: lvl, ..state3 };
-        (state4, result2.clone())
-      }
-      box DocObj::Pack(index, obj1) => {
-        let index = index as usize;
-        let lvl = state.lvl;
-        let marks = state.marks;
-        match marks.lookup(&total, index) {
-          None => {
-            let pos = state.pos;
-            let marks1 = marks.insert(mem, &total, index, pos);
-            let state1 = State { marks: marks1, ..state };
-            let state2 = State { lvl: max(lvl, pos), ..state1 };
-            let (state3, result1) = _visit_obj(
-              mem, obj1, state2, result.clone()
-            );
-            let state4 = State { lvl: lvl, ..state3 };
-            (state4, result1.clone())
+
+  /// Constructs a document from its lines in order, as given by `into_parts`, joining them with forced linebreaks.
+  ///
+  /// The lines are not checked, see `validate`; pack marks carry over from line to line as in any document, so dropping or reordering lines can move the marks of the packs spanning them.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{text, line, compile, render, Doc};
+  ///
+  /// let document = compile(line(text("foo"), line(text(""), text("bar"))));
+  /// let parts = document.into_parts().into_iter().rev();
+  /// assert_eq!(render(Doc::from_parts(parts), 2, 80), "bar\n\nfoo");
+  /// assert_eq!(render(Doc::from_parts(Vec::new()), 2, 80), "");
+  /// ```
+  pub fn from_parts(
+    parts: impl IntoIterator<Item = Option<Box<DocObj>>>
+  ) -> Box<Doc> {
+    let mut parts: Vec<Option<Box<DocObj>>> = parts.into_iter().collect();
+    let mut result = Box::new(match parts.pop().flatten() {
+      None => Doc::EOD,
+      Some(obj) => Doc::Line(obj)
+    });
+    while let Some(part) = parts.pop() {
+      result = Box::new(match part {
+        None => Doc::Empty(result),
+        Some(obj) => Doc::Break(obj, result)
+      })
+    }
+    result
+  }
+
+  /// Keeps only the lines of the document, as in `into_parts`, for which the predicate holds, e.g. to strip debug-only lines from a compiled document.
+  ///
+  /// The predicate is given each line in order, the object laid out on the line, or `None` for an empty line.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{text, line, compile, render, DocObj};
+  ///
+  /// let mut document = compile(line(text("foo();"), line(text("debug();"), text("bar();"))));
+  /// document.retain_lines(|obj| !matches!(obj, Some(DocObj::Text(data, _)) if data.starts_with("debug")));
+  /// assert_eq!(render(document, 2, 80), "foo();\nbar();");
+  /// ```
+  pub fn retain_lines(
+    &mut self,
+    mut pred: impl FnMut(Option<&DocObj>) -> bool
+  ) {
+    let parts = std::mem::replace(self, Doc::EOD).into_parts();
+    *self = *Doc::from_parts(parts.into_iter().filter(|part| pred(part.as_deref())))
+  }
+
+  /// Replaces every line of the document, as in `into_parts`, by the line the function gives for it, e.g. to prefix generated lines with a marker.
+  ///
+  /// The function is given each line in order, the object laid out on the line, or `None` for an empty line, and may give `None` to empty the line.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{text, comp, line, compile, render, DocObj, DocObjFix};
+  ///
+  /// let mut document = compile(line(text("foo"), comp(text("bar"), text("baz"), true, false)));
+  /// document.map_lines(|obj| obj.map(|obj| {
+  ///   let marker = Box::new(DocObj::Fix(Box::new(DocObjFix::Text("//".to_string(), None))));
+  ///   Box::new(DocObj::Comp(marker, obj, true))
+  /// }));
+  /// assert_eq!(render(document.clone(), 2, 80), "// foo\n// bar baz");
+  /// assert_eq!(render(document, 2, 8), "// foo\n// bar\nbaz");
+  /// ```
+  pub fn map_lines(
+    &mut self,
+    f: impl FnMut(Option<Box<DocObj>>) -> Option<Box<DocObj>>
+  ) {
+    let parts = std::mem::replace(self, Doc::EOD).into_parts();
+    *self = *Doc::from_parts(parts.into_iter().map(f))
+  }
+
+  /// Checks the structural invariants of the document, as upheld by the documents given by `compile`.
+  ///
+  /// Meant for documents that are constructed or deserialized directly; a document is valid when none of its texts are empty or contain a newline, no pack index is used both by a `Pack` and a `PackAt`, and the annotation markers are balanced.
+  /// Fixed documents can not contain groups or seqs by construction, so they are not checked for them.
+  /// Rendering a document whose pack index is used both by a `Pack` and a `PackAt` fails a debug assertion, rather than silently aligning the packs to the same mark.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{text, compile, Doc, DocObj, DocError};
+  ///
+  /// let document = compile(text("foo".to_string()));
+  /// assert_eq!(document.validate(), Ok(()));
+  ///
+  /// let document = Doc::Line(Box::new(DocObj::Text(String::new(), None)));
+  /// assert_eq!(document.validate(), Err(DocError::EmptyText));
+  /// ```
+  pub fn validate(&self) -> Result<(), DocError> {
+    let mut packs = BTreeMap::new();
+    let mut annotations = Vec::new();
+    let mut docs = vec![self];
+    let mut objs = Vec::new();
+    let text = |data: &str| {
+      if data.is_empty() { return Err(DocError::EmptyText) }
+      if data.contains('\n') { return Err(DocError::NewlineInText(data.to_string())) }
+      Ok(())
+    };
+    let mut marker = |marker: &Marker| {
+      match marker {
+        Marker::Open(annotation) => annotations.push(annotation.clone()),
+        Marker::Close(annotation) =>
+          if annotations.pop().as_ref() != Some(annotation) {
+            return Err(DocError::UnbalancedMarker(marker.clone()))
+          },
+        Marker::PackReset | Marker::PageBreak | Marker::Slot(_) => ()
+      }
+      Ok(())
+    };
+    while let Some(doc) = docs.pop() {
+      match doc {
+        Doc::EOD => (),
+        Doc::Empty(doc1) => docs.push(doc1),
+        Doc::Break(obj, doc1) => {
+          docs.push(doc1);
+          objs.push(Task::Obj(obj))
+        }
+        Doc::Line(obj) => objs.push(Task::Obj(obj))
+      }
+      while let Some(task) = objs.pop() {
+        match task {
+          Task::Obj(DocObj::Text(data, _)) | Task::Fix(DocObjFix::Text(data, _)) =>
+            text(data)?,
+          Task::Obj(DocObj::Marker(marker1)) | Task::Fix(DocObjFix::Marker(marker1)) =>
+            marker(marker1)?,
+          Task::Obj(DocObj::Fix(fix)) => objs.push(Task::Fix(fix)),
+          Task::Obj(DocObj::Grp(_, obj1) | DocObj::Seq(_, obj1) | DocObj::Nest(obj1)) =>
+            objs.push(Task::Obj(obj1)),
+          Task::Obj(obj @ (DocObj::Pack(index, obj1) | DocObj::PackAt(index, obj1))) => {
+            let anchored = matches!(obj, DocObj::PackAt(..));
+            if *packs.entry(*index).or_insert(anchored) != anchored {
+              return Err(DocError::InconsistentPack(*index))
+            }
+            objs.push(Task::Obj(obj1))
+          }
+          Task::Obj(DocObj::Comp(left, right, _)) => {
+            objs.push(Task::Obj(right));
+            objs.push(Task::Obj(left))
           }
-          Some(lvl1) => {
-            let state1 = State { lvl: max(lvl, lvl1), ..state };
-            let offset = _get_offset(state1);
-            let state2 = _inc_pos(offset, state1);
-            let result1 = _pad(offset, result.clone());
-            let (state3, result2) = _visit_obj(
-              mem, obj1, state2, result1.clone()
-            );
-            let state4 = State { lvl: lvl, ..state3 };
-            (state4, result2.clone())
+          Task::Fix(DocObjFix::Comp(left, right, _)) => {
+            objs.push(Task::Fix(right));
+            objs.push(Task::Fix(left))
           }
+          _ => invariant!(task, objs)
         }
       }
-      box DocObj::Comp(left, right, pad) => {
-        let (state1, result1) = _visit_obj(mem, left, state, result);
-        let state2 = _inc_pos(if pad { 1 } else { 0 }, state1);
-        let state3 = State { head: false, ..state2 };
-        if _should_break(mem, &right, state3) {
-          let state2 = _newline(state1);
-          let offset = _get_offset(state2);
-          let state3 = _inc_pos(offset, state2);
-          let result2 = _pad(offset, result1.clone() + "\n");
-          _visit_obj(mem, right, state3, result2)
-        } else {
-          let result2 = _pad(if pad { 1 } else { 0 }, result1.clone());
-          _visit_obj(mem, right, state3, result2)
+    }
+    match annotations.pop() {
+      None => Ok(()),
+      Some(annotation) => Err(DocError::UnbalancedMarker(Marker::Open(annotation)))
+    }
+  }
+
+  /// Appends a document to this document, the appended document starts on a new line.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{text, compile, render, Doc};
+  ///
+  /// let document = compile(text("foo".to_string()))
+  ///   .append(Doc::from_plain_text("  bar"))
+  ///   .append(compile(text("baz".to_string())));
+  /// assert_eq!(render(document, 2, 80), "foo\n  bar\nbaz");
+  /// ```
+  pub fn append(
+    self: Box<Self>,
+    other: Box<Doc>
+  ) -> Box<Doc> {
+    let mut result = self;
+    let mut cursor = &mut result;
+    while matches!(**cursor, Doc::Empty(_) | Doc::Break(_, _)) {
+      cursor = match &mut **cursor {
+        Doc::Empty(doc1) | Doc::Break(_, doc1) => doc1,
+        Doc::EOD | Doc::Line(_) => invariant!()
+      }
+    }
+    match &mut **cursor {
+      Doc::EOD => *cursor = other,
+      Doc::Line(obj) => **cursor = Doc::Break(_take_obj(obj), other),
+      Doc::Empty(_) | Doc::Break(_, _) => invariant!()
+    }
+    result
+  }
+
+  /// Appends a document to this document on a new page, the first line of the appended document is marked with a `Marker::PageBreak`.
+  ///
+  /// The page break is only honored by paginating renderers, other renderers ignore it like any other marker.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{text, compile, render, Doc};
+  ///
+  /// let document = compile(text("foo".to_string()))
+  ///   .append_page(compile(text("bar".to_string())));
+  /// assert_eq!(render(document, 2, 80), "foo\nbar");
+  /// ```
+  pub fn append_page(
+    self: Box<Self>,
+    other: Box<Doc>
+  ) -> Box<Doc> {
+    let mut other1 = other;
+    match &mut *other1 {
+      Doc::EOD => *other1 = Doc::Line(Box::new(DocObj::Marker(Marker::PageBreak))),
+      Doc::Empty(doc1) => *other1 = Doc::Break(Box::new(DocObj::Marker(Marker::PageBreak)), _take_doc(doc1)),
+      Doc::Break(obj, _) | Doc::Line(obj) => _page_break(obj)
+    }
+    self.append(other1)
+  }
+
+  /// Compiles a layout and places it before the first line of the document; the header is laid out on its own, independently of the document.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{text, comp, compile, render};
+  ///
+  /// let document = compile(text("body".to_string()))
+  ///   .with_header(comp(
+  ///     text("Report".to_string()),
+  ///     text("2024".to_string()),
+  ///     true, false
+  ///   ))
+  ///   .with_footer(text("page 1".to_string()));
+  /// assert_eq!(render(document, 2, 80), "Report 2024\nbody\npage 1");
+  /// ```
+  pub fn with_header(
+    self: Box<Self>,
+    layout: Box<Layout>
+  ) -> Box<Doc> {
+    let header = _shift_packs(_compile_default(layout), _next_pack(&self));
+    header.append(self)
+  }
+
+  /// Compiles a layout and places it after the last line of the document; the footer is laid out on its own, independently of the document.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{text, compile, render};
+  ///
+  /// let document = compile(text("body".to_string()))
+  ///   .with_footer(text("the end".to_string()));
+  /// assert_eq!(render(document, 2, 80), "body\nthe end");
+  /// ```
+  pub fn with_footer(
+    self: Box<Self>,
+    layout: Box<Layout>
+  ) -> Box<Doc> {
+    let footer = _shift_packs(_compile_default(layout), _next_pack(&self));
+    self.append(footer)
+  }
+
+  /// Compiles a layout and fills every slot of the given name in the document with it, see `slot`; the rest of the document is kept as compiled.
+  ///
+  /// The filled layout is laid out on its own, and each of its lines is kept on one line; its first line is placed where the slot is, fixed to the text around the slot, and every further line starts a new line, indented as the text of the slot, such that a layout ending with an empty line puts the text after the slot on a line of its own.
+  /// A document without a slot of the name is left unchanged.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{text, comp, line, nest, null, slot, compile, render};
+  ///
+  /// let layout = comp(
+  ///   text("fn foo() {"),
+  ///   nest(line(null(), comp(slot("comments"), text("bar();"), false, false))),
+  ///   false, false
+  /// );
+  /// let layout = line(layout, text("}"));
+  /// let comments = line(text("// baz"), line(text("// qux"), null()));
+  /// let document = compile(layout).fill_slot("comments", comments);
+  /// assert_eq!(render(document, 2, 80), "fn foo() {\n  // baz\n  // qux\n  bar();\n}");
+  /// ```
+  pub fn fill_slot(
+    self: Box<Self>,
+    name: &str,
+    layout: impl Into<Box<Layout>>
+  ) -> Box<Doc> {
+    self.fill_slot_doc(name, _compile_default(layout.into()))
+  }
+
+  /// Fills every slot of the given name in the document with a compiled document, as `fill_slot` does with a layout.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{text, comp, slot, compile, render, Doc};
+  ///
+  /// let layout = comp(text("foo"), slot("note"), false, false);
+  /// let document = compile(layout)
+  ///   .fill_slot_doc("note", Doc::from_plain_text(" // bar"));
+  /// assert_eq!(render(document, 2, 80), "foo // bar");
+  /// ```
+  pub fn fill_slot_doc(
+    self: Box<Self>,
+    name: &str,
+    doc: Box<Doc>
+  ) -> Box<Doc> {
+    let (lines, ended) = _doc_lines(doc);
+    let mut fills: Vec<Option<Box<DocObjFix>>> = lines.into_iter()
+      .map(|line| line.map(_flatten))
+      .collect();
+    if ended || fills.is_empty() { fills.push(None) }
+    let (lines, ended) = _doc_lines(self);
+    let mut result = Vec::new();
+    for line in lines {
+      let Some(obj) = line else { result.push(None); continue };
+      if let [fill] = fills.as_slice() {
+        result.push(_fill_obj(obj, name, fill));
+        continue
+      }
+      let (first, last) = (&fills[0], &fills[fills.len() - 1]);
+      let mut rest = obj;
+      loop {
+        let mut indents = Vec::new();
+        match _split_obj(rest, name, first, last, &mut indents) {
+          Err(obj1) => { result.push(Some(obj1)); break }
+          Ok((left, right)) => {
+            result.push(left);
+            for fill in &fills[1..fills.len() - 1] {
+              result.push(fill.clone().map(|fill| _indented(fill, &indents)))
+            }
+            match right {
+              None => { result.push(None); break }
+              Some(right1) => rest = right1
+            }
+          }
         }
       }
     }
+    _lines_doc(result, ended)
   }
-  fn _visit_fix<'b, 'a: 'b>(
-    mem: &'b Bump,
-    fix: Box<DocObjFix>,
-    state: State<'a>,
-    result: String
-  ) -> (State<'a>, String) {
-    match fix {
-      box DocObjFix::Text(data) => {
-        let state1 = _inc_pos(data.len(), state);
-        (state1, result.clone() + &data)
-      }
-      box DocObjFix::Comp(left, right, pad) => {
-        let (state1, result1) = _visit_fix(mem, left, state, result);
-        let padding = if pad { 1 } else { 0 };
-        let result2 = _pad(padding, result1);
-        let state2 = _inc_pos(padding, state1);
-        _visit_fix(mem, right, state2, result2.clone())
+}
+
+/*
+  Mark the leftmost leaf of a line with a page break, keeping it fixed to
+  the leaf so that the line can not break between them.
+*/
+fn _page_break(
+  obj: &mut DocObj
+) {
+  let mut cursor = obj;
+  while !matches!(*cursor, DocObj::Text(_, _) | DocObj::Marker(_) | DocObj::Fix(_)) {
+    cursor = match cursor {
+      DocObj::Grp(_, obj1) |
+      DocObj::Seq(_, obj1) |
+      DocObj::Nest(obj1) |
+      DocObj::Pack(_, obj1) |
+      DocObj::PackAt(_, obj1) |
+      DocObj::Comp(obj1, _, _) => obj1,
+      DocObj::Text(_, _) | DocObj::Marker(_) | DocObj::Fix(_) => invariant!()
+    }
+  }
+  let fix = match cursor {
+    DocObj::Text(data, width) => Box::new(DocObjFix::Text(std::mem::take(data), *width)),
+    DocObj::Marker(marker) => Box::new(DocObjFix::Marker(std::mem::replace(marker, Marker::PageBreak))),
+    DocObj::Fix(fix) => _take_fix(fix),
+    _ => invariant!()
+  };
+  let marker = Box::new(DocObjFix::Marker(Marker::PageBreak));
+  *cursor = DocObj::Fix(Box::new(DocObjFix::Comp(marker, fix, false)))
+}
+
+/*
+  The lines of a document, each an object or None for an empty line, and
+  whether the last line is ended by a newline
+*/
+fn _doc_lines(
+  doc: Box<Doc>
+) -> (Vec<Option<Box<DocObj>>>, bool) {
+  let mut result = Vec::new();
+  let mut cursor = *doc;
+  loop {
+    cursor = match &mut cursor {
+      Doc::EOD => return (result, true),
+      Doc::Empty(doc1) => {
+        result.push(None);
+        std::mem::replace(&mut **doc1, Doc::EOD)
+      }
+      Doc::Break(obj, doc1) => {
+        result.push(Some(_take_obj(obj)));
+        std::mem::replace(&mut **doc1, Doc::EOD)
+      }
+      Doc::Line(obj) => {
+        result.push(Some(_take_obj(obj)));
+        return (result, false)
+      }
+    }
+  }
+}
+
+/*
+  The inverse of _doc_lines, where an empty last line that is not ended by a
+  newline is dropped, as the newline before it already ends the document
+*/
+fn _lines_doc(
+  lines: Vec<Option<Box<DocObj>>>,
+  ended: bool
+) -> Box<Doc> {
+  let mut lines = lines;
+  let mut result = Box::new(Doc::EOD);
+  if !ended {
+    if let Some(Some(obj)) = lines.pop() { result = Box::new(Doc::Line(obj)) }
+  }
+  for line in lines.into_iter().rev() {
+    result = match line {
+      None => Box::new(Doc::Empty(result)),
+      Some(obj) => Box::new(Doc::Break(obj, result))
+    }
+  }
+  result
+}
+
+/*
+  A line of a document as one fixed object, as a fill of a slot is kept on
+  one line
+*/
+fn _flatten(
+  obj: Box<DocObj>
+) -> Box<DocObjFix> {
+  let mut obj = obj;
+  match &mut *obj {
+    DocObj::Text(data, width) => Box::new(DocObjFix::Text(std::mem::take(data), *width)),
+    DocObj::Marker(marker) => Box::new(DocObjFix::Marker(marker.clone())),
+    DocObj::Fix(fix) => _take_fix(fix),
+    DocObj::Grp(_, obj1) | DocObj::Seq(_, obj1) | DocObj::Nest(obj1) |
+    DocObj::Pack(_, obj1) | DocObj::PackAt(_, obj1) => _flatten(_take_obj(obj1)),
+    DocObj::Comp(left, right, pad) =>
+      Box::new(DocObjFix::Comp(_flatten(_take_obj(left)), _flatten(_take_obj(right)), *pad))
+  }
+}
+
+fn _fix_comp(
+  left: Option<Box<DocObjFix>>,
+  right: Option<Box<DocObjFix>>,
+  pad: bool
+) -> Option<Box<DocObjFix>> {
+  match (left, right) {
+    (Some(left1), Some(right1)) => Some(Box::new(DocObjFix::Comp(left1, right1, pad))),
+    (left1, right1) => left1.or(right1)
+  }
+}
+
+fn _obj_comp(
+  left: Option<Box<DocObj>>,
+  right: Option<Box<DocObj>>,
+  pad: bool
+) -> Option<Box<DocObj>> {
+  match (left, right) {
+    (Some(left1), Some(right1)) => Some(Box::new(DocObj::Comp(left1, right1, pad))),
+    (left1, right1) => left1.or(right1)
+  }
+}
+
+fn _is_slot(
+  marker: &Marker,
+  name: &str
+) -> bool {
+  matches!(marker, Marker::Slot(slot) if slot == name)
+}
+
+/*
+  Replace the slots of a name in a line with a fill, where None removes them
+*/
+fn _fill_fix(
+  fix: Box<DocObjFix>,
+  name: &str,
+  fill: &Option<Box<DocObjFix>>
+) -> Option<Box<DocObjFix>> {
+  let mut fix = fix;
+  match &mut *fix {
+    DocObjFix::Marker(marker) if _is_slot(marker, name) => fill.clone(),
+    DocObjFix::Comp(left, right, pad) =>
+      _fix_comp(_fill_fix(_take_fix(left), name, fill), _fill_fix(_take_fix(right), name, fill), *pad),
+    _ => Some(fix)
+  }
+}
+
+fn _fill_obj(
+  obj: Box<DocObj>,
+  name: &str,
+  fill: &Option<Box<DocObjFix>>
+) -> Option<Box<DocObj>> {
+  let wrap = |obj1: Box<DocObj>, make: &dyn Fn(Box<DocObj>) -> DocObj|
+    _fill_obj(obj1, name, fill).map(|obj2| Box::new(make(obj2)));
+  let mut obj = obj;
+  match &mut *obj {
+    DocObj::Marker(marker) if _is_slot(marker, name) =>
+      fill.clone().map(|fix| Box::new(DocObj::Fix(fix))),
+    DocObj::Fix(fix) =>
+      _fill_fix(_take_fix(fix), name, fill).map(|fix1| Box::new(DocObj::Fix(fix1))),
+    DocObj::Grp(name1, obj1) => wrap(_take_obj(obj1), &|obj2| DocObj::Grp(name1.clone(), obj2)),
+    DocObj::Seq(name1, obj1) => wrap(_take_obj(obj1), &|obj2| DocObj::Seq(name1.clone(), obj2)),
+    DocObj::Nest(obj1) => wrap(_take_obj(obj1), &DocObj::Nest),
+    DocObj::Pack(index, obj1) => wrap(_take_obj(obj1), &|obj2| DocObj::Pack(*index, obj2)),
+    DocObj::PackAt(index, obj1) => wrap(_take_obj(obj1), &|obj2| DocObj::PackAt(*index, obj2)),
+    DocObj::Comp(left, right, pad) =>
+      _obj_comp(_fill_obj(_take_obj(left), name, fill), _fill_obj(_take_obj(right), name, fill), *pad),
+    _ => Some(obj)
+  }
+}
+
+/*
+  The indentation of a slot, the nests and packs around it in its line, from
+  the innermost to the outermost
+*/
+#[derive(Debug, Copy, Clone)]
+enum Indent {
+  Nest,
+  Pack(Index),
+  PackAt(Index)
+}
+
+fn _indented(
+  fix: Box<DocObjFix>,
+  indents: &[Indent]
+) -> Box<DocObj> {
+  indents.iter().fold(Box::new(DocObj::Fix(fix)), |obj, indent| match indent {
+    Indent::Nest => Box::new(DocObj::Nest(obj)),
+    Indent::Pack(index) => Box::new(DocObj::Pack(*index, obj)),
+    Indent::PackAt(index) => Box::new(DocObj::PackAt(*index, obj))
+  })
+}
+
+/*
+  Split a line at the first slot of a name, placing the first line of the
+  fill at the end of the left part and the last line of the fill at the start
+  of the right part; a line without the slot is given back as is
+*/
+fn _split_fix(
+  fix: Box<DocObjFix>,
+  name: &str,
+  first: &Option<Box<DocObjFix>>,
+  last: &Option<Box<DocObjFix>>
+) -> Result<(Option<Box<DocObjFix>>, Option<Box<DocObjFix>>), Box<DocObjFix>> {
+  let mut fix = fix;
+  match &mut *fix {
+    DocObjFix::Marker(marker) if _is_slot(marker, name) =>
+      Ok((first.clone(), last.clone())),
+    DocObjFix::Comp(left, right, pad) =>
+      match _split_fix(_take_fix(left), name, first, last) {
+        Ok((left1, left2)) => Ok((left1, _fix_comp(left2, Some(_take_fix(right)), *pad))),
+        Err(left1) => match _split_fix(_take_fix(right), name, first, last) {
+          Ok((right1, right2)) => Ok((_fix_comp(Some(left1), right1, *pad), right2)),
+          Err(right1) => Err(Box::new(DocObjFix::Comp(left1, right1, *pad)))
+        }
+      },
+    _ => Err(fix)
+  }
+}
+
+fn _split_obj(
+  obj: Box<DocObj>,
+  name: &str,
+  first: &Option<Box<DocObjFix>>,
+  last: &Option<Box<DocObjFix>>,
+  indents: &mut Vec<Indent>
+) -> Result<(Option<Box<DocObj>>, Option<Box<DocObj>>), Box<DocObj>> {
+  type Split = Result<(Option<Box<DocObj>>, Option<Box<DocObj>>), Box<DocObj>>;
+  fn _wrap(
+    split: Split,
+    make: &dyn Fn(Box<DocObj>) -> DocObj
+  ) -> Split {
+    match split {
+      Ok((left, right)) => Ok((
+        left.map(|left1| Box::new(make(left1))),
+        right.map(|right1| Box::new(make(right1)))
+      )),
+      Err(obj) => Err(Box::new(make(obj)))
+    }
+  }
+  let fixed = |fix: Option<Box<DocObjFix>>| fix.map(|fix1| Box::new(DocObj::Fix(fix1)));
+  let mut obj = obj;
+  match &mut *obj {
+    DocObj::Marker(marker) if _is_slot(marker, name) =>
+      Ok((fixed(first.clone()), fixed(last.clone()))),
+    DocObj::Fix(fix) => match _split_fix(_take_fix(fix), name, first, last) {
+      Ok((left, right)) => Ok((fixed(left), fixed(right))),
+      Err(fix1) => Err(Box::new(DocObj::Fix(fix1)))
+    },
+    DocObj::Grp(name1, obj1) =>
+      _wrap(_split_obj(_take_obj(obj1), name, first, last, indents), &|obj2| DocObj::Grp(name1.clone(), obj2)),
+    DocObj::Seq(name1, obj1) =>
+      _wrap(_split_obj(_take_obj(obj1), name, first, last, indents), &|obj2| DocObj::Seq(name1.clone(), obj2)),
+    DocObj::Nest(obj1) => {
+      let split = _split_obj(_take_obj(obj1), name, first, last, indents);
+      if split.is_ok() { indents.push(Indent::Nest) }
+      _wrap(split, &DocObj::Nest)
+    }
+    DocObj::Pack(index, obj1) => {
+      let split = _split_obj(_take_obj(obj1), name, first, last, indents);
+      if split.is_ok() { indents.push(Indent::Pack(*index)) }
+      _wrap(split, &|obj2| DocObj::Pack(*index, obj2))
+    }
+    DocObj::PackAt(index, obj1) => {
+      let split = _split_obj(_take_obj(obj1), name, first, last, indents);
+      if split.is_ok() { indents.push(Indent::PackAt(*index)) }
+      _wrap(split, &|obj2| DocObj::PackAt(*index, obj2))
+    }
+    DocObj::Comp(left, right, pad) =>
+      match _split_obj(_take_obj(left), name, first, last, indents) {
+        Ok((left1, left2)) => Ok((left1, _obj_comp(left2, Some(_take_obj(right)), *pad))),
+        Err(left1) => match _split_obj(_take_obj(right), name, first, last, indents) {
+          Ok((right1, right2)) => Ok((_obj_comp(Some(left1), right1, *pad), right2)),
+          Err(right1) => Err(Box::new(DocObj::Comp(left1, right1, *pad)))
+        }
+      },
+    _ => Err(obj)
+  }
+}
+
+fn _doc_objs(
+  doc: &mut Doc
+) -> Vec<&mut DocObj> {
+  let mut result = Vec::new();
+  let mut cursor = doc;
+  loop {
+    cursor = match cursor {
+      Doc::EOD => return result,
+      Doc::Empty(doc1) => doc1,
+      Doc::Break(obj, doc1) => {
+        result.push(&mut **obj);
+        doc1
+      }
+      Doc::Line(obj) => {
+        result.push(&mut **obj);
+        return result
+      }
+    }
+  }
+}
+
+/*
+  The pack index following all pack indices of a document
+*/
+fn _next_pack(
+  doc: &Doc
+) -> Index {
+  let mut result = 0;
+  let mut docs = vec![doc];
+  let mut objs: Vec<&DocObj> = Vec::new();
+  while let Some(doc) = docs.pop() {
+    match doc {
+      Doc::EOD => (),
+      Doc::Empty(doc1) => docs.push(doc1),
+      Doc::Break(obj, doc1) => {
+        objs.push(obj);
+        docs.push(doc1)
+      }
+      Doc::Line(obj) => objs.push(obj)
+    }
+  }
+  while let Some(obj) = objs.pop() {
+    match obj {
+      DocObj::Text(_, _) | DocObj::Marker(_) | DocObj::Fix(_) => (),
+      DocObj::Grp(_, obj1) | DocObj::Seq(_, obj1) | DocObj::Nest(obj1) =>
+        objs.push(obj1),
+      DocObj::Pack(index, obj1) | DocObj::PackAt(index, obj1) => {
+        result = result.max(index.checked_add(1).expect("A document has at most MAX_PACK_INDEX packs"));
+        objs.push(obj1)
+      }
+      DocObj::Comp(left, right, _) => {
+        objs.push(left);
+        objs.push(right)
+      }
+    }
+  }
+  result
+}
+
+/*
+  Offset the pack indices of a document, so that it can be spliced with
+  another document without sharing pack marks
+*/
+fn _shift_packs(
+  doc: Box<Doc>,
+  offset: Index
+) -> Box<Doc> {
+  let mut result = doc;
+  let mut objs = _doc_objs(&mut result);
+  while let Some(obj) = objs.pop() {
+    match obj {
+      DocObj::Text(_, _) | DocObj::Marker(_) | DocObj::Fix(_) => (),
+      DocObj::Grp(_, obj1) | DocObj::Seq(_, obj1) | DocObj::Nest(obj1) =>
+        objs.push(obj1),
+      DocObj::Pack(index, obj1) | DocObj::PackAt(index, obj1) => {
+        *index = index.checked_add(offset).expect("A document has at most MAX_PACK_INDEX packs");
+        objs.push(obj1)
+      }
+      DocObj::Comp(left, right, _) => {
+        objs.push(left);
+        objs.push(right)
       }
     }
   }
-  let mem = Bump::new();
-  let (_state, result) = _visit_doc(&mem, doc, _make_state(&mem, width, tab));
   result
-}
\ No newline at end of file
+}
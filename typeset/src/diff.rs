@@ -0,0 +1,336 @@
+use std::{
+  fmt,
+  iter,
+  ops::Range
+};
+
+use crate::compiler::{
+  Doc,
+  RenderOptions,
+  render_to
+};
+
+/// The kind of a line in a diff between two renderings.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DiffKind {
+  Equal,
+  Removed,
+  Added
+}
+
+/// A line in a diff between two renderings.
+///
+/// The changes are the byte ranges of the line that differ from the line it replaces, or the whole line if it does not replace a line; they are always empty for equal lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffLine {
+  pub kind: DiffKind,
+  pub text: String,
+  pub changes: Vec<Range<usize>>
+}
+
+/// A line-based diff between two renderings, removed lines are listed before the lines added in their place.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffOutput {
+  pub lines: Vec<DiffLine>
+}
+
+impl DiffOutput {
+  /// Checks whether the two renderings are identical.
+  pub fn is_equal(&self) -> bool {
+    self.lines.iter().all(|line| line.kind == DiffKind::Equal)
+  }
+}
+
+impl fmt::Display for DiffOutput {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    for line in &self.lines {
+      let prefix = match line.kind {
+        DiffKind::Equal => ' ',
+        DiffKind::Removed => '-',
+        DiffKind::Added => '+'
+      };
+      writeln!(f, "{}{}", prefix, line.text)?
+    }
+    Ok(())
+  }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Edit {
+  Equal,
+  Delete,
+  Insert
+}
+
+/*
+  The shortest edit script from a to b, by the linear space variant of the
+  algorithm of Myers: the common prefix and suffix are kept, the rest is split
+  at the middle snake of a shortest path and each half is solved in turn, such
+  that the memory is linear in the length of the inputs however much they
+  differ, and the recursion is logarithmic in the number of edits.
+*/
+fn _edits<T: PartialEq>(
+  a: &[T],
+  b: &[T]
+) -> Vec<Edit> {
+  fn _solve<T: PartialEq>(
+    a: &[T],
+    b: &[T],
+    edits: &mut Vec<Edit>
+  ) {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let (a, b) = (&a[prefix..], &b[prefix..]);
+    let suffix = a.iter().rev().zip(b.iter().rev()).take_while(|(x, y)| x == y).count();
+    let (a, b) = (&a[..a.len() - suffix], &b[..b.len() - suffix]);
+    edits.extend(iter::repeat_n(Edit::Equal, prefix));
+    match _middle(a, b) {
+      Some((x, y)) => {
+        _solve(&a[..x], &b[..y], edits);
+        _solve(&a[x..], &b[y..], edits)
+      }
+      None => {
+        edits.extend(iter::repeat_n(Edit::Delete, a.len()));
+        edits.extend(iter::repeat_n(Edit::Insert, b.len()))
+      }
+    }
+    edits.extend(iter::repeat_n(Edit::Equal, suffix))
+  }
+  /*
+    The point where the forward and the reverse search for a shortest path
+    first overlap, or None if either is empty or they have nothing in common;
+    a and b differ in their first and last items.
+  */
+  fn _middle<T: PartialEq>(
+    a: &[T],
+    b: &[T]
+  ) -> Option<(usize, usize)> {
+    if a.is_empty() || b.is_empty() { return None }
+    let n = a.len() as isize;
+    let m = b.len() as isize;
+    let max_d = (n + m + 1) / 2;
+    let offset = max_d;
+    let length = (2 * max_d + 2) as usize;
+    let mut v1 = vec![-1isize; length];
+    let mut v2 = vec![-1isize; length];
+    v1[(offset + 1) as usize] = 0;
+    v2[(offset + 1) as usize] = 0;
+    let delta = n - m;
+    let front = delta % 2 != 0;
+    let (mut k1_start, mut k1_end, mut k2_start, mut k2_end) = (0, 0, 0, 0);
+    for d in 0..max_d {
+      for k1 in (-d + k1_start..=d - k1_end).step_by(2) {
+        let k1_offset = (offset + k1) as usize;
+        let mut x1 =
+          if k1 == -d || (k1 != d && v1[k1_offset - 1] < v1[k1_offset + 1]) {
+            v1[k1_offset + 1]
+          } else {
+            v1[k1_offset - 1] + 1
+          };
+        let mut y1 = x1 - k1;
+        while x1 < n && y1 < m && a[x1 as usize] == b[y1 as usize] {
+          x1 += 1;
+          y1 += 1
+        }
+        v1[k1_offset] = x1;
+        if n < x1 { k1_end += 2 }
+        else if m < y1 { k1_start += 2 }
+        else if front {
+          let k2_offset = offset + delta - k1;
+          if 0 <= k2_offset && k2_offset < length as isize && v2[k2_offset as usize] != -1 &&
+             n - v2[k2_offset as usize] <= x1 {
+            return Some((x1 as usize, y1 as usize))
+          }
+        }
+      }
+      for k2 in (-d + k2_start..=d - k2_end).step_by(2) {
+        let k2_offset = (offset + k2) as usize;
+        let mut x2 =
+          if k2 == -d || (k2 != d && v2[k2_offset - 1] < v2[k2_offset + 1]) {
+            v2[k2_offset + 1]
+          } else {
+            v2[k2_offset - 1] + 1
+          };
+        let mut y2 = x2 - k2;
+        while x2 < n && y2 < m && a[(n - x2 - 1) as usize] == b[(m - y2 - 1) as usize] {
+          x2 += 1;
+          y2 += 1
+        }
+        v2[k2_offset] = x2;
+        if n < x2 { k2_end += 2 }
+        else if m < y2 { k2_start += 2 }
+        else if !front {
+          let k1_offset = offset + delta - k2;
+          if 0 <= k1_offset && k1_offset < length as isize && v1[k1_offset as usize] != -1 {
+            let x1 = v1[k1_offset as usize];
+            let y1 = offset + x1 - k1_offset;
+            if n - x2 <= x1 { return Some((x1 as usize, y1 as usize)) }
+          }
+        }
+      }
+    }
+    None
+  }
+  let mut edits = Vec::with_capacity(a.len() + b.len());
+  _solve(a, b, &mut edits);
+  edits
+}
+
+fn _changes(
+  old: &str,
+  new: &str
+) -> (Vec<Range<usize>>, Vec<Range<usize>>) {
+  fn _push(
+    ranges: &mut Vec<Range<usize>>,
+    range: Range<usize>
+  ) {
+    match ranges.last_mut() {
+      Some(last) if last.end == range.start => last.end = range.end,
+      _ => ranges.push(range)
+    }
+  }
+  let old_chars: Vec<(usize, char)> = old.char_indices().collect();
+  let new_chars: Vec<(usize, char)> = new.char_indices().collect();
+  let old_data: Vec<char> = old_chars.iter().map(|(_, c)| *c).collect();
+  let new_data: Vec<char> = new_chars.iter().map(|(_, c)| *c).collect();
+  let mut old_ranges = Vec::new();
+  let mut new_ranges = Vec::new();
+  let mut i = 0;
+  let mut j = 0;
+  for edit in _edits(&old_data, &new_data) {
+    match edit {
+      Edit::Equal => {
+        i += 1;
+        j += 1
+      }
+      Edit::Delete => {
+        let (start, c) = old_chars[i];
+        _push(&mut old_ranges, start..start + c.len_utf8());
+        i += 1
+      }
+      Edit::Insert => {
+        let (start, c) = new_chars[j];
+        _push(&mut new_ranges, start..start + c.len_utf8());
+        j += 1
+      }
+    }
+  }
+  (old_ranges, new_ranges)
+}
+
+pub(crate) fn _diff(
+  old: &str,
+  new: &str
+) -> DiffOutput {
+  fn _whole(
+    text: &str
+  ) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    if !text.is_empty() { ranges.push(0..text.len()) }
+    ranges
+  }
+  fn _flush(
+    removed: &mut Vec<&str>,
+    added: &mut Vec<&str>,
+    lines: &mut Vec<DiffLine>
+  ) {
+    let mut added_lines = Vec::new();
+    for (index, old) in removed.iter().enumerate() {
+      let (old_changes, new_changes) = match added.get(index) {
+        Some(new) => _changes(old, new),
+        None => (_whole(old), Vec::new())
+      };
+      lines.push(DiffLine {
+        kind: DiffKind::Removed,
+        text: old.to_string(),
+        changes: old_changes
+      });
+      if let Some(new) = added.get(index) {
+        added_lines.push(DiffLine {
+          kind: DiffKind::Added,
+          text: new.to_string(),
+          changes: new_changes
+        })
+      }
+    }
+    for new in added.iter().skip(removed.len()) {
+      added_lines.push(DiffLine {
+        kind: DiffKind::Added,
+        text: new.to_string(),
+        changes: _whole(new)
+      })
+    }
+    lines.extend(added_lines);
+    removed.clear();
+    added.clear()
+  }
+  let old_lines: Vec<&str> = old.split('\n').collect();
+  let new_lines: Vec<&str> = new.split('\n').collect();
+  let mut lines = Vec::new();
+  let mut removed = Vec::new();
+  let mut added = Vec::new();
+  let mut i = 0;
+  let mut j = 0;
+  for edit in _edits(&old_lines, &new_lines) {
+    match edit {
+      Edit::Equal => {
+        _flush(&mut removed, &mut added, &mut lines);
+        lines.push(DiffLine {
+          kind: DiffKind::Equal,
+          text: old_lines[i].to_string(),
+          changes: Vec::new()
+        });
+        i += 1;
+        j += 1
+      }
+      Edit::Delete => {
+        removed.push(old_lines[i]);
+        i += 1
+      }
+      Edit::Insert => {
+        added.push(new_lines[j]);
+        j += 1
+      }
+    }
+  }
+  _flush(&mut removed, &mut added, &mut lines);
+  DiffOutput { lines }
+}
+
+/// A function for diffing the renderings of two documents, takes two `&Doc`, render options, and gives a `DiffOutput`.
+///
+/// Both documents are rendered with the same options, and the renderings are compared line by line; a removed line that is replaced by an added line has the changed byte ranges within both lines marked.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, compile, render_diff, DiffKind, RenderOptions};
+///
+/// let before = compile(comp(
+///   text("foo".to_string()),
+///   text("bar".to_string()),
+///   true, false
+/// ));
+/// let after = compile(comp(
+///   text("foo".to_string()),
+///   text("baz".to_string()),
+///   true, false
+/// ));
+/// let diff = render_diff(&before, &after, &RenderOptions::default());
+/// assert_eq!(diff.lines[0].kind, DiffKind::Removed);
+/// assert_eq!(diff.lines[0].changes, vec![6..7]);
+/// assert_eq!(diff.lines[1].kind, DiffKind::Added);
+/// assert_eq!(diff.lines[1].changes, vec![6..7]);
+/// assert_eq!(diff.to_string(), "-foo bar\n+foo baz\n");
+/// ```
+pub fn render_diff(
+  doc_a: &Doc,
+  doc_b: &Doc,
+  options: &RenderOptions
+) -> DiffOutput {
+  let mut old = String::new();
+  let mut new = String::new();
+  render_to(doc_a, options, &mut old)
+    .expect("Writing to a String can not fail");
+  render_to(doc_b, options, &mut new)
+    .expect("Writing to a String can not fail");
+  _diff(&old, &new)
+}
@@ -0,0 +1,131 @@
+use std::{
+  any::Any,
+  error::Error,
+  fmt
+};
+
+use crate::compiler::{Marker, Index};
+
+/// The errors that can occur when compiling a layout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CompilerError {
+  /// The compiler panicked, e.g. due to a violated internal invariant; carries the panic message.
+  InternalPanic(String),
+  /// A text contains a newline, which is rejected by the newline policy; carries the text.
+  NewlineInText(String),
+  /// A text contains a tab, which is rejected by the `reject_tabs` compile option; carries the text.
+  TabInText(String),
+  /// A `pack_at` refers to an anchor that is not declared by any enclosing `pack_group`; carries the name of the anchor.
+  UnboundAnchor(String),
+  /// The layout has more nodes than allowed by the `max_nodes` compile limit; carries the limit.
+  TooManyNodes(usize),
+  /// The texts of the layout have more bytes in total than allowed by the `max_text_bytes` compile limit; carries the limit.
+  TextTooLarge(usize),
+  /// The layout is nested deeper than allowed by the `max_depth` compile limit; carries the limit.
+  TooDeep(usize),
+  /// The compiler allocated more bytes than allowed by the `max_allocated_bytes` compile limit, or an allocation failed; carries the number of bytes allocated so far.
+  AllocationFailed(usize),
+  /// The layout has more groups and sequences, or more packs, than can be indexed, see `limits::MAX_SCOPE_INDEX` and `limits::MAX_PACK_INDEX`; carries the largest index.
+  IndexOverflow(Index),
+  /// A composition of a layout validated by `indent_sensitive` could break to a line that is not indented deeper than the line it breaks from; carries the composition.
+  DedentingBreak(String)
+}
+
+/// The violations of the structural invariants of a document, as found by `Doc::validate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DocError {
+  /// A text is empty.
+  EmptyText,
+  /// A text contains a newline; carries the text.
+  NewlineInText(String),
+  /// A pack index is used both by a `Pack` and a `PackAt`; carries the index.
+  InconsistentPack(Index),
+  /// An annotation marker is closed without being opened, closed out of order, or never closed; carries the marker.
+  UnbalancedMarker(Marker)
+}
+
+/// The errors that can occur when loading a formatting profile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProfileError {
+  /// The configuration is not TOML, or not a profile, e.g. it has an unknown key or an invalid value; carries the message of the TOML parser.
+  Toml(String),
+  /// The configuration is not JSON, or not a profile; carries the message of the JSON parser.
+  Json(String)
+}
+
+impl CompilerError {
+  pub(crate) fn from_panic(
+    payload: Box<dyn Any + Send>
+  ) -> Self {
+    let message = match payload.downcast::<String>() {
+      Ok(message) => *message,
+      Err(payload) => match payload.downcast::<&'static str>() {
+        Ok(message) => message.to_string(),
+        Err(_) => "unknown panic payload".to_string()
+      }
+    };
+    CompilerError::InternalPanic(message)
+  }
+}
+
+impl fmt::Display for CompilerError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      CompilerError::InternalPanic(message) =>
+        write!(f, "internal compiler panic: {}", message),
+      CompilerError::NewlineInText(data) =>
+        write!(f, "text contains a newline: {:?}", data),
+      CompilerError::TabInText(data) =>
+        write!(f, "text contains a tab: {:?}", data),
+      CompilerError::UnboundAnchor(name) =>
+        write!(f, "pack anchor is not bound by a pack group: {:?}", name),
+      CompilerError::TooManyNodes(limit) =>
+        write!(f, "layout has more than {} nodes", limit),
+      CompilerError::TextTooLarge(limit) =>
+        write!(f, "layout has more than {} bytes of text", limit),
+      CompilerError::TooDeep(limit) =>
+        write!(f, "layout is nested deeper than {}", limit),
+      CompilerError::AllocationFailed(allocated) =>
+        write!(f, "compiler allocation failed after {} bytes", allocated),
+      CompilerError::IndexOverflow(limit) =>
+        write!(f, "layout has more than {} scopes or packs", limit),
+      CompilerError::DedentingBreak(layout) =>
+        write!(f, "composition could break to a dedented line: {}", layout)
+    }
+  }
+}
+
+impl Error for CompilerError {}
+
+impl fmt::Display for DocError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      DocError::EmptyText =>
+        write!(f, "document contains an empty text"),
+      DocError::NewlineInText(data) =>
+        write!(f, "document text contains a newline: {:?}", data),
+      DocError::InconsistentPack(index) =>
+        write!(f, "pack index {} is used both with and without an anchor", index),
+      DocError::UnbalancedMarker(marker) =>
+        write!(f, "document contains an unbalanced marker: {:?}", marker)
+    }
+  }
+}
+
+impl Error for DocError {}
+
+impl fmt::Display for ProfileError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      ProfileError::Toml(message) =>
+        write!(f, "invalid TOML profile: {}", message),
+      ProfileError::Json(message) =>
+        write!(f, "invalid JSON profile: {}", message)
+    }
+  }
+}
+
+impl Error for ProfileError {}
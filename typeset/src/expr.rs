@@ -0,0 +1,145 @@
+//! Layout helpers for expressions with infix operators of different precedences.
+//!
+//! Operators are registered in an `Operators` table with their precedence and associativity,
+//! and `Operators::binary` then lays out an operator application, putting the operands in
+//! parentheses only where their own precedence requires it.
+
+use std::collections::HashMap;
+
+use crate::compiler::{
+  Layout,
+  text,
+  grp,
+  nest,
+  comp
+};
+
+/// The precedence of atoms, operands that never need parentheses.
+pub const ATOM: u32 = u32::MAX;
+
+/// The associativity of an infix operator.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Assoc {
+  #[default]
+  Left,
+  Right,
+  None
+}
+
+/// A table of infix operators and their precedences, where a higher precedence binds tighter.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, compile, render};
+/// use typeset::expr::{Operators, Assoc, ATOM};
+///
+/// let mut operators = Operators::new();
+/// operators
+///   .infix("+", 1, Assoc::Left)
+///   .infix("*", 2, Assoc::Left);
+///
+/// let sum = operators.binary("+", ATOM, ATOM, text("a".to_string()), text("b".to_string()));
+/// let product = operators.binary("*", 1, ATOM, sum, text("c".to_string()));
+/// assert_eq!(render(compile(product), 2, 80), "(a + b) * c");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Operators {
+  infixes: HashMap<String, (u32, Assoc)>
+}
+
+impl Operators {
+  /// Constructs an empty table of operators.
+  #[must_use]
+  pub fn new() -> Self {
+    Operators { infixes: HashMap::new() }
+  }
+
+  /// Registers an infix operator with its precedence and associativity, replacing an earlier registration of the same operator.
+  pub fn infix(
+    &mut self,
+    op: &str,
+    precedence: u32,
+    assoc: Assoc
+  ) -> &mut Self {
+    self.infixes.insert(op.to_string(), (precedence, assoc));
+    self
+  }
+
+  /// Gives the precedence of a registered operator, the precedence of the expressions it builds.
+  pub fn precedence(&self, op: &str) -> Option<u32> {
+    self.infixes.get(op).map(|(precedence, _assoc)| *precedence)
+  }
+
+  /// Lays out an application of an infix operator to two operands, given the precedences of the operands; use `ATOM` for operands that are not operator applications.
+  ///
+  /// An operand is put in parentheses when it binds looser than the operator, or as tight on the side the operator does not associate to; an operator that is not registered binds looser than any registered operator and does not associate.
+  /// The application is grouped, and when broken puts the operator and right operand on a line of their own, nested under the left operand.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{text, compile, render};
+  /// use typeset::expr::{Operators, Assoc, ATOM};
+  ///
+  /// let mut operators = Operators::new();
+  /// operators.infix("-", 1, Assoc::Left).infix("^", 3, Assoc::Right);
+  ///
+  /// let a = || text("a".to_string());
+  /// let left = operators.binary("-", ATOM, ATOM, a(), a());
+  /// let left1 = operators.binary("-", 1, ATOM, left, a());
+  /// assert_eq!(render(compile(left1), 2, 80), "a - a - a");
+  ///
+  /// let right = operators.binary("-", ATOM, ATOM, a(), a());
+  /// let right1 = operators.binary("-", ATOM, 1, a(), right);
+  /// assert_eq!(render(compile(right1.clone()), 2, 80), "a - (a - a)");
+  /// assert_eq!(render(compile(right1), 2, 8), "a\n  - (a\n    - a)");
+  ///
+  /// let power = operators.binary("^", ATOM, ATOM, a(), a());
+  /// let power1 = operators.binary("^", ATOM, 3, a(), power);
+  /// assert_eq!(render(compile(power1), 2, 80), "a ^ a ^ a");
+  /// ```
+  #[must_use]
+  pub fn binary(
+    &self,
+    op: &str,
+    lhs_prec: u32,
+    rhs_prec: u32,
+    lhs: Box<Layout>,
+    rhs: Box<Layout>
+  ) -> Box<Layout> {
+    let (precedence, assoc) = self.infixes.get(op)
+      .copied()
+      .unwrap_or((0, Assoc::None));
+    let lhs_parens = lhs_prec < precedence ||
+      (lhs_prec == precedence && assoc != Assoc::Left);
+    let rhs_parens = rhs_prec < precedence ||
+      (rhs_prec == precedence && assoc != Assoc::Right);
+    let lhs1 = if lhs_parens { parens(lhs) } else { lhs };
+    let rhs1 = if rhs_parens { parens(rhs) } else { rhs };
+    grp(comp(
+      lhs1,
+      nest(comp(text(op.to_string()), rhs1, true, true)),
+      true, false
+    ))
+  }
+}
+
+/// Puts a layout in parentheses, fixed to its first and last text.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, compile, render};
+/// use typeset::expr::parens;
+///
+/// let layout = parens(text("foo".to_string()));
+/// assert_eq!(render(compile(layout), 2, 80), "(foo)");
+/// ```
+#[must_use]
+pub fn parens(
+  layout: impl Into<Box<Layout>>
+) -> Box<Layout> {
+  comp(
+    comp(text("(".to_string()), layout.into(), false, true),
+    text(")".to_string()),
+    false, true
+  )
+}
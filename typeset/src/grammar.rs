@@ -0,0 +1,16 @@
+/// The grammar of the layout DSL in EBNF, shared by the `layout!` macro of `typeset-parser` and the runtime parser of the test runners.
+///
+/// The operators of each syntactic category are the alternatives of the productions `unary_op` and `binary_op`,
+/// such that tests and tools can be generated from the grammar rather than from either parser.
+///
+/// # Examples
+/// ```
+/// let grammar = typeset::grammar();
+/// let binary = grammar.lines()
+///   .find_map(|line| line.strip_prefix("binary_op = "))
+///   .unwrap();
+/// assert!(binary.contains("\"!&\"") && binary.contains("\"@@\""));
+/// ```
+pub fn grammar() -> &'static str {
+  include_str!("grammar.ebnf")
+}
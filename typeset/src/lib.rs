@@ -2,24 +2,183 @@
 #![allow(dead_code)]
 
 mod util;
-mod order;
 mod list;
 mod avl;
 mod map;
+mod set;
+mod error;
 mod compiler;
+mod arena;
+mod session;
+mod appender;
+mod backend;
+mod theme;
+mod diff;
+mod profile;
+mod testing;
+mod visit;
+mod grammar;
+#[cfg(feature = "term")]
+mod term;
+#[cfg(feature = "tokens")]
+mod tokens;
 
+pub mod compat;
+pub mod order;
+pub mod expr;
+pub mod semantics;
+pub mod tutorial;
+pub mod limits;
+
+#[cfg(feature = "testing")]
+pub mod passes;
+
+#[allow(deprecated)]
 pub use self::compiler::{
   Layout,
   Doc,
+  DocObj,
+  Index,
+  DocObjFix,
   null,
   text,
+  text_with_width,
+  breakable_text,
+  hyphenated_text,
+  Hyphenator,
+  link,
+  tag,
+  Annotation,
+  Hint,
+  Marker,
   fix,
   grp,
+  grp_named,
+  grp_hint,
   seq,
+  seq_named,
   nest,
   pack,
+  pack_reset,
+  slot,
+  pack_group,
+  pack_at,
+  PackAnchor,
+  align_on,
+  chain,
+  method_chain,
+  join_with_soft_lines,
+  join_paragraphs,
+  indent_sensitive,
+  from_indented_text,
+  OperatorStyle,
   line,
   comp,
   compile,
-  render
-};
\ No newline at end of file
+  compile_catch,
+  compile_safe,
+  compile_with,
+  compile_with_memory_limit,
+  CompileOptions,
+  CompileLimits,
+  NewlinePolicy,
+  compile_with_passes,
+  compile_trace,
+  CompileTrace,
+  compile_with_report,
+  CompileReport,
+  PassMask,
+  render,
+  render_to,
+  render_into,
+  render_with,
+  render_stable,
+  BreakMemory,
+  set_default_options,
+  default_options,
+  format_layout,
+  explain,
+  BreakDecision,
+  BreakKind,
+  Verdict,
+  RenderOptions,
+  TabPolicy,
+  NarrowPolicy,
+  PackScope,
+  GroupMeasure,
+  Preset,
+  Rendered,
+  RenderedLines,
+  Renderer,
+  RenderOutput,
+  LineInfo,
+  LineKind
+};
+
+pub use self::arena::{
+  LayoutArena,
+  LayoutId
+};
+
+pub use self::session::Session;
+
+pub use self::grammar::grammar;
+
+pub use self::appender::DocAppender;
+
+pub use self::error::{
+  CompilerError,
+  DocError,
+  ProfileError
+};
+
+pub use self::backend::{
+  Backend,
+  Plain,
+  Ansi,
+  Html,
+  Latex,
+  FontMetrics,
+  render_to_backend,
+  render_ansi,
+  render_html,
+  render_latex,
+  render_svg,
+  render_pages,
+  render_pages_with
+};
+
+pub use self::profile::{
+  TrailingComma,
+  FormatProfile,
+  format_with
+};
+
+pub use self::theme::{
+  Style,
+  Theme
+};
+
+pub use self::diff::{
+  DiffKind,
+  DiffLine,
+  DiffOutput,
+  render_diff
+};
+
+pub use self::testing::with_ruler;
+
+#[doc(hidden)]
+pub use self::testing::{
+  _assert_renders,
+  _assert_fits
+};
+
+#[cfg(feature = "term")]
+pub use self::term::{
+  terminal_width,
+  render_to_terminal
+};
+
+#[cfg(feature = "tokens")]
+pub use self::tokens::from_token_stream;
@@ -0,0 +1,55 @@
+//! The default options and the fixed limits of the compiler and renderer, as named constants.
+//!
+//! The defaults are the values that `RenderOptions::default()` and `CompileLimits::default()` are built from,
+//! and the limits are the sizes past which a layout can not be compiled, such that applications can
+//! refer to them rather than repeat their values.
+//!
+//! # Examples
+//! ```
+//! use typeset::{RenderOptions, CompileLimits};
+//! use typeset::limits::{DEFAULT_TAB, DEFAULT_WIDTH, DEFAULT_MAX_DEPTH};
+//!
+//! let options = RenderOptions::default();
+//! assert_eq!((options.tab, options.width), (DEFAULT_TAB, DEFAULT_WIDTH));
+//! assert_eq!(CompileLimits::default().max_depth, DEFAULT_MAX_DEPTH);
+//! ```
+
+use crate::compiler::Index;
+
+/// The default tab indentation size of the render options.
+pub const DEFAULT_TAB: usize = 2;
+
+/// The default buffer width of the render options.
+pub const DEFAULT_WIDTH: usize = 80;
+
+/// The default number of columns by which the hint of a group may widen or narrow the available width, see `grp_hint`.
+pub const DEFAULT_HINT_TOLERANCE: usize = 4;
+
+/// The default number of columns by which a break decision may keep the verdict of the last rendering, see `render_stable`.
+pub const DEFAULT_HYSTERESIS: usize = 0;
+
+/// The default limit on the number of layout nodes to compile, disabled.
+pub const DEFAULT_MAX_NODES: Option<usize> = None;
+
+/// The default limit on the total number of bytes of text to compile, disabled.
+pub const DEFAULT_MAX_TEXT_BYTES: Option<usize> = None;
+
+/// The default limit on the depth of the layout tree to compile, disabled.
+pub const DEFAULT_MAX_DEPTH: Option<usize> = None;
+
+/// The default limit on the number of bytes allocated by the compiler, disabled.
+pub const DEFAULT_MAX_ALLOCATED_BYTES: Option<usize> = None;
+
+/// The depth of layouts that the test suite compiles and renders, groups and sequences nested in turn with a composition in each; deeper layouts are expected to work, but are not tested.
+///
+/// Layouts this deep are compiled, rendered and dropped on the default stack of a test thread; cloning a layout, or an object of a document, and their `Debug` and `Display` output still recurse on its depth.
+pub const TESTED_DEPTH: usize = 10_000;
+
+/// The largest index of a group or a sequence while compiling a layout; the groups and sequences of a layout are indexed in the order they are compiled, from 0, and compiling a layout with more of them fails with a `CompilerError::IndexOverflow`.
+pub const MAX_SCOPE_INDEX: Index = Index::MAX - 1;
+
+/// The largest index of a pack in a compiled document; the packs of a layout are indexed in the order they are compiled, from 0, and compiling a layout with more of them fails with a `CompilerError::IndexOverflow`.
+pub const MAX_PACK_INDEX: Index = Index::MAX - 1;
+
+/// The largest number of annotated texts, i.e. texts under a `link` or a `tag`, in a layout; compiling a layout with more of them panics.
+pub const MAX_ANNOTATED_TEXTS: usize = u32::MAX as usize;
@@ -1,7 +1,7 @@
 use std::fmt::Debug;
 use bumpalo::Bump;
 
-use crate::util::compose;
+use crate::util::invariant;
 
 #[derive(Debug)]
 pub enum List<'a, T: Copy + Clone + Debug> {
@@ -23,29 +23,79 @@ pub fn cons<'a, T: Copy + Clone + Debug>(
   mem.alloc(List::Cons(items.length() + 1, item, items))
 }
 
+pub fn from_iter<'a, T: Copy + Clone + Debug>(
+  mem: &'a Bump,
+  items: impl IntoIterator<Item = T>
+) -> &'a List<'a, T> {
+  let items: Vec<T> = items.into_iter().collect();
+  items.into_iter().rev().fold(nil(mem), |items1, item| cons(mem, item, items1))
+}
+
+#[derive(Debug)]
+pub struct Iter<'a, T: Copy + Clone + Debug> {
+  items: &'a List<'a, T>
+}
+
+impl<'a, T: Copy + Clone + Debug> Iterator for Iter<'a, T> {
+  type Item = T;
+
+  fn next(&mut self) -> Option<T> {
+    match self.items {
+      List::Nil => None,
+      List::Cons(_, item, items1) => {
+        self.items = items1;
+        Some(*item)
+      }
+    }
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    let length = self.items.length() as usize;
+    (length, Some(length))
+  }
+}
+
+impl<'a, T: Copy + Clone + Debug> ExactSizeIterator for Iter<'a, T> {}
+
 impl<'b, 'a: 'b, T: Copy + Clone + Debug> List<'a, T> {
+  pub fn iter(
+    &'a self
+  ) -> Iter<'a, T> {
+    Iter { items: self }
+  }
+
+  pub fn append(
+    &'a self,
+    mem: &'b Bump,
+    other: &'b List<'b, T>
+  ) -> &'b List<'b, T> {
+    let items: Vec<T> = self.iter().collect();
+    items.into_iter().rev().fold(other, |items1, item| cons(mem, item, items1))
+  }
+
+  pub fn reverse(
+    &'a self,
+    mem: &'b Bump
+  ) -> &'b List<'b, T> {
+    self.iter().fold(nil(mem), |items1, item| cons(mem, item, items1))
+  }
+
+  pub fn filter(
+    &'a self,
+    mem: &'b Bump,
+    predicate: &dyn Fn(T) -> bool
+  ) -> &'b List<'b, T> {
+    from_iter(mem, self.iter().filter(|item| predicate(*item)))
+  }
+
   pub fn fold<R>(
     &'a self,
     mem: &'b Bump,
     nil_case: R,
     cons_case: &'a dyn Fn(&'b Bump, T, R) -> R
   ) -> R {
-    fn _visit<'b, 'a: 'b, T: Copy + Clone + Debug, R>(
-      mem: &'b Bump,
-      items: &'a List<'a, T>,
-      nil_case: R,
-      cons_case: &'a dyn Fn(&'b Bump, T, R) -> R,
-      cont: &'b dyn Fn(&'b Bump, R) -> R
-    ) -> R {
-      match items {
-        List::Nil => cont(mem, nil_case),
-        List::Cons(_, item, items1) =>
-          _visit(mem, items1, nil_case, cons_case,
-            compose(mem, cont, mem.alloc(|mem, result|
-              cons_case(mem, *item, result))))
-      }
-    }
-    _visit(mem, self, nil_case, cons_case, mem.alloc(|_mem, result| result))
+    let items: Vec<T> = self.iter().collect();
+    items.into_iter().rev().fold(nil_case, |result, item| cons_case(mem, item, result))
   }
 
   pub fn map<S: Copy + Clone + Debug>(
@@ -53,48 +103,21 @@ impl<'b, 'a: 'b, T: Copy + Clone + Debug> List<'a, T> {
     mem: &'b Bump,
     func: &'a dyn Fn(&'b Bump, T) -> S
   ) -> &'b List<'b, S> {
-    fn _visit<'b, 'a: 'b, A: Copy + Clone + Debug, B: Copy + Clone + Debug>(
-      mem: &'b Bump,
-      items: &'a List<'a, A>,
-      func: &'a dyn Fn(&'b Bump, A) -> B,
-      cont: &'a dyn Fn(&'b Bump, &'a List<'a, B>) -> &'a List<'a, B>
-    ) -> &'b List<'b, B> {
-      match items {
-        List::Nil => cont(mem, nil(mem)),
-        List::Cons(_, item, items1) =>
-          _visit(mem, items1, func, compose(mem, cont, mem.alloc(|mem, result|
-          cons(mem, func(mem, *item), result))))
-      }
-    }
-    _visit(mem, self, func, mem.alloc(|_mem, result| result))
+    from_iter(mem, self.iter().map(|item| func(mem, item)))
   }
 
   pub fn get(
     &'a self,
     index: u64
   ) -> Option<T> {
-    match self {
-      List::Nil => None,
-      List::Cons(_, item, items1) => {
-        if index == 0 { Some(*item) } else {
-          items1.get(index - 1)
-        }
-      }
-    }
+    self.iter().nth(index as usize)
   }
 
   pub fn get_unsafe(
     &'a self,
     index: u64
   ) -> T {
-    match self {
-      List::Nil => unreachable!("Invariant"),
-      List::Cons(_, item, items1) => {
-        if index == 0 { *item } else {
-          items1.get_unsafe(index - 1)
-        }
-      }
-    }
+    self.get(index).unwrap_or_else(|| invariant!(self, index))
   }
 
   pub fn length(
@@ -2,6 +2,7 @@ use std::fmt::Debug;
 use bumpalo::Bump;
 
 use crate::{
+  util::invariant,
   order::Order,
   list::List,
   avl::{self as _avl, AVL}
@@ -18,7 +19,7 @@ pub enum Entry<
 
 pub type Map<'a, K, V> = AVL<'a, Entry<K, V>>;
 
-fn _entry_peek<'a,
+fn _entry_peek<
   K: Copy + Clone + Debug,
   V: Copy + Clone + Debug
 >(
@@ -37,7 +38,7 @@ fn _entry_bind<
   Entry::Bind(key, value)
 }
 
-fn _entry_key<'a,
+fn _entry_key<
   K: Copy + Clone + Debug,
   V: Copy + Clone + Debug
 >(
@@ -68,6 +69,11 @@ impl<'b, 'a: 'b,
     _avl::get_count(self)
   }
 
+  /*
+    The bindings of a map are folded, and listed by entries, keys and values,
+    in the order of avl::to_list, which depends on the shape of the tree rather
+    than only on the order of the keys.
+  */
   pub fn fold<R>(
     &'a self,
     mem: &'b Bump,
@@ -77,9 +83,9 @@ impl<'b, 'a: 'b,
     let entries = _avl::to_list(mem, self);
     entries.fold(mem, empty_case, mem.alloc(|mem, bind: Entry<K, V>, result|
     match bind {
-      Entry::Peek(_) => unreachable!("Invariant"),
+      Entry::Peek(_) => invariant!(bind),
       Entry::Bind(key, value) =>
-        bind_case(mem, key.clone(), value.clone(), result)
+        bind_case(mem, key, value, result)
     }))
   }
 
@@ -90,9 +96,9 @@ impl<'b, 'a: 'b,
   ) -> &'a Map<'a, K, U> {
     _avl::map(mem, self, mem.alloc(|mem, bind: Entry<K, V>|
       match bind {
-        Entry::Peek(_) => unreachable!("Invariant"),
+        Entry::Peek(_) => invariant!(bind),
         Entry::Bind(key, value) =>
-          _entry_bind(key.clone(), func(mem, value))
+          _entry_bind(key, func(mem, value))
       }))
   }
 
@@ -164,14 +170,14 @@ impl<'b, 'a: 'b,
     key: K
   ) -> V {
     match self {
-      AVL::Null => unreachable!("Invariant"),
+      AVL::Null => invariant!(self),
       AVL::Node(_, _, entry, left, right) =>
         match key_order(key, _entry_key(*entry)) {
           Order::LT => left.lookup_unsafe(key_order, key),
           Order::GT => right.lookup_unsafe(key_order, key),
           Order::EQ =>
             match entry {
-              Entry::Peek(_) => unreachable!("Invariant"),
+              Entry::Peek(_) => invariant!(entry),
               Entry::Bind(_, value) => *value
             }
         }
@@ -185,7 +191,7 @@ impl<'b, 'a: 'b,
     let entries = _avl::to_list(mem, self);
     entries.map(mem, mem.alloc(|_mem, entry: Entry<K, V>|
       match entry {
-        Entry::Peek(_) => unreachable!("Invariant"),
+        Entry::Peek(_) => invariant!(entry),
         Entry::Bind(key, value) => (key, value)
       }))
   }
@@ -197,7 +203,7 @@ impl<'b, 'a: 'b,
     let entries = _avl::to_list(mem, self);
     entries.map(mem, mem.alloc(|_mem, entry: Entry<K, V>|
       match entry {
-        Entry::Peek(_) => unreachable!("Invariant"),
+        Entry::Peek(_) => invariant!(entry),
         Entry::Bind(key, _value) => key
       }))
   }
@@ -209,12 +215,91 @@ impl<'b, 'a: 'b,
     let entries = _avl::to_list(mem, self);
     entries.map(mem, mem.alloc(|_mem, entry: Entry<K, V>|
       match entry {
-        Entry::Peek(_) => unreachable!("Invariant"),
+        Entry::Peek(_) => invariant!(entry),
         Entry::Bind(_key, value) => value
       }))
   }
 }
 
+impl<'a,
+  K: Copy + Clone + Debug,
+  V: Copy + Clone + Debug
+> Map<'a, K, V> {
+  pub fn new_with(
+    mem: &'a Bump,
+    key_order: &'a dyn Fn(K, K) -> Order
+  ) -> OrderedMap<'a, K, V> {
+    OrderedMap { key_order, map: empty(mem) }
+  }
+}
+
+/*
+  A map together with the order of its keys, for maps keyed by a custom
+  order, such that the order is given once rather than to every operation.
+*/
+#[derive(Copy, Clone)]
+pub struct OrderedMap<'a,
+  K: Copy + Clone + Debug,
+  V: Copy + Clone + Debug
+> {
+  key_order: &'a dyn Fn(K, K) -> Order,
+  map: &'a Map<'a, K, V>
+}
+
+impl<'a,
+  K: Copy + Clone + Debug,
+  V: Copy + Clone + Debug
+> OrderedMap<'a, K, V> {
+  pub fn size(&self) -> u64 {
+    self.map.size()
+  }
+
+  pub fn contains(
+    &self,
+    mem: &'a Bump,
+    key: K
+  ) -> bool {
+    self.map.contains(mem, self.key_order, key)
+  }
+
+  pub fn insert(
+    &self,
+    mem: &'a Bump,
+    key: K,
+    value: V
+  ) -> Self {
+    let map = self.map.insert(mem, self.key_order, key, value);
+    OrderedMap { key_order: self.key_order, map }
+  }
+
+  pub fn remove(
+    &self,
+    mem: &'a Bump,
+    key: K
+  ) -> Self {
+    let map = self.map.remove(mem, self.key_order, key);
+    OrderedMap { key_order: self.key_order, map }
+  }
+
+  pub fn lookup(
+    &self,
+    key: K
+  ) -> Option<V> {
+    self.map.lookup(self.key_order, key)
+  }
+
+  pub fn entries(
+    &self,
+    mem: &'a Bump
+  ) -> &'a List<'a, (K, V)> {
+    self.map.entries(mem)
+  }
+
+  pub fn as_map(&self) -> &'a Map<'a, K, V> {
+    self.map
+  }
+}
+
 pub fn from_entries<'b, 'a: 'b,
   K: Copy + Clone + Debug,
   V: Copy + Clone + Debug
@@ -1,10 +1,26 @@
-#[derive(Debug, Copy, Clone)]
+//! Orders for keying the persistent collections of the compiler.
+//!
+//! An order is a function from two values to an `Order`; `total` is the order of any `Ord` type,
+//! and a custom order can be given as any function of the same shape.
+//!
+//! # Examples
+//! ```
+//! use typeset::order::{Order, total};
+//!
+//! let by_length = |left: &str, right: &str| total(left.len(), right.len());
+//! assert_eq!(by_length("foo", "barbaz"), Order::LT);
+//! assert_eq!(total(2, 1), Order::GT);
+//! ```
+
+/// The outcome of comparing two values, equal, less than or greater than.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Order {
   EQ, LT, GT
 }
 
+/// The total order of any type implementing `Ord`.
 pub fn total<T: Ord + Copy + Clone>(left: T, right: T) -> Order {
   if left == right { Order::EQ } else {
   if left < right { Order::LT } else {
   Order::GT }}
-}
\ No newline at end of file
+}
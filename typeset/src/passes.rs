@@ -0,0 +1,322 @@
+//! The intermediate representations and passes of the compiler, for testing each pass in isolation.
+//!
+//! Only available with the `testing` feature. The intermediate representations are allocated in
+//! a `Bump` arena; inputs to a pass can be handcrafted with `Bump::alloc`, and the output of a pass
+//! is compared by its `Debug` dump. These are internals and are not covered by semantic versioning.
+//!
+//! # Examples
+//! ```
+//! use typeset::passes::{Bump, Serial, SerialTerm, SerialComp, text_attr, linearize};
+//!
+//! let mem = Bump::new();
+//! let foo = mem.alloc(SerialTerm::Text("foo", text_attr(None)));
+//! let bar = mem.alloc(SerialTerm::Text("bar", text_attr(None)));
+//! let serial = mem.alloc(Serial::Next(
+//!   foo,
+//!   mem.alloc(SerialComp::Line),
+//!   mem.alloc(Serial::Last(bar, mem.alloc(Serial::Past)))
+//! ));
+//! let doc = linearize(&mem, serial);
+//! assert_eq!(
+//!   format!("{:?}", doc).matches("Cons").count(),
+//!   2
+//! );
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+  compiler::{
+    Layout,
+    Doc,
+    Index,
+    CompileOptions,
+    _text_attr,
+    _broken,
+    _serialize,
+    _linearize,
+    _fixed,
+    _structurize,
+    _denull,
+    _identities,
+    _reassociate,
+    _rescope,
+    _move_to_heap
+  },
+  error::CompilerError
+};
+
+pub use bumpalo::Bump;
+
+pub use crate::compiler::{
+  Attr,
+  TextAttr,
+  EDSL,
+  Serial,
+  SerialTerm,
+  SerialComp,
+  Scopes,
+  LinearDoc,
+  LinearObj,
+  LinearTerm,
+  LinearComp,
+  FixedDoc,
+  FixedObj,
+  FixedItem,
+  FixedTerm,
+  FixedComp,
+  FixedFix,
+  RebuildDoc,
+  RebuildObj,
+  RebuildFix,
+  RebuildTerm,
+  DenullDoc,
+  DenullObj,
+  DenullFix,
+  DenullTerm,
+  FinalDoc,
+  FinalDocObj,
+  FinalDocObjFix
+};
+
+/// The persistent lists of the passes, allocated in a `Bump` arena.
+///
+/// Lists are built from an iterator, and iterated in order:
+/// ```
+/// use typeset::passes::{Bump, list::{self, List}};
+///
+/// let mem = Bump::new();
+/// let items: &List<u64> = list::from_iter(&mem, [1, 2, 3]);
+/// assert_eq!(items.length(), 3);
+/// assert_eq!(items.get(2), Some(3));
+/// assert_eq!(items.iter().collect::<Vec<u64>>(), vec![1, 2, 3]);
+/// assert_eq!(list::cons(&mem, 0, items).get(0), Some(0));
+/// assert_eq!(list::nil::<u64>(&mem).iter().count(), 0);
+/// ```
+///
+/// Appending shares the second list:
+/// ```
+/// use typeset::passes::{Bump, list};
+///
+/// let mem = Bump::new();
+/// let items = list::from_iter(&mem, [1, 2]).append(&mem, list::from_iter(&mem, [3]));
+/// assert_eq!(items.iter().collect::<Vec<u64>>(), vec![1, 2, 3]);
+/// let items1 = list::nil(&mem).append(&mem, items);
+/// assert_eq!(items1.length(), 3);
+/// ```
+///
+/// Reversing:
+/// ```
+/// use typeset::passes::{Bump, list};
+///
+/// let mem = Bump::new();
+/// let items = list::from_iter(&mem, [1, 2, 3]).reverse(&mem);
+/// assert_eq!(items.iter().collect::<Vec<u64>>(), vec![3, 2, 1]);
+/// assert_eq!(list::nil::<u64>(&mem).reverse(&mem).length(), 0);
+/// ```
+///
+/// Mapping and filtering keep the order of the items:
+/// ```
+/// use typeset::passes::{Bump, list};
+///
+/// let mem = Bump::new();
+/// let items = list::from_iter(&mem, 0..6u64);
+/// let odd = items.filter(&mem, &|item| item % 2 == 1);
+/// assert_eq!(odd.iter().collect::<Vec<u64>>(), vec![1, 3, 5]);
+/// let doubled = items.map(&mem, &|_mem, item| item * 2);
+/// assert_eq!(doubled.iter().collect::<Vec<u64>>(), vec![0, 2, 4, 6, 8, 10]);
+/// ```
+pub mod list {
+  pub use crate::list::{List, Iter, nil, cons, from_iter};
+}
+
+/// The persistent sorted sets of the passes, AVL trees allocated in a `Bump` arena together with the order of their items.
+///
+/// A set holds every item once, and iterates its items in order:
+/// ```
+/// use typeset::{order::total, passes::{Bump, set::Set}};
+///
+/// let mem = Bump::new();
+/// let items = Set::from_iter(&mem, &total, [3u64, 1, 2, 3]);
+/// assert_eq!(items.len(), 3);
+/// assert_eq!(items.iter().collect::<Vec<u64>>(), vec![1, 2, 3]);
+/// let items1 = items.insert(&mem, 0).remove(&mem, 2);
+/// assert!(items1.contains(0) && !items1.contains(2));
+/// assert_eq!(items1.iter().collect::<Vec<u64>>(), vec![0, 1, 3]);
+/// assert_eq!(items.iter().collect::<Vec<u64>>(), vec![1, 2, 3]);
+/// ```
+///
+/// A set of `n` items is balanced, with a height of at most `1.44 * log2(n + 2)`, however it is built:
+/// ```
+/// use typeset::{order::total, passes::{Bump, set::Set}};
+///
+/// let mem = Bump::new();
+/// let bound = |n: u64| 1.44 * ((n + 2) as f64).log2();
+/// let mut inserted = Set::new(&mem, &total);
+/// for n in 0..300u64 {
+///   let built = Set::from_iter(&mem, &total, 0..n);
+///   assert_eq!(built.len(), n);
+///   assert!((built.height() as f64) <= bound(n));
+///   assert!(built.iter().eq(0..n));
+///   assert_eq!(inserted.len(), n);
+///   assert!((inserted.height() as f64) <= bound(n));
+///   inserted = inserted.insert(&mem, (n * 7919) % 300);
+/// }
+/// let mut removed = inserted;
+/// for n in 0..300u64 {
+///   removed = removed.remove(&mem, (n * 31) % 300);
+///   assert_eq!(removed.len(), 299 - n);
+///   assert!((removed.height() as f64) <= bound(299 - n));
+/// }
+/// ```
+pub mod set {
+  pub use crate::set::{Set, Iter};
+}
+
+/// Constructs the attributes of an unannotated text, given an optional display width overriding the measured width.
+pub fn text_attr(
+  width: Option<usize>
+) -> TextAttr {
+  _text_attr(width)
+}
+
+/// Runs the `broken` pass, lowering a layout into the EDSL with broken sequences collapsed; annotation markers are dropped.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, passes::{Bump, EDSL, broken}};
+///
+/// let mem = Bump::new();
+/// let layout = broken(&mem, text("foo".to_string()), Default::default()).unwrap();
+/// assert!(matches!(layout, EDSL::Text("foo", _)));
+/// ```
+#[allow(clippy::boxed_local)]
+pub fn broken<'a>(
+  mem: &'a Bump,
+  layout: Box<Layout>,
+  options: CompileOptions
+) -> Result<&'a EDSL<'a>, CompilerError> {
+  _broken(mem, &*layout, options, &mut Vec::new())
+}
+
+/// Runs the `serialize` pass, giving the serial form along with the names of the groups and sequences, and the pack indices of the anchors; every composition refers to the groups and sequences it is under, innermost first, as `Scopes` shared with the compositions of the enclosing scopes.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, line, nest, pack, seq, fix, grp_named, CompileOptions};
+/// use typeset::passes::{Bump, broken, serialize};
+///
+/// let layout = line(
+///   grp_named("g", comp(
+///     nest(text("a")),
+///     seq(comp(pack(text("b")), fix(comp(text("c"), text("d"), true, false)), true, false)),
+///     true, false
+///   )),
+///   text("e")
+/// );
+/// let mem = Bump::new();
+/// let edsl = broken(&mem, layout, CompileOptions::default()).unwrap();
+/// let (serial, names, _anchors) = serialize(&mem, edsl);
+/// let attr = "TextAttr { width: None, markers: None }";
+/// assert_eq!(format!("{:?}", serial), format!(concat!(
+///   "Next(Nest(Text(\"a\", {0})), Comp(Attr {{ pad: true, fix: false }}, Grp(0, Top)), ",
+///   "Next(Pack(0, Text(\"b\", {0})), Comp(Attr {{ pad: true, fix: false }}, Seq(1, Grp(0, Top))), ",
+///   "Next(Text(\"c\", {0}), Comp(Attr {{ pad: true, fix: true }}, Seq(1, Grp(0, Top))), ",
+///   "Next(Text(\"d\", {0}), Line, ",
+///   "Last(Text(\"e\", {0}), Past)))))"
+/// ), attr));
+/// assert_eq!(names.get(&0), Some(&"g"));
+/// ```
+pub fn serialize<'a>(
+  mem: &'a Bump,
+  layout: &'a EDSL<'a>
+) -> (&'a Serial<'a>, BTreeMap<Index, &'a str>, BTreeMap<Index, Index>) {
+  let mut names = BTreeMap::new();
+  let mut anchors = BTreeMap::new();
+  let serial = _serialize(mem, layout, &mut names, &mut anchors);
+  (serial, names, anchors)
+}
+
+/// Runs the `linearize` pass, lifting the newlines of a serial form to the spine of the document.
+pub fn linearize<'a>(
+  mem: &'a Bump,
+  serial: &'a Serial<'a>
+) -> &'a LinearDoc<'a> {
+  _linearize(mem, serial)
+}
+
+/// Runs the `fixed` pass, coalescing the fixed compositions of each line.
+pub fn fixed<'a>(
+  mem: &'a Bump,
+  doc: &'a LinearDoc<'a>
+) -> &'a FixedDoc<'a> {
+  _fixed(mem, doc)
+}
+
+/// Runs the `structurize` pass, rebuilding the groups and sequences of each line as trees, given the names from `serialize`.
+pub fn structurize<'a>(
+  mem: &'a Bump,
+  doc: &'a FixedDoc<'a>,
+  names: &BTreeMap<Index, &'a str>
+) -> &'a RebuildDoc<'a> {
+  _structurize(mem, doc, names)
+}
+
+/// Runs the `denull` pass, removing the null identities.
+pub fn denull<'a>(
+  mem: &'a Bump,
+  doc: &'a RebuildDoc<'a>
+) -> &'a DenullDoc<'a> {
+  _denull(mem, doc)
+}
+
+/// Runs the `identities` pass, removing the redundant groups and sequences.
+pub fn identities<'a>(
+  mem: &'a Bump,
+  doc: &'a DenullDoc<'a>
+) -> &'a DenullDoc<'a> {
+  _identities(mem, doc)
+}
+
+/// Runs the `reassociate` pass, reassociating the compositions after the removals of groups and sequences.
+pub fn reassociate<'a>(
+  mem: &'a Bump,
+  doc: &'a DenullDoc<'a>
+) -> &'a DenullDoc<'a> {
+  _reassociate(mem, doc)
+}
+
+/// Runs the `rescope` pass, pushing nests and packs down to the texts they scope over.
+pub fn rescope<'a>(
+  mem: &'a Bump,
+  doc: &'a DenullDoc<'a>
+) -> &'a FinalDoc<'a> {
+  _rescope(mem, doc)
+}
+
+/// Moves a final document out of the arena into a `Doc`, given the pack indices that are anchored by a `pack_at`.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, compile, passes::*};
+///
+/// let layout = comp(
+///   text("foo".to_string()),
+///   text("bar".to_string()),
+///   true, false
+/// );
+/// let mem = Bump::new();
+/// let layout1 = broken(&mem, layout.clone(), Default::default()).unwrap();
+/// let (serial, names, anchors) = serialize(&mem, layout1);
+/// let doc = fixed(&mem, linearize(&mem, serial));
+/// let doc1 = identities(&mem, denull(&mem, structurize(&mem, doc, &names)));
+/// let doc2 = rescope(&mem, reassociate(&mem, doc1));
+/// let doc3 = to_doc(doc2, &anchors.into_values().collect());
+/// assert_eq!(format!("{}", doc3), format!("{}", compile(layout)));
+/// ```
+pub fn to_doc(
+  doc: &FinalDoc,
+  anchored: &BTreeSet<Index>
+) -> Box<Doc> {
+  _move_to_heap(doc, &[], anchored)
+}
@@ -0,0 +1,140 @@
+use crate::{
+  compiler::{
+    Layout,
+    RenderOptions,
+    compile_safe,
+    render_to
+  },
+  error::CompilerError
+};
+
+#[cfg(feature = "serde")]
+use crate::error::ProfileError;
+
+/// The policy for trailing commas after the last item of a list, for layout builders to consult when laying out lists.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize), serde(rename_all = "lowercase"))]
+pub enum TrailingComma {
+  #[default]
+  Never,
+  Always,
+  Multiline
+}
+
+/// A formatting profile, the buffer width, the tab indentation size, the ribbon width, the policy for trailing commas and the limit on consecutive blank lines, meant to be shared as configuration between the tools of an ecosystem.
+///
+/// With the `serde` feature, profiles implement `Deserialize` and can be loaded from TOML or JSON configuration with `from_toml` and `from_json`; missing fields keep their defaults.
+/// The trailing comma policy is not applied by the renderer, it is for layout builders to consult; the other fields are applied by `format_with`.
+///
+/// # Examples
+/// ```
+/// use typeset::{FormatProfile, TrailingComma};
+///
+/// let profile = FormatProfile { width: 100, trailing_comma: TrailingComma::Multiline, ..FormatProfile::default() };
+/// assert_eq!(profile.render_options().width, 100);
+/// assert_eq!(profile.render_options().tab, 2);
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize), serde(default, deny_unknown_fields))]
+pub struct FormatProfile {
+  pub width: usize,
+  pub tab: usize,
+  pub ribbon: Option<usize>,
+  pub trailing_comma: TrailingComma,
+  pub max_blank_lines: Option<usize>
+}
+
+impl FormatProfile {
+  /// Loads a profile from TOML with the `serde` feature, a table of the fields of the profile with the trailing comma policy as one of the strings `"never"`, `"always"` or `"multiline"`; missing fields keep their defaults, and unknown fields are rejected.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{FormatProfile, ProfileError, TrailingComma};
+  ///
+  /// let profile = FormatProfile::from_toml("
+  ///   ## Shared by all our tools, e.g. \"a, b\"
+  ///   width = 1_00
+  ///   \"trailing_comma\" = \"multi\\u006cine\"
+  /// ").unwrap();
+  /// assert_eq!(profile.width, 100);
+  /// assert_eq!(profile.tab, 2);
+  /// assert_eq!(profile.trailing_comma, TrailingComma::Multiline);
+  ///
+  /// let error = FormatProfile::from_toml("height = 4").unwrap_err();
+  /// assert!(matches!(error, ProfileError::Toml(message) if message.contains("height")));
+  /// ```
+  #[cfg(feature = "serde")]
+  pub fn from_toml(
+    data: &str
+  ) -> Result<Self, ProfileError> {
+    toml::from_str(data).map_err(|error| ProfileError::Toml(error.message().to_string()))
+  }
+
+  /// Loads a profile from JSON with the `serde` feature, an object of the fields of the profile as in `from_toml`, where the optional fields may also be `null`.
+  ///
+  /// # Examples
+  /// ```
+  /// use typeset::{FormatProfile, ProfileError};
+  ///
+  /// let profile = FormatProfile::from_json("{\"tab\": 4, \"ribbon\": 60, \"max_blank_lines\": null}").unwrap();
+  /// assert_eq!(profile.tab, 4);
+  /// assert_eq!(profile.ribbon, Some(60));
+  /// assert_eq!(profile.max_blank_lines, None);
+  ///
+  /// let error = FormatProfile::from_json("{\"height\": 4}").unwrap_err();
+  /// assert!(matches!(error, ProfileError::Json(message) if message.contains("height")));
+  /// ```
+  #[cfg(feature = "serde")]
+  pub fn from_json(
+    data: &str
+  ) -> Result<Self, ProfileError> {
+    serde_json::from_str(data).map_err(|error| ProfileError::Json(error.to_string()))
+  }
+
+  /// Gives the render options of the profile, with the other render options at their defaults.
+  pub fn render_options(&self) -> RenderOptions {
+    RenderOptions {
+      ribbon: self.ribbon,
+      max_blank_lines: self.max_blank_lines,
+      ..RenderOptions::new(self.tab, self.width)
+    }
+  }
+}
+
+impl Default for FormatProfile {
+  fn default() -> Self {
+    let options = RenderOptions::default();
+    FormatProfile {
+      width: options.width,
+      tab: options.tab,
+      ribbon: options.ribbon,
+      trailing_comma: TrailingComma::default(),
+      max_blank_lines: options.max_blank_lines
+    }
+  }
+}
+
+/// A function for formatting layouts with a profile, takes a `Box<Layout>` and a `&FormatProfile`, and gives either the layout compiled and rendered with the render options of the profile, or the `CompilerError` of compiling it as by `compile_safe`.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, format_with, FormatProfile};
+///
+/// let layout = comp(
+///   text("foo".to_string()),
+///   text("bar".to_string()),
+///   true, false
+/// );
+/// let profile = FormatProfile { width: 4, ..FormatProfile::default() };
+/// assert_eq!(format_with(layout, &profile).unwrap(), "foo\nbar");
+/// ```
+pub fn format_with(
+  layout: Box<Layout>,
+  profile: &FormatProfile
+) -> Result<String, CompilerError> {
+  let doc = compile_safe(layout)?;
+  let mut result = String::new();
+  render_to(&doc, &profile.render_options(), &mut result)
+    .expect("Writing to a String can not fail");
+  Ok(result)
+}
@@ -0,0 +1,161 @@
+//! The rendering semantics of layouts, stated as rules with executable examples.
+//!
+//! Every rule below is followed by an example that is run as a doctest, so the rules are checked
+//! against the renderer on every test run; a rule that changes must change its example with it.
+//! The examples use the default tab size of 2 and the width given to `render`.
+//!
+//! # Compositions
+//!
+//! A breakable composition is broken when the text up to the next breakable composition does not
+//! fit on the line; the decisions are made from left to right, and a broken composition is never
+//! reconsidered.
+//!
+//! ```
+//! use typeset::{text, comp, compile, render};
+//!
+//! let layout = comp(text("foo"), comp(text("bar"), text("baz"), true, false), true, false);
+//! assert_eq!(render(compile(layout.clone()), 2, 11), "foo bar baz");
+//! assert_eq!(render(compile(layout.clone()), 2, 10), "foo bar\nbaz");
+//! assert_eq!(render(compile(layout), 2, 6), "foo\nbar\nbaz");
+//! ```
+//!
+//! A padded composition puts a space between its parts when it is not broken, and an unpadded
+//! composition puts nothing between them.
+//!
+//! ```
+//! use typeset::{text, comp, compile, render};
+//!
+//! let layout = comp(text("foo"), text("bar"), false, false);
+//! assert_eq!(render(compile(layout), 2, 80), "foobar");
+//! ```
+//!
+//! # Fixed compositions
+//!
+//! A fixed composition is never broken, and the texts it joins are measured as one text.
+//!
+//! ```
+//! use typeset::{text, comp, fix, compile, render};
+//!
+//! let layout = comp(text("foo"), fix(comp(text("bar"), text("baz"), true, false)), true, false);
+//! assert_eq!(render(compile(layout.clone()), 2, 10), "foo\nbar baz");
+//! assert_eq!(render(compile(layout), 2, 4), "foo\nbar baz");
+//! ```
+//!
+//! # Groups
+//!
+//! A group is measured whole by the composition before it, so that composition is broken
+//! first when the group does not fit; the compositions in the group are only broken when the
+//! group does not fit on a line of its own.
+//!
+//! ```
+//! use typeset::{text, comp, grp, compile, render};
+//!
+//! let layout = comp(text("foo"), grp(comp(text("bar"), text("baz"), true, false)), true, false);
+//! assert_eq!(render(compile(layout.clone()), 2, 11), "foo bar baz");
+//! assert_eq!(render(compile(layout.clone()), 2, 10), "foo\nbar baz");
+//! assert_eq!(render(compile(layout), 2, 6), "foo\nbar\nbaz");
+//! ```
+//!
+//! A group is measured to its own end, the text after it on the line is not counted; the
+//! `group_measure` render option measures up to the end of the line instead.
+//!
+//! ```
+//! use typeset::{text, comp, grp, compile, render_with, RenderOptions, GroupMeasure};
+//!
+//! let layout = comp(
+//!   text("foo"),
+//!   comp(grp(comp(text("bar"), text("baz"), true, false)), text(";;;;"), false, false),
+//!   true, false
+//! );
+//! let document = compile(layout);
+//! let options = RenderOptions { width: 12, ..RenderOptions::default() };
+//! assert_eq!(render_with(&document, &options).text, "foo bar baz\n;;;;");
+//! let options = RenderOptions { group_measure: GroupMeasure::LineEnd, ..options };
+//! assert_eq!(render_with(&document, &options).text, "foo\nbar baz;;;;");
+//! ```
+//!
+//! # Sequences
+//!
+//! The compositions of a sequence are either all broken or none of them are; a sequence is kept
+//! on one line when it fits from the column it starts at.
+//!
+//! ```
+//! use typeset::{text, comp, seq, compile, render};
+//!
+//! let layout = seq(comp(text("foo"), comp(text("bar"), text("baz"), true, false), true, false));
+//! assert_eq!(render(compile(layout.clone()), 2, 11), "foo bar baz");
+//! assert_eq!(render(compile(layout), 2, 10), "foo\nbar\nbaz");
+//! ```
+//!
+//! # Nests
+//!
+//! The lines broken within a nest are indented to the next tab stop past the indentation of the
+//! nest's context; a nest that is not broken adds nothing.
+//!
+//! ```
+//! use typeset::{text, comp, nest, compile, render};
+//!
+//! let layout = comp(text("foo"), nest(comp(text("bar"), text("baz"), true, false)), true, false);
+//! assert_eq!(render(compile(layout.clone()), 2, 80), "foo bar baz");
+//! assert_eq!(render(compile(layout), 2, 6), "foo\n  bar\n  baz");
+//! ```
+//!
+//! # Packs
+//!
+//! The lines broken within a pack are indented to the column of the pack's first text, unless
+//! the indentation of the context is already deeper.
+//!
+//! ```
+//! use typeset::{text, comp, pack, compile, render};
+//!
+//! let layout = comp(text("foo"), pack(comp(text("bar"), text("baz"), true, false)), true, false);
+//! assert_eq!(render(compile(layout.clone()), 2, 80), "foo bar baz");
+//! assert_eq!(render(compile(layout), 2, 8), "foo bar\n    baz");
+//! ```
+//!
+//! # Lines
+//!
+//! A line composition always breaks, and the indentation of nests and packs does not carry over
+//! it: the line after it starts at the first column.
+//!
+//! ```
+//! use typeset::{text, comp, nest, line, compile, render};
+//!
+//! let layout = line(comp(text("foo"), nest(text("bar")), true, false), text("baz"));
+//! assert_eq!(render(compile(layout.clone()), 2, 80), "foo bar\nbaz");
+//! assert_eq!(render(compile(layout), 2, 4), "foo\n  bar\nbaz");
+//! ```
+//!
+//! # Ribbons
+//!
+//! With a ribbon width, a line is also broken when it extends more than the ribbon width past
+//! its indentation.
+//!
+//! ```
+//! use typeset::{text, comp, nest, compile, render_with, RenderOptions};
+//!
+//! let layout = nest(nest(comp(text("foo"), text("bar"), true, false)));
+//! let document = compile(layout);
+//! let options = RenderOptions { ribbon: Some(6), ..RenderOptions::default() };
+//! assert_eq!(render_with(&document, &options).text, "    foo\n    bar");
+//! ```
+//!
+//! # Invariants
+//!
+//! Rendering is deterministic: the same document rendered twice with the same options gives the
+//! same output. A document that renders within a width when given all the room it needs renders
+//! the same at that width, such that no line of it exceeds the width and no composition of it is
+//! broken; this holds for fixed compositions as for breakable ones. Rendering the output again as
+//! plain text at the same width gives the output back.
+//!
+//! ```
+//! use typeset::{text, comp, fix, nest, compile, render, Doc};
+//!
+//! let layout = comp(text("foo"), nest(fix(comp(text("bar"), text("baz"), true, false))), true, false);
+//! let document = compile(layout);
+//! let wide = render(document.clone(), 2, usize::MAX);
+//! assert_eq!(wide, "foo bar baz");
+//! assert_eq!(render(document.clone(), 2, 11), wide);
+//! assert_eq!(render(document.clone(), 2, 11), render(document, 2, 11));
+//! assert_eq!(render(Doc::from_plain_text(&wide), 2, 11), wide);
+//! ```
@@ -0,0 +1,71 @@
+use std::panic::{self, AssertUnwindSafe};
+
+use bumpalo::Bump;
+
+use crate::{
+  compiler::{
+    Layout,
+    Doc,
+    CompileOptions,
+    _compile_in
+  },
+  error::CompilerError
+};
+
+/// A session for compiling many layouts in one process, e.g. every item of a crate in a formatter.
+///
+/// The session keeps the memory of the compiler between layouts, and resets it before every layout rather than freeing it, such that compiling many layouts does not allocate anew for each of them.
+/// A memory limit in the compile options counts the memory kept from earlier layouts as allocated.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, render, Session};
+///
+/// let mut session = Session::new();
+/// let items = ["foo", "bar"].into_iter()
+///   .map(|name| comp(text(name), text("baz"), true, false));
+/// let documents = session.compile_many(items).unwrap();
+/// assert_eq!(documents.len(), 2);
+/// assert_eq!(render(documents[1].clone(), 2, 80), "bar baz");
+/// ```
+#[derive(Debug, Default)]
+pub struct Session {
+  mem: Bump,
+  options: CompileOptions
+}
+
+impl Session {
+  /// Constructs a new session with the default compile options.
+  #[must_use]
+  pub fn new() -> Self {
+    Session::with_options(CompileOptions::default())
+  }
+
+  /// Constructs a new session with the given compile options.
+  #[must_use]
+  pub fn with_options(
+    options: CompileOptions
+  ) -> Self {
+    Session { mem: Bump::new(), options }
+  }
+
+  /// Compiles a layout into a document, as `compile_with` does with the options of the session.
+  pub fn compile(
+    &mut self,
+    layout: Box<Layout>
+  ) -> Result<Box<Doc>, CompilerError> {
+    self.mem.reset();
+    let mem = &self.mem;
+    let options = &self.options;
+    panic::catch_unwind(AssertUnwindSafe(|| _compile_in(mem, &layout, options)))
+      .map_err(CompilerError::from_panic)?
+  }
+
+  /// Compiles every layout of an iterator into a document, in order, stopping at the first error.
+  pub fn compile_many(
+    &mut self,
+    items: impl Iterator<Item = Box<Layout>>
+  ) -> Result<Vec<Box<Doc>>, CompilerError> {
+    items.map(|layout| self.compile(layout)).collect()
+  }
+}
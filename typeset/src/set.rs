@@ -0,0 +1,185 @@
+use std::{
+  cmp::Ordering,
+  fmt::Debug
+};
+
+use bumpalo::Bump;
+
+use crate::{
+  order::Order,
+  list,
+  avl::{self as _avl, AVL}
+};
+
+/*
+  A persistent sorted set over an AVL tree, together with the order of its
+  items, such that the order is given once rather than to every operation.
+*/
+#[derive(Copy, Clone)]
+pub struct Set<'a, T: Copy + Clone + Debug> {
+  order: &'a dyn Fn(T, T) -> Order,
+  tree: &'a AVL<'a, T>
+}
+
+impl<'a, T: Copy + Clone + Debug> Set<'a, T> {
+  pub fn new(
+    mem: &'a Bump,
+    order: &'a dyn Fn(T, T) -> Order
+  ) -> Self {
+    Set { order, tree: _avl::null(mem) }
+  }
+
+  pub fn from_iter(
+    mem: &'a Bump,
+    order: &'a dyn Fn(T, T) -> Order,
+    items: impl IntoIterator<Item = T>
+  ) -> Self {
+    let mut items: Vec<T> = items.into_iter().collect();
+    items.sort_by(|left, right| match order(*left, *right) {
+      Order::LT => Ordering::Less,
+      Order::EQ => Ordering::Equal,
+      Order::GT => Ordering::Greater
+    });
+    items.dedup_by(|right, left| matches!(order(*left, *right), Order::EQ));
+    let tree = _avl::from_list(mem, list::from_iter(mem, items));
+    Set { order, tree }
+  }
+
+  pub fn len(&self) -> u64 {
+    _avl::get_count(self.tree)
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  pub fn height(&self) -> u64 {
+    _avl::get_height(self.tree)
+  }
+
+  pub fn contains(&self, item: T) -> bool {
+    _avl::is_member(self.order, item, self.tree)
+  }
+
+  pub fn insert(
+    &self,
+    mem: &'a Bump,
+    item: T
+  ) -> Self {
+    if self.contains(item) { return *self }
+    Set { order: self.order, tree: _avl::insert_balanced(mem, self.order, item, self.tree) }
+  }
+
+  pub fn remove(
+    &self,
+    mem: &'a Bump,
+    item: T
+  ) -> Self {
+    if !self.contains(item) { return *self }
+    Set { order: self.order, tree: _avl::remove(mem, self.order, item, self.tree) }
+  }
+
+  pub fn iter(&self) -> Iter<'a, T> {
+    let mut iter = Iter { stack: Vec::new() };
+    iter._push_left(self.tree);
+    iter
+  }
+}
+
+pub struct Iter<'a, T: Copy + Clone + Debug> {
+  stack: Vec<&'a AVL<'a, T>>
+}
+
+impl<'a, T: Copy + Clone + Debug> Iter<'a, T> {
+  fn _push_left(&mut self, tree: &'a AVL<'a, T>) {
+    let mut tree = tree;
+    while let AVL::Node(_, _, _, left, _) = tree {
+      self.stack.push(tree);
+      tree = left
+    }
+  }
+}
+
+impl<'a, T: Copy + Clone + Debug> Iterator for Iter<'a, T> {
+  type Item = T;
+
+  fn next(&mut self) -> Option<T> {
+    match self.stack.pop()? {
+      AVL::Null => None,
+      AVL::Node(_, _, data, _, right) => {
+        self._push_left(right);
+        Some(*data)
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::BTreeSet;
+
+  use bumpalo::Bump;
+
+  use crate::order::total;
+
+  use super::*;
+
+  fn _bound(count: u64) -> f64 {
+    1.44 * ((count + 2) as f64).log2()
+  }
+
+  /* Compare a set with a model of it, its items, their order, and its count and height */
+  fn _check(
+    items: &Set<u64>,
+    model: &BTreeSet<u64>
+  ) {
+    assert_eq!(items.len(), model.len() as u64);
+    assert_eq!(items.is_empty(), model.is_empty());
+    assert!(items.iter().eq(model.iter().copied()));
+    assert!((items.height() as f64) <= _bound(items.len()))
+  }
+
+  #[test]
+  fn insert_and_remove_follow_the_model() {
+    let mem = Bump::new();
+    let mut items = Set::new(&mem, &total);
+    let mut model = BTreeSet::new();
+    let mut seed: u64 = 0x9e37_79b9_7f4a_7c15;
+    for _ in 0..2000 {
+      seed ^= seed << 13;
+      seed ^= seed >> 7;
+      seed ^= seed << 17;
+      let item = seed % 256;
+      if seed.is_multiple_of(3) {
+        items = items.remove(&mem, item);
+        model.remove(&item);
+      } else {
+        items = items.insert(&mem, item);
+        model.insert(item);
+      }
+      assert_eq!(items.contains(item), model.contains(&item));
+      _check(&items, &model)
+    }
+  }
+
+  #[test]
+  fn sets_are_persistent() {
+    let mem = Bump::new();
+    let items = Set::from_iter(&mem, &total, [2u64, 4, 6]);
+    let items1 = items.insert(&mem, 5);
+    let items2 = items.remove(&mem, 4);
+    _check(&items, &BTreeSet::from([2, 4, 6]));
+    _check(&items1, &BTreeSet::from([2, 4, 5, 6]));
+    _check(&items2, &BTreeSet::from([2, 6]))
+  }
+
+  #[test]
+  fn from_iter_sorts_and_dedups() {
+    let mem = Bump::new();
+    for count in 0..100u64 {
+      let items: Vec<u64> = (0..count).map(|index| (index * 37) % 50).collect();
+      let model: BTreeSet<u64> = items.iter().copied().collect();
+      _check(&Set::from_iter(&mem, &total, items), &model)
+    }
+  }
+}
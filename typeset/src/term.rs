@@ -0,0 +1,70 @@
+use std::io::{self, IsTerminal, Write};
+
+use crate::{
+  compiler::{Doc, RenderOptions, render_to},
+  backend::render_ansi,
+  theme::Theme
+};
+
+#[cfg(unix)]
+fn _tty_width() -> Option<usize> {
+  let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+  let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+  if result != 0 || size.ws_col == 0 { return None }
+  Some(size.ws_col as usize)
+}
+
+#[cfg(not(unix))]
+fn _tty_width() -> Option<usize> {
+  None
+}
+
+/// Gives the width of the terminal on stdout, from the terminal itself or else the `COLUMNS` environment variable, if either is known.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, compile, render, terminal_width};
+///
+/// let width = terminal_width().unwrap_or(80);
+/// assert_eq!(render(compile(text("foo")), 2, width), "foo");
+/// ```
+pub fn terminal_width() -> Option<usize> {
+  _tty_width().or_else(|| {
+    std::env::var("COLUMNS").ok()
+      .and_then(|columns| columns.trim().parse().ok())
+      .filter(|columns| 0 < *columns)
+  })
+}
+
+/// A function for rendering documents to stdout, takes a `&Doc` and a theme, and writes the rendered document followed by a newline.
+///
+/// The document is rendered with the options from `RenderOptions::from_env`, to the width of the terminal unless `TYPESET_WIDTH` is set, or 80 columns when it is not known.
+/// Annotations are styled by the theme only when stdout is a terminal and color is enabled; otherwise the document is written as plain text.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, tag, compile, render_to_terminal, Theme};
+///
+/// let document = compile(tag("keyword".to_string(), text("fn".to_string())));
+/// assert!(render_to_terminal(&document, &Theme::default()).is_ok());
+/// ```
+pub fn render_to_terminal(
+  doc: &Doc,
+  theme: &Theme
+) -> io::Result<()> {
+  let mut options = RenderOptions::from_env();
+  if std::env::var_os("TYPESET_WIDTH").is_none() {
+    options.width = terminal_width().unwrap_or(options.width)
+  }
+  let stdout = io::stdout();
+  let styled = stdout.is_terminal() && options.color;
+  let output = if styled { render_ansi(doc, &options, theme) } else {
+    let mut output = String::new();
+    render_to(doc, &options, &mut output)
+      .expect("Writing to a String can not fail");
+    output
+  };
+  let mut out = stdout.lock();
+  writeln!(out, "{}", output)?;
+  out.flush()
+}
@@ -0,0 +1,189 @@
+use crate::{
+  compiler::{
+    Layout,
+    CompileOptions,
+    RenderOptions,
+    TabPolicy,
+    compile_safe,
+    compile_trace,
+    render_to,
+    _text_columns
+  },
+  diff::{DiffKind, DiffLine, _diff}
+};
+
+/// Asserts that a layout renders as expected, given the buffer width and optionally the tab indentation size, which defaults to 2.
+///
+/// On failure the panic message holds a character-level diff between the expected and the actual rendering, the compiled document, and the dumps of the intermediate representations given by each pass of the compiler.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, nest, assert_renders};
+///
+/// let layout = comp(
+///   text("foo".to_string()),
+///   nest(text("bar".to_string())),
+///   true, false
+/// );
+/// assert_renders!(layout.clone(), width = 80, expected = "foo bar");
+/// assert_renders!(layout, width = 4, tab = 4, expected = "foo\n    bar");
+/// ```
+///
+/// ```should_panic
+/// use typeset::{text, assert_renders};
+///
+/// assert_renders!(text("foo".to_string()), width = 80, expected = "bar");
+/// ```
+#[macro_export]
+macro_rules! assert_renders {
+  ($layout:expr, width = $width:expr, expected = $expected:expr $(,)?) => {
+    $crate::assert_renders!($layout, width = $width, tab = 2, expected = $expected)
+  };
+  ($layout:expr, width = $width:expr, tab = $tab:expr, expected = $expected:expr $(,)?) => {
+    $crate::_assert_renders($layout, &$crate::RenderOptions::new($tab, $width), $expected)
+  };
+}
+
+/// Asserts that every line of a rendering fits within the buffer width, measuring the width of a line as the renderer does with the default tab policy, in bytes with tabs one column wide.
+///
+/// On failure the panic message holds the rendering under a column ruler, with the lines exceeding the width marked, see `with_ruler`.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, grp, render, assert_fits};
+///
+/// let layout = grp(comp(text("foo"), text("bar"), true, false));
+/// assert_fits!(render(typeset::compile(layout), 2, 4), 4);
+/// ```
+///
+/// ```should_panic
+/// use typeset::assert_fits;
+///
+/// assert_fits!("foo bar", 4);
+/// ```
+///
+/// ```should_panic
+/// use typeset::assert_fits;
+///
+/// assert_fits!("çà", 3);
+/// ```
+#[macro_export]
+macro_rules! assert_fits {
+  ($output:expr, $width:expr $(,)?) => {
+    $crate::_assert_fits(::std::convert::AsRef::<str>::as_ref(&$output), $width)
+  };
+}
+
+/// Prefixes a rendering with a column ruler of the given width, and marks every line exceeding the width with a `!` in a gutter before the lines, for reading renderings in test failures.
+///
+/// Lines are measured as by `assert_fits`, in bytes.
+/// The ruler numbers the columns from 1, the last digit of every column on the first row and the tens on a row above it when the width is 10 or more, and ends with a `|` after the last column.
+///
+/// # Examples
+/// ```
+/// use typeset::with_ruler;
+///
+/// assert_eq!(with_ruler("foo\nfoo bar", 4), "  1234|\n  foo\n! foo bar");
+/// assert_eq!(with_ruler("ç\nçà", 3), "  123|\n  ç\n! çà");
+/// assert_eq!(
+///   with_ruler("foo", 12),
+///   "           1  |\n  123456789012|\n  foo"
+/// );
+/// ```
+pub fn with_ruler(
+  output: &str,
+  width: usize
+) -> String {
+  let mut result = String::new();
+  if 10 <= width {
+    let tens: String = (1..=width)
+      .map(|column| if column % 10 == 0 { _digit(column / 10) } else { ' ' })
+      .collect();
+    result.push_str(&format!("  {}|\n", tens))
+  }
+  let ones: String = (1..=width).map(_digit).collect();
+  result.push_str(&format!("  {}|", ones));
+  for line in output.split('\n') {
+    let gutter = if width < _line_width(line) { '!' } else { ' ' };
+    result.push_str(&format!("\n{} {}", gutter, line))
+  }
+  result
+}
+
+fn _line_width(
+  line: &str
+) -> usize {
+  _text_columns(line, 0, TabPolicy::default())
+}
+
+fn _digit(
+  column: usize
+) -> char {
+  char::from_digit((column % 10) as u32, 10).unwrap()
+}
+
+#[doc(hidden)]
+#[track_caller]
+pub fn _assert_fits(
+  output: &str,
+  width: usize
+) {
+  let exceeding = output.split('\n')
+    .filter(|line| width < _line_width(line))
+    .count();
+  if exceeding == 0 { return }
+  panic!(
+    "{} line(s) of the rendering exceed the width {}\n{}",
+    exceeding, width, with_ruler(output, width)
+  )
+}
+
+fn _carets(
+  line: &DiffLine
+) -> Option<String> {
+  let last = line.changes.last()?;
+  let mut result = String::new();
+  for (index, _) in line.text[..last.end].char_indices() {
+    let changed = line.changes.iter().any(|range| range.contains(&index));
+    result.push(if changed { '^' } else { ' ' })
+  }
+  Some(result)
+}
+
+#[doc(hidden)]
+#[track_caller]
+pub fn _assert_renders(
+  layout: Box<Layout>,
+  options: &RenderOptions,
+  expected: &str
+) {
+  let document = match compile_safe(layout.clone()) {
+    Ok(document) => document,
+    Err(error) => panic!("layout failed to compile: {}", error)
+  };
+  let mut actual = String::new();
+  render_to(&document, options, &mut actual)
+    .expect("Writing to a String can not fail");
+  if actual == expected { return }
+  /* The trace is only made for the failure message, since it dumps every pass of the compiler */
+  let trace = compile_trace(layout, &CompileOptions::default())
+    .expect("A layout that compiles can be traced");
+  let mut diff = String::new();
+  for line in &_diff(expected, &actual).lines {
+    let prefix = match line.kind {
+      DiffKind::Equal => ' ',
+      DiffKind::Removed => '-',
+      DiffKind::Added => '+'
+    };
+    diff.push_str(&format!("{}{}\n", prefix, line.text));
+    if let Some(carets) = _carets(line) {
+      diff.push_str(&format!(" {}\n", carets))
+    }
+  }
+  panic!(
+    "layout does not render as expected at width {} and tab {}\n\
+     diff (-expected +actual):\n{}\
+     trace:\n{}",
+    options.width, options.tab, diff, trace
+  )
+}
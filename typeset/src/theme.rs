@@ -0,0 +1,78 @@
+use std::collections::BTreeMap;
+
+/// The styling of a tag, as SGR parameters for the ANSI backend, e.g. `"1;34"` for bold blue, and as a CSS class for the HTML backend.
+///
+/// An empty string leaves the tag unstyled in the corresponding backend.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Style {
+  pub ansi: String,
+  pub class: String
+}
+
+impl Style {
+  /// Constructs a new style from SGR parameters and a CSS class.
+  pub fn new(
+    ansi: &str,
+    class: &str
+  ) -> Self {
+    Style { ansi: ansi.to_string(), class: class.to_string() }
+  }
+}
+
+/// A mapping from annotation tags to styles, shared by the ANSI and HTML backends.
+///
+/// The default theme styles the tags `keyword`, `type`, `string`, `number`, `comment`, `operator` and `error`, each with a CSS class of the same name; tags without a style are rendered as plain text.
+///
+/// # Examples
+/// ```
+/// use typeset::{Style, Theme};
+///
+/// let theme = Theme::default()
+///   .with("keyword", Style::new("1;35", "kw"))
+///   .with("label", Style::new("4", "label"));
+/// assert_eq!(theme.get("keyword"), Some(&Style::new("1;35", "kw")));
+/// assert_eq!(theme.get("string"), Some(&Style::new("32", "string")));
+/// assert_eq!(theme.get("unknown"), None);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+  styles: BTreeMap<String, Style>
+}
+
+impl Theme {
+  /// Constructs a new theme without any styles.
+  pub fn empty() -> Self {
+    Theme { styles: BTreeMap::new() }
+  }
+
+  /// Gives the theme with the style of a tag added or replaced.
+  pub fn with(
+    mut self,
+    tag: &str,
+    style: Style
+  ) -> Self {
+    self.styles.insert(tag.to_string(), style);
+    self
+  }
+
+  /// Gives the style of a tag, if it has one.
+  pub fn get(
+    &self,
+    tag: &str
+  ) -> Option<&Style> {
+    self.styles.get(tag)
+  }
+}
+
+impl Default for Theme {
+  fn default() -> Self {
+    Theme::empty()
+      .with("keyword", Style::new("1;34", "keyword"))
+      .with("type", Style::new("36", "type"))
+      .with("string", Style::new("32", "string"))
+      .with("number", Style::new("33", "number"))
+      .with("comment", Style::new("2", "comment"))
+      .with("operator", Style::new("35", "operator"))
+      .with("error", Style::new("1;31", "error"))
+  }
+}
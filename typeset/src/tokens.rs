@@ -0,0 +1,218 @@
+use proc_macro2::{Delimiter, Spacing, TokenStream, TokenTree};
+
+use crate::compiler::{Layout, null, text, grp, seq, nest, line, comp};
+
+const SPACED_KEYWORDS: [&str; 17] = [
+  "as", "break", "dyn", "else", "for", "if", "impl", "in", "let",
+  "loop", "match", "move", "mut", "return", "unsafe", "where", "while"
+];
+
+const GENERIC_KEYWORDS: [&str; 6] = [
+  "enum", "fn", "impl", "struct", "trait", "type"
+];
+
+const PREFIX_OPERATORS: [&str; 5] = ["&", "&&", "*", "-", "!"];
+
+const TIGHT_BEFORE: [&str; 6] = [",", ";", ".", ":", "::", "?"];
+
+const TIGHT_AFTER: [&str; 5] = ["::", ".", "'", "#", "$"];
+
+const OPERATORS: [&str; 24] = [
+  "::", "->", "=>", "==", "!=", "<=", ">=", "&&", "||", "+=", "-=", "*=",
+  "/=", "%=", "^=", "&=", "|=", "<<", ">>", "<<=", ">>=", "..", "...", "..="
+];
+
+/*
+  The atoms of a token stream, where the punctuation joined by its spacing
+  into an operator is kept as one, the angle brackets of generics are told
+  apart from comparisons, and every atom is given its layout and whether
+  it is within generics.
+*/
+enum Atom {
+  Word(String),
+  Literal,
+  Operator(String),
+  Angle(bool),
+  Group(Delimiter)
+}
+
+type Atoms = Vec<(Atom, Box<Layout>, bool)>;
+
+fn _atoms(
+  stream: TokenStream
+) -> Atoms {
+  let mut atoms: Atoms = Vec::new();
+  let mut joint = false;
+  let mut generics = 0;
+  for tree in stream {
+    let nested = 0 < generics;
+    match tree {
+      TokenTree::Ident(ident) => {
+        let data = ident.to_string();
+        atoms.push((Atom::Word(data.clone()), text(data), nested));
+        joint = false
+      }
+      TokenTree::Literal(literal) => {
+        atoms.push((Atom::Literal, text(literal.to_string()), nested));
+        joint = false
+      }
+      TokenTree::Group(group) => {
+        let delimiter = group.delimiter();
+        let path = matches!(atoms.last(), Some((Atom::Operator(data), _, _)) if data == "::");
+        atoms.push((Atom::Group(delimiter), _group(delimiter, !path, group.stream()), nested));
+        joint = false
+      }
+      TokenTree::Punct(punct) => {
+        let char = punct.as_char();
+        let operator = match atoms.last() {
+          Some((Atom::Operator(data), _, _)) if joint =>
+            Some(format!("{}{}", data, char)).filter(|data| OPERATORS.contains(&data.as_str())),
+          _ => None
+        };
+        joint = punct.spacing() == Spacing::Joint && char != '\'';
+        if let Some(data) = operator {
+          atoms.pop();
+          atoms.push((Atom::Operator(data.clone()), text(data), nested))
+        } else if char == '<' && _opens_generics(&atoms) {
+          generics += 1;
+          atoms.push((Atom::Angle(true), text("<"), nested))
+        } else if char == '>' && 0 < generics {
+          generics -= 1;
+          atoms.push((Atom::Angle(false), text(">"), 0 < generics))
+        } else {
+          atoms.push((Atom::Operator(char.to_string()), text(char.to_string()), nested))
+        }
+      }
+    }
+  }
+  atoms
+}
+
+fn _opens_generics(
+  atoms: &Atoms
+) -> bool {
+  match &atoms[..] {
+    [.., (Atom::Operator(data), _, _)] => data == "::",
+    [.., (Atom::Word(before), _, _), (Atom::Word(_), _, _)]
+      if GENERIC_KEYWORDS.contains(&before.as_str()) => true,
+    [.., (Atom::Word(data), _, _)] =>
+      data == "impl" || data.starts_with(char::is_uppercase),
+    _ => false
+  }
+}
+
+fn _is_operand(
+  atom: Option<&Atom>
+) -> bool {
+  match atom {
+    Some(Atom::Word(data)) => !SPACED_KEYWORDS.contains(&data.as_str()),
+    Some(Atom::Literal | Atom::Group(_) | Atom::Angle(false)) => true,
+    Some(Atom::Operator(_) | Atom::Angle(true)) | None => false
+  }
+}
+
+/* Whether two atoms are padded, and whether the composition between them is breakable */
+fn _between(
+  before: Option<&Atom>,
+  left: &Atom,
+  right: &Atom,
+  nested: bool
+) -> (bool, bool) {
+  let pad = match (left, right) {
+    (_, Atom::Operator(data)) if TIGHT_BEFORE.contains(&data.as_str()) => false,
+    (_, Atom::Angle(_)) => false,
+    (Atom::Operator(data), _) if TIGHT_AFTER.contains(&data.as_str()) => false,
+    (Atom::Angle(true), _) => false,
+    (Atom::Angle(false), Atom::Group(Delimiter::Parenthesis)) => false,
+    (Atom::Word(_), Atom::Operator(data)) if data == "!" => !_is_operand(Some(left)),
+    (Atom::Operator(data), Atom::Group(_))
+      if data == "!" && matches!(before, Some(Atom::Word(_))) && _is_operand(before) => false,
+    (Atom::Operator(data), _) if PREFIX_OPERATORS.contains(&data.as_str()) => _is_operand(before),
+    (Atom::Word(data), Atom::Group(Delimiter::Parenthesis | Delimiter::Bracket)) =>
+      SPACED_KEYWORDS.contains(&data.as_str()),
+    (Atom::Group(Delimiter::Parenthesis | Delimiter::Bracket),
+     Atom::Group(Delimiter::Parenthesis | Delimiter::Bracket)) => false,
+    _ => true
+  };
+  let breakable = match (left, right) {
+    _ if nested => false,
+    (Atom::Operator(data), _) => data == "," || data == ";",
+    (Atom::Group(Delimiter::Brace), Atom::Word(data)) => data != "else" && data != "as",
+    (Atom::Group(Delimiter::Brace), Atom::Operator(data)) => data == "#",
+    (Atom::Group(Delimiter::Bracket), _) =>
+      matches!(before, Some(Atom::Operator(data)) if data == "#"),
+    _ => false
+  };
+  (pad, breakable)
+}
+
+/*
+  Compose the atoms of a stream, broken after commas, semicolons and
+  blocks; the items of the outermost stream that end with a semicolon or a
+  block are put on lines of their own.
+*/
+fn _stream(
+  stream: TokenStream,
+  outermost: bool
+) -> Box<Layout> {
+  let mut atoms = _atoms(stream).into_iter();
+  let Some((mut left, mut result, _)) = atoms.next() else { return null() };
+  let mut before = None;
+  for (right, layout, nested) in atoms {
+    let (pad, breakable) = _between(before.as_ref(), &left, &right, nested);
+    let separated = !matches!(&left, Atom::Operator(data) if data == ",");
+    result =
+      if outermost && breakable && separated { line(result, layout) }
+      else { comp(result, layout, pad, !breakable) };
+    before = Some(left);
+    left = right
+  }
+  result
+}
+
+fn _group(
+  delimiter: Delimiter,
+  padded: bool,
+  stream: TokenStream
+) -> Box<Layout> {
+  let (open, close, pad) = match delimiter {
+    Delimiter::Parenthesis => ("(", ")", false),
+    Delimiter::Bracket => ("[", "]", false),
+    Delimiter::Brace => ("{", "}", padded),
+    Delimiter::None => return _stream(stream, false)
+  };
+  if stream.is_empty() { return text(format!("{}{}", open, close)) }
+  grp(seq(comp(
+    comp(text(open), nest(_stream(stream, false)), pad, false),
+    text(close),
+    pad, false
+  )))
+}
+
+/// A function for laying out Rust tokens, takes a `proc_macro2::TokenStream` and gives a `Box<Layout>`, e.g. to pretty-print the code generated by a procedural macro in snapshot tests.
+///
+/// Every delimited group is a group of a sequence, with its contents nested and broken after the opening delimiter, after every comma and semicolon, and before the closing delimiter; the items of the stream, as ended by semicolons and blocks, are put on lines of their own.
+/// The spacing between tokens follows the usual Rust style, and the other tokens are never broken apart.
+/// Only available with the `tokens` feature.
+///
+/// # Examples
+/// ```
+/// use typeset::{compile, render, from_token_stream};
+///
+/// let tokens = "fn main ( ) { let x = foo ( 1 , & y ) ; }".parse().unwrap();
+/// let document = compile(from_token_stream(tokens));
+/// assert_eq!(render(document.clone(), 2, 80), "fn main() { let x = foo(1, &y); }");
+/// assert_eq!(render(document, 2, 24), "fn main() {\n  let x = foo(1, &y);\n}");
+/// ```
+#[must_use]
+pub fn from_token_stream(
+  stream: TokenStream
+) -> Box<Layout> {
+  _stream(stream, true)
+}
+
+impl From<TokenStream> for Box<Layout> {
+  fn from(stream: TokenStream) -> Self {
+    from_token_stream(stream)
+  }
+}
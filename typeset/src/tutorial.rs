@@ -0,0 +1,191 @@
+//! A tutorial on laying out code with typeset, from texts and compositions to a complete layout of function calls.
+//!
+//! Every lesson shows a layout and its output at several widths, and ends with an exercise, graded from easy to hard.
+//! The solution of each exercise follows it; try writing it before reading on.
+//! The examples are run as doctests, so the outputs shown here are the outputs of the renderer as it is, and the tutorial stays correct as the renderer evolves.
+//! The examples use a tab size of 2; the rules behind them are stated in full in [`semantics`](crate::semantics).
+//!
+//! # Lesson 1: texts and compositions
+//!
+//! A layout is built from texts, joined by compositions.
+//! A composition is either padded, putting a space between its parts, or unpadded, and either way it is broken onto a new line when its parts do not fit on one line.
+//!
+//! ```
+//! use typeset::{text, comp, compile, render};
+//!
+//! let layout = comp(text("hello"), text("world"), true, false);
+//! let document = compile(layout);
+//! assert_eq!(render(document.clone(), 2, 80), "hello world");
+//! assert_eq!(render(document, 2, 8), "hello\nworld");
+//! ```
+//!
+//! An unpadded composition breaks as readily as a padded one, which is rarely what is wanted for the parts of a single token:
+//!
+//! ```
+//! use typeset::{text, comp, compile, render};
+//!
+//! let layout = comp(text("foo"), text("()"), false, false);
+//! assert_eq!(render(compile(layout), 2, 3), "foo\n()");
+//! ```
+//!
+//! ### Exercise 1 (easy)
+//!
+//! Lay out `foo()` such that it is never broken, however narrow the width.
+//!
+//! The last argument of `comp` fixes the composition, and `fix` fixes every composition within a layout:
+//!
+//! ```
+//! use typeset::{text, comp, fix, compile, render};
+//!
+//! let layout = comp(text("foo"), text("()"), false, true);
+//! assert_eq!(render(compile(layout), 2, 3), "foo()");
+//!
+//! let layout = fix(comp(text("foo"), text("()"), false, false));
+//! assert_eq!(render(compile(layout), 2, 3), "foo()");
+//! ```
+//!
+//! # Lesson 2: groups versus sequences
+//!
+//! Compositions are broken greedily from left to right, so a long list breaks only where it must.
+//! A group, `grp`, is measured whole by the composition before it, which breaks first to give the group a line of its own:
+//!
+//! ```
+//! use typeset::{text, comp, grp, compile, render};
+//!
+//! let args = comp(text("a,"), comp(text("bb,"), text("ccc"), true, false), true, false);
+//! let document = compile(comp(text("call"), grp(args), true, false));
+//! assert_eq!(render(document.clone(), 2, 80), "call a, bb, ccc");
+//! assert_eq!(render(document.clone(), 2, 12), "call\na, bb, ccc");
+//! assert_eq!(render(document, 2, 8), "call\na, bb,\nccc");
+//! ```
+//!
+//! A sequence, `seq`, breaks all of its compositions or none of them, and is not measured whole by the composition before it:
+//!
+//! ```
+//! use typeset::{text, comp, seq, compile, render};
+//!
+//! let args = comp(text("a,"), comp(text("bb,"), text("ccc"), true, false), true, false);
+//! let document = compile(comp(text("call"), seq(args), true, false));
+//! assert_eq!(render(document.clone(), 2, 80), "call a, bb, ccc");
+//! assert_eq!(render(document.clone(), 2, 12), "call a,\nbb,\nccc");
+//! assert_eq!(render(document, 2, 6), "call\na,\nbb,\nccc");
+//! ```
+//!
+//! ### Exercise 2 (easy)
+//!
+//! Lay out the arguments `a, bb, ccc` such that they are either all on the line of `call`, or all on a line of their own.
+//!
+//! A group makes the composition before it break first, and a sequence around it breaks all of the arguments or none of them:
+//!
+//! ```
+//! use typeset::{text, comp, grp, seq, compile, render};
+//!
+//! let args = comp(text("a,"), comp(text("bb,"), text("ccc"), true, false), true, false);
+//! let document = compile(comp(text("call"), seq(grp(args)), true, false));
+//! assert_eq!(render(document.clone(), 2, 80), "call a, bb, ccc");
+//! assert_eq!(render(document.clone(), 2, 12), "call\na, bb, ccc");
+//! assert_eq!(render(document, 2, 8), "call\na,\nbb,\nccc");
+//! ```
+//!
+//! # Lesson 3: nests versus packs
+//!
+//! So far every broken line started at the first column.
+//! A nest, `nest`, indents the lines broken within it by one tab stop:
+//!
+//! ```
+//! use typeset::{text, comp, nest, compile, render};
+//!
+//! let args = comp(text("a,"), comp(text("bb,"), text("ccc"), true, false), true, false);
+//! let document = compile(comp(text("call("), nest(args), false, false));
+//! assert_eq!(render(document.clone(), 2, 14), "call(a, bb,\n  ccc");
+//! assert_eq!(render(document, 2, 10), "call(a,\n  bb, ccc");
+//! ```
+//!
+//! ### Exercise 3 (medium)
+//!
+//! Lay out the arguments such that the broken lines line up with the first argument.
+//!
+//! A pack, `pack`, indents the lines broken within it to the column of its first text:
+//!
+//! ```
+//! use typeset::{text, comp, pack, compile, render};
+//!
+//! let args = comp(text("a,"), comp(text("bb,"), text("ccc"), true, false), true, false);
+//! let document = compile(comp(text("call("), pack(args), false, false));
+//! assert_eq!(render(document.clone(), 2, 14), "call(a, bb,\n     ccc");
+//! assert_eq!(render(document, 2, 10), "call(a,\n     bb,\n     ccc");
+//! ```
+//!
+//! # Lesson 4: fixes
+//!
+//! A fix covers everything within it, groups and sequences included, so fixing a whole call keeps it on one line however narrow the width:
+//!
+//! ```
+//! use typeset::{text, comp, fix, seq, compile, render};
+//!
+//! let args = comp(text("a,"), comp(text("bb,"), text("ccc"), true, false), true, false);
+//! let call = comp(text("call("), comp(seq(args), text(")"), false, false), false, false);
+//! assert_eq!(render(compile(fix(call)), 2, 4), "call(a, bb, ccc)");
+//! ```
+//!
+//! Fixes are best kept to the smallest layouts that must not break, such as a token and the punctuation glued to it.
+//! Without them, the punctuation can end up on a line of its own:
+//!
+//! ```
+//! use typeset::{text, comp, seq, nest, compile, render};
+//!
+//! let args = comp(text("first,"), comp(text("second,"), text("third"), true, false), true, false);
+//! let call = comp(text("print("), comp(seq(nest(args)), text(")"), false, false), false, false);
+//! assert_eq!(render(compile(call), 2, 26), "print(first, second, third\n)");
+//! ```
+//!
+//! ### Exercise 4 (medium)
+//!
+//! Lay out the call such that the closing parenthesis stays on the line of the last argument.
+//!
+//! The closing parenthesis is fixed to the last argument, and the sequence covers the arguments only:
+//!
+//! ```
+//! use typeset::{text, comp, fix, seq, nest, compile, render};
+//!
+//! let last = fix(comp(text("third"), text(")"), false, false));
+//! let args = comp(text("first,"), comp(text("second,"), last, true, false), true, false);
+//! let call = comp(text("print("), seq(nest(args)), false, false);
+//! let document = compile(call);
+//! assert_eq!(render(document.clone(), 2, 27), "print(first, second, third)");
+//! assert_eq!(render(document, 2, 26), "print(first,\n  second,\n  third)");
+//! ```
+//!
+//! # Lesson 5: putting it together
+//!
+//! Layouts are plain values, so the lessons above are best packaged as functions that lay out one construct of a language each, and are composed like the constructs they lay out.
+//!
+//! ### Exercise 5 (hard)
+//!
+//! Write a function laying out a call of a function by its name and arguments, such that the arguments are either all on one line or one per line, indented under the name, with every comma and the closing parenthesis glued to the argument before it.
+//! Then lay out an assignment of a call, such that the assignment is broken before the call is.
+//!
+//! The arguments are folded from the last, fixing each to its punctuation; a group around the nested call makes the assignment break first:
+//!
+//! ```
+//! use typeset::{Layout, text, comp, fix, grp, seq, nest, compile, render};
+//!
+//! fn call(name: &str, args: &[&str]) -> Box<Layout> {
+//!   let glue = |arg: &str, punctuation: &str| fix(comp(text(arg.to_string()), text(punctuation.to_string()), false, false));
+//!   let (last, rest) = args.split_last().expect("A call has an argument");
+//!   let args = rest.iter().rev().fold(glue(last, ")"), |args, arg|
+//!     comp(glue(arg, ","), args, true, false));
+//!   comp(text(format!("{}(", name)), seq(nest(args)), false, false)
+//! }
+//!
+//! let document = compile(call("print", &["first", "second", "third"]));
+//! assert_eq!(render(document.clone(), 2, 80), "print(first, second, third)");
+//! assert_eq!(render(document.clone(), 2, 16), "print(first,\n  second,\n  third)");
+//! assert_eq!(render(document, 2, 10), "print(\n  first,\n  second,\n  third)");
+//!
+//! let assignment = comp(text("let x ="), grp(nest(call("print", &["first", "second", "third"]))), true, false);
+//! let document = compile(assignment);
+//! assert_eq!(render(document.clone(), 2, 80), "let x = print(first, second, third)");
+//! assert_eq!(render(document.clone(), 2, 30), "let x =\n  print(first, second, third)");
+//! assert_eq!(render(document, 2, 20), "let x =\n  print(first,\n    second,\n    third)");
+//! ```
@@ -1,9 +1,19 @@
-use bumpalo::Bump;
+#[cfg(not(feature = "strict-invariants"))]
+macro_rules! invariant {
+  ($($context:expr),* $(,)?) => {{
+    $(let _ = &$context;)*
+    unreachable!("Invariant")
+  }};
+}
 
-pub fn compose<'a, A, B, C>(
-  mem: &'a Bump,
-  f: &'a (dyn Fn(&'a Bump, B) -> C + 'a),
-  g: &'a (dyn Fn(&'a Bump, A) -> B + 'a)
-) -> &'a (dyn Fn(&'a Bump, A) -> C + 'a) {
-  mem.alloc(|mem, val| f(mem, g(mem, val)))
-}
\ No newline at end of file
+#[cfg(feature = "strict-invariants")]
+macro_rules! invariant {
+  ($($context:expr),* $(,)?) => {{
+    let dump: Vec<String> = vec![$(
+      format!("\n  {} = {:?}", stringify!($context), $context)
+    ),*];
+    unreachable!("Invariant violated at {}:{}{}", file!(), line!(), dump.concat())
+  }};
+}
+
+pub(crate) use invariant;
@@ -0,0 +1,202 @@
+/// Matches a layout against every variant of `Layout`, with one arm per variant in the order of their declaration.
+///
+/// The layouts are `#[non_exhaustive]`, such that a `match` outside of the crate needs a wildcard arm, which silently catches variants added later; the macro instead requires an arm for every variant, and fails to compile when a variant is added until an arm is given for it.
+/// The arms bind the fields of the variants with patterns, and the layout is matched by reference.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, nest, visit_layout, Layout};
+///
+/// fn texts(layout: &Layout) -> usize {
+///   visit_layout!(layout, {
+///     Null => 0,
+///     Text(_data, _width) => 1,
+///     PackReset => 0,
+///     Slot(_name) => 0,
+///     Annotate(_annotation, layout1) => texts(layout1),
+///     Fix(layout1) => texts(layout1),
+///     Grp(_name, layout1) => texts(layout1),
+///     Seq(_name, layout1) => texts(layout1),
+///     Nest(layout1) => texts(layout1),
+///     Pack(layout1) => texts(layout1),
+///     PackGroup(_anchors, layout1) => texts(layout1),
+///     PackAt(_anchor, layout1) => texts(layout1),
+///     Line(left, right) => texts(left) + texts(right),
+///     Comp(left, right, _attr) => texts(left) + texts(right)
+///   })
+/// }
+///
+/// let layout = comp(text("foo"), nest(text("bar")), true, false);
+/// assert_eq!(texts(&layout), 2);
+/// ```
+#[macro_export]
+macro_rules! visit_layout {
+  ($layout:expr, {
+    Null => $null:expr,
+    Text($text_data:pat, $text_width:pat) => $text:expr,
+    PackReset => $pack_reset:expr,
+    Slot($slot_name:pat) => $slot:expr,
+    Annotate($annotate_annotation:pat, $annotate_layout:pat) => $annotate:expr,
+    Fix($fix_layout:pat) => $fix:expr,
+    Grp($grp_name:pat, $grp_layout:pat) => $grp:expr,
+    Seq($seq_name:pat, $seq_layout:pat) => $seq:expr,
+    Nest($nest_layout:pat) => $nest:expr,
+    Pack($pack_layout:pat) => $pack:expr,
+    PackGroup($pack_group_anchors:pat, $pack_group_layout:pat) => $pack_group:expr,
+    PackAt($pack_at_anchor:pat, $pack_at_layout:pat) => $pack_at:expr,
+    Line($line_left:pat, $line_right:pat) => $line:expr,
+    Comp($comp_left:pat, $comp_right:pat, $comp_attr:pat) => $comp:expr $(,)?
+  }) => {
+    match &*$layout {
+      $crate::Layout::Null => $null,
+      $crate::Layout::Text($text_data, $text_width) => $text,
+      $crate::Layout::PackReset => $pack_reset,
+      $crate::Layout::Slot($slot_name) => $slot,
+      $crate::Layout::Annotate($annotate_annotation, $annotate_layout) => $annotate,
+      $crate::Layout::Fix($fix_layout) => $fix,
+      $crate::Layout::Grp($grp_name, $grp_layout) => $grp,
+      $crate::Layout::Seq($seq_name, $seq_layout) => $seq,
+      $crate::Layout::Nest($nest_layout) => $nest,
+      $crate::Layout::Pack($pack_layout) => $pack,
+      $crate::Layout::PackGroup($pack_group_anchors, $pack_group_layout) => $pack_group,
+      $crate::Layout::PackAt($pack_at_anchor, $pack_at_layout) => $pack_at,
+      $crate::Layout::Line($line_left, $line_right) => $line,
+      $crate::Layout::Comp($comp_left, $comp_right, $comp_attr) => $comp,
+      #[allow(unreachable_patterns)]
+      _ => unreachable!("visit_layout! covers every variant of Layout")
+    }
+  };
+}
+
+/// Matches a document against every variant of `Doc`, with one arm per variant in the order of their declaration, as `visit_layout!` does for layouts.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, line, compile, visit_doc, Doc};
+///
+/// fn lines(doc: &Doc) -> usize {
+///   visit_doc!(doc, {
+///     EOD => 0,
+///     Empty(doc1) => 1 + lines(doc1),
+///     Break(_obj, doc1) => 1 + lines(doc1),
+///     Line(_obj) => 1
+///   })
+/// }
+///
+/// let document = compile(line(text("foo"), text("bar")));
+/// assert_eq!(lines(&document), 2);
+/// ```
+#[macro_export]
+macro_rules! visit_doc {
+  ($doc:expr, {
+    EOD => $eod:expr,
+    Empty($empty_doc:pat) => $empty:expr,
+    Break($break_obj:pat, $break_doc:pat) => $break:expr,
+    Line($line_obj:pat) => $line:expr $(,)?
+  }) => {
+    match &*$doc {
+      $crate::Doc::EOD => $eod,
+      $crate::Doc::Empty($empty_doc) => $empty,
+      $crate::Doc::Break($break_obj, $break_doc) => $break,
+      $crate::Doc::Line($line_obj) => $line,
+      #[allow(unreachable_patterns)]
+      _ => unreachable!("visit_doc! covers every variant of Doc")
+    }
+  };
+}
+
+/// Matches a document object against every variant of `DocObj`, with one arm per variant in the order of their declaration, as `visit_layout!` does for layouts.
+///
+/// # Examples
+/// ```
+/// use typeset::{text, comp, nest, compile, visit_doc, visit_doc_obj, DocObj};
+///
+/// fn nests(obj: &DocObj) -> usize {
+///   visit_doc_obj!(obj, {
+///     Text(_data, _width) => 0,
+///     Marker(_marker) => 0,
+///     Fix(_obj1) => 0,
+///     Grp(_name, obj1) => nests(obj1),
+///     Seq(_name, obj1) => nests(obj1),
+///     Nest(obj1) => 1 + nests(obj1),
+///     Pack(_index, obj1) => nests(obj1),
+///     PackAt(_index, obj1) => nests(obj1),
+///     Comp(left, right, _pad) => nests(left) + nests(right)
+///   })
+/// }
+///
+/// let document = compile(comp(text("foo"), nest(text("bar")), true, false));
+/// let count = visit_doc!(document, {
+///   EOD => 0,
+///   Empty(_doc1) => 0,
+///   Break(obj, _doc1) => nests(obj),
+///   Line(obj) => nests(obj)
+/// });
+/// assert_eq!(count, 1);
+/// ```
+#[macro_export]
+macro_rules! visit_doc_obj {
+  ($obj:expr, {
+    Text($text_data:pat, $text_width:pat) => $text:expr,
+    Marker($marker_marker:pat) => $marker:expr,
+    Fix($fix_obj:pat) => $fix:expr,
+    Grp($grp_name:pat, $grp_obj:pat) => $grp:expr,
+    Seq($seq_name:pat, $seq_obj:pat) => $seq:expr,
+    Nest($nest_obj:pat) => $nest:expr,
+    Pack($pack_index:pat, $pack_obj:pat) => $pack:expr,
+    PackAt($pack_at_index:pat, $pack_at_obj:pat) => $pack_at:expr,
+    Comp($comp_left:pat, $comp_right:pat, $comp_pad:pat) => $comp:expr $(,)?
+  }) => {
+    match &*$obj {
+      $crate::DocObj::Text($text_data, $text_width) => $text,
+      $crate::DocObj::Marker($marker_marker) => $marker,
+      $crate::DocObj::Fix($fix_obj) => $fix,
+      $crate::DocObj::Grp($grp_name, $grp_obj) => $grp,
+      $crate::DocObj::Seq($seq_name, $seq_obj) => $seq,
+      $crate::DocObj::Nest($nest_obj) => $nest,
+      $crate::DocObj::Pack($pack_index, $pack_obj) => $pack,
+      $crate::DocObj::PackAt($pack_at_index, $pack_at_obj) => $pack_at,
+      $crate::DocObj::Comp($comp_left, $comp_right, $comp_pad) => $comp,
+      #[allow(unreachable_patterns)]
+      _ => unreachable!("visit_doc_obj! covers every variant of DocObj")
+    }
+  };
+}
+
+/// Matches a fixed document object against every variant of `DocObjFix`, with one arm per variant in the order of their declaration, as `visit_layout!` does for layouts.
+///
+/// # Examples
+/// ```
+/// use typeset::{visit_doc_obj_fix, DocObjFix};
+///
+/// fn width(obj: &DocObjFix) -> usize {
+///   visit_doc_obj_fix!(obj, {
+///     Text(data, _width) => data.len(),
+///     Marker(_marker) => 0,
+///     Comp(left, right, pad) => width(left) + usize::from(*pad) + width(right)
+///   })
+/// }
+///
+/// let obj = DocObjFix::Comp(
+///   Box::new(DocObjFix::Text("foo".to_string(), None)),
+///   Box::new(DocObjFix::Text("bar".to_string(), None)),
+///   true
+/// );
+/// assert_eq!(width(&obj), 7);
+/// ```
+#[macro_export]
+macro_rules! visit_doc_obj_fix {
+  ($obj:expr, {
+    Text($text_data:pat, $text_width:pat) => $text:expr,
+    Marker($marker_marker:pat) => $marker:expr,
+    Comp($comp_left:pat, $comp_right:pat, $comp_pad:pat) => $comp:expr $(,)?
+  }) => {
+    match &*$obj {
+      $crate::DocObjFix::Text($text_data, $text_width) => $text,
+      $crate::DocObjFix::Marker($marker_marker) => $marker,
+      $crate::DocObjFix::Comp($comp_left, $comp_right, $comp_pad) => $comp,
+      #[allow(unreachable_patterns)]
+      _ => unreachable!("visit_doc_obj_fix! covers every variant of DocObjFix")
+    }
+  };
+}